@@ -0,0 +1,132 @@
+//! History of the heads of each PR which were approved for landing, and by whom, so that auditors
+//! can see exactly which code each approval covered and so that an approval can be restored when a
+//! PR's head returns to a commit which was approved before, e.g. when a force-push is reverted
+
+use crate::{store::Store, Result};
+use github::Oid;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const APPROVALS_STORE: &str = "approvals.jsonl";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    /// The PR was queued for landing, or stayed queued, with this head
+    Approved,
+    /// The approval of this head was withdrawn, e.g. because the land was canceled
+    Revoked,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApprovalRecord {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub pr: u64,
+    pub head_oid: Oid,
+    pub approver: String,
+    pub decision: Decision,
+}
+
+/// A repo's approval history, stored as one JSON object per line
+#[derive(Debug, Default)]
+pub struct ApprovalHistory {
+    store: Store,
+    records: Vec<ApprovalRecord>,
+}
+
+impl ApprovalHistory {
+    /// Read the history kept in `state_dir`, the repo's state directory
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let store = Store::new(state_dir, APPROVALS_STORE);
+        let records = store.records()?;
+
+        Ok(Self { store, records })
+    }
+
+    pub fn record(
+        &mut self,
+        pr: u64,
+        head_oid: &Oid,
+        approver: &str,
+        decision: Decision,
+    ) -> Result<()> {
+        let record = ApprovalRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            pr,
+            head_oid: head_oid.clone(),
+            approver: approver.to_owned(),
+            decision,
+        };
+
+        self.store.append(&record)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// The records of a PR's approvals, oldest first
+    pub fn records(&self, pr: u64) -> impl Iterator<Item = &ApprovalRecord> {
+        self.records.iter().filter(move |record| record.pr == pr)
+    }
+
+    /// The user whose approval of `head_oid` of a PR still stands. An approval only stops
+    /// standing when it's revoked while that head is still the PR's head, not when new commits are
+    /// pushed.
+    pub fn approver_of(&self, pr: u64, head_oid: &Oid) -> Option<&str> {
+        let mut approver = None;
+        for record in self.records(pr) {
+            if record.head_oid != *head_oid {
+                continue;
+            }
+            approver = match record.decision {
+                Decision::Approved => Some(record.approver.as_str()),
+                Decision::Revoked => None,
+            };
+        }
+        approver
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ApprovalHistory, ApprovalRecord, Decision};
+    use github::Oid;
+
+    #[test]
+    fn approvers_of_heads() {
+        let record = |pr, head: &str, approver: &str, decision| ApprovalRecord {
+            timestamp: 0,
+            pr,
+            head_oid: Oid::from_str(head),
+            approver: approver.to_owned(),
+            decision,
+        };
+        let history = ApprovalHistory {
+            records: vec![
+                record(1, "aaaa", "alice", Decision::Approved),
+                record(2, "bbbb", "bob", Decision::Approved),
+                record(2, "bbbb", "bob", Decision::Revoked),
+                record(1, "cccc", "carol", Decision::Approved),
+            ],
+            ..ApprovalHistory::default()
+        };
+
+        assert_eq!(
+            history.approver_of(1, &Oid::from_str("aaaa")),
+            Some("alice")
+        );
+        assert_eq!(
+            history.approver_of(1, &Oid::from_str("cccc")),
+            Some("carol")
+        );
+        assert_eq!(history.approver_of(1, &Oid::from_str("bbbb")), None);
+        assert_eq!(history.approver_of(2, &Oid::from_str("bbbb")), None);
+        assert_eq!(history.records(2).count(), 2);
+    }
+}
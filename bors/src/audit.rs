@@ -0,0 +1,113 @@
+//! A per-repo log of the webhook events bors received about each PR along with the decisions it
+//! made about them, used by `bors explain` to reconstruct why a PR was or wasn't landed
+
+use crate::{store::Store, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const AUDIT_STORE: &str = "audit.jsonl";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuditEntry {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub pr: u64,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum AuditRecord {
+    /// A webhook event concerning the PR arrived
+    Event {
+        delivery_id: String,
+        description: String,
+    },
+    /// A comment on the PR was ignored without being parsed for commands
+    CommentIgnored { user: String, reason: String },
+    /// A command was issued on the PR, which is only executed if the user was authorized
+    Command {
+        user: String,
+        command: String,
+        authorized: bool,
+    },
+    /// The PR's status changed. PRs which are closed, or land, move to the `closed` status
+    Status {
+        from: String,
+        to: String,
+        reason: Option<String>,
+    },
+    /// A queued PR started being passed over when picking what to test next, or stopped being
+    /// passed over if there's no reason
+    Held { reason: Option<String> },
+    /// An administrator acted on the PR outside of the usual comment flow
+    Admin { action: String },
+    /// A PR opened by a bot was considered for automatic approval
+    AutoApproval { approved: bool, reason: String },
+}
+
+/// Log of the events and decisions made about a repo's PRs, stored as one JSON object per line
+#[derive(Debug)]
+pub struct AuditLog {
+    store: Store,
+
+    /// The last reason each queued PR was recorded as being held back for, so that only changes
+    /// are recorded
+    held: HashMap<u64, String>,
+}
+
+impl AuditLog {
+    /// The log kept in `state_dir`, the repo's state directory
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            store: Store::new(state_dir, AUDIT_STORE),
+            held: HashMap::new(),
+        }
+    }
+
+    pub fn record(&self, pr: u64, record: AuditRecord) -> Result<()> {
+        let entry = AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            pr,
+            record,
+        };
+
+        self.store.append(&entry)
+    }
+
+    /// Record why a queued PR is being held back, if that has changed since it was last recorded
+    pub fn record_held(&mut self, pr: u64, reason: Option<String>) -> Result<()> {
+        if self.held.get(&pr) == reason.as_ref() {
+            return Ok(());
+        }
+
+        match &reason {
+            Some(reason) => self.held.insert(pr, reason.clone()),
+            None => self.held.remove(&pr),
+        };
+        self.record(pr, AuditRecord::Held { reason })
+    }
+
+    /// Forget the hold on a PR which is no longer queued
+    pub fn release(&mut self, pr: u64) {
+        self.held.remove(&pr);
+    }
+
+    /// Read all the entries recorded for a repo's PR, oldest first, from the log kept in
+    /// `state_dir`
+    pub fn read(state_dir: &Path, pr: u64) -> Result<Vec<AuditEntry>> {
+        Ok(Store::new(state_dir, AUDIT_STORE)
+            .records::<AuditEntry>()?
+            .into_iter()
+            .filter(|entry| entry.pr == pr)
+            .collect())
+    }
+}
@@ -0,0 +1,342 @@
+//! Automatically approving dependency updates opened by bots, e.g. Dependabot or Renovate, which
+//! are limited to small version bumps of dependency files and have passed their checks
+
+use crate::{
+    autolabel::{list_files, matches_path},
+    config::{AutoApproveConfig, RepoConfig},
+    graphql::GithubClient,
+    state::{PullRequestState, Status},
+    store::Store,
+    Result,
+};
+use github::{client::PaginationOptions, Conclusion, Oid, StatusEventState};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const AUTO_APPROVALS_STORE: &str = "auto-approvals.json";
+
+const DAY_SECONDS: u64 = 24 * 60 * 60;
+
+/// Outcome of considering a PR for automatic approval
+#[derive(Debug)]
+pub enum Decision {
+    /// The PR's current head can be approved, for the given reason
+    Approve(String),
+    /// The PR's current head can't be approved
+    Reject(String),
+    /// The PR can't be approved yet, e.g. because the daily cap has been reached, and is
+    /// considered again the next time something about it changes
+    Defer(String),
+    /// The PR's required checks are still running
+    Pending,
+}
+
+/// The size of a version bump, ordered from least to most risky
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    fn as_str(self) -> &'static str {
+        match self {
+            Bump::Patch => "patch",
+            Bump::Minor => "minor",
+            Bump::Major => "major",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AutoApprover {
+    store: Store,
+
+    /// The head of each PR last approved or rejected, so that each head is only decided on once
+    decided: HashMap<u64, Oid>,
+
+    /// When PRs were approved, in seconds since the unix epoch, oldest first, used to enforce the
+    /// daily cap. Stored as a JSON array so that restarting bors doesn't reset the cap.
+    approvals: VecDeque<u64>,
+}
+
+impl AutoApprover {
+    /// Read the approvals kept in `state_dir`, the repo's state directory
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let store = Store::new(state_dir, AUTO_APPROVALS_STORE);
+        let approvals = store.load()?;
+
+        Ok(Self {
+            store,
+            decided: HashMap::new(),
+            approvals,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.approvals)
+    }
+
+    /// Decide if `pull` should be approved, or `None` if it isn't a candidate, e.g. because it
+    /// wasn't opened by a configured bot or its current head has already been decided on
+    pub async fn decide(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pull: &PullRequestState,
+    ) -> Result<Option<Decision>> {
+        let auto_approve = match config.auto_approve() {
+            Some(auto_approve) => auto_approve,
+            None => return Ok(None),
+        };
+
+        let is_candidate = pull
+            .author
+            .as_deref()
+            .map_or(false, |author| auto_approve.is_author(author))
+            && !pull.is_draft()
+            && matches!(pull.status, Status::InReview)
+            && self.decided.get(&pull.number) != Some(&pull.head_ref_oid);
+        if !is_candidate {
+            return Ok(None);
+        }
+
+        let decision = self.evaluate(auto_approve, config, github, pull).await?;
+        match decision {
+            Decision::Approve(_) => {
+                self.approvals.push_back(unix_now());
+                self.decided.insert(pull.number, pull.head_ref_oid.clone());
+                self.save()?;
+            }
+            Decision::Reject(_) => {
+                self.decided.insert(pull.number, pull.head_ref_oid.clone());
+            }
+            Decision::Defer(_) | Decision::Pending => {}
+        }
+
+        Ok(Some(decision))
+    }
+
+    async fn evaluate(
+        &mut self,
+        auto_approve: &AutoApproveConfig,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pull: &PullRequestState,
+    ) -> Result<Decision> {
+        let allowed = if auto_approve.allow_minor() {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        };
+        let bump = match parse_bump(&pull.title) {
+            Some(bump) if bump > allowed => {
+                return Ok(Decision::Reject(format!(
+                    "{} version bumps aren't approved automatically",
+                    bump.as_str()
+                )))
+            }
+            Some(bump) => bump,
+            None => {
+                return Ok(Decision::Reject(
+                    "the title doesn't describe a version bump".to_owned(),
+                ))
+            }
+        };
+
+        let files = list_files(config, github, pull.number).await?;
+        if let Some(file) = files
+            .iter()
+            .find(|file| !auto_approve.paths().any(|path| matches_path(path, file)))
+        {
+            return Ok(Decision::Reject(format!(
+                "`{}` isn't one of the allowed dependency files",
+                file
+            )));
+        }
+
+        match required_checks(config, github, auto_approve, &pull.head_ref_oid).await? {
+            Checks::Passed => {}
+            Checks::Pending => return Ok(Decision::Pending),
+            Checks::Failed(name) => {
+                return Ok(Decision::Reject(format!(
+                    "the required check `{}` didn't pass",
+                    name
+                )))
+            }
+        }
+
+        if let Some(cap) = auto_approve.daily_cap() {
+            let now = unix_now();
+            while self.approvals.front().map_or(false, |approved| {
+                now.saturating_sub(*approved) >= DAY_SECONDS
+            }) {
+                self.approvals.pop_front();
+            }
+
+            if self.approvals.len() >= cap as usize {
+                return Ok(Decision::Defer(format!(
+                    "the daily cap of {} automatic approvals has been reached",
+                    cap
+                )));
+            }
+        }
+
+        Ok(Decision::Approve(format!(
+            "a {} version bump which only changes dependency files and has passed its required \
+            checks",
+            bump.as_str()
+        )))
+    }
+}
+
+enum Checks {
+    Passed,
+    Pending,
+    Failed(String),
+}
+
+/// Check the state of the required checks on `oid`, which may be reported as either check runs or
+/// commit statuses
+async fn required_checks(
+    config: &RepoConfig,
+    github: &GithubClient,
+    auto_approve: &AutoApproveConfig,
+    oid: &Oid,
+) -> Result<Checks> {
+    if auto_approve.required_checks().is_empty() {
+        return Ok(Checks::Passed);
+    }
+
+    let runs = github
+        .checks()
+        .list_check_runs_for_ref(config.owner(), config.name(), &oid.to_string())
+        .await?
+        .into_inner()
+        .check_runs;
+    let options = PaginationOptions {
+        page: None,
+        per_page: Some(100),
+    };
+    let statuses = github
+        .repos()
+        .get_combined_status(config.owner(), config.name(), &oid.to_string(), options)
+        .await?
+        .into_inner()
+        .statuses;
+
+    let mut pending = false;
+    for name in auto_approve.required_checks() {
+        // A check which was rerun only counts its latest run
+        let run = runs
+            .iter()
+            .filter(|run| &run.name == name)
+            .max_by_key(|run| run.id);
+        let passed = match run {
+            Some(run) => run
+                .conclusion
+                .map(|conclusion| matches!(conclusion, Conclusion::Success | Conclusion::Neutral)),
+            None => statuses
+                .iter()
+                .find(|status| &status.context == name)
+                .and_then(|status| match status.state {
+                    StatusEventState::Pending => None,
+                    StatusEventState::Success => Some(true),
+                    StatusEventState::Failure | StatusEventState::Error => Some(false),
+                }),
+        };
+
+        match passed {
+            Some(true) => {}
+            Some(false) => return Ok(Checks::Failed(name.clone())),
+            None => pending = true,
+        }
+    }
+
+    if pending {
+        Ok(Checks::Pending)
+    } else {
+        Ok(Checks::Passed)
+    }
+}
+
+/// Find the size of the version bump described by a PR's title, e.g. "Bump serde from 1.0.110 to
+/// 1.0.111". Downgrades and versions which aren't purely numeric, like pre-releases, aren't bumps.
+fn parse_bump(title: &str) -> Option<Bump> {
+    let mut words = title.split_whitespace();
+    words.find(|word| word.eq_ignore_ascii_case("from"))?;
+    let from = parse_version(words.next()?)?;
+    if !words.next()?.eq_ignore_ascii_case("to") {
+        return None;
+    }
+    let to = parse_version(words.next()?)?;
+
+    let component = |version: &[u64], idx: usize| version.get(idx).copied().unwrap_or(0);
+    let changed =
+        (0..from.len().max(to.len())).find(|idx| component(&from, *idx) != component(&to, *idx))?;
+    if component(&to, changed) < component(&from, changed) {
+        return None;
+    }
+
+    // While the major version is 0 breaking changes bump the minor version, and anything else
+    // the patch version
+    match (component(&from, 0), changed) {
+        (_, 0) | (0, 1) => Some(Bump::Major),
+        (_, 1) => Some(Bump::Minor),
+        _ => Some(Bump::Patch),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_bump, Bump};
+
+    #[test]
+    fn version_bumps() {
+        assert_eq!(
+            parse_bump("Bump serde from 1.0.110 to 1.0.111"),
+            Some(Bump::Patch)
+        );
+        assert_eq!(
+            parse_bump("build(deps): bump tokio from 0.2.21 to 0.2.22 in /bors"),
+            Some(Bump::Patch)
+        );
+        assert_eq!(
+            parse_bump("Bump regex from v1.3.9 to v1.4.0"),
+            Some(Bump::Minor)
+        );
+        assert_eq!(parse_bump("Bump log from 0.4 to 0.5"), Some(Bump::Major));
+        assert_eq!(
+            parse_bump("Bump anyhow from 1.0.31 to 2.0.0"),
+            Some(Bump::Major)
+        );
+    }
+
+    #[test]
+    fn not_version_bumps() {
+        assert_eq!(parse_bump("Update the README"), None);
+        assert_eq!(parse_bump("Bump serde from 1.0.111 to 1.0.110"), None);
+        assert_eq!(parse_bump("Bump serde from 1.0.110 to 1.0.110"), None);
+        assert_eq!(parse_bump("Bump tokio from 0.2.22 to 0.3.0-beta.1"), None);
+        assert_eq!(parse_bump("Bump tokio from 0.2.22"), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Automatically labeling PRs based on the paths they change
+
+use crate::{config::RepoConfig, graphql::GithubClient, state::PullRequestState, Result};
+use github::client::PaginationOptions;
+use log::info;
+
+/// Add any configured labels triggered by the files `pull` changes which it doesn't already have
+pub async fn apply_autolabels(
+    config: &RepoConfig,
+    github: &GithubClient,
+    pull: &mut PullRequestState,
+) -> Result<()> {
+    if config.autolabels().next().is_none() {
+        return Ok(());
+    }
+
+    let files = list_files(config, github, pull.number).await?;
+    let labels: Vec<String> = config
+        .autolabels()
+        .filter(|(label, _autolabel)| !pull.labels.contains(*label))
+        .filter(|(_label, autolabel)| {
+            files.iter().any(|file| {
+                autolabel
+                    .trigger_files()
+                    .any(|trigger| matches_path(trigger, file))
+            })
+        })
+        .map(|(label, _autolabel)| label.to_owned())
+        .collect();
+
+    if labels.is_empty() {
+        return Ok(());
+    }
+
+    info!("adding labels {:?} to pr #{}", labels, pull.number);
+    github
+        .issues()
+        .add_lables(config.owner(), config.name(), pull.number, labels.clone())
+        .await?;
+    pull.labels.extend(labels);
+
+    Ok(())
+}
+
+/// List the paths of all the files changed by a PR, including the previous paths of renamed files
+pub async fn list_files(
+    config: &RepoConfig,
+    github: &GithubClient,
+    number: u64,
+) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut page = Some(1);
+
+    while let Some(current) = page {
+        let options = PaginationOptions {
+            page: Some(current),
+            per_page: Some(100),
+        };
+        let response = github
+            .pulls()
+            .list_files(config.owner(), config.name(), number, Some(options))
+            .await?;
+        page = response.pagination().next_page;
+
+        for file in response.into_inner() {
+            files.extend(file.previous_filename);
+            files.push(file.filename);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Check if `path` is `trigger` or lies within the directory `trigger`
+pub fn matches_path(trigger: &str, path: &str) -> bool {
+    let trigger = trigger.trim_end_matches('/');
+    match path.strip_prefix(trigger) {
+        Some(rest) => trigger.is_empty() || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::matches_path;
+
+    #[test]
+    fn trigger_paths() {
+        assert!(matches_path("src/queue.rs", "src/queue.rs"));
+        assert!(matches_path("docs", "docs/README.md"));
+        assert!(matches_path("docs/", "docs/book/intro.md"));
+        assert!(matches_path("", "Cargo.toml"));
+
+        assert!(!matches_path("docs", "docs-old/README.md"));
+        assert!(!matches_path("src/queue.rs", "src/queue.rs.orig"));
+        assert!(!matches_path("src/git", "bors/src/git.rs"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Archive of the raw check run and commit status payloads reported on the merge commits bors
+//! tests, so that what CI reported can still be settled after Github prunes or rewrites a commit's
+//! check history
+
+use crate::{config::CheckArchiveConfig, store::Store, Result};
+use github::Oid;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const CHECK_ARCHIVE_DIR: &str = "check-archive";
+
+/// A payload as it was delivered, along with when and how it arrived
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ArchivedPayload {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub delivery_id: String,
+    /// The webhook event the payload was delivered as, e.g. "check_run" or "status"
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+/// The payloads reported on each merge commit are stored together, one JSON object per line, and
+/// the oldest archives are removed once they're too old or the archive grows too large
+#[derive(Debug)]
+pub struct CheckArchive {
+    dir: PathBuf,
+    max_age: Duration,
+    max_bytes: u64,
+}
+
+impl CheckArchive {
+    /// The archive kept in `state_dir`, the repo's state directory
+    pub fn new(state_dir: &Path, config: &CheckArchiveConfig) -> Result<Self> {
+        let dir = state_dir.join(CHECK_ARCHIVE_DIR);
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            max_age: config.max_age(),
+            max_bytes: config.max_bytes(),
+        })
+    }
+
+    fn store(&self, merge_oid: &Oid) -> Store {
+        Store::new(&self.dir, &format!("{}.jsonl", merge_oid))
+    }
+
+    /// Archive a payload reported on `merge_oid`. Archives are rotated each time one is started for
+    /// a new merge commit.
+    pub fn record(
+        &self,
+        merge_oid: &Oid,
+        event: &str,
+        payload: &[u8],
+        delivery_id: &str,
+    ) -> Result<()> {
+        let entry = ArchivedPayload {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            delivery_id: delivery_id.to_owned(),
+            event: event.to_owned(),
+            payload: serde_json::from_slice(payload)?,
+        };
+
+        let store = self.store(merge_oid);
+        if !store.exists() {
+            self.rotate()?;
+        }
+
+        store.append(&entry)
+    }
+
+    /// Read the payloads archived for a merge commit, oldest first
+    pub fn read(&self, merge_oid: &Oid) -> Result<Vec<ArchivedPayload>> {
+        self.store(merge_oid).records()
+    }
+
+    /// Remove the archives which are too old, followed by the oldest remaining archives until the
+    /// rest fit within the size limit
+    fn rotate(&self) -> Result<()> {
+        let mut archives = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            archives.push((entry.path(), metadata.modified()?, metadata.len()));
+        }
+
+        for path in expired(archives, SystemTime::now(), self.max_age, self.max_bytes) {
+            info!("removing check archive {}", path.display());
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pick the archives, given as their path, last modification and size, to remove
+fn expired(
+    mut archives: Vec<(PathBuf, SystemTime, u64)>,
+    now: SystemTime,
+    max_age: Duration,
+    max_bytes: u64,
+) -> Vec<PathBuf> {
+    // Newest first, so that the archives beyond the size limit are the oldest
+    archives.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut total = 0;
+    archives
+        .into_iter()
+        .filter(|(_path, modified, size)| {
+            total += size;
+            let age = now.duration_since(*modified).unwrap_or_default();
+            age > max_age || total > max_bytes
+        })
+        .map(|(path, _modified, _size)| path)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::expired;
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn oldest_archives_expire_first() {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+        let archive = |name: &str, days: u32, size| (PathBuf::from(name), now - DAY * days, size);
+
+        let archives = vec![
+            archive("a", 1, 40),
+            archive("b", 40, 10),
+            archive("c", 3, 40),
+            archive("d", 2, 40),
+        ];
+        assert_eq!(
+            expired(archives.clone(), now, DAY * 30, 1000),
+            vec![PathBuf::from("b")]
+        );
+        assert_eq!(
+            expired(archives, now, DAY * 30, 100),
+            vec![PathBuf::from("c"), PathBuf::from("b")]
+        );
+    }
+}
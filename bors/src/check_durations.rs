@@ -0,0 +1,116 @@
+//! Rolling statistics of how long each of a repo's checks takes to pass, used to estimate when
+//! queued PRs will be tested
+
+use crate::{store::Store, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::Path,
+    time::Duration,
+};
+
+const CHECK_DURATIONS_STORE: &str = "check-durations.json";
+
+/// Number of recent durations kept for each check
+const MAX_SAMPLES: usize = 50;
+
+/// The most recent durations of a check, in seconds, most recent last
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RollingStats {
+    samples: VecDeque<u64>,
+}
+
+impl RollingStats {
+    fn record(&mut self, duration: Duration) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration.as_secs());
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let total: u64 = self.samples.iter().sum();
+        Some(Duration::from_secs(total / self.samples.len() as u64))
+    }
+}
+
+/// The rolling stats of each of a repo's checks, stored as a JSON object keyed by check name
+#[derive(Debug, Default)]
+pub struct CheckDurations {
+    store: Store,
+    checks: BTreeMap<String, RollingStats>,
+}
+
+impl CheckDurations {
+    /// Read the stats kept in `state_dir`, the repo's state directory
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let store = Store::new(state_dir, CHECK_DURATIONS_STORE);
+        let checks = store.load()?;
+
+        Ok(Self { store, checks })
+    }
+
+    /// Record that `check` passed `duration` after testing started, saving the updated stats
+    pub fn record(&mut self, check: &str, duration: Duration) -> Result<()> {
+        self.checks
+            .entry(check.to_owned())
+            .or_default()
+            .record(duration);
+
+        self.store.save(&self.checks)
+    }
+
+    /// How long testing is expected to take until all of `checks` have passed. Checks run in
+    /// parallel so this is the longest of their average durations, or `None` if none of them have
+    /// passed before.
+    pub fn expected<'a>(&self, checks: impl Iterator<Item = &'a str>) -> Option<Duration> {
+        checks
+            .filter_map(|check| self.checks.get(check))
+            .filter_map(RollingStats::mean)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CheckDurations, RollingStats, MAX_SAMPLES};
+    use std::time::Duration;
+
+    #[test]
+    fn rolling_means() {
+        let mut stats = RollingStats::default();
+        assert_eq!(stats.mean(), None);
+
+        for minutes in 1..=MAX_SAMPLES as u64 + 10 {
+            stats.record(Duration::from_secs(minutes * 60));
+        }
+        // Only the most recent samples, 11 through 60 minutes, are kept
+        assert_eq!(stats.samples.len(), MAX_SAMPLES);
+        assert_eq!(stats.mean(), Some(Duration::from_secs(2130)));
+    }
+
+    #[test]
+    fn expected_durations() {
+        let mut durations = CheckDurations::default();
+        durations
+            .checks
+            .entry("ci/test".to_owned())
+            .or_default()
+            .record(Duration::from_secs(1800));
+        durations
+            .checks
+            .entry("ci/lint".to_owned())
+            .or_default()
+            .record(Duration::from_secs(300));
+
+        assert_eq!(
+            durations.expected(vec!["ci/lint", "ci/test", "ci/doc"].into_iter()),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(durations.expected(vec!["ci/doc"].into_iter()), None);
+    }
+}
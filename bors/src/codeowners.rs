@@ -0,0 +1,300 @@
+//! Requiring that each file a PR changes which has code owners, according to the repo's
+//! CODEOWNERS file, is approved by one of its owners before the PR can be queued for landing
+
+use crate::{
+    config::RepoConfig, git::GitRepository, graphql::GithubClient, state::PullRequestState, Result,
+};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// Where Github looks for a repo's CODEOWNERS file, in the order it looks
+const CODEOWNERS_PATHS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Maximum number of files listed when refusing to queue a PR
+const MAX_LISTED_FILES: usize = 10;
+
+/// A repo's CODEOWNERS file
+#[derive(Debug, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// A line of a CODEOWNERS file, naming the owners of the files matching its pattern
+#[derive(Debug)]
+struct Rule {
+    pattern: Regex,
+    /// Users, as `@user`, teams, as `@org/team`, or email addresses. A rule without owners leaves
+    /// the files it matches without owners.
+    owners: Vec<String>,
+}
+
+/// A file changed by a PR which none of its owners have approved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unapproved {
+    pub path: String,
+    pub owners: Vec<String>,
+}
+
+impl CodeOwners {
+    pub fn parse(contents: &str) -> Self {
+        let rules = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let pattern = pattern_regex(fields.next()?);
+                let owners = fields
+                    .take_while(|owner| !owner.starts_with('#'))
+                    .map(ToOwned::to_owned)
+                    .collect();
+                Some(Rule { pattern, owners })
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// The owners of the file at `path`. Later lines of the CODEOWNERS file take precedence over
+    /// earlier ones.
+    pub fn owners_of(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(path))
+            .map_or(&[][..], |rule| rule.owners.as_slice())
+    }
+
+    /// The teams, as `org/team`, which own any of `files`
+    pub fn teams<'a>(&'a self, files: &[String]) -> HashSet<&'a str> {
+        files
+            .iter()
+            .flat_map(|file| self.owners_of(file))
+            .filter_map(|owner| owner.strip_prefix('@'))
+            .filter(|owner| owner.contains('/'))
+            .collect()
+    }
+
+    /// The files with owners, none of whom are among `approvers`, either themselves or as a member
+    /// of one of the owning teams. `approvers` and `team_members`, keyed by `org/team`, are
+    /// lowercase since Github doesn't distinguish logins by case. Owners given by their email
+    /// address can't be matched to the login of an approver, so they never approve a file.
+    pub fn unapproved(
+        &self,
+        files: &[String],
+        approvers: &HashSet<String>,
+        team_members: &HashMap<String, HashSet<String>>,
+    ) -> Vec<Unapproved> {
+        let is_approver = |owner: &String| {
+            let owner = match owner.strip_prefix('@') {
+                Some(owner) => owner.to_lowercase(),
+                None => return false,
+            };
+            match team_members.get(&owner) {
+                Some(members) => !members.is_disjoint(approvers),
+                None => approvers.contains(&owner),
+            }
+        };
+
+        files
+            .iter()
+            .filter_map(|file| {
+                let owners = self.owners_of(file);
+                if owners.is_empty() || owners.iter().any(&is_approver) {
+                    None
+                } else {
+                    Some(Unapproved {
+                        path: file.clone(),
+                        owners: owners.to_vec(),
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+/// Read the CODEOWNERS file on the tip of `base_ref`, if it has one
+pub fn load(git_repository: &mut GitRepository, base_ref: &str) -> Result<Option<CodeOwners>> {
+    let base_oid = git_repository.fetch_base(base_ref)?;
+    for path in CODEOWNERS_PATHS {
+        if let Some(contents) = git_repository.file_contents(&base_oid, path)? {
+            return Ok(Some(CodeOwners::parse(&contents)));
+        }
+    }
+    Ok(None)
+}
+
+/// The files changed by `pull` which have code owners, none of whom have approved it, either with
+/// an approving review or by being `approver`, the user queueing it for landing
+pub async fn unapproved_files(
+    config: &RepoConfig,
+    github: &GithubClient,
+    code_owners: &CodeOwners,
+    pull: &PullRequestState,
+    approver: &str,
+) -> Result<Vec<Unapproved>> {
+    let files = github
+        .changed_files(config.owner(), config.name(), pull.number)
+        .await?;
+    let approvers = approvers(pull, approver);
+
+    let mut team_members = HashMap::new();
+    for team in code_owners.teams(&files) {
+        let members = github.team_members(team).await?;
+        team_members.insert(team.to_lowercase(), members);
+    }
+
+    Ok(code_owners.unapproved(&files, &approvers, &team_members))
+}
+
+/// The lowercase logins of the users who have approved `pull`, either with an approving review or
+/// by being `approver`, the user queueing it for landing. The PR's author doesn't count, since
+/// nobody can vouch for their own changes.
+pub fn approvers(pull: &PullRequestState, approver: &str) -> HashSet<String> {
+    let author = pull.author.as_deref().map(str::to_lowercase);
+    pull.approved_by
+        .iter()
+        .map(String::as_str)
+        .chain(Some(approver))
+        .map(str::to_lowercase)
+        .filter(|login| Some(login) != author.as_ref())
+        .collect()
+}
+
+/// Format the files no owner has approved as a markdown list, for a comment refusing to queue the
+/// PR
+pub fn report(unapproved: &[Unapproved]) -> String {
+    let mut report = String::new();
+    for file in unapproved.iter().take(MAX_LISTED_FILES) {
+        report.push_str(&format!(
+            "\n- `{}`, owned by {}",
+            file.path,
+            file.owners.join(", ")
+        ));
+    }
+    if unapproved.len() > MAX_LISTED_FILES {
+        report.push_str(&format!(
+            "\n- and {} more",
+            unapproved.len() - MAX_LISTED_FILES
+        ));
+    }
+    report
+}
+
+/// Translate a CODEOWNERS pattern, which follows most of the rules of gitignore patterns, into a
+/// regex matching the paths of the files it applies to
+pub fn pattern_regex(pattern: &str) -> Regex {
+    // A pattern with a slash anywhere but at its end is relative to the root of the repo, otherwise
+    // it matches at any depth
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let directory = pattern.ends_with('/');
+    let glob = pattern.trim_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    // A pattern matching a directory applies to everything within it, except that a trailing `/*`
+    // only applies to the directory's direct contents
+    if directory {
+        regex.push_str("/.*$");
+    } else if glob.ends_with("/*") && !glob.ends_with("**") {
+        regex.push('$');
+    } else {
+        regex.push_str("(?:/.*)?$");
+    }
+
+    Regex::new(&regex).expect("patterns are escaped")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{report, CodeOwners, Unapproved};
+    use std::collections::{HashMap, HashSet};
+
+    const CODEOWNERS: &str = r#"
+        # Default owners of everything in the repo
+        *       @global-owner
+
+        *.js    @js-owner  # Inline comment
+        /build/logs/ @doctocat
+        docs/*  docs@example.com
+        apps/   @octocat
+        /scripts/** @org/scripters
+        **/logs @logger
+        /vendor/
+    "#;
+
+    #[test]
+    fn owners() {
+        let code_owners = CodeOwners::parse(CODEOWNERS);
+        let owners = |path| code_owners.owners_of(path).to_vec();
+
+        assert_eq!(owners("README.md"), vec!["@global-owner"]);
+        assert_eq!(owners("src/index.js"), vec!["@js-owner"]);
+        assert_eq!(owners("build/logs/out.txt"), vec!["@logger"]);
+        assert_eq!(owners("build/logs"), vec!["@logger"]);
+        assert_eq!(owners("docs/intro.md"), vec!["docs@example.com"]);
+        assert_eq!(owners("docs/build-app/setup.md"), vec!["@global-owner"]);
+        assert_eq!(owners("web/apps/main.rs"), vec!["@octocat"]);
+        assert_eq!(owners("scripts/ci/run.sh"), vec!["@org/scripters"]);
+        assert_eq!(owners("src/scripts/run.sh"), vec!["@global-owner"]);
+        assert_eq!(owners("a/b/logs/today"), vec!["@logger"]);
+        assert!(owners("vendor/lib.rs").is_empty());
+    }
+
+    #[test]
+    fn approvals() {
+        let code_owners = CodeOwners::parse(CODEOWNERS);
+        let files: Vec<String> = vec![
+            "README.md".to_owned(),
+            "scripts/run.sh".to_owned(),
+            "docs/intro.md".to_owned(),
+            "vendor/lib.rs".to_owned(),
+        ];
+        assert_eq!(
+            code_owners.teams(&files).into_iter().collect::<Vec<_>>(),
+            vec!["org/scripters"]
+        );
+
+        let approvers: HashSet<String> = vec!["global-owner".to_owned(), "alice".to_owned()]
+            .into_iter()
+            .collect();
+        let mut team_members = HashMap::new();
+        team_members.insert(
+            "org/scripters".to_owned(),
+            vec!["alice".to_owned()].into_iter().collect(),
+        );
+
+        assert_eq!(
+            code_owners.unapproved(&files, &approvers, &team_members),
+            vec![Unapproved {
+                path: "docs/intro.md".to_owned(),
+                owners: vec!["docs@example.com".to_owned()],
+            }]
+        );
+
+        team_members.insert("org/scripters".to_owned(), HashSet::new());
+        let unapproved = code_owners.unapproved(&files, &approvers, &team_members);
+        assert_eq!(
+            report(&unapproved),
+            "\n- `scripts/run.sh`, owned by @org/scripters\
+            \n- `docs/intro.md`, owned by docs@example.com"
+        );
+    }
+}
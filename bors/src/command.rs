@@ -1,11 +1,65 @@
 //! Defines commands which can be asked to be performed
 
 use crate::{
-    config::RepoConfig, event_processor::CommandContext, project_board::ProjectBoard, Result,
+    codeowners,
+    comment::{link, sanitize},
+    commit_lint::{self, Violations},
+    commit_message::{self, overlong_subject},
+    config::{CommandPermission, MergeStrategy, RepoConfig, Role, Rollup, Tier},
+    description::{Importance, StatusDescription},
+    event_processor::{AdminCommand, CommandContext},
+    git::PushFailure,
+    land_window::format_wait,
+    pr_template,
+    project_board::ProjectBoard,
+    protected_paths,
+    queue::{create_pending_status, is_tree_closed_for},
+    selfcheck,
+    state::AttemptOverrides,
+    Result,
 };
-use log::info;
+use log::{info, warn};
 use thiserror::Error;
 
+/// Maximum number of unresolved review threads listed when refusing to queue a PR
+const MAX_LISTED_THREADS: usize = 10;
+
+/// Local branch PRs are rebased on by the `rebase` command
+const REBASE_BRANCH: &str = "bors-rebase";
+
+/// Prefix of the branches backports are pushed to, followed by the PR and the branch backported to
+const BACKPORT_BRANCH_PREFIX: &str = "backport/";
+
+/// Prefix of the branches reverts are pushed to, followed by the PR being reverted
+const REVERT_BRANCH_PREFIX: &str = "revert/";
+
+/// Priority reverts are queued at, the same as the high-priority label's
+const REVERT_PRIORITY: u32 = 1;
+
+/// The key of every command, by which it's given permissions, or a tier, in a repo's config
+pub const PERMISSION_KEYS: &[&str] = &[
+    "land",
+    "cancel",
+    "help",
+    "priority",
+    "solo",
+    "try",
+    "retry",
+    "status",
+    "treeclosed",
+    "depends",
+    "rollup",
+    "supersede",
+    "delegate",
+    "selfcheck",
+    "rekick",
+    "pause",
+    "resume",
+    "rebase",
+    "backport",
+    "revert",
+];
+
 #[derive(Error, Debug)]
 #[error("invalid command")]
 pub struct ParseCommnadError;
@@ -22,20 +76,133 @@ enum CommandType {
     Cancel,
     Help,
     Priority(Priority),
+    Solo(bool),
+    Try,
+    Retry(AttemptOverrides),
+    Status,
+    TreeClosed(Option<u32>),
+    DependsOn(Option<u64>),
+    Rollup(Option<Rollup>),
+    Supersede(u64),
+    Delegate(Option<Delegate>),
+    SelfCheck,
+    Rekick,
+    Pause(bool),
+    Rebase,
+    Backport(String),
+    Revert(Option<u64>),
+}
+
+/// Who a PR's approval rights are delegated to
+#[derive(Debug, PartialEq, Eq)]
+enum Delegate {
+    Author,
+    User(String),
 }
 
 impl CommandType {
+    /// The key used to look up this command's permissions in a repository's config, one of
+    /// `PERMISSION_KEYS`
+    fn permission_key(&self) -> &'static str {
+        match &self {
+            CommandType::Land(_) => "land",
+            CommandType::Cancel => "cancel",
+            CommandType::Help => "help",
+            CommandType::Priority(_) => "priority",
+            CommandType::Solo(_) => "solo",
+            CommandType::Try => "try",
+            CommandType::Retry(_) => "retry",
+            CommandType::Status => "status",
+            CommandType::TreeClosed(_) => "treeclosed",
+            CommandType::DependsOn(_) => "depends",
+            CommandType::Rollup(_) => "rollup",
+            CommandType::Supersede(_) => "supersede",
+            CommandType::Delegate(_) => "delegate",
+            CommandType::SelfCheck => "selfcheck",
+            CommandType::Rekick => "rekick",
+            CommandType::Pause(true) => "pause",
+            CommandType::Pause(false) => "resume",
+            CommandType::Rebase => "rebase",
+            CommandType::Backport(_) => "backport",
+            CommandType::Revert(_) => "revert",
+        }
+    }
+
     fn name(&self) -> &'static str {
         match &self {
             CommandType::Land(_) => "Land",
             CommandType::Cancel => "Cancel",
             CommandType::Help => "Help",
             CommandType::Priority(_) => "Priority",
+            CommandType::Solo(_) => "Solo",
+            CommandType::Try => "Try",
+            CommandType::Retry(_) => "Retry",
+            CommandType::Status => "Status",
+            CommandType::TreeClosed(_) => "TreeClosed",
+            CommandType::DependsOn(_) => "DependsOn",
+            CommandType::Rollup(_) => "Rollup",
+            CommandType::Supersede(_) => "Supersede",
+            CommandType::Delegate(_) => "Delegate",
+            CommandType::SelfCheck => "SelfCheck",
+            CommandType::Rekick => "Rekick",
+            CommandType::Pause(true) => "Pause",
+            CommandType::Pause(false) => "Resume",
+            CommandType::Rebase => "Rebase",
+            CommandType::Backport(_) => "Backport",
+            CommandType::Revert(_) => "Revert",
+        }
+    }
+
+    /// The least privileged tier which may use this command, when the repo uses permission tiers
+    fn tier(&self) -> Tier {
+        match &self {
+            CommandType::Help | CommandType::Status | CommandType::Try => Tier::TryOnly,
+            CommandType::Land(_)
+            | CommandType::Cancel
+            | CommandType::Priority(_)
+            | CommandType::Solo(_)
+            | CommandType::Retry(_)
+            | CommandType::DependsOn(_)
+            | CommandType::Rollup(_)
+            | CommandType::Supersede(_)
+            | CommandType::Delegate(_)
+            | CommandType::Rebase
+            | CommandType::Backport(_)
+            | CommandType::Revert(_) => Tier::Reviewer,
+            CommandType::TreeClosed(_)
+            | CommandType::SelfCheck
+            | CommandType::Rekick
+            | CommandType::Pause(_) => Tier::Admin,
+        }
+    }
+
+    /// Check if a user the PR's approval rights have been delegated to may issue this command.
+    /// Landing a stack isn't, as it also queues the PRs below, which weren't delegated.
+    fn is_delegable(&self) -> bool {
+        match self {
+            CommandType::Land(land) => !land.stack,
+            CommandType::Cancel | CommandType::Retry(_) => true,
+            _ => false,
         }
     }
+
+    /// Check if this command acts on PRs once they've been closed, e.g. after landing
+    fn applies_to_closed(&self) -> bool {
+        matches!(self, CommandType::Backport(_) | CommandType::Revert(_))
+    }
 }
 
 impl Command {
+    /// The line of the comment the command was parsed from
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+
+    /// Check if this command may be run on a PR which bors no longer tracks because it's closed
+    pub fn applies_to_closed(&self) -> bool {
+        self.command_type.applies_to_closed()
+    }
+
     pub fn from_comment(c: &str) -> Option<Result<Self, ParseCommnadError>> {
         c.lines()
             .find(|line| line.starts_with('/'))
@@ -100,8 +267,30 @@ impl Command {
             return Err(ParseCommnadError);
         };
 
+        // Commands may also take the form of `<name>=<value>`, e.g. `p=1`
+        let (command_name, value) = match command_name.find('=') {
+            Some(idx) => (&command_name[..idx], Some(&command_name[idx + 1..])),
+            None => (command_name, None),
+        };
+        if value.is_some()
+            && !matches!(
+                command_name,
+                "p" | "priority"
+                    | "treeclosed"
+                    | "depends"
+                    | "rollup"
+                    | "supersede"
+                    | "delegate"
+                    | "backport"
+                    | "revert"
+                    | "r"
+            )
+        {
+            return Err(ParseCommnadError);
+        }
+
         // Arguments take the form of `<key>=<value>`
-        let args = iter.map(|arg| {
+        let mut args = iter.map(|arg| {
             if let Some(idx) = arg.find('=') {
                 (&arg[..idx], Some(&arg[idx + 1..]))
             } else {
@@ -110,10 +299,81 @@ impl Command {
         });
 
         let command_type = match command_name {
-            "land" | "merge" => CommandType::Land(Land::with_args(args)?),
+            "land" | "merge" | "r+" => CommandType::Land(Land::with_args(args)?),
+            // `r=<user>` approves on behalf of the reviewer, e.g. after a review outside of Github
+            "r" => {
+                let mut land = Land::with_args(args)?;
+                land.reviewer = Some(login(value)?);
+                CommandType::Land(land)
+            }
             "cancel" | "stop" => CommandType::Cancel,
             "help" | "h" => CommandType::Help,
+            "p" | "priority" if value.is_some() => {
+                CommandType::Priority(Priority::from_arg(value)?)
+            }
             "priority" => CommandType::Priority(Priority::with_args(args)?),
+            "solo" => CommandType::Solo(true),
+            "solo-" => CommandType::Solo(false),
+            "try" => CommandType::Try,
+            "rebase" => CommandType::Rebase,
+            "retry" => CommandType::Retry(attempt_overrides(args)?),
+            "status" => CommandType::Status,
+            "selfcheck" => CommandType::SelfCheck,
+            "rekick" => CommandType::Rekick,
+            "pause" => CommandType::Pause(true),
+            "resume" => CommandType::Pause(false),
+            "treeclosed" if value.is_some() => {
+                CommandType::TreeClosed(Some(Priority::from_arg(value)?.priority()))
+            }
+            "treeclosed-" => CommandType::TreeClosed(None),
+            "depends" if value.is_some() => {
+                CommandType::DependsOn(Some(pr_number(value).ok_or(ParseCommnadError)?))
+            }
+            "depends-" => CommandType::DependsOn(None),
+            "rollup" => match value {
+                Some(value) => {
+                    CommandType::Rollup(Some(Rollup::parse(value).ok_or(ParseCommnadError)?))
+                }
+                None => CommandType::Rollup(Some(Rollup::Always)),
+            },
+            "rollup-" => CommandType::Rollup(None),
+            "delegate" => match value {
+                Some(user) if !user.is_empty() => CommandType::Delegate(Some(Delegate::User(
+                    user.trim_start_matches('@').to_owned(),
+                ))),
+                _ => return Err(ParseCommnadError),
+            },
+            "delegate+" => CommandType::Delegate(Some(Delegate::Author)),
+            "delegate-" => CommandType::Delegate(None),
+            "supersede" => {
+                let value = value.or_else(|| match args.next() {
+                    Some((arg, None)) => Some(arg),
+                    _ => None,
+                });
+                CommandType::Supersede(pr_number(value).ok_or(ParseCommnadError)?)
+            }
+            "backport" => {
+                let value = value.or_else(|| match args.next() {
+                    Some((arg, None)) => Some(arg),
+                    _ => None,
+                });
+                match value {
+                    Some(branch) if !branch.is_empty() => CommandType::Backport(branch.to_owned()),
+                    _ => return Err(ParseCommnadError),
+                }
+            }
+            "revert" => {
+                let value = value.or_else(|| match args.next() {
+                    Some((arg, None)) => Some(arg),
+                    _ => None,
+                });
+                match value {
+                    Some(_) => {
+                        CommandType::Revert(Some(pr_number(value).ok_or(ParseCommnadError)?))
+                    }
+                    None => CommandType::Revert(None),
+                }
+            }
 
             _ => return Err(ParseCommnadError),
         };
@@ -133,8 +393,36 @@ impl Command {
         let mut is_authorized = false;
         let mut reason = None;
 
+        // Users delegated approval rights on this PR may land it regardless of their permissions
+        if self.command_type.is_delegable() && ctx.pr().is_delegate(ctx.sender()) {
+            return Ok(true);
+        }
+
+        let key = self.command_type.permission_key();
+        if let Some(permission) = ctx.config().command_permission(key) {
+            if Self::is_permitted(ctx, permission).await? {
+                is_authorized = true;
+            } else {
+                reason = Some(Self::denial_reason(permission));
+            }
+        // Check to see if the user belongs to a tier which may use the command
+        } else if let Some(tiers) = ctx.config().tiers() {
+            let required = tiers
+                .command_tier(key)
+                .unwrap_or_else(|| self.command_type.tier());
+            for tier in Tier::ALL.iter().filter(|tier| **tier >= required) {
+                if let Some(members) = tiers.members(*tier) {
+                    if Self::is_permitted(ctx, members).await? {
+                        is_authorized = true;
+                        break;
+                    }
+                }
+            }
+            if !is_authorized {
+                reason = Some(format!("Requires the '{}' tier", required.as_str()));
+            }
         // Check to see if the user is a collaborator
-        if ctx
+        } else if ctx
             .github()
             .repos()
             .is_collaborator(ctx.config().owner(), ctx.config().name(), ctx.sender())
@@ -143,7 +431,7 @@ impl Command {
         {
             is_authorized = true;
         } else {
-            reason = Some("Not Collaborator");
+            reason = Some("Not Collaborator".to_owned());
         }
 
         // Post a comment to Github if there was a reason why the user wasn't authorized
@@ -161,19 +449,103 @@ impl Command {
         Ok(is_authorized)
     }
 
+    /// Check to see if the user is explicitly allowed, is a member of an allowed team or has a
+    /// sufficient permission level
+    async fn is_permitted(
+        ctx: &CommandContext<'_>,
+        permission: &CommandPermission,
+    ) -> Result<bool> {
+        if permission.allows_user(ctx.sender()) {
+            return Ok(true);
+        }
+
+        // A team which can't be looked up, e.g. without the `read:org` scope, doesn't stop the
+        // user from being admitted by their role
+        match ctx
+            .github()
+            .is_team_member(permission.teams(), ctx.sender())
+            .await
+        {
+            Ok(true) => return Ok(true),
+            Ok(false) => {}
+            Err(e) => warn!(
+                "unable to check if {} is a member of {:?}: {:?}",
+                ctx.sender(),
+                permission.teams().collect::<Vec<_>>(),
+                e
+            ),
+        }
+
+        let role = match permission.role() {
+            Some(role) => role,
+            None => return Ok(false),
+        };
+        let level = ctx
+            .github()
+            .repos()
+            .get_collaborator_permission_level(
+                ctx.config().owner(),
+                ctx.config().name(),
+                ctx.sender(),
+            )
+            .await?
+            .into_inner();
+
+        Ok(Role::from_permission_level(&level) >= role)
+    }
+
+    fn denial_reason(permission: &CommandPermission) -> String {
+        if let Some(role) = permission.role() {
+            format!("Requires '{}' permission", role.as_str())
+        } else if permission.teams().next().is_some() {
+            let teams: Vec<_> = permission
+                .teams()
+                .map(|team| format!("@{}", team))
+                .collect();
+            format!("Requires membership of {}", teams.join(" or "))
+        } else {
+            "Not permitted to use this command".to_owned()
+        }
+    }
+
     pub async fn execute(&self, mut ctx: &mut CommandContext<'_>) -> Result<()> {
         info!("Executing command '{}'", self.command_type.name());
 
         match &self.command_type {
             CommandType::Land(l) => {
+                // Also one of the land gates, but checked up front so that the author's options
+                // aren't applied either
+                if Self::is_self_approval(ctx).await? {
+                    return Ok(());
+                }
+                if l.reviewer.is_some() && !Self::is_admin(ctx).await? {
+                    ctx.create_pr_comment(&format!(
+                        "@{}: :key: Insufficient privileges: Requires 'admin' permission to \
+                        approve on behalf of another reviewer",
+                        ctx.sender(),
+                    ))
+                    .await?;
+                    return Ok(());
+                }
+
                 if let Some(priority) = l.priority() {
                     Self::set_priority(&mut ctx, priority).await?;
                 }
                 if let Some(squash) = l.squash {
                     Self::set_squash(&mut ctx, squash).await?;
                 }
+                if let Some(solo) = l.solo {
+                    Self::set_solo(&mut ctx, solo).await?;
+                }
+                if let Some(strategy) = l.strategy {
+                    Self::set_merge_strategy(&mut ctx, strategy);
+                }
 
-                Self::mark_pr_ready_to_land(&mut ctx).await?;
+                if l.stack {
+                    Self::mark_stack_ready_to_land(&mut ctx, l).await?;
+                } else {
+                    Self::mark_pr_ready_to_land(&mut ctx, l).await?;
+                }
             }
             CommandType::Cancel => Self::cancel_land(ctx).await?,
             CommandType::Help => {
@@ -181,6 +553,21 @@ impl Command {
                     .await?
             }
             CommandType::Priority(p) => Self::set_priority(&mut ctx, p.priority()).await?,
+            CommandType::Solo(solo) => Self::set_solo(&mut ctx, *solo).await?,
+            CommandType::Try => Self::try_build(&mut ctx).await?,
+            CommandType::Retry(overrides) => Self::retry(&mut ctx, overrides).await?,
+            CommandType::Status => Self::status(ctx).await?,
+            CommandType::TreeClosed(threshold) => Self::set_tree_closed(ctx, *threshold).await?,
+            CommandType::DependsOn(number) => Self::set_depends_on(ctx, *number).await?,
+            CommandType::Rollup(rollup) => Self::set_rollup(ctx, *rollup),
+            CommandType::Supersede(old) => Self::supersede(ctx, *old).await?,
+            CommandType::Delegate(delegate) => Self::set_delegate(ctx, delegate.as_ref()).await?,
+            CommandType::SelfCheck => Self::selfcheck(ctx).await?,
+            CommandType::Rekick => Self::rekick(ctx).await?,
+            CommandType::Pause(pause) => Self::set_paused(ctx, *pause).await?,
+            CommandType::Rebase => Self::rebase(ctx).await?,
+            CommandType::Backport(branch) => Self::backport(ctx, branch).await?,
+            CommandType::Revert(number) => Self::revert(ctx, *number).await?,
         }
 
         Ok(())
@@ -189,6 +576,9 @@ impl Command {
     async fn set_priority(ctx: &mut CommandContext<'_>, priority: u32) -> Result<()> {
         info!("#{}: set priority to {}", ctx.pr().number, priority);
 
+        ctx.pr_mut().priority = priority;
+
+        // The high-priority label is kept in sync so that a PR's priority is visible on Github
         let label = ctx.config().labels().high_priority().to_owned();
         if priority > 0 {
             ctx.set_label(&label).await?;
@@ -213,37 +603,206 @@ impl Command {
         Ok(())
     }
 
-    async fn mark_pr_ready_to_land(ctx: &mut CommandContext<'_>) -> Result<()> {
-        use crate::state::Status;
+    fn set_merge_strategy(ctx: &mut CommandContext<'_>, strategy: MergeStrategy) {
+        info!(
+            "#{}: set merge strategy to {}",
+            ctx.pr().number,
+            strategy.as_str()
+        );
+
+        ctx.pr_mut().merge_strategy = Some(strategy);
+    }
+
+    async fn set_solo(ctx: &mut CommandContext<'_>, solo: bool) -> Result<()> {
+        info!("#{}: set solo to {}", ctx.pr().number, solo);
+
+        // The solo label would otherwise be overridden by any rollup flag set via command
+        ctx.pr_mut().rollup = None;
+        let label = ctx.config().labels().solo().to_owned();
+
+        if solo {
+            ctx.set_label(&label).await?;
+        } else {
+            ctx.remove_label(&label).await?;
+        }
 
+        Ok(())
+    }
+
+    fn set_rollup(ctx: &mut CommandContext<'_>, rollup: Option<Rollup>) {
+        info!(
+            "#{}: set rollup to {}",
+            ctx.pr().number,
+            rollup.map(|rollup| rollup.as_str()).unwrap_or("unset")
+        );
+
+        ctx.pr_mut().rollup = rollup;
+    }
+
+    /// Queue the PR for landing. If a sha is given the approval is pinned to it, which must be the
+    /// PR's head, and the PR is only tested as long as its head doesn't change.
+    async fn mark_pr_ready_to_land(ctx: &mut CommandContext<'_>, land: &Land) -> Result<()> {
         info!("attempting to mark pr #{} ReadyToLand", ctx.pr().number);
 
+        if Self::passes_land_gates(ctx, land.sha.as_deref()).await? {
+            Self::queue_for_landing(ctx, land).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that the PR in focus can be queued for landing, letting the sender know on the PR why
+    /// it can't be. A sha the approval is pinned to must be the PR's head.
+    async fn passes_land_gates(ctx: &mut CommandContext<'_>, sha: Option<&str>) -> Result<bool> {
+        if Self::is_self_approval(ctx).await? {
+            return Ok(false);
+        }
+
         // Skip marking for land on draft PRs
         if ctx.pr().is_draft() {
             ctx.create_pr_comment(
                 ":clipboard: Looks like this PR is still in progress, unable to queue for landing",
             )
             .await?;
-            return Ok(());
+            return Ok(false);
         }
 
-        match ctx.pr().status {
-            Status::InReview => {
-                if ctx.pr().approved || !ctx.config().require_review() {
-                    ctx.update_pr_status(Status::Queued).await?;
-                    info!("pr #{} queued for landing", ctx.pr().number);
-                } else {
-                    info!(
-                        "pr #{} is missing approvals, unable to queue for landing",
-                        ctx.pr().number
-                    );
+        let blocked = ctx.config().labels().blocked();
+        if ctx.pr().has_label(blocked) {
+            let msg = format!(
+                ":no_entry: This PR is labeled `{}`, unable to queue for landing",
+                blocked
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(false);
+        }
+
+        if Self::is_missing_template_items(ctx).await? {
+            return Ok(false);
+        }
+
+        if let Some(len) = overlong_subject(ctx.config(), ctx.pr()) {
+            let msg = format!(
+                ":straight_ruler: This PR is squashed when it lands and its commit's subject line \
+                would be {} characters long, more than the {} allowed. Shorten the PR's title, \
+                unable to queue for landing",
+                len,
+                ctx.config().max_subject_length(),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(false);
+        }
+
+        if Self::is_cooling_down(ctx).await?
+            || Self::has_unresolved_threads(ctx).await?
+            || Self::lacks_code_owner_approval(ctx).await?
+            || Self::lacks_protected_path_approval(ctx).await?
+            || Self::breaks_commit_lint(ctx).await?
+        {
+            return Ok(false);
+        }
+
+        let head = ctx.pr().head_ref_oid.to_string();
+        if let Some(sha) = sha.filter(|sha| !head.starts_with(&sha.to_ascii_lowercase())) {
+            info!(
+                "pr #{} was approved at {} but its head is {}",
+                ctx.pr().number,
+                sha,
+                head
+            );
+
+            let msg = format!(
+                "@{} :exclamation: `{}` isn't this PR's head, `{}`, unable to queue for \
+                landing. Review the latest changes and approve them instead.",
+                ctx.sender(),
+                sha,
+                head,
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(false);
+        }
+
+        // A PR which is already queued was approved when it was queued
+        if ctx.pr().status.is_queued() || ctx.pr().status.is_testing() {
+            return Ok(true);
+        }
+
+        let missing_approvals = ctx.pr().missing_approvals(ctx.config());
+        if missing_approvals > 0 {
+            info!(
+                "pr #{} needs {} more approvals, unable to queue for landing",
+                ctx.pr().number,
+                missing_approvals
+            );
+
+            let msg = format!(
+                "@{} :exclamation: This PR has {} of the {} approving reviews it requires, \
+                unable to queue for landing",
+                ctx.sender(),
+                ctx.pr().approved_by.len(),
+                ctx.config().required_approvals(),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(false);
+        }
+
+        if !ctx.pr().approved && ctx.config().require_review() {
+            info!(
+                "pr #{} is missing approvals, unable to queue for landing",
+                ctx.pr().number
+            );
+
+            let msg = format!(
+                "@{} :exclamation: This PR is still missing approvals, unable to queue for landing",
+                ctx.sender(),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Queue the PR in focus for landing, once it has passed the land gates
+    async fn queue_for_landing(ctx: &mut CommandContext<'_>, land: &Land) -> Result<()> {
+        use crate::state::Status;
 
+        match ctx.pr().status {
+            // Queueing a PR for landing supersedes any try build in progress and approving a
+            // failed PR requeues it
+            Status::InReview | Status::Trying { .. } | Status::Failed { .. } => {
+                let approver = ctx.sender().to_owned();
+                let approved_oid = land.sha.as_ref().map(|_| ctx.pr().head_ref_oid.clone());
+                ctx.pr_mut().approver = Some(approver);
+                ctx.pr_mut().approved_oid = approved_oid;
+                ctx.pr_mut().reviewer = land.reviewer.clone();
+                ctx.pr_mut().spurious_retries = 0;
+                ctx.update_pr_status(Status::Queued).await?;
+                info!("pr #{} queued for landing", ctx.pr().number);
+
+                let dependencies = ctx.unlanded_dependencies();
+                if !dependencies.is_empty() {
                     let msg = format!(
-                        "@{} :exclamation: This PR is still missing approvals, unable to queue for landing",
-                        ctx.sender(),
+                        ":link: This PR will be tested once its dependencies have landed: {}",
+                        pr_list(&dependencies),
                     );
                     ctx.create_pr_comment(&msg).await?;
                 }
+
+                if let Some(remaining) = ctx.pr().grace_remaining(ctx.config()) {
+                    Self::create_grace_status(ctx, remaining).await?;
+                }
+
+                if let Some(threshold) = ctx.tree_closed() {
+                    if is_tree_closed_for(Some(threshold), ctx.config(), ctx.pr()) {
+                        let msg = format!(
+                            ":evergreen_tree: The tree is currently closed for PRs below \
+                            priority {}, this PR will be tested once it reopens",
+                            threshold,
+                        );
+                        ctx.create_pr_comment(&msg).await?;
+                    }
+                }
             }
             Status::Queued | Status::Testing { .. } => {
                 info!("pr #{} already queued for landing", ctx.pr().number);
@@ -260,124 +819,1253 @@ impl Command {
         Ok(())
     }
 
-    async fn cancel_land(ctx: &mut CommandContext<'_>) -> Result<()> {
+    /// Queue the PRs this PR is stacked on top of, from the bottom of the stack up, followed by
+    /// this PR itself. Each PR depends on the one below it so that the stack lands in order.
+    /// Every PR in the stack which isn't already queued must pass the land gates; a sha the
+    /// approval is pinned to is this PR's head, and the PRs below are pinned to their own heads.
+    async fn mark_stack_ready_to_land(ctx: &mut CommandContext<'_>, land: &Land) -> Result<()> {
         use crate::state::Status;
 
-        info!("Canceling land of pr #{}", ctx.pr().number);
+        let stack = ctx.stack();
+        info!(
+            "attempting to mark the stack below pr #{} ReadyToLand: {:?}",
+            ctx.pr().number,
+            stack
+        );
+
+        // Nothing in the stack is queued unless all of it can be. Each PR which can't be is told
+        // why, as if it had been landed on its own.
+        let top = ctx.pr().number;
+        let mut not_ready = Vec::new();
+        for &number in &stack {
+            let is_queued = ctx.pull(number).map_or(false, |pull| {
+                pull.status.is_queued() || pull.status.is_testing()
+            });
+            if is_queued {
+                continue;
+            }
 
-        ctx.update_pr_status(Status::InReview).await
-    }
-}
+            ctx.focus(number);
+            let passes = Self::passes_land_gates(ctx, None).await;
+            ctx.focus(top);
+            if !passes? {
+                not_ready.push(number);
+            }
+        }
+        let top_passes = Self::passes_land_gates(ctx, land.sha.as_deref()).await?;
+        if !not_ready.is_empty() {
+            let msg = format!(
+                "@{} :exclamation: Unable to queue this stack for landing, these PRs in it can't \
+                be queued yet: {}",
+                ctx.sender(),
+                pr_list(&not_ready),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(());
+        }
+        if !top_passes {
+            return Ok(());
+        }
 
-struct Help<'a> {
-    config: &'a RepoConfig,
-    project_board: Option<&'a ProjectBoard>,
-}
+        let approver = ctx.sender().to_owned();
+        let mut below = None;
+        for number in stack {
+            let pull = ctx.pull_mut(number).expect("PR should exist");
+            pull.depends_on.extend(below);
+
+            if matches!(
+                pull.status,
+                Status::InReview | Status::Trying { .. } | Status::Failed { .. }
+            ) {
+                pull.approver = Some(approver.clone());
+                pull.approved_oid = land.sha.as_ref().map(|_| pull.head_ref_oid.clone());
+                pull.reviewer = land.reviewer.clone();
+                pull.spurious_retries = 0;
+                ctx.update_status_of(number, Status::Queued).await?;
+                info!("pr #{} queued for landing as part of a stack", number);
 
-impl<'a> Help<'a> {
-    fn new(config: &'a RepoConfig, project_board: Option<&'a ProjectBoard>) -> Self {
-        Self {
-            config,
-            project_board,
+                let msg = format!(
+                    ":pancakes: Queued for landing as part of the stack below #{}",
+                    top
+                );
+                ctx.create_comment_on(number, &msg).await?;
+            }
+
+            below = Some(number);
         }
+        ctx.pr_mut().depends_on.extend(below);
+
+        Self::queue_for_landing(ctx, land).await
     }
-}
 
-impl std::fmt::Display for Help<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        const NON_BREAKING_SPACE: &str = "&nbsp;";
+    async fn try_build(ctx: &mut CommandContext<'_>) -> Result<()> {
+        use crate::state::Status;
 
-        writeln!(f, "<details>")?;
-        write!(f, "<summary>")?;
-        write!(f, "Bors help and documentation")?;
-        writeln!(f, "</summary>")?;
-        writeln!(f, "<br />")?;
-        writeln!(f)?;
+        info!("attempting a try build of pr #{}", ctx.pr().number);
 
-        let queue_link = self.project_board.map(|p| p.board().html_url.as_str());
-        let queue = if let Some(link) = queue_link {
-            format!("[Merge Queue]({})", link)
-        } else {
-            "Merge Queue".to_owned()
-        };
-        writeln!(
-            f,
-            "Bors is a Github App used to manage merging of PRs in order to ensure that CI is always \
-            green. It does so by maintaining a {queue}. Once a PR reaches the head of the Merge \
-            Queue it is rebased on top of the latest version of the PR's `base-branch` (generally \
-            `master`) and then triggers CI. If CI comes back green the PR is then merged into the \
-            `base-branch`. Regardless of the outcome, the next PR is the queue is then processed.",
-            queue = queue,
-        )?;
-        writeln!(f)?;
+        if ctx.pr().status.is_queued() || ctx.pr().status.is_testing() {
+            let msg = format!(
+                "@{} :bulb: This PR is already queued for landing",
+                ctx.sender(),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(());
+        }
 
-        //
-        // General
-        //
-        writeln!(f, "### General")?;
+        let branch = ctx.config().try_branch().to_owned();
+        let base_ref_name = ctx.pr().base_ref_name.clone();
+        let head_ref_oid = ctx.pr().head_ref_oid.clone();
+        let number = ctx.pr().number;
+        let strategy = ctx.pr().merge_strategy(ctx.config());
+        let annotations = commit_message::annotations(ctx.config(), ctx.pr());
+
+        // Attempt to rebase the PR onto 'base_ref' and push to the try branch for testing
+        let merge_oid = match ctx.git_repository().fetch_and_rebase(
+            &base_ref_name,
+            &head_ref_oid,
+            &branch,
+            number,
+            &annotations,
+            strategy,
+        )? {
+            Some(merge_oid) => merge_oid,
+            None => {
+                ctx.create_pr_comment(":lock: Merge Conflict").await?;
+                return Ok(());
+            }
+        };
+        ctx.git_repository().push_branch(&branch)?;
+        info!("pushed '{}' branch", branch);
+
+        ctx.create_pr_comment(&format!(
+            ":hourglass: Trying commit {} with merge {}",
+            head_ref_oid, merge_oid
+        ))
+        .await?;
+        let overrides = ctx.pr_mut().next_attempt.take().unwrap_or_default();
+        ctx.update_pr_status(Status::trying(merge_oid, overrides))
+            .await
+    }
 
-        if let Some(link) = queue_link {
-            writeln!(
-                f,
-                "- This project's Merge Queue can be found [here]({}).",
-                link
-            )?;
+    /// Rebase the PR's branch onto the latest version of its base branch, updating it in-place
+    async fn rebase(ctx: &mut CommandContext<'_>) -> Result<()> {
+        info!("attempting to rebase pr #{}", ctx.pr().number);
+
+        if ctx.pr().status.is_testing() {
+            let msg = format!(
+                "@{} :hourglass: This PR is being tested, unable to rebase it until testing \
+                completes",
+                ctx.sender()
+            );
+            return ctx.create_pr_comment(&msg).await;
         }
+        let head_repo = match ctx.pr().head_repo.clone() {
+            Some(head_repo) => head_repo,
+            None => {
+                let msg = format!(
+                    "@{} :exclamation: This PR's branch no longer exists, unable to rebase it",
+                    ctx.sender()
+                );
+                return ctx.create_pr_comment(&msg).await;
+            }
+        };
 
-        if self.config.require_review() {
-            writeln!(
-                f,
-                "- This project requires PRs to be [Reviewed]\
-                (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/about-pull-request-reviews) \
-                and Approved before they can be queued for merging.",
-            )?;
+        let base_ref = ctx.pr().base_ref_name.clone();
+        let head_oid = ctx.pr().head_ref_oid.clone();
+        let base_oid = ctx.git_repository().fetch_base(&base_ref)?;
+        if ctx.git_repository().is_ancestor(&base_oid, &head_oid)? {
+            let msg = format!(
+                "@{} :information_source: This PR is already up to date with `{}`",
+                ctx.sender(),
+                base_ref
+            );
+            return ctx.create_pr_comment(&msg).await;
         }
 
-        if self.config.maintainer_mode() {
-            writeln!(
-                f,
-                "- Before PR's can be merged they must be configured with \
+        let rebased = match ctx.git_repository().fetch_and_rebase_as_is(
+            &base_ref,
+            &head_oid,
+            REBASE_BRANCH,
+        )? {
+            Some(rebased) => rebased,
+            None => {
+                let msg = format!(
+                    ":lock: This PR conflicts with `{}`, it needs to be rebased by hand",
+                    base_ref
+                );
+                return ctx.create_pr_comment(&msg).await;
+            }
+        };
+
+        let msg = match ctx.update_pr_in_place(&head_repo, &rebased).await {
+            None => format!(
+                ":arrows_counterclockwise: Rebased onto `{}` at {}, the PR's head is now {}",
+                base_ref, base_oid, rebased
+            ),
+            Some(PushFailure::PermissionDenied) => {
+                ":exclamation: Unable to push the rebased commits to this PR. Make sure that \
                 [\"Allow edits from maintainers\"]\
                 (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/allowing-changes-to-a-pull-request-branch-created-from-a-fork) \
-                enabled. This is needed for Bors to be able to update PR's in-place \
-                so that Github can properly recognize and mark them as \"merged\" \
-                once they've been merged into the upstream branch.",
-            )?;
+                is enabled."
+                    .to_owned()
+            }
+            Some(PushFailure::Stale) => {
+                ":exclamation: This PR was pushed to while it was being rebased, unable to push \
+                the rebased commits"
+                    .to_owned()
+            }
+            Some(PushFailure::Transient) => {
+                ":exclamation: Unable to push the rebased commits to this PR, try again later"
+                    .to_owned()
+            }
+        };
+        ctx.create_pr_comment(&msg).await
+    }
+
+    /// Cherry-pick the commits a PR landed onto `target`, opening a PR to land them there
+    async fn backport(ctx: &mut CommandContext<'_>, target: &str) -> Result<()> {
+        use github::client::NewPullRequest;
+
+        let number = ctx.pr().number;
+        let base_ref = ctx.pr().base_ref_name.clone();
+        info!("attempting to backport pr #{} to '{}'", number, target);
+
+        if target == base_ref {
+            let msg = format!(
+                "@{} :information_source: This PR already targets `{}`",
+                ctx.sender(),
+                target
+            );
+            return ctx.create_pr_comment(&msg).await;
         }
 
-        //
-        // Commands
-        //
-        writeln!(f, "### Commands")?;
-        writeln!(
-            f,
-            "Bors actions can be triggered by posting a comment which includes a line of the form `/<action>`."
-        )?;
-        writeln!(f, "| Command | Action | Description |")?;
-        writeln!(f, "| --- | --- | --- |")?;
-        writeln!(
-            f,
-            "| __Land__ | `land`, `merge` | attempt to land or merge a PR |"
-        )?;
-        writeln!(
-            f,
-            "| __Cancel__ | `cancel`, `stop` | stop an in-progress land |"
-        )?;
-        writeln!(f, "| __Help__ | `help`, `h` | show this help message |")?;
-        writeln!(
-            f,
-            "| __Priority__ | `priority` | set the priority level for a PR |"
-        )?;
-        writeln!(f)?;
+        let commits = ctx.git_repository().landed_commits(&base_ref, number)?;
+        if commits.is_empty() {
+            let msg = format!(
+                "@{} :exclamation: This PR hasn't landed on `{}`, it can only be backported once \
+                it has",
+                ctx.sender(),
+                base_ref
+            );
+            return ctx.create_pr_comment(&msg).await;
+        }
 
-        //
-        // Options
-        //
-        writeln!(f, "### Options")?;
-        writeln!(
-            f,
-            "Options for Pull Requests are configured through the application of labels.",
-        )?;
+        let target_oid = match ctx.git_repository().fetch_base(target) {
+            Ok(oid) => oid,
+            Err(e) => {
+                info!("unable to fetch '{}': {}", target, e);
+                let msg = format!(
+                    "@{} :exclamation: Unable to fetch `{}`, make sure that the branch exists",
+                    ctx.sender(),
+                    target
+                );
+                return ctx.create_pr_comment(&msg).await;
+            }
+        };
+
+        let branch = format!("{}{}-{}", BACKPORT_BRANCH_PREFIX, number, target);
+        let head = match ctx
+            .git_repository()
+            .cherry_pick_onto(&target_oid, &commits, &branch)?
+        {
+            Some(head) => head,
+            None => {
+                let msg = format!(
+                    ":lock: This PR's commits conflict with `{}`, it needs to be backported by hand",
+                    target
+                );
+                return ctx.create_pr_comment(&msg).await;
+            }
+        };
+        ctx.git_repository().force_push(&branch, &head)?;
+        info!("pushed '{}' branch", branch);
+
+        let picked = commits
+            .iter()
+            .map(|oid| format!("- {}", oid))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let pull = NewPullRequest {
+            title: format!("[{}] {}", target, ctx.pr().title),
+            body: Some(format!(
+                "Backport of #{} to `{}`, requested by @{}.\n\n\
+                Cherry-picked from the commits it landed on `{}`:\n{}",
+                number,
+                target,
+                ctx.sender(),
+                base_ref,
+                picked
+            )),
+            head: branch,
+            base: target.to_owned(),
+            maintainer_can_modify: None,
+            draft: None,
+        };
+        let created = ctx
+            .github()
+            .pulls()
+            .create(ctx.config().owner(), ctx.config().name(), pull)
+            .await?
+            .into_inner();
+        info!("opened backport pr #{} of pr #{}", created.number, number);
+
+        let msg = format!(
+            ":cherries: Backported to `{}` in #{}",
+            target, created.number
+        );
+        ctx.create_pr_comment(&msg).await
+    }
+
+    /// Revert the commits PR `number`, or this PR, landed, opening a PR for the revert which is
+    /// queued for landing straight away at high priority
+    async fn revert(ctx: &mut CommandContext<'_>, number: Option<u64>) -> Result<()> {
+        use crate::state::{PullRequestState, Status};
+        use github::client::NewPullRequest;
+
+        let number = number.unwrap_or(ctx.pr().number);
+        info!("attempting to revert pr #{}", number);
+
+        let reverted = ctx
+            .github()
+            .pulls()
+            .get(ctx.config().owner(), ctx.config().name(), number)
+            .await?
+            .into_inner();
+        let base_ref = reverted.base.git_ref.clone();
+
+        let commits = ctx.git_repository().landed_commits(&base_ref, number)?;
+        if commits.is_empty() {
+            let msg = format!(
+                "@{} :exclamation: #{} hasn't landed on `{}`, unable to revert it",
+                ctx.sender(),
+                number,
+                base_ref
+            );
+            return ctx.create_pr_comment(&msg).await;
+        }
+
+        let base_oid = ctx.git_repository().fetch_base(&base_ref)?;
+        let branch = format!("{}{}", REVERT_BRANCH_PREFIX, number);
+        let head = match ctx
+            .git_repository()
+            .revert_onto(&base_oid, &commits, &branch)?
+        {
+            Some(head) => head,
+            None => {
+                let msg = format!(
+                    ":lock: #{} conflicts with what has landed on `{}` since, it needs to be \
+                    reverted by hand",
+                    number, base_ref
+                );
+                return ctx.create_pr_comment(&msg).await;
+            }
+        };
+        ctx.git_repository().force_push(&branch, &head)?;
+        info!("pushed '{}' branch", branch);
+
+        let reverted_commits = commits
+            .iter()
+            .map(|oid| format!("- {}", oid))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let pull = NewPullRequest {
+            title: format!("Revert \"{}\"", reverted.title),
+            body: Some(format!(
+                "Reverts #{}, requested by @{}.\n\nReverted commits:\n{}",
+                number,
+                ctx.sender(),
+                reverted_commits
+            )),
+            head: branch,
+            base: base_ref,
+            maintainer_can_modify: None,
+            draft: None,
+        };
+        let created = ctx
+            .github()
+            .pulls()
+            .create(ctx.config().owner(), ctx.config().name(), pull)
+            .await?
+            .into_inner();
+        info!("opened revert pr #{} of pr #{}", created.number, number);
+
+        // The revert is approved by whoever asked for it, without waiting for a review
+        let mut revert = PullRequestState::from_pull_request(&created);
+        revert.approver = Some(ctx.sender().to_owned());
+        revert.approved = true;
+        revert.priority = REVERT_PRIORITY;
+        let label = ctx.config().labels().high_priority().to_owned();
+        revert.add_label(ctx.config(), ctx.github(), &label).await?;
+        ctx.track_pull(revert);
+        ctx.update_status_of(created.number, Status::Queued).await?;
+
+        let msg = format!(
+            ":rewind: Opened #{} to revert #{}, it's queued for landing at high priority",
+            created.number, number
+        );
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn retry(ctx: &mut CommandContext<'_>, overrides: &AttemptOverrides) -> Result<()> {
+        use crate::state::{Retry, Status};
+
+        info!("attempting to retry pr #{}", ctx.pr().number);
+
+        // Overriding the repo's settings, e.g. to skip a required check, is reserved for admins
+        if !overrides.is_empty() && !Self::is_admin(ctx).await? {
+            let msg = format!(
+                "@{}: :key: Insufficient privileges: Requires 'admin' permission to override \
+                settings for an attempt",
+                ctx.sender(),
+            );
+            ctx.create_pr_comment(&msg).await?;
+            return Ok(());
+        }
+        let next_attempt = Some(overrides.clone()).filter(|o| !o.is_empty());
+
+        // A failed PR is requeued, keeping its original approver and priority
+        if ctx.pr().status.is_failed() {
+            if Self::is_cooling_down(ctx).await? {
+                return Ok(());
+            }
+
+            ctx.pr_mut().next_attempt = next_attempt;
+            ctx.update_pr_status(Status::Queued).await?;
+            info!("pr #{} requeued for landing", ctx.pr().number);
+            return Ok(());
+        }
+
+        let in_review = matches!(ctx.pr().status, Status::InReview);
+        match (ctx.pr().retry, in_review) {
+            (Some(Retry::Try), true) => {
+                ctx.pr_mut().retry = None;
+                ctx.pr_mut().next_attempt = next_attempt;
+                Self::try_build(ctx).await?;
+            }
+            _ => {
+                let msg = format!(
+                    "@{} :bulb: There is no failed land or try build to retry",
+                    ctx.sender(),
+                );
+                ctx.create_pr_comment(&msg).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show when a newly queued PR's grace period ends, and it will start being tested, in its
+    /// pending status
+    async fn create_grace_status(
+        ctx: &CommandContext<'_>,
+        remaining: std::time::Duration,
+    ) -> Result<()> {
+        let starts_at = chrono::Utc::now()
+            + chrono::Duration::from_std(remaining).unwrap_or_else(|_| chrono::Duration::zero());
+        let mut description = StatusDescription::new("Queued")
+            .detail(
+                Importance::High,
+                format!(
+                    "tests start in {} (at {} UTC)",
+                    format_wait(remaining),
+                    starts_at.format("%H:%M"),
+                ),
+            )
+            .detail(Importance::Low, "unless canceled");
+        if let Some(position) = ctx.queue_position() {
+            description = description.detail(
+                Importance::Medium,
+                format!("position {} in the queue", position),
+            );
+        }
+        // The PR may have to wait for its turn in the queue after its grace period ends
+        if let Some(wait) = ctx.estimated_wait().filter(|wait| *wait > remaining) {
+            description = description.detail(
+                Importance::Low,
+                format!("estimated to start in {}", format_wait(wait)),
+            );
+        }
+        let summary = description.build();
+        create_pending_status(ctx.config(), ctx.github(), ctx.pr(), &description, &summary).await
+    }
+
+    /// Check if the PR's author is trying to approve their own PR, when that isn't allowed, letting
+    /// them know why nothing happened. Authors the PR's approval rights were delegated to may
+    /// still land it.
+    async fn is_self_approval(ctx: &CommandContext<'_>) -> Result<bool> {
+        let author = match &ctx.pr().author {
+            Some(author) => author,
+            None => return Ok(false),
+        };
+        if !ctx.config().block_self_approval()
+            || author != ctx.sender()
+            || ctx.pr().is_delegate(ctx.sender())
+        {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} can't be approved by its author {}",
+            ctx.pr().number,
+            author
+        );
+
+        let msg = format!(
+            "@{} :no_entry_sign: PRs can't be approved by their own author, another maintainer \
+            needs to queue this PR for landing",
+            ctx.sender(),
+        );
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check if the sender is an admin of the repo
+    async fn is_admin(ctx: &CommandContext<'_>) -> Result<bool> {
+        let level = ctx
+            .github()
+            .repos()
+            .get_collaborator_permission_level(
+                ctx.config().owner(),
+                ctx.config().name(),
+                ctx.sender(),
+            )
+            .await?
+            .into_inner();
+
+        Ok(Role::from_permission_level(&level) >= Role::Admin)
+    }
+
+    /// Check if the PR is cooling down after a failed land, letting the sender know when it can be
+    /// queued again if it is
+    async fn is_cooling_down(ctx: &CommandContext<'_>) -> Result<bool> {
+        let remaining = match ctx.pr().cooldown_remaining() {
+            Some(remaining) => remaining,
+            None => return Ok(false),
+        };
+
+        info!("pr #{} is cooling down", ctx.pr().number);
+
+        let msg = format!(
+            "@{} :hourglass: This PR recently failed to land and can be queued again in {}s",
+            ctx.sender(),
+            remaining.as_secs() + 1,
+        );
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check if the PR has review threads which haven't been resolved, when the repo requires them
+    /// all to be resolved before a PR is queued, listing them for the sender
+    async fn has_unresolved_threads(ctx: &CommandContext<'_>) -> Result<bool> {
+        if !ctx.config().require_resolved_threads() {
+            return Ok(false);
+        }
+
+        let threads = ctx
+            .github()
+            .unresolved_review_threads(ctx.config().owner(), ctx.config().name(), ctx.pr().number)
+            .await?;
+        if threads.is_empty() {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} has {} unresolved review threads",
+            ctx.pr().number,
+            threads.len()
+        );
+
+        let mut msg = format!(
+            "@{} :speech_balloon: This PR has {} unresolved review threads, which need to be \
+            resolved before it can be queued for landing:\n",
+            ctx.sender(),
+            threads.len(),
+        );
+        for thread in threads.iter().take(MAX_LISTED_THREADS) {
+            msg.push_str(&format!("\n- {}", link(&thread.path, &thread.url)));
+        }
+        if threads.len() > MAX_LISTED_THREADS {
+            msg.push_str(&format!(
+                "\n- and {} more",
+                threads.len() - MAX_LISTED_THREADS
+            ));
+        }
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check that each file the PR changes which has code owners is approved by one of them,
+    /// refusing to queue it if any aren't
+    async fn lacks_code_owner_approval(ctx: &mut CommandContext<'_>) -> Result<bool> {
+        if !ctx.config().require_code_owner_approval() {
+            return Ok(false);
+        }
+
+        let base_ref = ctx.pr().base_ref_name.clone();
+        let code_owners = match codeowners::load(ctx.git_repository(), &base_ref)? {
+            Some(code_owners) => code_owners,
+            None => return Ok(false),
+        };
+
+        let unapproved = codeowners::unapproved_files(
+            ctx.config(),
+            ctx.github(),
+            &code_owners,
+            ctx.pr(),
+            ctx.sender(),
+        )
+        .await?;
+        if unapproved.is_empty() {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} changes {} files without their code owners' approval",
+            ctx.pr().number,
+            unapproved.len()
+        );
+
+        let msg = format!(
+            "@{} :lock: This PR changes files which none of their code owners have approved, \
+            unable to queue for landing:\n{}",
+            ctx.sender(),
+            codeowners::report(&unapproved),
+        );
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check that the PR has the approvals required to change any protected files it changes,
+    /// refusing to queue it if it doesn't
+    async fn lacks_protected_path_approval(ctx: &CommandContext<'_>) -> Result<bool> {
+        let missing =
+            protected_paths::missing_approvals(ctx.config(), ctx.github(), ctx.pr(), ctx.sender())
+                .await?;
+        if missing.is_empty() {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} lacks the approvals required to change protected files",
+            ctx.pr().number
+        );
+
+        let mut msg = format!(
+            "@{} :closed_lock_with_key: This PR changes protected files, unable to queue for \
+            landing until it has:\n",
+            ctx.sender(),
+        );
+        for item in missing {
+            msg.push_str(&format!("\n- {}", item));
+        }
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check that the PR's title and description contain what the repo requires of them, refusing
+    /// to queue it if anything is missing
+    async fn is_missing_template_items(ctx: &CommandContext<'_>) -> Result<bool> {
+        let template = match ctx.config().pr_template() {
+            Some(template) => template,
+            None => return Ok(false),
+        };
+
+        let missing = pr_template::missing(template, &ctx.pr().title, &ctx.pr().body);
+        if missing.is_empty() {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} is missing {} items required by the PR template",
+            ctx.pr().number,
+            missing.len()
+        );
+
+        let mut msg = format!(
+            "@{} :memo: This PR is missing some of what this repo requires of PRs, unable to \
+            queue for landing. Edit its title or description to add:\n",
+            ctx.sender(),
+        );
+        for item in missing {
+            msg.push_str(&format!("\n- {}", item));
+        }
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Check the PR's commit messages against the repo's commit lint rules, refusing to queue it if
+    /// any of them are broken. A PR which is squashed is checked as the commit it would land as.
+    async fn breaks_commit_lint(ctx: &CommandContext<'_>) -> Result<bool> {
+        use github::client::PaginationOptions;
+
+        let rules = match ctx.config().commit_lint() {
+            Some(rules) => rules,
+            None => return Ok(false),
+        };
+
+        let commits: Vec<Violations> = if ctx.pr().merge_strategy(ctx.config())
+            == MergeStrategy::Squash
+        {
+            let message = commit_message::squash_message(ctx.config().squash_template(), ctx.pr());
+            vec![Violations {
+                commit: "The squashed commit".to_owned(),
+                problems: commit_lint::lint(rules, &message),
+            }]
+        } else {
+            let mut commits = Vec::new();
+            let mut page = Some(1);
+            while let Some(current) = page {
+                let response = ctx
+                    .github()
+                    .pulls()
+                    .list_commits(
+                        ctx.config().owner(),
+                        ctx.config().name(),
+                        ctx.pr().number,
+                        Some(PaginationOptions {
+                            page: Some(current),
+                            per_page: Some(100),
+                        }),
+                    )
+                    .await?;
+                page = response.pagination().next_page;
+
+                commits.extend(response.into_inner().iter().map(|commit| Violations {
+                    commit: commit.sha.to_string(),
+                    problems: commit_lint::lint(rules, &commit.commit.message),
+                }));
+            }
+            commits
+        };
+        let violations: Vec<_> = commits
+            .into_iter()
+            .filter(|commit| !commit.problems.is_empty())
+            .collect();
+        if violations.is_empty() {
+            return Ok(false);
+        }
+
+        info!(
+            "pr #{} has {} commits breaking the commit lint rules",
+            ctx.pr().number,
+            violations.len()
+        );
+
+        let msg = format!(
+            "@{} :pencil2: Some of this PR's commit messages don't follow this repo's rules, \
+            unable to queue for landing:\n{}",
+            ctx.sender(),
+            commit_lint::report(&violations),
+        );
+        ctx.create_pr_comment(&msg).await?;
+
+        Ok(true)
+    }
+
+    async fn status(ctx: &CommandContext<'_>) -> Result<()> {
+        use crate::state::Status;
+
+        let status = match &ctx.pr().status {
+            Status::InReview => "In review".to_owned(),
+            Status::Queued => "Queued for landing".to_owned(),
+            Status::Testing { .. } => "Testing".to_owned(),
+            Status::Trying { .. } => "Try build in progress".to_owned(),
+            Status::Failed { reason, .. } => format!("Failed to land: {}", sanitize(reason)),
+        };
+
+        let mut msg = format!(
+            "@{} :information_source: {} (priority {}, rollup {}, merge attempts: {})",
+            ctx.sender(),
+            status,
+            ctx.pr().queue_priority(ctx.config()),
+            ctx.pr().rollup(ctx.config()).as_str(),
+            ctx.pr().attempts,
+        );
+        if let Some(remaining) = ctx.pr().cooldown_remaining() {
+            msg.push_str(&format!(
+                "\n\nCooling down after a failed land, {}s remaining",
+                remaining.as_secs() + 1
+            ));
+        }
+        if let (Some(approver), Some(reviewer)) = (&ctx.pr().approver, &ctx.pr().reviewer) {
+            msg.push_str(&format!(
+                "\n\nApproved by {} on behalf of {}",
+                approver, reviewer
+            ));
+        }
+        if let Some(delegate) = &ctx.pr().delegate {
+            msg.push_str(&format!(
+                "\n\nApproval rights are delegated to {}",
+                delegate
+            ));
+        }
+        if ctx.pr().status.is_queued() {
+            if let Some(remaining) = ctx.pr().grace_remaining(ctx.config()) {
+                msg.push_str(&format!(
+                    "\n\nIn its grace period, tests start in {} unless canceled",
+                    format_wait(remaining)
+                ));
+            }
+        }
+        if let Some(threshold) = ctx.tree_closed() {
+            msg.push_str(&format!(
+                "\n\nThe tree is closed for PRs below priority {}",
+                threshold
+            ));
+        }
+        let dependencies = ctx.unlanded_dependencies();
+        if !dependencies.is_empty() {
+            msg.push_str(&format!(
+                "\n\nWaiting on dependencies to land: {}",
+                pr_list(&dependencies)
+            ));
+        }
+
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn set_tree_closed(ctx: &mut CommandContext<'_>, threshold: Option<u32>) -> Result<()> {
+        info!("set tree closed threshold to {:?}", threshold);
+
+        ctx.set_tree_closed(threshold)?;
+
+        let msg = match threshold {
+            Some(threshold) => format!(
+                ":evergreen_tree: Tree closed for PRs with a priority below {}",
+                threshold
+            ),
+            None => ":sunny: Tree reopened".to_owned(),
+        };
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn set_paused(ctx: &mut CommandContext<'_>, pause: bool) -> Result<()> {
+        let (command, msg) = if pause {
+            info!("pausing the merge queue");
+            (AdminCommand::Pause, ":pause_button: Merge queue paused")
+        } else {
+            info!("resuming the merge queue");
+            (AdminCommand::Resume, ":arrow_forward: Merge queue resumed")
+        };
+
+        ctx.request_admin(command);
+        ctx.create_pr_comment(msg).await
+    }
+
+    async fn set_depends_on(ctx: &mut CommandContext<'_>, number: Option<u64>) -> Result<()> {
+        let msg = match number {
+            Some(number) if number == ctx.pr().number => {
+                format!(
+                    "@{} :exclamation: A PR can't depend on itself",
+                    ctx.sender()
+                )
+            }
+            Some(number) => {
+                info!("#{}: add dependency on #{}", ctx.pr().number, number);
+                ctx.pr_mut().depends_on.insert(number);
+                format!(
+                    ":link: This PR won't be tested until #{} has landed",
+                    number
+                )
+            }
+            None => {
+                info!("#{}: clear dependencies", ctx.pr().number);
+                ctx.pr_mut().depends_on.clear();
+                ":link: Cleared this PR's dependencies".to_owned()
+            }
+        };
+
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn set_delegate(ctx: &mut CommandContext<'_>, delegate: Option<&Delegate>) -> Result<()> {
+        let user = match delegate {
+            Some(Delegate::Author) => match ctx.pr().author.clone() {
+                Some(author) => Some(author),
+                None => {
+                    let msg = format!(
+                        "@{} :exclamation: This PR's author is unknown, delegate to them by name \
+                        with `/delegate=<user>`",
+                        ctx.sender()
+                    );
+                    return ctx.create_pr_comment(&msg).await;
+                }
+            },
+            Some(Delegate::User(user)) => Some(user.clone()),
+            None => None,
+        };
+
+        info!("#{}: set delegate to {:?}", ctx.pr().number, user);
+
+        let msg = match &user {
+            Some(user) => format!(
+                ":v: @{} can now queue this PR for landing with `/land`, or cancel or retry it",
+                user
+            ),
+            None => ":v: Revoked the delegation of this PR's approval rights".to_owned(),
+        };
+        ctx.pr_mut().delegate = user;
+        ctx.create_pr_comment(&msg).await
+    }
+
+    /// Take over from `old`, e.g. after it was reopened as this PR, by closing it and inheriting
+    /// its approval, priority and place in the queue
+    async fn supersede(ctx: &mut CommandContext<'_>, old: u64) -> Result<()> {
+        use crate::state::Status;
+
+        let number = ctx.pr().number;
+        let msg = if old == number {
+            Some("A PR can't supersede itself".to_owned())
+        } else if ctx.pull(old).is_none() {
+            Some(format!("#{} isn't an open PR", old))
+        } else if ctx.pr().status.is_queued() || ctx.pr().status.is_testing() {
+            Some("This PR is already queued for landing".to_owned())
+        } else {
+            None
+        };
+        if let Some(msg) = msg {
+            ctx.create_pr_comment(&format!("@{} :exclamation: {}", ctx.sender(), msg))
+                .await?;
+            return Ok(());
+        }
+
+        info!("#{}: superseding #{}", number, old);
+
+        let previous = ctx.pull(old).expect("PR should exist");
+        let was_queued = matches!(previous.status, Status::Queued | Status::Testing { .. });
+        let approver = previous.approver.clone();
+        let approved_by = previous.approved_by.clone();
+        let priority = previous.priority;
+        let queue_number = previous.queue_number();
+
+        // Remove the old PR from the queue before closing it, abandoning any test it's a part of
+        ctx.update_status_of(old, Status::InReview).await?;
+        ctx.create_comment_on(old, &format!(":recycle: Superseded by #{}", number))
+            .await?;
+        let update = github::client::UpdatePullRequest {
+            state: Some(github::State::Closed),
+            ..Default::default()
+        };
+        ctx.github()
+            .pulls()
+            .update(ctx.config().owner(), ctx.config().name(), old, update)
+            .await?;
+
+        let pull = ctx.pr_mut();
+        pull.superseded = Some(queue_number);
+        pull.approved_by.extend(approved_by);
+        if priority > pull.priority {
+            Self::set_priority(ctx, priority).await?;
+        }
+
+        let mut msg = format!(
+            ":recycle: Supersedes #{}, which has been closed. Previous discussion can be found there.",
+            old
+        );
+        if was_queued && !ctx.pr().is_draft() {
+            let approver = approver.unwrap_or_else(|| ctx.sender().to_owned());
+            msg.push_str(&format!(
+                "\n\nQueued for landing in its place, with the approval of @{}",
+                approver
+            ));
+            ctx.pr_mut().approver = Some(approver);
+            ctx.pr_mut().spurious_retries = 0;
+            ctx.update_pr_status(Status::Queued).await?;
+        }
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn selfcheck(ctx: &mut CommandContext<'_>) -> Result<()> {
+        if !Self::is_admin(ctx).await? {
+            let msg = format!(
+                "@{}: :key: Insufficient privileges: Requires 'admin' permission to run a selfcheck",
+                ctx.sender(),
+            );
+            return ctx.create_pr_comment(&msg).await;
+        }
+
+        info!("running selfcheck");
+
+        let checks = selfcheck::run(ctx).await;
+        let failed = checks
+            .iter()
+            .filter(|check| check.outcome == selfcheck::Outcome::Fail)
+            .count();
+        let summary = if failed == 0 {
+            ":white_check_mark: All checks passed".to_owned()
+        } else {
+            format!(":x: {} of {} checks failed", failed, checks.len())
+        };
+
+        let msg = format!(
+            "@{} {}\n\n{}",
+            ctx.sender(),
+            summary,
+            selfcheck::report(&checks)
+        );
+        ctx.create_pr_comment(&msg).await
+    }
+
+    async fn rekick(ctx: &mut CommandContext<'_>) -> Result<()> {
+        if !Self::is_admin(ctx).await? {
+            let msg = format!(
+                "@{}: :key: Insufficient privileges: Requires 'admin' permission to rekick the merge queue",
+                ctx.sender(),
+            );
+            return ctx.create_pr_comment(&msg).await;
+        }
+
+        info!("rekicking the batch being tested");
+        ctx.request_admin(AdminCommand::Rekick);
+
+        Ok(())
+    }
+
+    async fn cancel_land(ctx: &mut CommandContext<'_>) -> Result<()> {
+        use crate::state::Status;
+
+        info!("Canceling land of pr #{}", ctx.pr().number);
+
+        let was_testing = ctx.pr().status.is_testing();
+        ctx.update_pr_status(Status::InReview).await?;
+
+        if was_testing {
+            ctx.pr()
+                .report_check_run(
+                    ctx.config(),
+                    ctx.github(),
+                    &ctx.pr().head_ref_oid,
+                    Some(github::Conclusion::Cancelled),
+                    None,
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+struct Help<'a> {
+    config: &'a RepoConfig,
+    project_board: Option<&'a ProjectBoard>,
+}
+
+impl<'a> Help<'a> {
+    fn new(config: &'a RepoConfig, project_board: Option<&'a ProjectBoard>) -> Self {
+        Self {
+            config,
+            project_board,
+        }
+    }
+}
+
+impl std::fmt::Display for Help<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const NON_BREAKING_SPACE: &str = "&nbsp;";
+
+        writeln!(f, "<details>")?;
+        write!(f, "<summary>")?;
+        write!(f, "Bors help and documentation")?;
+        writeln!(f, "</summary>")?;
+        writeln!(f, "<br />")?;
+        writeln!(f)?;
+
+        let queue_link = self.project_board.map(|p| p.board().html_url.as_str());
+        let queue = if let Some(link) = queue_link {
+            format!("[Merge Queue]({})", link)
+        } else {
+            "Merge Queue".to_owned()
+        };
+        writeln!(
+            f,
+            "Bors is a Github App used to manage merging of PRs in order to ensure that CI is always \
+            green. It does so by maintaining a {queue}. Once a PR reaches the head of the Merge \
+            Queue it is rebased on top of the latest version of the PR's `base-branch` (generally \
+            `master`) and then triggers CI. If CI comes back green the PR is then merged into the \
+            `base-branch`. Regardless of the outcome, the next PR is the queue is then processed.",
+            queue = queue,
+        )?;
+        writeln!(f)?;
+
+        //
+        // General
+        //
+        writeln!(f, "### General")?;
+
+        if let Some(link) = queue_link {
+            writeln!(
+                f,
+                "- This project's Merge Queue can be found [here]({}).",
+                link
+            )?;
+        }
+
+        if self.config.require_review() {
+            writeln!(
+                f,
+                "- This project requires PRs to be [Reviewed]\
+                (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/about-pull-request-reviews) \
+                and Approved before they can be queued for merging.",
+            )?;
+        }
+
+        if self.config.required_approvals() > 0 {
+            writeln!(
+                f,
+                "- PRs need at least {} approving reviews before they can be queued for merging.",
+                self.config.required_approvals(),
+            )?;
+        }
+
+        if self.config.block_self_approval() {
+            writeln!(f, "- PRs can't be queued for merging by their own author.")?;
+        }
+
+        if self.config.require_resolved_threads() {
+            writeln!(
+                f,
+                "- All of a PR's review threads must be resolved before it can be queued for merging."
+            )?;
+        }
+
+        if self.config.maintainer_mode() {
+            writeln!(
+                f,
+                "- Before PR's can be merged they must be configured with \
+                [\"Allow edits from maintainers\"]\
+                (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/allowing-changes-to-a-pull-request-branch-created-from-a-fork) \
+                enabled. This is needed for Bors to be able to update PR's in-place \
+                so that Github can properly recognize and mark them as \"merged\" \
+                once they've been merged into the upstream branch.",
+            )?;
+        }
+
+        if self.config.max_batch_size() > 1 {
+            writeln!(
+                f,
+                "- Up to {} queued PRs are rebased on top of one another and tested together as a \
+                single batch. If CI comes back green the whole batch is merged at once, otherwise \
+                the batch is automatically split in half and each half retested in order to find \
+                the PR responsible.",
+                self.config.max_batch_size(),
+            )?;
+        }
+
+        if let Some(grace_period) = self.config.grace_period() {
+            writeln!(
+                f,
+                "- Queued PRs aren't tested until {} after they were queued, until then they can be \
+                canceled without having used up a CI run.",
+                format_wait(grace_period),
+            )?;
+        }
+
+        //
+        // Commands
+        //
+        writeln!(f, "### Commands")?;
+        writeln!(
+            f,
+            "Bors actions can be triggered by posting a comment which includes a line of the form `/<action>`."
+        )?;
+        writeln!(f, "| Command | Action | Description |")?;
+        writeln!(f, "| --- | --- | --- |")?;
+        writeln!(
+            f,
+            "| __Land__ | `land`, `merge`, `r+` | attempt to land or merge a PR, optionally followed by `rebase`, `squash` or `merge` to override the merge strategy (default: {}), or by `stack` to also land the PRs it's stacked on top of, `solo` or `solo-` to set whether it's tested on its own, or by a commit sha to only land that reviewed head |",
+            self.config.merge_strategy().as_str(),
+        )?;
+        writeln!(
+            f,
+            "| __Land__ | `r=<user>` | (admins only) land a PR on behalf of the user who reviewed it, who is credited in a `Reviewed-by` trailer |"
+        )?;
+        writeln!(
+            f,
+            "| __Cancel__ | `cancel`, `stop` | stop an in-progress land |"
+        )?;
+        writeln!(f, "| __Help__ | `help`, `h` | show this help message |")?;
+        writeln!(
+            f,
+            "| __Priority__ | `priority`, `p=<N>` | set the priority level for a PR, higher priority PRs are tested first |"
+        )?;
+        writeln!(
+            f,
+            "| __Solo__ | `solo`, `solo-` | require that a PR is always tested on its own |"
+        )?;
+        writeln!(
+            f,
+            "| __Rollup__ | `rollup=<always\\|maybe\\|never>`, `rollup`, `rollup-` | set whether a PR is pulled into batches ahead of others of the same priority (`always`), batched in queue order (`maybe`) or never batched (`never`). `rollup` is short for `rollup=always` and `rollup-` reverts to the PR's labels ({} or {}) |",
+            self.config.labels().rollup_always(),
+            self.config.labels().rollup_never(),
+        )?;
+        writeln!(
+            f,
+            "| __Try__ | `try` | test a PR's merge commit without landing it |"
+        )?;
+        writeln!(
+            f,
+            "| __Retry__ | `retry`, `retry timeout=<duration> checks=<check>,...` | requeue a PR whose land failed, or rerun its failed try build. Admins can override the timeout, e.g. `3h`, or the required checks for that attempt only |"
+        )?;
+        writeln!(
+            f,
+            "| __Status__ | `status` | report a PR's status in the merge queue |"
+        )?;
+        writeln!(
+            f,
+            "| __Rebase__ | `rebase` | rebase a PR's branch onto the latest version of its base branch, pushing the result to the PR |"
+        )?;
+        writeln!(
+            f,
+            "| __Backport__ | `backport <branch>` | once a PR has landed, cherry-pick its commits onto `<branch>` and open a PR to land them there |"
+        )?;
+        writeln!(
+            f,
+            "| __Revert__ | `revert`, `revert #<N>` | revert the commits this PR, or #N, landed in a new PR which is queued for landing at high priority |"
+        )?;
+        writeln!(
+            f,
+            "| __Depends On__ | `depends=#<N>`, `depends-` | don't test a PR until #N has landed, or clear its dependencies. Dependencies can also be listed in a PR's description with `Depends-on: #<N>` |"
+        )?;
+        writeln!(
+            f,
+            "| __Delegate__ | `delegate+`, `delegate=<user>`, `delegate-` | let the PR's author, or the named user, land, cancel or retry this PR, or revoke the delegation |"
+        )?;
+        writeln!(
+            f,
+            "| __Supersede__ | `supersede #<N>` | close #N, e.g. after it was reopened as this PR, with this PR inheriting its approval, priority and place in the queue |"
+        )?;
+        writeln!(
+            f,
+            "| __Tree Closed__ | `treeclosed=<N>`, `treeclosed-` | only test PRs with a priority of at least N, or reopen the tree |"
+        )?;
+        writeln!(
+            f,
+            "| __Pause__ | `pause`, `resume` | stop testing PRs from the merge queue, or start again |"
+        )?;
+        writeln!(
+            f,
+            "| __Self Check__ | `selfcheck` | (admins only) check that bors can receive webhooks, push, report statuses and see the repo's checks and project board |"
+        )?;
+        writeln!(
+            f,
+            "| __Rekick__ | `rekick` | (admins only) re-request the check suites of the batch being tested without rebuilding it, e.g. after a CI outage |"
+        )?;
+        writeln!(f)?;
+
+        //
+        // Options
+        //
+        writeln!(f, "### Options")?;
+        writeln!(
+            f,
+            "Options for Pull Requests are configured through the application of labels.",
+        )?;
 
         writeln!(
             f,
@@ -389,8 +2077,8 @@ impl std::fmt::Display for Help<'_> {
             f,
             "| ![label: {name}](https://img.shields.io/static/v1?label=&message={name}&color=lightgrey) | {desc} |",
             name = self.config.labels().high_priority(),
-            desc = "Indicates that the PR is high-priority. \
-            When queued the PR will be placed at the head of the merge queue.",
+            desc = "Indicates that the PR is high-priority, equivalent to a priority of 1. \
+            When queued the PR will be placed ahead of any lower priority PRs in the merge queue.",
         )?;
         writeln!(
             f,
@@ -399,16 +2087,52 @@ impl std::fmt::Display for Help<'_> {
             desc = "Before merging the PR will be squashed down to a single commit, \
             only retaining the commit message of the first commit in the PR.",
         )?;
+        writeln!(
+            f,
+            "| ![label: {name}](https://img.shields.io/static/v1?label=&message={name}&color=lightgrey) | {desc} |",
+            name = self.config.labels().solo(),
+            desc = "The PR will always be tested on its own and never batched together with other PRs.",
+        )?;
+        writeln!(
+            f,
+            "| ![label: {name}](https://img.shields.io/static/v1?label=&message={name}&color=lightgrey) | {desc} |",
+            name = self.config.labels().blocked(),
+            desc = "The PR can't be queued for landing. Adding it to a queued PR, or converting the PR \
+            to a draft, removes the PR from the queue and cancels any tests in progress.",
+        )?;
 
         writeln!(f)?;
         writeln!(f, "</details>")
     }
 }
 
+/// Parse a reference to a PR, e.g. "#12" or "12"
+fn pr_number(value: Option<&str>) -> Option<u64> {
+    value
+        .map(|v| v.strip_prefix('#').unwrap_or(v))
+        .and_then(|v| v.parse().ok())
+}
+
+/// Format a list of PRs, e.g. "#1, #2"
+pub fn pr_list(numbers: &[u64]) -> String {
+    numbers
+        .iter()
+        .map(|number| format!("#{}", number))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Debug)]
 struct Land {
     priority: Option<Priority>,
     squash: Option<bool>,
+    solo: Option<bool>,
+    strategy: Option<MergeStrategy>,
+    stack: bool,
+    /// The commit, possibly abbreviated, which was reviewed and is the only head that may land
+    sha: Option<String>,
+    /// The reviewer the PR is approved on behalf of
+    reviewer: Option<String>,
 }
 
 impl Land {
@@ -418,6 +2142,11 @@ impl Land {
     {
         let mut priority = None;
         let mut squash = None;
+        let mut solo = None;
+        let mut strategy = None;
+        let mut stack = false;
+        let mut sha = None;
+        let mut reviewer = None;
 
         for (key, value) in iter {
             match key {
@@ -430,13 +2159,45 @@ impl Land {
                 "squash-" => {
                     squash = Some(false);
                 }
+                "solo" => {
+                    solo = Some(true);
+                }
+                "solo-" => {
+                    solo = Some(false);
+                }
+                "rebase" => {
+                    strategy = Some(MergeStrategy::Rebase);
+                }
+                "squash" => {
+                    strategy = Some(MergeStrategy::Squash);
+                }
+                "merge" => {
+                    strategy = Some(MergeStrategy::Merge);
+                }
+                "stack" => {
+                    stack = true;
+                }
+                "r" if value.is_some() => {
+                    reviewer = Some(login(value)?);
+                }
+                key if value.is_none() && is_sha(key) => {
+                    sha = Some(key.to_owned());
+                }
 
                 // First key we hit that we don't understand we should just bail
                 _ => break,
             }
         }
 
-        Ok(Self { priority, squash })
+        Ok(Self {
+            priority,
+            squash,
+            solo,
+            strategy,
+            stack,
+            sha,
+            reviewer,
+        })
     }
 
     fn priority(&self) -> Option<u32> {
@@ -444,7 +2205,80 @@ impl Land {
     }
 }
 
-// XXX Don't have priority be an integer, it should be a boolean
+/// Parse a Github login, e.g. "alice" or "@alice"
+fn login(value: Option<&str>) -> Result<String, ParseCommnadError> {
+    let login = value.ok_or(ParseCommnadError)?.trim_start_matches('@');
+    let is_login = !login.is_empty()
+        && !login.starts_with('-')
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if is_login {
+        Ok(login.to_owned())
+    } else {
+        Err(ParseCommnadError)
+    }
+}
+
+/// Parse the repo settings overridden for a single attempt, e.g. `timeout=3h checks=ci,doc`
+fn attempt_overrides<'a, I>(args: I) -> Result<AttemptOverrides, ParseCommnadError>
+where
+    I: IntoIterator<Item = (&'a str, Option<&'a str>)>,
+{
+    let mut overrides = AttemptOverrides::default();
+
+    for (key, value) in args {
+        match (key, value) {
+            ("timeout", Some(value)) => {
+                overrides.timeout = Some(parse_duration(value).ok_or(ParseCommnadError)?);
+            }
+            ("checks", Some(value)) => {
+                let checks: Vec<String> = value
+                    .split(',')
+                    .filter(|check| !check.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect();
+                if checks.is_empty() {
+                    return Err(ParseCommnadError);
+                }
+                overrides.checks = Some(checks);
+            }
+            _ => return Err(ParseCommnadError),
+        }
+    }
+
+    Ok(overrides)
+}
+
+/// Parse a duration made up of one or more amounts of seconds, minutes, hours or days, e.g. "90m",
+/// "3h" or "1h30m"
+fn parse_duration(s: &str) -> Option<std::time::Duration> {
+    if s.is_empty() {
+        return None;
+    }
+
+    let mut seconds: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let idx = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount: u64 = rest[..idx].parse().ok()?;
+        let unit = match rest.as_bytes()[idx] {
+            b's' => 1,
+            b'm' => 60,
+            b'h' => 60 * 60,
+            b'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(amount.checked_mul(unit)?)?;
+        rest = &rest[idx + 1..];
+    }
+
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+/// Check if `s` looks like a commit sha, possibly abbreviated
+fn is_sha(s: &str) -> bool {
+    (7..=40).contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 #[derive(Debug)]
 struct Priority {
     priority: u32,
@@ -480,3 +2314,314 @@ impl Priority {
         self.priority
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Command, CommandType, Delegate, MergeStrategy, Rollup, PERMISSION_KEYS};
+    use std::time::Duration;
+
+    fn priority(comment: &str) -> Option<u32> {
+        match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Priority(p) => Some(p.priority()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn numeric_priority() {
+        assert_eq!(priority("/priority 3"), Some(3));
+        assert_eq!(priority("/p=5"), Some(5));
+        assert_eq!(priority("/priority=2"), Some(2));
+        assert_eq!(priority("/p=high"), None);
+        assert!(Command::from_comment("/land=1").unwrap().is_err());
+    }
+
+    #[test]
+    fn tree_closed() {
+        let tree_closed = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::TreeClosed(threshold) => Some(threshold),
+            _ => None,
+        };
+
+        assert_eq!(tree_closed("/treeclosed=5"), Some(Some(5)));
+        assert_eq!(tree_closed("/treeclosed-"), Some(None));
+        assert_eq!(tree_closed("/treeclosed"), None);
+        assert_eq!(tree_closed("/treeclosed=x"), None);
+    }
+
+    #[test]
+    fn depends_on() {
+        let depends_on = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::DependsOn(number) => Some(number),
+            _ => None,
+        };
+
+        assert_eq!(depends_on("/depends=#12"), Some(Some(12)));
+        assert_eq!(depends_on("/depends=12"), Some(Some(12)));
+        assert_eq!(depends_on("/depends-"), Some(None));
+        assert_eq!(depends_on("/depends"), None);
+        assert_eq!(depends_on("/depends=#x"), None);
+    }
+
+    #[test]
+    fn rekick() {
+        let rekick = |comment: &str| {
+            matches!(
+                Command::from_comment(comment).and_then(Result::ok),
+                Some(Command {
+                    command_type: CommandType::Rekick,
+                    ..
+                })
+            )
+        };
+
+        assert!(rekick("/rekick"));
+        assert!(!rekick("/retry"));
+        assert!(Command::from_comment("/rekick=1").unwrap().is_err());
+    }
+
+    #[test]
+    fn pause() {
+        let pause = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Pause(pause) => Some(pause),
+            _ => None,
+        };
+
+        assert_eq!(pause("/pause"), Some(true));
+        assert_eq!(pause("/resume"), Some(false));
+        assert_eq!(pause("/pause=1"), None);
+    }
+
+    #[test]
+    fn rollup() {
+        let rollup = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Rollup(rollup) => Some(rollup),
+            _ => None,
+        };
+
+        assert_eq!(rollup("/rollup=never"), Some(Some(Rollup::Never)));
+        assert_eq!(rollup("/rollup=maybe"), Some(Some(Rollup::Maybe)));
+        assert_eq!(rollup("/rollup"), Some(Some(Rollup::Always)));
+        assert_eq!(rollup("/rollup-"), Some(None));
+        assert_eq!(rollup("/rollup=iffy"), None);
+    }
+
+    #[test]
+    fn merge_strategy() {
+        let strategy = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Land(l) => l.strategy,
+            _ => None,
+        };
+
+        assert_eq!(strategy("/land squash"), Some(MergeStrategy::Squash));
+        assert_eq!(strategy("/merge merge"), Some(MergeStrategy::Merge));
+        assert_eq!(strategy("/land p=1 rebase"), Some(MergeStrategy::Rebase));
+        assert_eq!(strategy("/land squash+"), None);
+    }
+
+    #[test]
+    fn land_stack() {
+        let stack = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Land(l) => Some(l.stack),
+            _ => None,
+        };
+
+        assert_eq!(stack("/land stack"), Some(true));
+        assert_eq!(stack("/land squash stack"), Some(true));
+        assert_eq!(stack("/land"), Some(false));
+    }
+
+    #[test]
+    fn permission_keys() {
+        let commands = [
+            "/land",
+            "/cancel",
+            "/help",
+            "/priority=1",
+            "/solo",
+            "/try",
+            "/retry",
+            "/status",
+            "/treeclosed=1",
+            "/depends=#2",
+            "/rollup",
+            "/supersede #2",
+            "/delegate+",
+            "/selfcheck",
+            "/rekick",
+            "/pause",
+            "/resume",
+            "/rebase",
+            "/backport release",
+            "/revert",
+        ];
+        let keys: Vec<_> = commands
+            .iter()
+            .map(|comment| {
+                Command::from_comment(comment)
+                    .unwrap()
+                    .unwrap()
+                    .command_type
+                    .permission_key()
+            })
+            .collect();
+
+        assert_eq!(keys, PERMISSION_KEYS);
+    }
+
+    #[test]
+    fn solo() {
+        let solo = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Solo(solo) => Some(solo),
+            CommandType::Land(l) => l.solo,
+            _ => None,
+        };
+
+        assert_eq!(solo("/solo"), Some(true));
+        assert_eq!(solo("/solo-"), Some(false));
+        assert_eq!(solo("/land solo"), Some(true));
+        assert_eq!(solo("/land squash solo-"), Some(false));
+        assert_eq!(solo("/land"), None);
+    }
+
+    #[test]
+    fn delegates_cannot_land_stacks() {
+        let delegable = |comment: &str| {
+            Command::from_comment(comment)
+                .unwrap()
+                .unwrap()
+                .command_type
+                .is_delegable()
+        };
+
+        assert!(delegable("/land"));
+        assert!(delegable("/land squash"));
+        assert!(!delegable("/land stack"));
+        assert!(!delegable("/land squash stack"));
+    }
+
+    #[test]
+    fn land_pinned_sha() {
+        let sha = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Land(l) => l.sha,
+            _ => None,
+        };
+
+        assert_eq!(sha("/land 3f2c9ab"), Some("3f2c9ab".to_owned()));
+        assert_eq!(
+            sha("/land squash 3f2c9ab0d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9"),
+            Some("3f2c9ab0d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9".to_owned())
+        );
+        assert_eq!(sha("/land p=1"), None);
+        assert_eq!(sha("/land 3f2c9"), None);
+        assert_eq!(sha("/land deadbeefx"), None);
+        assert_eq!(sha("/r+ 3f2c9ab"), Some("3f2c9ab".to_owned()));
+    }
+
+    #[test]
+    fn reviewer() {
+        let reviewer = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Land(l) => l.reviewer,
+            _ => None,
+        };
+
+        assert_eq!(reviewer("/r=alice"), Some("alice".to_owned()));
+        assert_eq!(reviewer("/r=@bob-2 squash"), Some("bob-2".to_owned()));
+        assert_eq!(reviewer("/land r=alice"), Some("alice".to_owned()));
+        assert_eq!(reviewer("/land"), None);
+        assert!(Command::from_comment("/r=").unwrap().is_err());
+        assert!(Command::from_comment("/r=alice\"").unwrap().is_err());
+    }
+
+    #[test]
+    fn retry_overrides() {
+        let overrides = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Retry(overrides) => Some(overrides),
+            _ => None,
+        };
+
+        let retry = overrides("/retry timeout=1h30m checks=ci,doc").unwrap();
+        assert_eq!(retry.timeout, Some(Duration::from_secs(90 * 60)));
+        assert_eq!(retry.checks, Some(vec!["ci".to_owned(), "doc".to_owned()]));
+        assert_eq!(
+            overrides("/retry timeout=3h").unwrap().timeout,
+            Some(Duration::from_secs(3 * 60 * 60))
+        );
+        assert!(overrides("/retry").unwrap().is_empty());
+        assert_eq!(overrides("/retry timeout=3"), None);
+        assert_eq!(overrides("/retry timeout=h"), None);
+        assert_eq!(overrides("/retry checks="), None);
+        assert_eq!(overrides("/retry soon"), None);
+    }
+
+    #[test]
+    fn supersede() {
+        let supersede = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Supersede(number) => Some(number),
+            _ => None,
+        };
+
+        assert_eq!(supersede("/supersede #12"), Some(12));
+        assert_eq!(supersede("/supersede 12"), Some(12));
+        assert_eq!(supersede("/supersede=#12"), Some(12));
+        assert_eq!(supersede("/supersede"), None);
+        assert_eq!(supersede("/supersede #x"), None);
+    }
+
+    #[test]
+    fn backport() {
+        let backport = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Backport(branch) => Some(branch),
+            _ => None,
+        };
+
+        assert_eq!(
+            backport("/backport release-1.2"),
+            Some("release-1.2".to_owned())
+        );
+        assert_eq!(
+            backport("/backport=release/1.2"),
+            Some("release/1.2".to_owned())
+        );
+        assert_eq!(backport("/backport"), None);
+        assert_eq!(backport("/backport="), None);
+        assert!(Command::from_comment("/backport release-1.2")
+            .unwrap()
+            .unwrap()
+            .applies_to_closed());
+        assert!(!Command::from_comment("/rebase")
+            .unwrap()
+            .unwrap()
+            .applies_to_closed());
+    }
+
+    #[test]
+    fn revert() {
+        let revert = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Revert(number) => Some(number),
+            _ => None,
+        };
+
+        assert_eq!(revert("/revert"), Some(None));
+        assert_eq!(revert("/revert #12"), Some(Some(12)));
+        assert_eq!(revert("/revert=12"), Some(Some(12)));
+        assert_eq!(revert("/revert #x"), None);
+    }
+
+    #[test]
+    fn delegate() {
+        let delegate = |comment: &str| match Command::from_comment(comment)?.ok()?.command_type {
+            CommandType::Delegate(delegate) => Some(delegate),
+            _ => None,
+        };
+
+        assert_eq!(delegate("/delegate+"), Some(Some(Delegate::Author)));
+        assert_eq!(
+            delegate("/delegate=@alice"),
+            Some(Some(Delegate::User("alice".to_owned())))
+        );
+        assert_eq!(delegate("/delegate-"), Some(None));
+        assert_eq!(delegate("/delegate"), None);
+        assert_eq!(delegate("/delegate="), None);
+    }
+}
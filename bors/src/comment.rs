@@ -0,0 +1,200 @@
+//! Hidden, machine readable markers which are embedded in the comments bors posts
+//!
+//! Each comment posted by bors is signed with an HTML comment containing a small JSON payload
+//! identifying the event being reported and the version of the PR's state at the time. This lets
+//! bors find (and update) its own comments and lets external tooling parse queue events from a PR's
+//! conversation.
+
+use serde::{Deserialize, Serialize};
+
+const MARKER_START: &str = "<!-- bors:";
+const MARKER_END: &str = "-->";
+
+/// Maximum number of characters of untrusted text, e.g. a check's name, used as a link's text
+const MAX_LINK_TEXT_LEN: usize = 100;
+
+/// Maximum number of characters of an untrusted excerpt, e.g. a check's summary, embedded in a
+/// comment
+const MAX_EXCERPT_LEN: usize = 500;
+
+/// The kind of event reported by a comment posted by bors
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommentEvent {
+    AutoApproved,
+    AwaitingLandWindow,
+    BaseAdvanced,
+    Bisecting,
+    Command,
+    ConflictDetected,
+    FirstLand,
+    ForeignPush,
+    InvalidCommand,
+    LandFailed,
+    MaintainerEdits,
+    MergeConflict,
+    OptionalChecksFailed,
+    QueuePosition,
+    QueueResidency,
+    Soaking,
+    SpuriousFailure,
+    StaleApproval,
+    TestFailed,
+    TimedOut,
+    TryBuild,
+    UpdateFailed,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CommentMarker {
+    pub event: CommentEvent,
+    pub pr: u64,
+    /// Version of the PR's state at the time the comment was posted
+    pub version: u64,
+}
+
+impl CommentMarker {
+    pub fn new(event: CommentEvent, pr: u64, version: u64) -> Self {
+        Self { event, pr, version }
+    }
+
+    /// Append this marker, as a hidden HTML comment, to the provided comment body
+    pub fn sign(&self, body: &str) -> String {
+        let json = serde_json::to_string(self).expect("CommentMarker is always serializable");
+        format!("{}\n\n{}{}{}", body, MARKER_START, json, MARKER_END)
+    }
+
+    /// Extract the marker from the body of a comment, if one is present
+    pub fn from_comment(body: &str) -> Option<Self> {
+        let start = body.rfind(MARKER_START)? + MARKER_START.len();
+        let end = body[start..].find(MARKER_END)? + start;
+        serde_json::from_str(body[start..end].trim()).ok()
+    }
+}
+
+/// Escape text which isn't controlled by bors, e.g. the name of a check reported by a CI
+/// provider, so that it can be safely embedded in a comment. Markdown and HTML, including images,
+/// are escaped, @-mentions are defused and the text is collapsed onto a single line.
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '<' => sanitized.push_str("&lt;"),
+            '>' => sanitized.push_str("&gt;"),
+            '&' => sanitized.push_str("&amp;"),
+            // Insert a zero-width space so that GitHub doesn't notify the mentioned user or team
+            '@' => sanitized.push_str("@\u{200b}"),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '(' | ')' | '#' | '|' | '~' | '!' => {
+                sanitized.push('\\');
+                sanitized.push(c);
+            }
+            c if c.is_control() => sanitized.push(' '),
+            c => sanitized.push(c),
+        }
+    }
+
+    sanitized
+}
+
+/// Format a markdown link to `url`, falling back to just the sanitized `text` if `url` isn't a
+/// plain http(s) URL which can be safely embedded in a comment
+pub fn link(text: &str, url: &str) -> String {
+    let is_safe = (url.starts_with("https://") || url.starts_with("http://"))
+        && !url
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "()<>\"'`".contains(c));
+
+    let (text, truncated) = truncate(text, MAX_LINK_TEXT_LEN);
+    let text = if truncated {
+        format!("{}…", sanitize(text))
+    } else {
+        sanitize(text)
+    };
+
+    if is_safe {
+        format!("[{}]({})", text, url)
+    } else {
+        text
+    }
+}
+
+/// Sanitize an untrusted excerpt, e.g. the summary of a failed check, truncating it to a
+/// reasonable length with a link to `url` for the rest of it
+pub fn excerpt(text: &str, url: &str) -> String {
+    let (text, truncated) = truncate(text.trim(), MAX_EXCERPT_LEN);
+
+    if truncated {
+        format!("{}… ({})", sanitize(text), link("more", url))
+    } else {
+        sanitize(text)
+    }
+}
+
+/// Truncate `text` to at most `max_len` characters, indicating if anything was removed
+fn truncate(text: &str, max_len: usize) -> (&str, bool) {
+    match text.char_indices().nth(max_len) {
+        Some((idx, _c)) => (&text[..idx], true),
+        None => (text, false),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{excerpt, link, sanitize, CommentEvent, CommentMarker};
+
+    #[test]
+    fn round_trip() {
+        let marker = CommentMarker::new(CommentEvent::TestFailed, 42, 3);
+        let body = marker.sign(":broken_heart: Test Failed");
+
+        assert!(body.starts_with(":broken_heart: Test Failed"));
+        assert!(body.ends_with(r#"<!-- bors:{"event":"test-failed","pr":42,"version":3}-->"#));
+        assert_eq!(CommentMarker::from_comment(&body), Some(marker));
+    }
+
+    #[test]
+    fn unsigned_comment() {
+        assert_eq!(CommentMarker::from_comment("just a comment"), None);
+        assert_eq!(CommentMarker::from_comment("<!-- bors:not json -->"), None);
+    }
+
+    #[test]
+    fn sanitize_untrusted_text() {
+        assert_eq!(sanitize("ci / build"), "ci / build");
+        assert_eq!(sanitize("[click](x)"), r"\[click\]\(x\)");
+        assert_eq!(sanitize("<img src=x>"), "&lt;img src=x&gt;");
+        assert_eq!(sanitize("cc @admins"), "cc @\u{200b}admins");
+        assert_eq!(sanitize("![x](http://y)"), r"\!\[x\]\(http://y\)");
+        assert_eq!(sanitize("a\n# b"), r"a \# b");
+        assert_eq!(sanitize("<!-- bors:{} -->"), r"&lt;\!-- bors:{} --&gt;");
+    }
+
+    #[test]
+    fn link_untrusted_url() {
+        assert_eq!(
+            link("build", "https://ci.example.com/1"),
+            "[build](https://ci.example.com/1)"
+        );
+        assert_eq!(link("build", "javascript:alert(1)"), "build");
+        assert_eq!(link("build", "https://x.com/a) [y](z"), "build");
+        assert_eq!(link("*build*", ""), r"\*build\*");
+    }
+
+    #[test]
+    fn truncated_excerpt() {
+        assert_eq!(
+            excerpt("  3 tests failed\n", "https://ci/1"),
+            "3 tests failed"
+        );
+
+        let long = "é".repeat(600);
+        let truncated = excerpt(&long, "https://ci/1");
+        assert!(truncated.starts_with(&"é".repeat(500)));
+        assert!(truncated.ends_with("… ([more](https://ci/1))"));
+        assert_eq!(
+            truncated.chars().count(),
+            500 + "… ([more](https://ci/1))".chars().count()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Rules a PR's commit messages must follow before it can be queued for landing
+
+use crate::{commit_message::subject, config::CommitLintConfig};
+
+/// Prefixes of the subjects of commits which aren't finished, e.g. made with `git commit --fixup`
+const WIP_PREFIXES: &[&str] = &["fixup!", "squash!", "amend!", "[WIP]", "WIP"];
+
+/// Maximum number of commits listed when refusing to queue a PR
+const MAX_LISTED_COMMITS: usize = 10;
+
+/// The rules a commit's message breaks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violations {
+    /// The commit, e.g. its sha
+    pub commit: String,
+    pub problems: Vec<String>,
+}
+
+/// Check `message` against each of the configured rules, returning those it breaks
+pub fn lint(config: &CommitLintConfig, message: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let subject = subject(message);
+
+    if let Some(max) = config.max_subject_length() {
+        let len = subject.chars().count();
+        if len > max {
+            problems.push(format!(
+                "its subject is {} characters long, more than the {} allowed",
+                len, max
+            ));
+        }
+    }
+
+    if config.forbid_wip() {
+        if let Some(prefix) = WIP_PREFIXES
+            .iter()
+            .find(|prefix| starts_with_word(subject, prefix))
+        {
+            problems.push(format!(
+                "it's marked `{}`, squash or reword it before landing",
+                prefix
+            ));
+        }
+    }
+
+    if let Some(pattern) = config.issue_reference() {
+        if !pattern.is_match(message) {
+            problems.push(format!(
+                "it doesn't reference an issue matching `{}`",
+                pattern.as_str()
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Format the commits which break the rules as a markdown list, for a comment refusing to queue
+/// the PR
+pub fn report(violations: &[Violations]) -> String {
+    let mut report = String::new();
+    for violation in violations.iter().take(MAX_LISTED_COMMITS) {
+        report.push_str(&format!(
+            "\n- {}: {}",
+            violation.commit,
+            violation.problems.join("; ")
+        ));
+    }
+    if violations.len() > MAX_LISTED_COMMITS {
+        report.push_str(&format!(
+            "\n- and {} more",
+            violations.len() - MAX_LISTED_COMMITS
+        ));
+    }
+    report
+}
+
+/// Check if `subject` starts with `prefix`, case-insensitively, as a whole word, so that "WIP:"
+/// matches "WIP" but "Wipe the cache" doesn't
+fn starts_with_word(subject: &str, prefix: &str) -> bool {
+    match subject.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => !subject[prefix.len()..]
+            .chars()
+            .next()
+            .map_or(false, char::is_alphanumeric),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lint, report, Violations};
+    use crate::config::CommitLintConfig;
+
+    #[test]
+    fn rules() {
+        let config: CommitLintConfig = toml::from_str(
+            r#"
+            max-subject-length = 20
+            forbid-wip = true
+            issue-reference = "(?i)\\b(fixes|refs) #\\d+"
+            "#,
+        )
+        .unwrap();
+
+        assert!(lint(&config, "Fix the parser\n\nFixes #12").is_empty());
+        assert!(lint(&config, "Wipe the cache\n\nRefs #3").is_empty());
+        assert_eq!(
+            lint(&config, "fixup! Fix the parser\n\nFixes #12"),
+            vec![
+                "its subject is 21 characters long, more than the 20 allowed",
+                "it's marked `fixup!`, squash or reword it before landing",
+            ]
+        );
+        assert_eq!(
+            lint(&config, "WIP: parser"),
+            vec![
+                "it's marked `WIP`, squash or reword it before landing",
+                "it doesn't reference an issue matching `(?i)\\b(fixes|refs) #\\d+`",
+            ]
+        );
+
+        let config: CommitLintConfig = toml::from_str("").unwrap();
+        assert!(lint(&config, "WIP").is_empty());
+    }
+
+    #[test]
+    fn reports() {
+        let violations: Vec<_> = (0..12)
+            .map(|i| Violations {
+                commit: format!("abc{}", i),
+                problems: vec!["it's marked `WIP`".to_owned(), "no issue".to_owned()],
+            })
+            .collect();
+
+        let report = report(&violations);
+        assert!(report.starts_with("\n- abc0: it's marked `WIP`; no issue\n- abc1:"));
+        assert!(report.ends_with("\n- abc9: it's marked `WIP`; no issue\n- and 2 more"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Messages of the commits PRs land as
+
+use crate::{
+    config::{MergeStrategy, RepoConfig, TrailersConfig},
+    git::CommitAnnotations,
+    state::PullRequestState,
+};
+use std::collections::HashSet;
+
+/// How the commits `pull` lands as are to be annotated
+pub fn annotations(config: &RepoConfig, pull: &PullRequestState) -> CommitAnnotations {
+    let squash_message = match pull.merge_strategy(config) {
+        MergeStrategy::Squash => Some(squash_message(config.squash_template(), pull)),
+        MergeStrategy::Rebase | MergeStrategy::Merge => None,
+    };
+
+    CommitAnnotations {
+        squash_message,
+        trailers: trailers(
+            config.trailers(),
+            pull.reviewer.as_deref(),
+            pull.approver.as_deref(),
+            &pull.approved_by,
+        ),
+        co_authors: config.trailers().co_authored_by(),
+    }
+}
+
+/// The trailers recording who reviewed and approved a PR. A reviewer named when the PR was
+/// approved is always credited, even if they also approved it on Github.
+fn trailers(
+    config: &TrailersConfig,
+    reviewer: Option<&str>,
+    approver: Option<&str>,
+    approved_by: &HashSet<String>,
+) -> Vec<(String, String)> {
+    let mut reviewers: Vec<&str> = reviewer.into_iter().collect();
+    if config.reviewed_by() {
+        let mut approving: Vec<&str> = approved_by
+            .iter()
+            .map(String::as_str)
+            .filter(|user| Some(*user) != reviewer)
+            .collect();
+        approving.sort_unstable();
+        reviewers.extend(approving);
+    }
+
+    let mut trailers: Vec<_> = reviewers
+        .into_iter()
+        .map(|user| ("Reviewed-by".to_owned(), user.to_owned()))
+        .collect();
+    if config.approved_by() {
+        if let Some(approver) = approver {
+            trailers.push(("Approved-by".to_owned(), approver.to_owned()));
+        }
+    }
+    trailers
+}
+
+/// The message of the commit `pull` lands as when squashed, rendered from `template`
+pub fn squash_message(template: &str, pull: &PullRequestState) -> String {
+    format_squash(
+        template,
+        &pull.title,
+        pull.number,
+        &pull.body,
+        pull.author.as_deref(),
+    )
+}
+
+fn format_squash(
+    template: &str,
+    title: &str,
+    number: u64,
+    body: &str,
+    author: Option<&str>,
+) -> String {
+    let body = body.replace("\r\n", "\n");
+    render(template, |placeholder| match placeholder {
+        "title" => Some(title.trim().to_owned()),
+        "number" => Some(number.to_string()),
+        "body" => Some(body.trim().to_owned()),
+        "author" => Some(author.unwrap_or_default().to_owned()),
+        _ => None,
+    })
+    .trim()
+    .to_owned()
+}
+
+/// The length of the subject line `pull` would land with, if it's squashed and the subject is
+/// longer than is allowed
+pub fn overlong_subject(config: &RepoConfig, pull: &PullRequestState) -> Option<usize> {
+    if pull.merge_strategy(config) != MergeStrategy::Squash {
+        return None;
+    }
+
+    let message = squash_message(config.squash_template(), pull);
+    let len = subject(&message).chars().count();
+    if len > config.max_subject_length() {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// The first line of a commit message
+pub fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+/// Replace each `{placeholder}` in `template` with its value. Placeholders without a value are
+/// left as they are, and values are never themselves searched for placeholders.
+fn render<F>(template: &str, value: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let replaced = rest
+            .find('}')
+            .and_then(|end| value(&rest[1..end]).map(|value| (value, end)));
+        match replaced {
+            Some((value, end)) => {
+                rendered.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_squash, render, subject, trailers};
+    use crate::config::TrailersConfig;
+    use std::collections::HashSet;
+
+    #[test]
+    fn default_template() {
+        let template = "{title} (#{number})\n\n{body}";
+
+        assert_eq!(
+            format_squash(
+                template,
+                "Fix the frobnicator",
+                1234,
+                "It was broken.\r\n",
+                None
+            ),
+            "Fix the frobnicator (#1234)\n\nIt was broken."
+        );
+        assert_eq!(
+            format_squash(template, "Fix the frobnicator", 1234, "", None),
+            "Fix the frobnicator (#1234)"
+        );
+        assert_eq!(
+            format_squash(
+                "{title}\n\nAuthored by {author}",
+                "Fix",
+                1,
+                "",
+                Some("alice")
+            ),
+            "Fix\n\nAuthored by alice"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_values_are_kept() {
+        assert_eq!(
+            render(
+                "{title}: {body} {unknown}",
+                |placeholder| match placeholder {
+                    "title" => Some("{body}".to_owned()),
+                    "body" => Some("x".to_owned()),
+                    _ => None,
+                }
+            ),
+            "{body}: x {unknown}"
+        );
+        assert_eq!(render("{title", |_| Some("x".to_owned())), "{title");
+    }
+
+    #[test]
+    fn review_trailers() {
+        let approved_by: HashSet<String> = vec!["carol".to_owned(), "bob".to_owned()]
+            .into_iter()
+            .collect();
+        let trailer = |key: &str, value: &str| (key.to_owned(), value.to_owned());
+
+        let config: TrailersConfig = toml::from_str("").unwrap();
+        assert_eq!(
+            trailers(&config, Some("bob"), Some("alice"), &approved_by),
+            vec![trailer("Reviewed-by", "bob")]
+        );
+
+        let config: TrailersConfig =
+            toml::from_str("reviewed-by = true\napproved-by = true").unwrap();
+        assert_eq!(
+            trailers(&config, Some("bob"), Some("alice"), &approved_by),
+            vec![
+                trailer("Reviewed-by", "bob"),
+                trailer("Reviewed-by", "carol"),
+                trailer("Approved-by", "alice"),
+            ]
+        );
+        assert_eq!(
+            trailers(&config, None, None, &approved_by),
+            vec![
+                trailer("Reviewed-by", "bob"),
+                trailer("Reviewed-by", "carol")
+            ]
+        );
+    }
+
+    #[test]
+    fn subjects() {
+        assert_eq!(subject("Fix it (#1)\n\nBody"), "Fix it (#1)");
+        assert_eq!(subject(""), "");
+    }
+}
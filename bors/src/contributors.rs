@@ -0,0 +1,130 @@
+//! Counts of the PRs each author has landed through bors, used to celebrate an author's first
+//! landed PR and to report contributor stats
+
+use crate::{graphql::GithubClient, state::Repo, store::Store, Result};
+use github::client::{ListPullsOptions, PaginationOptions, StateFilter};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+const CONTRIBUTORS_STORE: &str = "contributors.json";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ContributorStats {
+    /// Number of PRs the author has landed
+    pub landed: u32,
+    /// When the author's first PR landed, in seconds since the unix epoch
+    pub first_landed_at: u64,
+    /// When the author's most recent PR landed, in seconds since the unix epoch
+    pub last_landed_at: u64,
+}
+
+/// The stats of each author who has landed a PR in a repo, stored as a JSON object keyed by login
+#[derive(Debug, Default)]
+pub struct Contributors {
+    authors: BTreeMap<String, ContributorStats>,
+}
+
+impl Contributors {
+    fn store(state_dir: &Path) -> Store {
+        Store::new(state_dir, CONTRIBUTORS_STORE)
+    }
+
+    /// Read the contributor stats kept in `state_dir`, the repo's state directory. They're read
+    /// afresh each time a PR lands so that they can be reported on without going through bors.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            authors: Self::store(state_dir).load()?,
+        })
+    }
+
+    /// Check if a repo's contributor stats have been recorded yet
+    pub fn is_recorded(state_dir: &Path) -> bool {
+        Self::store(state_dir).exists()
+    }
+
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        Self::store(state_dir).save(&self.authors)
+    }
+
+    /// Record that `author` landed a PR at `now`, returning the number of PRs they've landed
+    pub fn record_land(&mut self, author: &str, now: u64) -> u32 {
+        let stats = self
+            .authors
+            .entry(author.to_owned())
+            .or_insert_with(|| ContributorStats {
+                first_landed_at: now,
+                ..ContributorStats::default()
+            });
+        stats.landed += 1;
+        stats.first_landed_at = stats.first_landed_at.min(now);
+        stats.last_landed_at = stats.last_landed_at.max(now);
+        stats.landed
+    }
+
+    pub fn into_stats(self) -> BTreeMap<String, ContributorStats> {
+        self.authors
+    }
+}
+
+/// Record the PRs merged into a repo before its contributor stats were enabled, so that the
+/// authors who have already landed PRs aren't celebrated as first-time contributors
+pub async fn backfill(github: &GithubClient, repo: &Repo, state_dir: &Path) -> Result<()> {
+    let mut contributors = Contributors::default();
+    let mut merged = 0;
+
+    let mut page = Some(1);
+    while let Some(current) = page {
+        let options = ListPullsOptions {
+            state: Some(StateFilter::Closed),
+            pagination_options: PaginationOptions {
+                page: Some(current),
+                per_page: Some(100),
+            },
+            ..Default::default()
+        };
+        let response = github
+            .pulls()
+            .list(repo.owner(), repo.name(), Some(options))
+            .await?;
+        page = response.pagination().next_page;
+
+        for pull in response.into_inner() {
+            if let Some(merged_at) = &pull.merged_at {
+                contributors.record_land(&pull.user.login, merged_at.timestamp().max(0) as u64);
+                merged += 1;
+            }
+        }
+    }
+
+    info!(
+        "{}/{} - backfilled contributor stats from {} merged PRs",
+        repo.owner(),
+        repo.name(),
+        merged
+    );
+    contributors.save(state_dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Contributors;
+
+    #[test]
+    fn counting_lands() {
+        let mut contributors = Contributors::default();
+        assert_eq!(contributors.record_land("alice", 100), 1);
+        assert_eq!(contributors.record_land("bob", 200), 1);
+        assert_eq!(contributors.record_land("alice", 300), 2);
+        // PRs can be recorded out of order, e.g. when backfilling
+        assert_eq!(contributors.record_land("bob", 50), 2);
+
+        let stats = contributors.into_stats();
+        assert_eq!(stats["alice"].landed, 2);
+        assert_eq!(stats["alice"].first_landed_at, 100);
+        assert_eq!(stats["alice"].last_landed_at, 300);
+        assert_eq!(stats["bob"].landed, 2);
+        assert_eq!(stats["bob"].first_landed_at, 50);
+        assert_eq!(stats["bob"].last_landed_at, 200);
+    }
+}
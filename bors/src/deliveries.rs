@@ -0,0 +1,140 @@
+//! Replaying of webhook deliveries which were missed while bors was down
+
+use crate::{config::RepoConfig, graphql::GithubClient, Result};
+use github::{client::PaginationOptions, Event, EventType};
+use log::{info, warn};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const WATERMARK_FILE: &str = "last-delivery";
+
+/// The GUID of the most recent webhook delivery handled for a repo, persisted to disk so that
+/// deliveries missed while bors was down can be found on startup
+#[derive(Debug)]
+pub struct DeliveryWatermark {
+    path: PathBuf,
+}
+
+impl DeliveryWatermark {
+    /// The watermark kept in `state_dir`, the repo's state directory
+    pub fn new(state_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(state_dir)?;
+
+        Ok(Self {
+            path: state_dir.join(WATERMARK_FILE),
+        })
+    }
+
+    pub fn get(&self) -> Result<Option<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(guid) => Ok(Some(guid.trim().to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn set(&self, guid: &str) -> Result<()> {
+        fs::write(&self.path, guid)?;
+        Ok(())
+    }
+}
+
+/// Check if an event carries commands, which synchronizing with Github can't recover, rather than
+/// only state which it fetches afresh
+fn carries_commands(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::IssueComment | EventType::PullRequestReview
+    )
+}
+
+/// Fetch the webhook deliveries made since the one identified by `watermark`, oldest first, along
+/// with their payloads.
+///
+/// Only the 100 most recent deliveries are considered. If `watermark` isn't among them then it
+/// isn't possible to tell which deliveries were missed and nothing is replayed.
+///
+/// Deliveries made before `synced_at`, the unix time the repo's state was last synchronized with
+/// Github, are skipped unless they carry commands, since their state is already reflected and
+/// replaying it would overwrite fresher state, e.g. after a long outage.
+pub async fn missed_deliveries(
+    github: &GithubClient,
+    config: &RepoConfig,
+    hook_id: u64,
+    watermark: &str,
+    synced_at: i64,
+) -> Result<Vec<(Event, Vec<u8>, String)>> {
+    let deliveries = github
+        .repos()
+        .list_hook_deliveries(
+            config.owner(),
+            config.name(),
+            hook_id,
+            PaginationOptions {
+                page: None,
+                per_page: Some(100),
+            },
+        )
+        .await?
+        .into_inner();
+
+    if !deliveries.iter().any(|delivery| delivery.guid == watermark) {
+        warn!(
+            "{}/{} - last handled delivery {} is too old, unable to replay missed deliveries",
+            config.owner(),
+            config.name(),
+            watermark
+        );
+        return Ok(Vec::new());
+    }
+
+    // Redelivered webhooks share the GUID of their original delivery so only the most recent
+    // attempt of each is replayed
+    let mut seen = HashSet::new();
+    let missed: Vec<_> = deliveries
+        .into_iter()
+        .take_while(|delivery| delivery.guid != watermark)
+        .filter(|delivery| seen.insert(delivery.guid.clone()))
+        .collect();
+
+    info!(
+        "{}/{} - replaying {} missed deliveries",
+        config.owner(),
+        config.name(),
+        missed.len()
+    );
+
+    let mut events = Vec::new();
+    for delivery in missed.into_iter().rev() {
+        let event_type: EventType = match delivery.event.parse() {
+            Ok(event_type) => event_type,
+            Err(_) => continue,
+        };
+        if delivery.delivered_at.timestamp() < synced_at && !carries_commands(event_type) {
+            info!(
+                "{}/{} - skipping delivery {} from before the last sync",
+                config.owner(),
+                config.name(),
+                delivery.guid
+            );
+            continue;
+        }
+
+        let details = github
+            .repos()
+            .get_hook_delivery(config.owner(), config.name(), hook_id, delivery.id)
+            .await?
+            .into_inner();
+        let payload = serde_json::to_vec(&details.request.payload)?;
+
+        match Event::from_json(event_type, &payload) {
+            Ok(event) => events.push((event, payload, delivery.guid)),
+            Err(e) => warn!("unable to parse delivery {}: {}", delivery.guid, e),
+        }
+    }
+
+    Ok(events)
+}
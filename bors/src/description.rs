@@ -0,0 +1,122 @@
+//! Descriptions of the "bors" commit status, which Github limits to 140 characters
+
+/// Maximum number of characters Github accepts in a commit status's description
+const MAX_DESCRIPTION_LEN: usize = 140;
+
+const SEPARATOR: &str = ", ";
+
+/// How important a detail of a status description is. Less important details are dropped first
+/// when the description doesn't fit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Importance {
+    Low,
+    Medium,
+    High,
+}
+
+/// A status description built from a headline, which is always kept, followed by details which
+/// are dropped, least important and then last first, until the description fits. The headline is
+/// truncated if it doesn't fit on its own.
+#[derive(Clone, Debug)]
+pub struct StatusDescription {
+    headline: String,
+    details: Vec<(Importance, String)>,
+}
+
+impl StatusDescription {
+    pub fn new(headline: impl Into<String>) -> Self {
+        Self {
+            headline: headline.into(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn detail(mut self, importance: Importance, text: impl Into<String>) -> Self {
+        self.details.push((importance, text.into()));
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let mut kept: Vec<_> = self.details.iter().map(|_| true).collect();
+        let mut len = self.headline.chars().count()
+            + self
+                .details
+                .iter()
+                .map(|(_importance, text)| SEPARATOR.len() + text.chars().count())
+                .sum::<usize>();
+
+        // Least important first and, among equally important details, the last first
+        let mut drop_order: Vec<_> = (0..self.details.len()).collect();
+        drop_order.sort_by_key(|idx| (self.details[*idx].0, std::cmp::Reverse(*idx)));
+        for idx in drop_order {
+            if len <= MAX_DESCRIPTION_LEN {
+                break;
+            }
+            kept[idx] = false;
+            len -= SEPARATOR.len() + self.details[idx].1.chars().count();
+        }
+
+        let mut description = truncate(&self.headline);
+        for (_importance, text) in
+            self.details
+                .iter()
+                .zip(kept)
+                .filter_map(|(detail, kept)| if kept { Some(detail) } else { None })
+        {
+            description.push_str(SEPARATOR);
+            description.push_str(text);
+        }
+        description
+    }
+}
+
+/// Truncate `text` to the maximum description length, marking where it was cut short
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_DESCRIPTION_LEN - 1) {
+        Some((idx, _c)) if text.chars().count() > MAX_DESCRIPTION_LEN => {
+            format!("{}…", &text[..idx])
+        }
+        _ => text.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Importance, StatusDescription, MAX_DESCRIPTION_LEN};
+
+    #[test]
+    fn short_descriptions_are_kept_whole() {
+        let description = StatusDescription::new("Attempt #2")
+            .detail(Importance::High, "testing in a batch of 3 PRs")
+            .detail(Importance::Low, "position 4 in the queue");
+
+        assert_eq!(
+            description.build(),
+            "Attempt #2, testing in a batch of 3 PRs, position 4 in the queue"
+        );
+    }
+
+    #[test]
+    fn least_important_details_are_dropped_first() {
+        let long = "x".repeat(100);
+        let description = StatusDescription::new("Queued")
+            .detail(Importance::Low, "unless canceled")
+            .detail(Importance::High, long.clone())
+            .detail(Importance::Medium, "position 12 in the queue")
+            .detail(Importance::Low, "after #41");
+
+        let built = description.build();
+        assert_eq!(built, format!("Queued, {}, position 12 in the queue", long));
+        assert!(built.chars().count() <= MAX_DESCRIPTION_LEN);
+    }
+
+    #[test]
+    fn long_headlines_are_truncated() {
+        let description =
+            StatusDescription::new("é".repeat(200)).detail(Importance::High, "dropped");
+
+        let built = description.build();
+        assert_eq!(built.chars().count(), MAX_DESCRIPTION_LEN);
+        assert!(built.ends_with("é…"));
+    }
+}
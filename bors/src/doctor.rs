@@ -0,0 +1,158 @@
+//! Checks, run from the command line, that bors has everything it needs to serve each configured
+//! repo, e.g. while onboarding a new one, reported with what to do about any which fail
+
+use crate::{
+    config::RepoConfig,
+    git,
+    graphql::{GithubClient, GithubClients},
+    selfcheck::{self, Outcome, SelfCheck},
+    Config, Result,
+};
+use anyhow::anyhow;
+use structopt::StructOpt;
+
+/// Requests an hour Github allows without authentication, which anything authenticated exceeds
+const UNAUTHENTICATED_RATE_LIMIT: usize = 60;
+
+#[derive(StructOpt)]
+pub struct DoctorOptions {
+    /// repo, as <owner>/<name>, to check, rather than every configured repo
+    repo: Option<String>,
+}
+
+pub async fn run_doctor(config: Config, options: &DoctorOptions) -> Result<()> {
+    let repos: Vec<_> = config
+        .repo
+        .iter()
+        .filter(|repo| {
+            options.repo.as_ref().map_or(true, |only| {
+                *only == format!("{}/{}", repo.owner(), repo.name())
+            })
+        })
+        .collect();
+    if repos.is_empty() {
+        return Err(match &options.repo {
+            Some(only) => anyhow!("no config for repo '{}'", only),
+            None => anyhow!("no repos are configured, add a [[repo]] table"),
+        });
+    }
+
+    let mut clients = GithubClients::new(&config.github)?;
+    let mut failed = 0;
+    for repo in repos {
+        println!("{}/{}", repo.owner(), repo.name());
+
+        let checks = match clients.for_repo(repo.owner(), repo.name()).await {
+            Ok(github) => run(&config, &github, repo).await,
+            Err(e) => vec![SelfCheck::new(
+                "Github credentials",
+                Outcome::Fail,
+                format!(
+                    "error: {:#}; check `github-api-token`, or `[github.app]` and that the app is \
+                    installed on the repo",
+                    e
+                ),
+            )],
+        };
+
+        for check in &checks {
+            println!(
+                "  {:<4} {:<20} {}",
+                check.outcome.as_str(),
+                check.name,
+                check.detail
+            );
+        }
+        failed += checks
+            .iter()
+            .filter(|check| check.outcome == Outcome::Fail)
+            .count();
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{} checks failed", failed));
+    }
+    Ok(())
+}
+
+/// Run each of the checks against a repo, in the order they're reported
+async fn run(config: &Config, github: &GithubClient, repo: &RepoConfig) -> Vec<SelfCheck> {
+    vec![
+        SelfCheck::from_result("Github credentials", credentials(github).await),
+        SelfCheck::from_result("Token permissions", permissions(github, repo).await),
+        SelfCheck::from_result("Git access", git_access(config, repo)),
+        SelfCheck::from_result("Webhook secret", Ok(webhook_secret(config, repo))),
+        SelfCheck::from_result(
+            "Webhook deliveries",
+            selfcheck::webhook_deliveries(github, repo)
+                .await
+                .map(|(outcome, detail)| with_hint(outcome, detail, DELIVERIES_HINT)),
+        ),
+    ]
+}
+
+const PERMISSIONS_HINT: &str = "add bors' user as a collaborator with write access";
+const DELIVERIES_HINT: &str =
+    "check the webhook's recent deliveries in the repo's settings, and that bors is reachable";
+
+fn with_hint(outcome: Outcome, detail: String, hint: &str) -> (Outcome, String) {
+    match outcome {
+        Outcome::Fail => (outcome, format!("{}; {}", detail, hint)),
+        _ => (outcome, detail),
+    }
+}
+
+/// Check that requests to Github are authenticated, which a missing token wouldn't be
+async fn credentials(github: &GithubClient) -> Result<(Outcome, String)> {
+    let limit = github.rate_limit().get().await?.rate().limit;
+    if limit > UNAUTHENTICATED_RATE_LIMIT {
+        Ok((
+            Outcome::Pass,
+            format!("authenticated, {} requests an hour", limit),
+        ))
+    } else {
+        Ok((
+            Outcome::Fail,
+            "requests aren't authenticated; set `github-api-token` or `[github.app]`".to_owned(),
+        ))
+    }
+}
+
+/// Check that the user bors acts as has write access to the repo
+async fn permissions(github: &GithubClient, repo: &RepoConfig) -> Result<(Outcome, String)> {
+    let (outcome, detail) = selfcheck::token_permissions(github, repo).await?;
+    Ok(with_hint(outcome, detail, PERMISSIONS_HINT))
+}
+
+/// Check that the SSH key bors fetches and pushes with is accepted for the repo
+fn git_access(config: &Config, repo: &RepoConfig) -> Result<(Outcome, String)> {
+    match git::check_access(&config.git, repo.repo()) {
+        Ok(()) => Ok((
+            Outcome::Pass,
+            format!("'{}' is accepted", config.git.ssh_key_file.display()),
+        )),
+        Err(e) => Ok((
+            Outcome::Fail,
+            format!(
+                "{:#}; add the public half of '{}' as a deploy key with write access",
+                e,
+                config.git.ssh_key_file.display()
+            ),
+        )),
+    }
+}
+
+/// Check that webhooks are verified as coming from Github
+fn webhook_secret(config: &Config, repo: &RepoConfig) -> (Outcome, String) {
+    let app_secret = config
+        .github
+        .app()
+        .and_then(|app| app.webhook_secret.as_deref());
+    match repo.secret().or(app_secret) {
+        Some(secret) if !secret.is_empty() => (Outcome::Pass, "configured".to_owned()),
+        _ => (
+            Outcome::Fail,
+            "webhooks can't be verified; set `secret` to the webhook's secret".to_owned(),
+        ),
+    }
+}
@@ -0,0 +1,239 @@
+//! Serving repos, each with an event processor of its own, including the repos a Github App is
+//! installed on while bors is running, which are enrolled without a restart, and applying the
+//! config file when it's reloaded to the repos already being served. Repos the Github App is
+//! uninstalled from stop being served, leaving their clones and state on disk in case it's
+//! reinstalled.
+
+use crate::{
+    config::{Config, RepoConfig},
+    event_processor::{EventProcessor, EventProcessorSender},
+    graphql::GithubClients,
+    state::Repo,
+    Result,
+};
+use anyhow::anyhow;
+use github::{Event, EventType, InstallationRepository};
+use log::{error, info, warn};
+use probot::{Installation, Registry};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+use tokio::sync::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct Enroller {
+    config: Arc<RwLock<Arc<Config>>>,
+    clients: Arc<Mutex<GithubClients>>,
+    registry: Registry,
+
+    /// The event processor of each repo being served, keyed by lowercase `owner/name`
+    senders: Arc<RwLock<HashMap<String, (Repo, EventProcessorSender)>>>,
+
+    /// The repos being cloned so that they can be served, keyed like `senders`
+    enrolling: Arc<RwLock<HashSet<String>>>,
+}
+
+impl Enroller {
+    pub fn new(config: Config, registry: Registry) -> Result<Self> {
+        let clients = GithubClients::new(&config.github)?;
+        Ok(Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            clients: Arc::new(Mutex::new(clients)),
+            registry,
+            senders: Arc::new(RwLock::new(HashMap::new())),
+            enrolling: Arc::new(RwLock::new(HashSet::new())),
+        })
+    }
+
+    fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Apply a reloaded config file: each repo already being served is reconfigured, keeping its
+    /// queue, and any newly configured repo is enrolled. Repos which were enrolled when the Github
+    /// App was installed on them pick up their org's new defaults. Other repos which are no longer
+    /// configured keep being served until bors restarts, as do changes to the `[github]`, `[git]`
+    /// and `[lease]` tables.
+    pub async fn reload(&self, mut config: Config) -> Result<()> {
+        let repos = std::mem::take(&mut config.repo);
+        let auto_enroll = config.github.app().map_or(false, |app| app.auto_enroll);
+        *self.config.write().unwrap() = Arc::new(config);
+
+        let mut unconfigured = self.senders.read().unwrap().clone();
+        for repo in repos {
+            let (owner, name) = (repo.owner().to_owned(), repo.name().to_owned());
+            let result = match unconfigured.remove(&key(&owner, &name)) {
+                Some((_, mut sender)) => sender.reconfigure(repo).await,
+                None => self.enroll(repo).await,
+            };
+            if let Err(e) = result {
+                error!("unable to reload config of {}/{}: {:?}", owner, name, e);
+            }
+        }
+
+        for (repo, mut sender) in unconfigured.into_iter().map(|(_, served)| served) {
+            let (owner, name) = (repo.owner(), repo.name());
+            if !auto_enroll {
+                warn!(
+                    "{}/{} is no longer configured, but is served until restart",
+                    owner, name
+                );
+                continue;
+            }
+
+            let result = match self.config().default_repo(owner, name) {
+                Ok(config) => sender.reconfigure(config).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("unable to reload config of {}/{}: {:?}", owner, name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start serving a repo, cloning it if it isn't already on disk, and route its webhooks to its
+    /// event processor. Fails if the repo is already served or being enrolled.
+    pub async fn enroll(&self, repo: RepoConfig) -> Result<()> {
+        // Reserve the repo before cloning it, so that overlapping webhooks for the same repo don't
+        // clone it twice. The reservation is only released once the repo is registered.
+        let key = key(repo.owner(), repo.name());
+        {
+            let mut enrolling = self.enrolling.write().unwrap();
+            if self.registry.contains(repo.owner(), repo.name()) || !enrolling.insert(key.clone()) {
+                return Err(anyhow!(
+                    "{}/{} is already enrolled",
+                    repo.owner(),
+                    repo.name()
+                ));
+            }
+        }
+
+        let result = self.serve(repo, key.clone()).await;
+        self.enrolling.write().unwrap().remove(&key);
+        result
+    }
+
+    async fn serve(&self, repo: RepoConfig, key: String) -> Result<()> {
+        let mut installation = Installation::new(repo.owner(), repo.name());
+        let config = self.config();
+        let app_secret = config
+            .github
+            .app()
+            .and_then(|app| app.webhook_secret.as_deref());
+        match repo.secret().or(app_secret) {
+            Some(secret) => {
+                installation.with_secret(secret);
+            }
+            None => warn!(
+                "{}/{} - no webhook secret configured; unsigned deliveries will be accepted",
+                repo.owner(),
+                repo.name()
+            ),
+        }
+
+        let github = self
+            .clients
+            .lock()
+            .await
+            .for_repo(repo.owner(), repo.name())
+            .await?;
+
+        // Cloning the repo can take a while, so keep it off of the threads serving webhooks
+        let served = repo.repo().clone();
+        let (tx, event_processor) = tokio::task::spawn_blocking(move || {
+            EventProcessor::new(repo, github, &config.git, config.lease.as_ref())
+        })
+        .await??;
+        installation.with_service(Box::new(tx.clone()));
+
+        self.registry.register(installation)?;
+        self.senders.write().unwrap().insert(key, (served, tx));
+        tokio::spawn(event_processor.start());
+        Ok(())
+    }
+
+    /// Stop serving the repos the Github App has been uninstalled from
+    async fn unenroll(&self, repos: &[InstallationRepository]) {
+        for repo in repos {
+            let served = self
+                .senders
+                .write()
+                .unwrap()
+                .remove(&key(repo.owner(), &repo.name));
+            let mut sender = match served {
+                Some((_, sender)) => sender,
+                None => continue,
+            };
+
+            info!("unenrolling {}", repo.full_name);
+            self.registry.unregister(repo.owner(), &repo.name);
+            if let Err(e) = sender.shutdown().await {
+                error!("unable to shut down {}: {:?}", repo.full_name, e);
+            }
+        }
+    }
+
+    /// Enroll the repos the Github App has been installed on which aren't already served, with
+    /// their org's defaults
+    async fn enroll_installed(&self, repos: &[InstallationRepository]) {
+        for repo in repos {
+            if self.registry.contains(repo.owner(), &repo.name) {
+                continue;
+            }
+
+            info!("enrolling {}", repo.full_name);
+            let result = match self.config().default_repo(repo.owner(), &repo.name) {
+                Ok(config) => self.enroll(config).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("unable to enroll {}: {:?}", repo.full_name, e);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl probot::Service for Enroller {
+    fn name(&self) -> &'static str {
+        "enroll"
+    }
+
+    fn route(&self, event_type: EventType) -> bool {
+        matches!(
+            event_type,
+            EventType::Installation | EventType::InstallationRepositories
+        )
+    }
+
+    async fn handle(&self, event: &Event, _payload: &[u8], _delivery_id: &str) {
+        let repos = match event {
+            Event::Installation(event) if event.action == "created" => {
+                event.repositories.clone().unwrap_or_default()
+            }
+            Event::InstallationRepositories(event) if event.action == "added" => {
+                event.repositories_added.clone()
+            }
+            Event::Installation(event) if event.action == "deleted" => {
+                let repos = event.repositories.clone().unwrap_or_default();
+                return self.unenroll(&repos).await;
+            }
+            Event::InstallationRepositories(event) if event.action == "removed" => {
+                return self.unenroll(&event.repositories_removed).await;
+            }
+            _ => return,
+        };
+
+        // Enrolling clones each repo, which takes far longer than Github waits for a response to
+        // a webhook
+        let enroller = self.clone();
+        tokio::spawn(async move { enroller.enroll_installed(&repos).await });
+    }
+}
+
+fn key(owner: &str, name: &str) -> String {
+    format!("{}/{}", owner, name).to_lowercase()
+}
@@ -1,22 +1,140 @@
 use crate::{
-    command::Command,
-    config::{GitConfig, GithubConfig, RepoConfig},
-    git::GitRepository,
+    approvals::{self, ApprovalHistory},
+    audit::{AuditLog, AuditRecord},
+    auto_approve::{AutoApprover, Decision},
+    autolabel::apply_autolabels,
+    check_archive::{ArchivedPayload, CheckArchive},
+    check_durations::CheckDurations,
+    command::{pr_list, Command},
+    comment::{CommentEvent, CommentMarker},
+    config::{GitConfig, LeaseConfig, RepoConfig, IN_REPO_CONFIG_PATH},
+    contributors::{self, ContributorStats, Contributors},
+    deliveries::DeliveryWatermark,
+    git::{GitRepository, PushFailure},
     graphql::GithubClient,
+    known_issues::{fingerprint, KnownIssue, KnownIssues},
+    land_window::format_wait,
+    lease::{Handoff, HandoffEntry, Lease},
     project_board::ProjectBoard,
-    queue::MergeQueue,
-    state::{PullRequestState, Status},
+    protected_paths,
+    queue::{
+        estimated_waits, grace_period_wait, hold_reason, queue_order, update_in_place, MergeQueue,
+    },
+    state::{mark_landed, parse_depends_on, PullRequestState, Repo, Status},
+    store::Store,
+    stream::{PullSummary, QueueState, Subscribers, SUBSCRIBER_BUFFER},
     Result,
 };
-use futures::{channel::mpsc, sink::SinkExt, stream::StreamExt};
-use github::{Event, EventType, NodeId, PullRequestReviewEvent};
+use anyhow::anyhow;
+use futures::{
+    channel::{mpsc, oneshot},
+    sink::SinkExt,
+    stream::StreamExt,
+};
+use github::{
+    AuthorAssociation, Event, EventType, NodeId, Oid, PullRequestReviewEvent, ReviewState,
+};
 use log::{error, info, warn};
-use std::collections::HashMap;
+use probot::{EventStream, StreamEvent};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    iter,
+    path::Path,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum Request {
-    Webhook { event: Event, delivery_id: String },
+    Webhook {
+        event: Event,
+        /// The raw JSON the event was parsed from
+        payload: Vec<u8>,
+        delivery_id: String,
+    },
+    Admin {
+        command: AdminCommand,
+        responder: oneshot::Sender<Result<()>>,
+    },
+    /// Look up the check run and commit status payloads archived for a merge commit
+    CheckPayloads {
+        merge_oid: Oid,
+        responder: oneshot::Sender<Result<Vec<ArchivedPayload>>>,
+    },
+    RenewLease,
+    /// A PR hasn't been pushed to again within the quiet period since it was pushed `head_oid`
+    PushSettled {
+        number: u64,
+        head_oid: Oid,
+    },
+    /// Report the number of PRs each author has landed
+    ContributorStats {
+        responder: oneshot::Sender<Result<BTreeMap<String, ContributorStats>>>,
+    },
+    /// Stream the repo's queue state to a new subscriber
+    Subscribe(mpsc::Sender<StreamEvent>),
+    /// The merge queue is due to be processed again, e.g. because the land window may have opened
+    /// or a PR's grace period may have ended
+    QueueWakeup,
+    /// Periodic check for PRs which have been queued for longer than the configured maximum
+    CheckQueueResidency,
+    /// The server-side config has been reloaded, replacing the repo's config without disturbing
+    /// its queue or PRs
+    Reconfigure {
+        config: RepoConfig,
+        responder: oneshot::Sender<Result<()>>,
+    },
+    /// Stop serving the repo, e.g. once the Github App has been uninstalled from it
+    Shutdown,
+}
+
+/// Actions taken by an administrator outside of the usual webhook and PR comment flow, through the
+/// admin API. These are serialized through the same per-repo loop as webhooks so that all mutations
+/// of a repo's state happen in one place.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum AdminCommand {
+    /// Remove a PR from the merge queue, abandoning any test it is a part of
+    Eject { number: u64 },
+
+    /// Set the priority of a PR
+    Prioritize { number: u64, priority: u32 },
+
+    /// Set the priority of every queued PR carrying a label, e.g. during a release crunch
+    PrioritizeLabel { label: String, priority: u32 },
+
+    /// Stop processing the merge queue
+    Pause,
+
+    /// Finish testing the batch currently at the head of the merge queue without starting any new
+    /// ones, responding once the queue is idle
+    Drain,
+
+    /// Resume processing the merge queue, canceling any drain in progress
+    Resume,
+
+    /// Re-request the check suites of the merge commit being tested, without rebuilding it, e.g.
+    /// after a CI outage lost its runs
+    Rekick,
+
+    /// Land the batch at the head of the merge queue without waiting on its soak checks
+    SkipSoak,
+}
+
+/// Lookups of a repo's records made through the admin API, alongside its `AdminCommand`s, which are
+/// answered with the records found
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum AdminQuery {
+    CheckPayloads { merge_oid: Oid },
+    ContributorStats,
+}
+
+impl AdminQuery {
+    /// The `command` of each of the queries
+    const COMMANDS: &'static [&'static str] = &["check-payloads", "contributor-stats"];
 }
 
 #[derive(Clone, Debug)]
@@ -32,89 +150,1337 @@ impl EventProcessorSender {
     pub async fn webhook(
         &mut self,
         event: Event,
+        payload: Vec<u8>,
         delivery_id: String,
     ) -> Result<(), mpsc::SendError> {
         self.inner
-            .send(Request::Webhook { event, delivery_id })
+            .send(Request::Webhook {
+                event,
+                payload,
+                delivery_id,
+            })
+            .await
+    }
+
+    /// Submit an admin command and wait for it to be processed
+    pub async fn admin(&mut self, command: AdminCommand) -> Result<()> {
+        let (responder, rx) = oneshot::channel();
+        self.inner
+            .send(Request::Admin { command, responder })
+            .await?;
+        rx.await?
+    }
+
+    /// Fetch the check run and commit status payloads archived for a merge commit, oldest first,
+    /// e.g. to settle what CI reported on it after Github pruned its checks
+    pub async fn check_payloads(&mut self, merge_oid: Oid) -> Result<Vec<ArchivedPayload>> {
+        let (responder, rx) = oneshot::channel();
+        self.inner
+            .send(Request::CheckPayloads {
+                merge_oid,
+                responder,
+            })
+            .await?;
+        rx.await?
+    }
+
+    /// Fetch the stats of each author who has landed a PR, keyed by login
+    pub async fn contributor_stats(&mut self) -> Result<BTreeMap<String, ContributorStats>> {
+        let (responder, rx) = oneshot::channel();
+        self.inner
+            .send(Request::ContributorStats { responder })
+            .await?;
+        rx.await?
+    }
+
+    /// Replace the repo's config, e.g. once the config file has been reloaded, waiting until it's
+    /// been applied
+    pub async fn reconfigure(&mut self, config: RepoConfig) -> Result<()> {
+        let (responder, rx) = oneshot::channel();
+        self.inner
+            .send(Request::Reconfigure { config, responder })
+            .await?;
+        rx.await?
+    }
+
+    /// Stop the repo's event processor once it's finished with the requests ahead of this one
+    pub async fn shutdown(&mut self) -> Result<(), mpsc::SendError> {
+        self.inner.send(Request::Shutdown).await
+    }
+
+    /// Carry out a request made through the admin API, given its JSON `body`, returning the JSON
+    /// response
+    async fn admin_request(&mut self, body: &[u8]) -> Result<serde_json::Value> {
+        let request: serde_json::Value = serde_json::from_slice(body)?;
+        let is_query = request
+            .get("command")
+            .and_then(serde_json::Value::as_str)
+            .map_or(false, |command| AdminQuery::COMMANDS.contains(&command));
+        if !is_query {
+            self.admin(serde_json::from_value(request)?).await?;
+            return Ok(serde_json::json!({}));
+        }
+
+        match serde_json::from_value(request)? {
+            AdminQuery::CheckPayloads { merge_oid } => {
+                Ok(serde_json::to_value(self.check_payloads(merge_oid).await?)?)
+            }
+            AdminQuery::ContributorStats => {
+                Ok(serde_json::to_value(self.contributor_stats().await?)?)
+            }
+        }
+    }
+
+    /// Subscribe to live updates of the repo's queue state
+    pub async fn subscribe_to_queue(&mut self) -> Result<mpsc::Receiver<StreamEvent>> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+        self.inner.send(Request::Subscribe(tx)).await?;
+        Ok(rx)
+    }
+}
+
+#[async_trait::async_trait]
+impl probot::Service for EventProcessorSender {
+    fn name(&self) -> &'static str {
+        "bors"
+    }
+
+    fn route(&self, _event_type: EventType) -> bool {
+        true
+    }
+
+    async fn handle(&self, event: &Event, payload: &[u8], delivery_id: &str) {
+        self.clone()
+            .webhook(event.clone(), payload.to_owned(), delivery_id.to_owned())
             .await
+            .unwrap();
+    }
+
+    async fn subscribe(&self) -> Option<EventStream> {
+        match self.clone().subscribe_to_queue().await {
+            Ok(rx) => Some(rx.boxed()),
+            Err(e) => {
+                error!("unable to subscribe to queue: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn admin(&self, body: &[u8]) -> Option<Result<serde_json::Value, String>> {
+        Some(
+            self.clone()
+                .admin_request(body)
+                .await
+                .map_err(|e| format!("{:#}", e)),
+        )
     }
 }
 
-#[async_trait::async_trait]
-impl probot::Service for EventProcessorSender {
-    fn name(&self) -> &'static str {
-        "bors"
+#[derive(Debug)]
+pub struct EventProcessor {
+    config: RepoConfig,
+    /// Client of the installation serving this repo, shared with its other repos
+    github: Arc<GithubClient>,
+    git_repository: GitRepository,
+    merge_queue: MergeQueue,
+    project_board: Option<ProjectBoard>,
+    pulls: HashMap<u64, PullRequestState>,
+    requests_rx: mpsc::Receiver<Request>,
+    requests_tx: mpsc::Sender<Request>,
+
+    /// Lease which must be held in order to mutate this repo, if configured
+    lease: Option<Lease>,
+
+    /// Indicates if the lease has been taken over by another instance, in which case this
+    /// instance must no longer mutate the repo
+    lease_lost: bool,
+
+    /// The most recent webhook delivery handled, tracked if the repo's webhook is configured so
+    /// that missed deliveries can be replayed on startup
+    watermark: Option<DeliveryWatermark>,
+
+    /// Indicates if processing of the merge queue has been paused by an administrator
+    paused: bool,
+
+    /// Indicates if the merge queue is being drained, in which case no new PRs are tested
+    draining: bool,
+
+    /// Requesters waiting for the merge queue to finish draining
+    drain_waiters: Vec<oneshot::Sender<Result<()>>>,
+
+    /// Clients subscribed to live updates of the queue's state
+    subscribers: Subscribers,
+
+    /// The Github user bors acts as, used to recognize events triggered by bors itself
+    login: Option<String>,
+
+    /// Log of the events received and decisions made about each PR, if enabled
+    audit: Option<AuditLog>,
+
+    /// Which heads of each PR were approved, if recorded
+    approvals: Option<ApprovalHistory>,
+
+    /// Archive of the check payloads reported on tested merge commits, if enabled
+    check_archive: Option<CheckArchive>,
+
+    /// How long each check usually takes to pass
+    check_durations: CheckDurations,
+
+    /// When the queue is next due to be processed again, e.g. once the land window opens
+    queue_wakeup: Option<Instant>,
+
+    /// Decisions made about automatically approving PRs opened by bots
+    auto_approver: AutoApprover,
+
+    /// PRs depended on which were closed without being merged, so that they aren't looked up again
+    unmerged: HashSet<u64>,
+}
+
+impl EventProcessor {
+    pub fn new(
+        config: RepoConfig,
+        github: Arc<GithubClient>,
+        git_config: &GitConfig,
+        lease_config: Option<&LeaseConfig>,
+    ) -> Result<(EventProcessorSender, Self)> {
+        let git_repository = GitRepository::from_config(
+            git_config,
+            config.repo(),
+            config.upstream().map(|upstream| upstream.repo()),
+        )?;
+        Self::with_git_repository(config, github, git_repository, lease_config)
+    }
+
+    fn with_git_repository(
+        config: RepoConfig,
+        github: Arc<GithubClient>,
+        git_repository: GitRepository,
+        lease_config: Option<&LeaseConfig>,
+    ) -> Result<(EventProcessorSender, Self)> {
+        let (tx, rx) = mpsc::channel(1024);
+        let lease = lease_config.map(|lease_config| Lease::new(lease_config, config.repo()));
+        let state_dir = git_repository.state_dir();
+        let watermark = if config.webhook_id().is_some() {
+            Some(DeliveryWatermark::new(&state_dir)?)
+        } else {
+            None
+        };
+        let audit = if config.audit_log() {
+            Some(AuditLog::new(&state_dir))
+        } else {
+            None
+        };
+        let check_archive = config
+            .check_archive()
+            .map(|archive| CheckArchive::new(&state_dir, archive))
+            .transpose()?;
+        let check_durations = CheckDurations::load(&state_dir)?;
+        let auto_approver = AutoApprover::load(&state_dir)?;
+        let approvals = if config.approval_history() {
+            Some(ApprovalHistory::load(&state_dir)?)
+        } else {
+            None
+        };
+        let mut merge_queue = MergeQueue::new();
+        *merge_queue.tree_closed_mut() = tree_closed_store(&state_dir).load()?;
+
+        Ok((
+            EventProcessorSender::new(tx.clone()),
+            Self {
+                config,
+                github,
+                git_repository,
+                merge_queue,
+                project_board: None,
+                pulls: HashMap::new(),
+                requests_rx: rx,
+                requests_tx: tx,
+                lease,
+                lease_lost: false,
+                watermark,
+                paused: false,
+                draining: false,
+                drain_waiters: Vec::new(),
+                subscribers: Subscribers::default(),
+                login: None,
+                audit,
+                approvals,
+                check_archive,
+                check_durations,
+                queue_wakeup: None,
+                auto_approver,
+                unmerged: HashSet::new(),
+            },
+        ))
+    }
+
+    pub async fn start(mut self) {
+        // Take over this repo's lease, if configured, picking up any in-flight state from the
+        // instance which previously held it
+        let handoff = match self.acquire_lease().await {
+            Ok(handoff) => handoff,
+            Err(e) => {
+                error!(
+                    "{}/{} - Unable to acquire lease, not serving the repo: {:?}",
+                    self.config.owner(),
+                    self.config.name(),
+                    e
+                );
+                return;
+            }
+        };
+
+        self.identify().await;
+
+        if let Err(e) = self.load_in_repo_config().await {
+            error!("Error while loading {}: {:?}", IN_REPO_CONFIG_PATH, e);
+        }
+
+        let synced_at = chrono::Utc::now().timestamp();
+        self.synchronize()
+            .await
+            .expect("unable to synchronize initial state");
+
+        if let Some(handoff) = handoff {
+            if let Err(e) = self.apply_handoff(handoff).await {
+                error!("Error while applying handoff: {:?}", e);
+            }
+        }
+
+        if let Err(e) = self.replay_missed_deliveries(synced_at).await {
+            error!("Error while replaying missed deliveries: {:?}", e);
+        }
+
+        self.spawn_lease_renewal();
+        self.spawn_residency_checks();
+
+        while let Some(request) = self.requests_rx.next().await {
+            if let Request::Shutdown = request {
+                info!(
+                    "{}/{} - Shutting down",
+                    self.config.owner(),
+                    self.config.name()
+                );
+                break;
+            }
+            if let Err(e) = self.handle_request(request).await {
+                error!("Error while handling request: {:?}", e);
+            }
+        }
+    }
+
+    async fn handle_request(&mut self, request: Request) -> Result<()> {
+        // Once the lease has been taken over, the new holder is solely responsible for this repo
+        if self.lease_lost {
+            warn!(
+                "{}/{} - Lease lost, ignoring request",
+                self.config.owner(),
+                self.config.name()
+            );
+            return Ok(());
+        }
+
+        // PRs closed by the request, as opposed to landed by bors
+        let closed = match &request {
+            Request::Webhook {
+                event: Event::PullRequest(e),
+                ..
+            } if matches!(e.action, github::PullRequestEventAction::Closed) => {
+                Some(e.pull_request.number)
+            }
+            _ => None,
+        };
+        let statuses = self.audit.as_ref().map(|_| self.status_snapshot());
+        let approvals = self.approvals.as_ref().map(|_| self.approval_snapshot());
+
+        let result = self.dispatch_request(request).await;
+
+        if let Some(statuses) = statuses {
+            self.audit_changes(statuses, closed);
+        }
+        if let Some(approvals) = approvals {
+            self.record_approvals(approvals);
+        }
+        self.warn_on_low_rate_limits();
+        result?;
+
+        if self.subscribers.is_empty() {
+            return Ok(());
+        }
+
+        let state = self.queue_state();
+        self.subscribers.publish(&state)
+    }
+
+    async fn dispatch_request(&mut self, request: Request) -> Result<()> {
+        use Request::*;
+        match request {
+            Webhook {
+                event,
+                payload,
+                delivery_id,
+            } => self.handle_webhook(event, &payload, delivery_id).await?,
+            Admin { command, responder } => {
+                let is_drain = matches!(command, AdminCommand::Drain);
+                let result = self.handle_admin_command(command).await;
+
+                // A drain is only complete once the merge queue is idle
+                if is_drain && result.is_ok() && !self.merge_queue.is_idle() {
+                    self.drain_waiters.push(responder);
+                } else {
+                    // Nothing to do if the requester has stopped waiting for a response
+                    let _ = responder.send(result);
+                }
+            }
+            RenewLease => self.renew_lease()?,
+            PushSettled { number, head_oid } => {
+                // Skip pushes which have since been superseded, a later request will follow
+                let is_latest = self
+                    .pulls
+                    .get(&number)
+                    .map(|pull| pull.head_ref_oid == head_oid)
+                    .unwrap_or(false);
+                if is_latest {
+                    self.handle_push_settled(number).await?;
+                    self.process_merge_queue().await?;
+                }
+            }
+            CheckPayloads {
+                merge_oid,
+                responder,
+            } => {
+                let payloads = match &self.check_archive {
+                    Some(archive) => archive.read(&merge_oid),
+                    None => Err(anyhow!("the check archive isn't enabled for this repo")),
+                };
+                let _ = responder.send(payloads);
+            }
+            ContributorStats { responder } => {
+                let stats = if self.config.contributor_stats() {
+                    Contributors::load(&self.git_repository.state_dir())
+                        .map(Contributors::into_stats)
+                } else {
+                    Err(anyhow!("contributor stats aren't enabled for this repo"))
+                };
+                let _ = responder.send(stats);
+            }
+            Subscribe(sender) => {
+                let state = self.queue_state();
+                self.subscribers.add(sender, &state)?;
+            }
+            QueueWakeup => {
+                // An earlier wakeup may have been scheduled since this one was
+                if self.queue_wakeup.map_or(false, |at| at <= Instant::now()) {
+                    self.queue_wakeup = None;
+                }
+                self.process_merge_queue().await?;
+            }
+            CheckQueueResidency => self.check_queue_residency().await?,
+            Reconfigure { config, responder } => {
+                let result = self.reconfigure(config).await;
+                let _ = responder.send(result);
+            }
+            // Handled by the event loop, which stops once it sees the request
+            Shutdown => {}
+        }
+
+        Ok(())
+    }
+
+    /// Record an entry in a PR's audit log, if enabled. Failures are only logged since the audit
+    /// log doesn't affect how PRs are handled.
+    fn audit(&self, pr: u64, record: AuditRecord) {
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.record(pr, record) {
+                warn!("unable to record to the audit log: {:?}", e);
+            }
+        }
+    }
+
+    /// The version and status of each PR, used to find the changes made while handling a request
+    fn status_snapshot(&self) -> HashMap<u64, (u64, &'static str)> {
+        self.pulls
+            .values()
+            .map(|pull| (pull.number, (pull.state_version, pull.status.as_str())))
+            .collect()
+    }
+
+    /// Audit the changes in status since `before` was taken, along with any changes in why queued
+    /// PRs are being held back. PRs which went away while testing, other than those in `closed`,
+    /// were landed.
+    fn audit_changes(&mut self, before: HashMap<u64, (u64, &'static str)>, closed: Option<u64>) {
+        for (number, (version, from)) in before {
+            let (to, reason) = match self.pulls.get(&number) {
+                Some(pull) if pull.state_version == version => continue,
+                Some(pull) => match &pull.status {
+                    Status::Failed { reason, .. } => (pull.status.as_str(), Some(reason.clone())),
+                    status => (status.as_str(), None),
+                },
+                None if from == "testing" && closed != Some(number) => ("landed", None),
+                None => ("closed", None),
+            };
+            self.audit(
+                number,
+                AuditRecord::Status {
+                    from: from.to_owned(),
+                    to: to.to_owned(),
+                    reason,
+                },
+            );
+        }
+
+        let tree_closed = self.merge_queue.tree_closed();
+        let holds: Vec<_> = self
+            .pulls
+            .values()
+            .map(|pull| {
+                let reason = if pull.status.is_queued() {
+                    hold_reason(tree_closed, &self.config, pull)
+                } else {
+                    None
+                };
+                (pull.number, pull.status.is_queued(), reason)
+            })
+            .collect();
+
+        let audit = match &mut self.audit {
+            Some(audit) => audit,
+            None => return,
+        };
+        for (number, is_queued, reason) in holds {
+            if !is_queued {
+                audit.release(number);
+            } else if let Err(e) = audit.record_held(number, reason) {
+                warn!("unable to record to the audit log: {:?}", e);
+            }
+        }
+    }
+
+    /// The approver and approved head of each approved PR, used to find the approvals granted and
+    /// revoked while handling a request. A PR whose approval is pinned to a head it has since moved
+    /// on from doesn't count as approved.
+    fn approval_snapshot(&self) -> HashMap<u64, (String, Oid)> {
+        self.pulls
+            .values()
+            .filter_map(|pull| {
+                let approver = pull.approver.as_ref()?;
+                match &pull.approved_oid {
+                    Some(approved_oid) if *approved_oid != pull.head_ref_oid => None,
+                    _ => Some((pull.number, (approver.clone(), pull.head_ref_oid.clone()))),
+                }
+            })
+            .collect()
+    }
+
+    /// Record the approvals granted and revoked since `before` was taken. An approval is only
+    /// revoked if it was withdrawn from the head it approved, rather than the PR moving on to a new
+    /// head, and landed or closed PRs keep theirs. Failures are only logged since the history
+    /// doesn't affect how PRs are handled.
+    fn record_approvals(&mut self, before: HashMap<u64, (String, Oid)>) {
+        let after = self.approval_snapshot();
+        let history = match &mut self.approvals {
+            Some(history) => history,
+            None => return,
+        };
+
+        let mut changes = Vec::new();
+        for (number, (approver, head_oid)) in &after {
+            if before.get(number) != Some(&(approver.clone(), head_oid.clone()))
+                && history.approver_of(*number, head_oid) != Some(approver.as_str())
+            {
+                changes.push((*number, head_oid, approver, approvals::Decision::Approved));
+            }
+        }
+        for (number, (approver, head_oid)) in &before {
+            let still_at_head = self
+                .pulls
+                .get(number)
+                .map_or(false, |pull| pull.head_ref_oid == *head_oid);
+            if !after.contains_key(number) && still_at_head {
+                changes.push((*number, head_oid, approver, approvals::Decision::Revoked));
+            }
+        }
+
+        for (number, head_oid, approver, decision) in changes {
+            if let Err(e) = history.record(number, head_oid, approver, decision) {
+                warn!("unable to record to the approval history: {:?}", e);
+            }
+        }
+    }
+
+    /// Archive the payload of a check run or commit status reported on a merge commit being
+    /// tested. Failures are only logged since the archive doesn't affect how PRs are handled.
+    fn archive_payload(&self, event: &Event, payload: &[u8], delivery_id: &str) {
+        let archive = match &self.check_archive {
+            Some(archive) => archive,
+            None => return,
+        };
+
+        let (event_name, merge_oid) = match event {
+            Event::CheckRun(e) => ("check_run", &e.check_run.head_sha),
+            Event::CheckSuite(e) => ("check_suite", &e.check_suite.head_sha),
+            Event::Status(e) => ("status", &e.sha),
+            Event::WorkflowRun(e) => ("workflow_run", &e.workflow_run.head_sha),
+            Event::WorkflowJob(e) => ("workflow_job", &e.workflow_job.head_sha),
+            _ => return,
+        };
+        if self.pulls_with_merge_oid(merge_oid).is_empty() {
+            return;
+        }
+
+        if let Err(e) = archive.record(merge_oid, event_name, payload, delivery_id) {
+            warn!("unable to archive check payload: {:?}", e);
+        }
+    }
+
+    /// Archive the arrival of a webhook event in the audit log of each PR it concerns
+    fn audit_event(&self, event: &Event, delivery_id: &str) {
+        if self.audit.is_none() {
+            return;
+        }
+
+        let (numbers, description) = match event {
+            Event::PullRequest(e) => (
+                vec![e.pull_request.number],
+                format!(
+                    "pull request {:?} by {}{}",
+                    e.action,
+                    e.sender.login,
+                    if e.pull_request.merged == Some(true) {
+                        " (merged)"
+                    } else {
+                        ""
+                    }
+                ),
+            ),
+            Event::IssueComment(e) if e.issue.is_pull_request() => (
+                vec![e.issue.number],
+                format!("comment {:?} by {}", e.action, e.sender.login),
+            ),
+            Event::PullRequestReview(e) => (
+                vec![e.pull_request.number],
+                format!(
+                    "review {:?} by {}: {:?}",
+                    e.action, e.sender.login, e.review.state
+                ),
+            ),
+            Event::PullRequestReviewComment(e) => (
+                vec![e.pull_request.number],
+                format!("review comment {:?} by {}", e.action, e.sender.login),
+            ),
+            Event::CheckRun(e) => (
+                self.pulls_with_merge_oid(&e.check_run.head_sha),
+                match e.check_run.conclusion {
+                    Some(conclusion) => format!(
+                        "check run '{}' {:?}: {:?}",
+                        e.check_run.name, e.check_run.status, conclusion
+                    ),
+                    None => format!("check run '{}' {:?}", e.check_run.name, e.check_run.status),
+                },
+            ),
+            Event::CheckSuite(e) => (
+                self.pulls_with_merge_oid(&e.check_suite.head_sha),
+                format!(
+                    "check suite of '{}' {:?}: {:?}",
+                    e.check_suite.app.slug, e.check_suite.status, e.check_suite.conclusion
+                ),
+            ),
+            Event::Status(e) => (
+                self.pulls_with_merge_oid(&e.sha),
+                format!("status '{}' {:?}", e.context, e.state),
+            ),
+            Event::WorkflowRun(e) => (
+                self.pulls_with_merge_oid(&e.workflow_run.head_sha),
+                format!(
+                    "workflow run '{}' {:?}: {:?}",
+                    e.workflow_run.name.as_deref().unwrap_or(""),
+                    e.action,
+                    e.workflow_run.conclusion
+                ),
+            ),
+            Event::WorkflowJob(e) => (
+                self.pulls_with_merge_oid(&e.workflow_job.head_sha),
+                format!(
+                    "workflow job '{}' {:?}: {:?}",
+                    e.workflow_job.name, e.action, e.workflow_job.conclusion
+                ),
+            ),
+            _ => return,
+        };
+
+        for number in numbers {
+            self.audit(
+                number,
+                AuditRecord::Event {
+                    delivery_id: delivery_id.to_owned(),
+                    description: description.clone(),
+                },
+            );
+        }
+    }
+
+    fn queue_state(&self) -> QueueState {
+        let mut pulls: Vec<_> = self
+            .pulls
+            .values()
+            .map(|pull| PullSummary::from_pull(pull, &self.config))
+            .collect();
+        pulls.sort_unstable_by_key(|pull| pull.number);
+
+        QueueState {
+            paused: self.paused,
+            draining: self.draining,
+            tree_closed: self.merge_queue.tree_closed(),
+            pulls,
+        }
+    }
+
+    async fn replay_missed_deliveries(&mut self, synced_at: i64) -> Result<()> {
+        let (hook_id, watermark) = match (self.config.webhook_id(), &self.watermark) {
+            (Some(hook_id), Some(watermark)) => (hook_id, watermark),
+            _ => return Ok(()),
+        };
+
+        // Nothing has been handled yet, so there's no way to know what was missed
+        let last_delivery = match watermark.get()? {
+            Some(guid) => guid,
+            None => return Ok(()),
+        };
+
+        let missed = crate::deliveries::missed_deliveries(
+            &self.github,
+            &self.config,
+            hook_id,
+            &last_delivery,
+            synced_at,
+        )
+        .await?;
+        for (event, payload, delivery_id) in missed {
+            if let Err(e) = self.handle_webhook(event, &payload, delivery_id).await {
+                error!("Error while handling replayed delivery: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn acquire_lease(&self) -> Result<Option<Handoff>> {
+        let lease = match &self.lease {
+            Some(lease) => lease,
+            None => return Ok(None),
+        };
+
+        if let Some(previous) = lease.acquire()? {
+            info!(
+                "{}/{} - Waiting for handoff from '{}'",
+                self.config.owner(),
+                self.config.name(),
+                previous
+            );
+            lease.wait_for_handoff(&previous).await
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn spawn_lease_renewal(&self) {
+        if let Some(lease) = &self.lease {
+            let mut tx = self.requests_tx.clone();
+            let mut interval = tokio::time::interval(lease.renew_interval());
+            tokio::spawn(async move {
+                loop {
+                    interval.tick().await;
+                    if tx.send(Request::RenewLease).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    /// Look up the user bors acts as so that events triggered by bors itself can be recognized. An
+    /// installation of a Github App acts as the app's bot user, which isn't an authenticated user.
+    async fn identify(&mut self) {
+        if let Some(login) = self.github.app_login() {
+            info!("acting as Github App '{}'", login);
+            self.login = Some(login.to_owned());
+            return;
+        }
+
+        match self.github.users().get_authenticated().await {
+            Ok(user) => {
+                let login = user.into_inner().login;
+                info!("acting as Github user '{}'", login);
+                self.login = Some(login);
+            }
+            Err(e) => warn!("unable to look up the authenticated user: {:?}", e),
+        }
+    }
+
+    /// Warn when this repo's installation is running low on any of its rate limits, which are
+    /// shared with the other repos it serves
+    fn warn_on_low_rate_limits(&self) {
+        for (resource, rate) in self.github.rates() {
+            if rate.remaining < rate.limit / 10 {
+                warn!(
+                    "{}/{} - installation has {} of {} '{}' requests remaining",
+                    self.config.owner(),
+                    self.config.name(),
+                    rate.remaining,
+                    rate.limit,
+                    resource
+                );
+            }
+        }
+    }
+
+    /// Check if an event was triggered by bors itself
+    fn is_own_event(&self, sender: &str) -> bool {
+        self.login.as_deref() == Some(sender)
+    }
+
+    /// Notify the event loop once `period` has passed since a PR was pushed `head_oid`
+    fn spawn_push_settled(&self, number: u64, head_oid: Oid, period: std::time::Duration) {
+        let mut tx = self.requests_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::delay_for(period).await;
+            let _ = tx.send(Request::PushSettled { number, head_oid }).await;
+        });
+    }
+
+    /// Periodically check for PRs which have been queued for too long, if alerting on them is
+    /// configured
+    fn spawn_residency_checks(&self) {
+        const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+        if self.config.queue_residency().is_some() {
+            let mut tx = self.requests_tx.clone();
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            tokio::spawn(async move {
+                loop {
+                    interval.tick().await;
+                    if tx.send(Request::CheckQueueResidency).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    /// Alert maintainers to PRs which have been queued for longer than the configured maximum,
+    /// once each time a PR is queued
+    async fn check_queue_residency(&mut self) -> Result<()> {
+        let residency = match self.config.queue_residency() {
+            Some(residency) => residency,
+            None => return Ok(()),
+        };
+
+        let now = SystemTime::now();
+        for pull in self.pulls.values_mut() {
+            if pull.residency_alerted {
+                continue;
+            }
+            let queued_for = match pull.queued_at.and_then(|at| now.duration_since(at).ok()) {
+                Some(queued_for) if queued_for >= residency.max() => queued_for,
+                _ => continue,
+            };
+
+            warn!(
+                "{}/{} - #{} has been queued for {}",
+                self.config.owner(),
+                self.config.name(),
+                pull.number,
+                format_wait(queued_for)
+            );
+            pull.residency_alerted = true;
+
+            let mut cc = pull.cc_reviewers();
+            for user in residency.notify() {
+                if cc.is_empty() {
+                    cc.push_str("\n\ncc");
+                }
+                cc.push_str(&format!(" @{}", user.trim_start_matches('@')));
+            }
+            let body = format!(
+                ":turtle: This PR has been queued for {} without landing, longer than the \
+                configured maximum of {}. It may be starved by higher priority PRs.{}",
+                format_wait(queued_for),
+                format_wait(residency.max()),
+                cc
+            );
+            pull.create_comment(
+                &self.config,
+                &self.github,
+                CommentEvent::QueueResidency,
+                &body,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Process the queue again once `wait` has passed, e.g. when the land window opens, unless
+    /// already scheduled to by then
+    fn schedule_queue_wakeup(&mut self, wait: std::time::Duration) {
+        let at = Instant::now() + wait;
+        if self.queue_wakeup.map_or(false, |scheduled| scheduled <= at) {
+            return;
+        }
+        self.queue_wakeup = Some(at);
+
+        let mut tx = self.requests_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::delay_for(wait).await;
+            let _ = tx.send(Request::QueueWakeup).await;
+        });
+    }
+
+    /// React to pushes to a PR once no more have arrived within the quiet period: drop its
+    /// approval, point out a head which was approved before, autolabel it and re-check the
+    /// protected files it changes
+    async fn handle_push_settled(&mut self, number: u64) -> Result<()> {
+        if let Some(pr) = self.pulls.get_mut(&number) {
+            info!("pushes to PR #{} have settled", number);
+            pr.push_settling = false;
+            let head_oid = pr.head_ref_oid.clone();
+
+            // The approval covered the PR's previous head, not what was just pushed
+            if self.config.invalidate_on_push() && pr.status.is_queued() {
+                info!("PR #{} was pushed to while queued, dropping it", number);
+                pr.approver = None;
+                pr.approved_oid = None;
+                pr.reviewer = None;
+                pr.update_status(
+                    Status::InReview,
+                    &self.config,
+                    &self.github,
+                    self.project_board.as_ref(),
+                )
+                .await?;
+                pr.create_comment(
+                    &self.config,
+                    &self.github,
+                    CommentEvent::Command,
+                    ":warning: New commits were pushed after this PR was approved, so it has been \
+                    removed from the queue and needs to be approved again",
+                )
+                .await?;
+            }
+
+            // A head which was approved before, e.g. one restored by reverting a force-push, is
+            // pointed out so that it can be approved again. It isn't requeued outright since the
+            // checks made when approving, e.g. of the approver's permissions, may no longer pass.
+            let can_restore = self.config.restore_approvals()
+                && pr.approver.is_none()
+                && matches!(pr.status, Status::InReview);
+            let restored = match &self.approvals {
+                Some(history) if can_restore => history
+                    .approver_of(number, &head_oid)
+                    .map(ToOwned::to_owned),
+                _ => None,
+            };
+            if let Some(approver) = restored {
+                info!("PR #{} returned to a head approved by {}", number, approver);
+                pr.create_comment(
+                    &self.config,
+                    &self.github,
+                    CommentEvent::Command,
+                    &format!(
+                        ":recycle: This PR's head is back at `{}`, which was approved by {} before. \
+                        It can be queued for landing again with `r+`.",
+                        head_oid, approver
+                    ),
+                )
+                .await?;
+            }
+
+            if let Err(e) = apply_autolabels(&self.config, &self.github, pr).await {
+                warn!("unable to autolabel PR #{}: {:?}", pr.number, e);
+            }
+
+            // The new commits may change protected files the PR's approval didn't cover
+            let missing = match (&pr.approver, pr.status.is_queued()) {
+                (Some(approver), true) => {
+                    protected_paths::missing_approvals(&self.config, &self.github, pr, approver)
+                        .await
+                        .unwrap_or_else(|e| {
+                            warn!(
+                                "unable to re-check the protected files of PR #{}: {:?}",
+                                number, e
+                            );
+                            Vec::new()
+                        })
+                }
+                _ => Vec::new(),
+            };
+            if !missing.is_empty() {
+                info!(
+                    "PR #{} now changes protected files it isn't approved for, dropping it",
+                    number
+                );
+                pr.approver = None;
+                pr.approved_oid = None;
+                pr.reviewer = None;
+                pr.update_status(
+                    Status::InReview,
+                    &self.config,
+                    &self.github,
+                    self.project_board.as_ref(),
+                )
+                .await?;
+
+                let mut msg = String::from(
+                    ":closed_lock_with_key: New commits pushed to this PR change protected files, \
+                    so it has been removed from the queue until it has:\n",
+                );
+                for item in missing {
+                    msg.push_str(&format!("\n- {}", item));
+                }
+                pr.create_comment(&self.config, &self.github, CommentEvent::Command, &msg)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn renew_lease(&mut self) -> Result<()> {
+        let lease = match &self.lease {
+            Some(lease) => lease,
+            None => return Ok(()),
+        };
+
+        if lease.renew()? {
+            return Ok(());
+        }
+
+        warn!(
+            "{}/{} - Lease taken over by another instance, handing off in-flight state",
+            self.config.owner(),
+            self.config.name()
+        );
+        self.lease_lost = true;
+
+        // Speculative results can't be trusted by the next instance so the speculative batch is
+        // handed off as being queued
+        let speculative = self
+            .merge_queue
+            .speculative()
+            .iter()
+            .filter_map(|number| self.pulls.get(number))
+            .filter(|pull| pull.status.is_testing())
+            .map(HandoffEntry::requeued);
+        let queued = self
+            .pulls
+            .values()
+            .filter(|pull| pull.status.is_queued())
+            .map(HandoffEntry::from_pull)
+            .chain(speculative)
+            .collect();
+        let testing = self
+            .merge_queue
+            .head()
+            .iter()
+            .filter_map(|number| self.pulls.get(number))
+            .filter(|pull| pull.status.is_testing())
+            .map(HandoffEntry::from_pull)
+            .collect();
+
+        let delegates = self
+            .pulls
+            .values()
+            .filter_map(|pull| Some((pull.number, pull.delegate.clone()?)))
+            .collect();
+
+        lease.write_handoff(queued, testing, self.merge_queue.tree_closed(), delegates)
+    }
+
+    async fn apply_handoff(&mut self, handoff: Handoff) -> Result<()> {
+        info!(
+            "{}/{} - Applying handoff from '{}'",
+            self.config.owner(),
+            self.config.name(),
+            handoff.from
+        );
+
+        // The batch being tested is only resumed if none of its PRs have changed since, otherwise
+        // they are returned to the queue to be retested
+        let batch: Vec<u64> = handoff.testing.iter().map(|entry| entry.number).collect();
+        let resume_batch = handoff.testing.iter().all(|entry| {
+            entry.merge_oid.is_some()
+                && self
+                    .pulls
+                    .get(&entry.number)
+                    .map(|pull| pull.head_ref_oid == entry.head_oid)
+                    .unwrap_or(false)
+        });
+
+        for (number, delegate) in handoff.delegates {
+            if let Some(pull) = self.pulls.get_mut(&number) {
+                pull.delegate = Some(delegate);
+            }
+        }
+
+        for entry in handoff.queued.into_iter().chain(handoff.testing) {
+            let pull = match self.pulls.get_mut(&entry.number) {
+                Some(pull) if pull.head_ref_oid == entry.head_oid => pull,
+                _ => continue,
+            };
+
+            let overrides = entry.overrides;
+            let (status, next_attempt) = match entry.merge_oid {
+                Some(merge_oid) if resume_batch => (
+                    Status::Testing {
+                        merge_oid,
+                        tests_started_at: Instant::now(),
+                        test_results: entry.test_results,
+                        overrides,
+                    },
+                    entry.next_attempt,
+                ),
+                // An attempt which can't be resumed is started again with the same overrides
+                _ => (
+                    Status::Queued,
+                    entry
+                        .next_attempt
+                        .or_else(|| Some(overrides).filter(|o| !o.is_empty())),
+                ),
+            };
+
+            pull.priority = entry.priority;
+            pull.approver = entry.approver;
+            pull.attempts = entry.attempts;
+            pull.spurious_retries = entry.spurious_retries;
+            pull.merge_strategy = entry.merge_strategy;
+            pull.rollup = entry.rollup;
+            pull.depends_on.extend(entry.depends_on);
+            pull.superseded = entry.superseded;
+            pull.queued_at = entry.queued_at;
+            pull.residency_alerted = entry.residency_alerted;
+            pull.approved_oid = entry.approved_oid;
+            pull.reviewer = entry.reviewer;
+            pull.next_attempt = next_attempt;
+            pull.update_status(
+                status,
+                &self.config,
+                &self.github,
+                self.project_board.as_ref(),
+            )
+            .await?;
+        }
+
+        if resume_batch && !batch.is_empty() {
+            self.merge_queue.restore_head(batch);
+        }
+        *self.merge_queue.tree_closed_mut() = handoff.tree_closed;
+        tree_closed_store(&self.git_repository.state_dir()).save(&handoff.tree_closed)?;
+
+        Ok(())
+    }
+
+    async fn handle_admin_command(&mut self, command: AdminCommand) -> Result<()> {
+        info!(
+            "{}/{} - Handling Admin command: {:?}",
+            self.config.owner(),
+            self.config.name(),
+            command
+        );
+
+        match command {
+            AdminCommand::Eject { number } => {
+                let pull = self
+                    .pulls
+                    .get_mut(&number)
+                    .ok_or_else(|| anyhow!("unknown PR #{}", number))?;
+
+                if pull.status.is_queued() || pull.status.is_testing() {
+                    pull.update_status(
+                        Status::InReview,
+                        &self.config,
+                        &self.github,
+                        self.project_board.as_ref(),
+                    )
+                    .await?;
+                    pull.create_comment(
+                        &self.config,
+                        &self.github,
+                        CommentEvent::Command,
+                        ":eject: Removed from the merge queue by an administrator",
+                    )
+                    .await?;
+                }
+            }
+            AdminCommand::Prioritize { number, priority } => {
+                let pull = self
+                    .pulls
+                    .get_mut(&number)
+                    .ok_or_else(|| anyhow!("unknown PR #{}", number))?;
+
+                pull.priority = priority;
+
+                let label = self.config.labels().high_priority();
+                if priority > 0 {
+                    pull.add_label(&self.config, &self.github, label).await?;
+                } else {
+                    pull.remove_label(&self.config, &self.github, label).await?;
+                }
+            }
+            AdminCommand::PrioritizeLabel { label, priority } => {
+                self.prioritize_label(&label, priority).await?
+            }
+            AdminCommand::Rekick => self.rekick().await?,
+            AdminCommand::SkipSoak => self.skip_soak().await?,
+            AdminCommand::Pause => self.paused = true,
+            AdminCommand::Drain => self.draining = true,
+            AdminCommand::Resume => {
+                self.paused = false;
+                self.draining = false;
+                for waiter in self.drain_waiters.drain(..) {
+                    let _ = waiter.send(Err(anyhow!("drain canceled by resume")));
+                }
+            }
+        }
+
+        self.process_merge_queue().await
     }
 
-    fn route(&self, _event_type: EventType) -> bool {
-        true
-    }
+    /// Set the priority of every queued PR carrying `label`, recording the change in each PR's
+    /// audit log
+    async fn prioritize_label(&mut self, label: &str, priority: u32) -> Result<()> {
+        let high_priority = self.config.labels().high_priority();
+        let mut prioritized = Vec::new();
 
-    async fn handle(&self, event: &Event, delivery_id: &str) {
-        self.clone()
-            .webhook(event.clone(), delivery_id.to_owned())
-            .await
-            .unwrap();
-    }
-}
+        for pull in self.pulls.values_mut() {
+            if !pull.status.is_queued() || !pull.labels.contains(label) {
+                continue;
+            }
 
-#[derive(Debug)]
-pub struct EventProcessor {
-    config: RepoConfig,
-    github: GithubClient,
-    git_repository: GitRepository,
-    merge_queue: MergeQueue,
-    project_board: Option<ProjectBoard>,
-    pulls: HashMap<u64, PullRequestState>,
-    requests_rx: mpsc::Receiver<Request>,
-}
+            pull.priority = priority;
+            if priority > 0 {
+                pull.add_label(&self.config, &self.github, high_priority)
+                    .await?;
+            } else {
+                pull.remove_label(&self.config, &self.github, high_priority)
+                    .await?;
+            }
+            prioritized.push(pull.number);
+        }
 
-impl EventProcessor {
-    pub fn new(
-        config: RepoConfig,
-        github_config: &GithubConfig,
-        git_config: &GitConfig,
-    ) -> Result<(EventProcessorSender, Self)> {
-        let (tx, rx) = mpsc::channel(1024);
-        let github = GithubClient::new(&github_config.github_api_token);
-        let git_repository = GitRepository::from_config(git_config, config.repo())?;
+        info!(
+            "set the priority of queued PRs labeled '{}' to {}: {:?}",
+            label, priority, prioritized
+        );
+        for number in prioritized {
+            self.audit(
+                number,
+                AuditRecord::Admin {
+                    action: format!("set priority to {} for label '{}'", priority, label),
+                },
+            );
+        }
 
-        Ok((
-            EventProcessorSender::new(tx),
-            Self {
-                config,
-                github,
-                git_repository,
-                merge_queue: MergeQueue::new(),
-                project_board: None,
-                pulls: HashMap::new(),
-                requests_rx: rx,
-            },
-        ))
+        Ok(())
     }
 
-    pub async fn start(mut self) {
-        self.synchronize()
-            .await
-            .expect("unable to synchronize initial state");
-
-        while let Some(request) = self.requests_rx.next().await {
-            if let Err(e) = self.handle_request(request).await {
-                error!("Error while handling request: {:?}", e);
+    /// Land the batch waiting on its soak checks without waiting for them to finish
+    async fn skip_soak(&mut self) -> Result<()> {
+        let batch = self
+            .merge_queue
+            .skip_soak(&self.pulls)
+            .ok_or_else(|| anyhow!("no batch is waiting on soak checks"))?
+            .to_vec();
+
+        for number in batch {
+            if let Some(pull) = self.pulls.get(&number) {
+                pull.create_comment(
+                    &self.config,
+                    &self.github,
+                    CommentEvent::Soaking,
+                    ":fast_forward: Soak checks skipped by an administrator",
+                )
+                .await?;
             }
         }
+
+        Ok(())
     }
 
-    async fn handle_request(&mut self, request: Request) -> Result<()> {
-        use Request::*;
-        match request {
-            Webhook { event, delivery_id } => self.handle_webhook(event, delivery_id).await?,
+    /// Re-request the check suites of the merge commit at the tip of the batch being tested and
+    /// restart its timeout, discarding any results reported so far
+    async fn rekick(&mut self) -> Result<()> {
+        let merge_oid = self
+            .merge_queue
+            .head()
+            .last()
+            .and_then(|number| match &self.pulls.get(number)?.status {
+                Status::Testing { merge_oid, .. } => Some(merge_oid.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("no PR is being tested"))?;
+
+        let suites = self
+            .github
+            .checks()
+            .list_check_suites_for_ref(
+                self.config.owner(),
+                self.config.name(),
+                &merge_oid.to_string(),
+            )
+            .await?
+            .into_inner()
+            .check_suites;
+        for suite in &suites {
+            self.github
+                .checks()
+                .rerequest_check_suite(self.config.owner(), self.config.name(), suite.id)
+                .await?;
+        }
+        info!(
+            "{}/{} - Re-requested {} check suites of {}",
+            self.config.owner(),
+            self.config.name(),
+            suites.len(),
+            merge_oid
+        );
+
+        for pull in self.pulls.values_mut() {
+            match &mut pull.status {
+                Status::Testing {
+                    merge_oid: oid,
+                    tests_started_at,
+                    test_results,
+                    ..
+                } if *oid == merge_oid => {
+                    *tests_started_at = Instant::now();
+                    test_results.clear();
+                }
+                _ => continue,
+            }
+
+            pull.create_comment(
+                &self.config,
+                &self.github,
+                CommentEvent::Command,
+                &format!(
+                    ":arrows_counterclockwise: Re-requested the {} check suites of {} at an \
+                    administrator's request",
+                    suites.len(),
+                    merge_oid
+                ),
+            )
+            .await?;
         }
 
         Ok(())
     }
 
-    async fn handle_webhook(&mut self, event: Event, delivery_id: String) -> Result<()> {
+    async fn handle_webhook(
+        &mut self,
+        event: Event,
+        payload: &[u8],
+        delivery_id: String,
+    ) -> Result<()> {
         // Verify that the event is from our configured repository
         if !event
             .repository()
@@ -133,15 +1499,53 @@ impl EventProcessor {
             delivery_id
         );
 
+        if let Some(watermark) = &self.watermark {
+            watermark.set(&delivery_id)?;
+        }
+
+        // Ignore pushes made by bors itself, e.g. to the staging branches, so that they can't
+        // feed back into bors. Landing a PR which changes the repo's settings still reloads them.
+        if let Event::Push(e) = &event {
+            if self.config.in_repo_config() && touches_in_repo_config(e) {
+                if let Err(error) = self.load_in_repo_config().await {
+                    warn!(
+                        "{}/{} - unable to reload {}: {:?}",
+                        self.config.owner(),
+                        self.config.name(),
+                        IN_REPO_CONFIG_PATH,
+                        error
+                    );
+                } else {
+                    self.sync_protected_checks().await;
+                }
+            }
+
+            if self.is_own_event(&e.sender.login) {
+                info!("ignoring push to '{}' made by bors", e.git_ref);
+                return Ok(());
+            }
+        }
+
+        self.audit_event(&event, &delivery_id);
+        self.archive_payload(&event, payload, &delivery_id);
+
         match &event {
+            // The rule's branch pattern isn't matched against here, the required checks are simply
+            // fetched afresh
+            Event::BranchProtectionRule(_) => self.sync_protected_checks().await,
             Event::PullRequest(e) => self.handle_pull_request_event(e).await?,
+            Event::Push(e) => self.handle_push_event(e).await?,
             Event::CheckRun(e) => self.handle_check_run_event(e),
+            Event::CheckSuite(e) => self.handle_check_suite_event(e).await?,
             Event::Status(e) => self.handle_status_event(e),
+            Event::WorkflowRun(e) => self.handle_workflow_run_event(e),
+            Event::WorkflowJob(e) => self.handle_workflow_job_event(e),
             Event::IssueComment(e) => {
                 // Only process commands from newly created comments
                 if e.action.is_created() && e.issue.is_pull_request() {
                     self.process_comment(
                         &e.sender.login,
+                        e.comment.author_association.as_deref(),
                         e.issue.number,
                         e.comment.body(),
                         &e.comment.node_id,
@@ -154,6 +1558,7 @@ impl EventProcessor {
                 if e.action.is_created() {
                     self.process_comment(
                         &e.sender.login,
+                        Some(&e.comment.author_association),
                         e.pull_request.number,
                         e.comment.body(),
                         &e.comment.node_id,
@@ -165,7 +1570,104 @@ impl EventProcessor {
             _ => {}
         }
 
+        for number in self.auto_approval_candidates(&event) {
+            self.consider_auto_approval(number).await?;
+        }
+
         self.process_merge_queue().await?;
+        self.process_try_builds().await?;
+
+        Ok(())
+    }
+
+    /// Guard the staging and try branches against pushes made by anything other than bors, e.g. a
+    /// second bot or a person, which would mean the merge commit being tested is no longer the one
+    /// bors built. Any PRs being tested on the branch are tested again from scratch.
+    async fn handle_push_event(&mut self, event: &github::PushEvent) -> Result<()> {
+        use crate::state::Retry;
+
+        let branch = match event.git_ref.strip_prefix("refs/heads/") {
+            Some(branch) if !event.deleted => branch,
+            _ => return Ok(()),
+        };
+
+        let is_attempt_branch = |staging: &str| {
+            branch == staging
+                || (self.config.unique_staging_branches()
+                    && branch.starts_with(staging)
+                    && branch[staging.len()..].starts_with("/pr-"))
+        };
+        let numbers: Vec<u64> = if is_attempt_branch(self.config.staging_branch()) {
+            self.merge_queue.head().to_vec()
+        } else if self
+            .config
+            .speculative_branch()
+            .map_or(false, is_attempt_branch)
+        {
+            self.merge_queue.speculative().to_vec()
+        } else if branch == self.config.try_branch() {
+            self.pulls
+                .values()
+                .filter(|pull| pull.status.is_trying())
+                .map(|pull| pull.number)
+                .collect()
+        } else {
+            return Ok(());
+        };
+
+        // Only pushes by someone other than bors clobber what's being tested. A push can be
+        // delivered after bors has already moved on to testing another merge commit, so it can't be
+        // recognized by its commit; if bors' login is unknown it's left be.
+        let is_foreign = self
+            .login
+            .as_deref()
+            .map_or(false, |login| login != event.sender.login);
+        if numbers.is_empty() || !is_foreign {
+            return Ok(());
+        }
+
+        warn!(
+            "{}/{} - '{}' was pushed to {} by {}, retesting {}",
+            self.config.owner(),
+            self.config.name(),
+            branch,
+            event.after,
+            event.sender.login,
+            pr_list(&numbers)
+        );
+
+        for number in numbers {
+            let pull = self.pulls.get_mut(&number).expect("PR should exist");
+            let (status, retesting) = if pull.status.is_trying() {
+                pull.retry = Some(Retry::Try);
+                (Status::InReview, "It can be tried again with `retry`.")
+            } else {
+                (
+                    Status::Queued,
+                    "It has been returned to the queue to be retested.",
+                )
+            };
+            pull.update_status(
+                status,
+                &self.config,
+                &self.github,
+                self.project_board.as_ref(),
+            )
+            .await?;
+
+            let comment = format!(
+                ":warning: `{}` was pushed to by @{} while this PR was being tested on it, so the \
+                results can't be trusted. {}",
+                branch, event.sender.login, retesting
+            );
+            pull.create_comment(
+                &self.config,
+                &self.github,
+                CommentEvent::ForeignPush,
+                &comment,
+            )
+            .await?;
+        }
 
         Ok(())
     }
@@ -180,12 +1682,53 @@ impl EventProcessor {
 
         match event.action {
             PullRequestEventAction::Synchronize => {
-                if let Some(pr) = self.pulls.get_mut(&event.pull_request.number) {
-                    pr.update_head(event.pull_request.head.sha.clone())
+                let number = event.pull_request.number;
+                let head_oid = &event.pull_request.head.sha;
+                let is_own_event = self.is_own_event(&event.sender.login);
+
+                if let Some(pr) = self.pulls.get_mut(&number) {
+                    pr.update_head(head_oid.clone());
+
+                    // Beyond tracking the new head there's nothing to react to when bors updated
+                    // the PR in-place itself. Otherwise, since authors often push several times
+                    // in quick succession, anything more involved, e.g. dropping the PR from the
+                    // queue or commenting on it, waits until the pushes settle. The PR isn't
+                    // tested in the meantime.
+                    if is_own_event {
+                        info!("PR #{} was updated in-place by bors", number);
+                    } else {
+                        match self.config.synchronize_quiet_period() {
+                            Some(period) => {
+                                pr.push_settling = true;
+                                self.spawn_push_settled(number, head_oid.clone(), period)
+                            }
+                            None => self.handle_push_settled(number).await?,
+                        }
+                    }
                 }
             }
             PullRequestEventAction::Opened | PullRequestEventAction::Reopened => {
-                let mut state = PullRequestState::from_pull_request(&event.pull_request);
+                // Query for the PR's full state in one go, including reviews and mergeability,
+                // falling back to what is provided by the webhook
+                let mut state = match self
+                    .github
+                    .pull_request(
+                        self.config.owner(),
+                        self.config.name(),
+                        event.pull_request.number,
+                    )
+                    .await
+                {
+                    Ok(Some(state)) => state,
+                    Ok(None) => PullRequestState::from_pull_request(&event.pull_request),
+                    Err(e) => {
+                        warn!(
+                            "unable to query state of PR #{}: {:?}",
+                            event.pull_request.number, e
+                        );
+                        PullRequestState::from_pull_request(&event.pull_request)
+                    }
+                };
 
                 info!("PR #{} Opened", state.number);
 
@@ -202,28 +1745,51 @@ impl EventProcessor {
                     && !state.maintainer_can_modify
                     && !pr_is_from_base_repo
                 {
-                    self.github
-                        .issues()
+                    state
                         .create_comment(
-                            self.config.repo().owner(),
-                            self.config.repo().name(),
-                            state.number,
-                            &format!(":exclamation: before this PR can be merged please make sure that you enable \
+                            &self.config,
+                            &self.github,
+                            CommentEvent::MaintainerEdits,
+                            ":exclamation: before this PR can be merged please make sure that you enable \
                             [\"Allow edits from maintainers\"]\
                             (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/allowing-changes-to-a-pull-request-branch-created-from-a-fork).\n\n\
                             This is needed for tooling to be able to update this PR in-place so that Github can \
-                            properly recognize and mark it as merged once its merged into the upstream branch"),
+                            properly recognize and mark it as merged once its merged into the upstream branch",
                         )
                         .await?;
                 }
 
+                if let Err(e) = apply_autolabels(&self.config, &self.github, &mut state).await {
+                    warn!("unable to autolabel PR #{}: {:?}", state.number, e);
+                }
+
                 if let Some(board) = &self.project_board {
                     board.create_card(&self.github, &mut state).await?;
                 }
 
-                if self.pulls.insert(state.number, state).is_some() {
-                    warn!("Opened/Reopened event replaced an existing PullRequestState");
+                // PRs which bors opens itself, e.g. reverts, may be queued before their Opened
+                // event arrives, in which case they keep their place in the queue
+                match self.pulls.remove(&state.number) {
+                    Some(tracked) if tracked.approver.is_some() => {
+                        state.approver = tracked.approver;
+                        state.approved = tracked.approved;
+                        state.approved_oid = tracked.approved_oid;
+                        state.priority = tracked.priority;
+                        state.queued_at = tracked.queued_at;
+                        state.state_version = tracked.state_version;
+                        state
+                            .update_status(
+                                tracked.status,
+                                &self.config,
+                                &self.github,
+                                self.project_board.as_ref(),
+                            )
+                            .await?;
+                    }
+                    Some(_) => warn!("Opened/Reopened event replaced an existing PullRequestState"),
+                    None => {}
                 }
+                self.pulls.insert(state.number, state);
             }
             PullRequestEventAction::Closed => {
                 // From [Github's API docs](https://developer.github.com/v3/activity/events/types/#events-api-payload-31):
@@ -234,6 +1800,7 @@ impl EventProcessor {
 
                 if merged {
                     info!("pr #{} successfully Merged!", event.pull_request.number);
+                    mark_landed(&mut self.pulls, event.pull_request.number);
                 }
 
                 // XXX Do we need to call into the MergeQueue to notify it that a PR was merged or
@@ -249,6 +1816,10 @@ impl EventProcessor {
                     if let Some(pull) = self.pulls.get_mut(&event.pull_request.number) {
                         pull.labels.insert(label.name.clone());
                     }
+                    if label.name == self.config.labels().blocked() {
+                        let reason = format!("This PR was labeled `{}`", label.name);
+                        self.abort_land(event.pull_request.number, &reason).await?;
+                    }
                 }
             }
             PullRequestEventAction::Unlabeled => {
@@ -262,6 +1833,11 @@ impl EventProcessor {
                 if let Some(pull) = self.pulls.get_mut(&event.pull_request.number) {
                     pull.is_draft = true;
                 }
+                self.abort_land(
+                    event.pull_request.number,
+                    "This PR was converted to a draft",
+                )
+                .await?;
             }
             PullRequestEventAction::ReadyForReview => {
                 if let Some(pull) = self.pulls.get_mut(&event.pull_request.number) {
@@ -277,6 +1853,12 @@ impl EventProcessor {
                     }
                     let body = event.pull_request.body.as_deref().unwrap_or("");
                     if body != pull.body {
+                        // Only the dependencies from the description's trailers are replaced,
+                        // keeping any added with the `depends` command
+                        for number in parse_depends_on(&pull.body) {
+                            pull.depends_on.remove(&number);
+                        }
+                        pull.depends_on.extend(parse_depends_on(body));
                         pull.body = body.to_owned();
                     }
                     if !matches!(pull.status, Status::Testing { .. }) {
@@ -293,70 +1875,497 @@ impl EventProcessor {
                 }
             }
 
-            // Do nothing for actions we're not interested in
-            _ => {}
-        }
+            // Do nothing for actions we're not interested in
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Take a PR out of the queue, canceling its land if it's being tested, because it can't land
+    /// as it is any more, e.g. it was converted back to a draft
+    async fn abort_land(&mut self, number: u64, reason: &str) -> Result<()> {
+        let pull = match self.pulls.get_mut(&number) {
+            Some(pull) if pull.status.is_queued() || pull.status.is_testing() => pull,
+            _ => return Ok(()),
+        };
+
+        info!("aborting land of pr #{}: {}", number, reason);
+
+        let was_testing = pull.status.is_testing();
+        pull.update_status(
+            Status::InReview,
+            &self.config,
+            &self.github,
+            self.project_board.as_ref(),
+        )
+        .await?;
+        if was_testing {
+            pull.report_check_run(
+                &self.config,
+                &self.github,
+                &pull.head_ref_oid,
+                Some(github::Conclusion::Cancelled),
+                None,
+            )
+            .await;
+        }
+        pull.create_comment(
+            &self.config,
+            &self.github,
+            CommentEvent::Command,
+            &format!(
+                ":no_entry: {}, so it has been removed from the queue and needs to be approved \
+                again once it's ready to land",
+                reason
+            ),
+        )
+        .await
+    }
+
+    /// The PRs whose merge commit, either landing or trying, is `oid`
+    fn pulls_with_merge_oid(&self, oid: &github::Oid) -> Vec<u64> {
+        self.pulls
+            .values()
+            .filter(|pr| match &pr.status {
+                Status::Testing { merge_oid, .. } | Status::Trying { merge_oid, .. } => {
+                    merge_oid == oid
+                }
+                Status::InReview | Status::Queued | Status::Failed { .. } => false,
+            })
+            .map(|pr| pr.number)
+            .collect()
+    }
+
+    /// The PRs an event may have made eligible for automatic approval, either because the PR itself
+    /// changed or because checks on its head completed
+    fn auto_approval_candidates(&self, event: &Event) -> Vec<u64> {
+        if self.config.auto_approve().is_none() {
+            return Vec::new();
+        }
+
+        let head = match event {
+            Event::PullRequest(e) => return vec![e.pull_request.number],
+            Event::CheckRun(e) => &e.check_run.head_sha,
+            Event::Status(e) => &e.sha,
+            Event::WorkflowRun(e) => &e.workflow_run.head_sha,
+            Event::WorkflowJob(e) => &e.workflow_job.head_sha,
+            _ => return Vec::new(),
+        };
+        self.pulls
+            .values()
+            .filter(|pull| &pull.head_ref_oid == head)
+            .map(|pull| pull.number)
+            .collect()
+    }
+
+    /// Approve and queue a PR opened by one of the configured bots if it satisfies the policy,
+    /// recording the decision to the audit log
+    async fn consider_auto_approval(&mut self, number: u64) -> Result<()> {
+        let pull = match self.pulls.get_mut(&number) {
+            Some(pull) => pull,
+            None => return Ok(()),
+        };
+        let decision = match self
+            .auto_approver
+            .decide(&self.config, &self.github, pull)
+            .await?
+        {
+            Some(decision) => decision,
+            None => return Ok(()),
+        };
+
+        let record = match decision {
+            Decision::Pending => return Ok(()),
+            Decision::Reject(reason) | Decision::Defer(reason) => {
+                info!("not auto-approving pr #{}: {}", number, reason);
+                AuditRecord::AutoApproval {
+                    approved: false,
+                    reason,
+                }
+            }
+            Decision::Approve(reason) => {
+                info!("auto-approving pr #{}: {}", number, reason);
+                pull.spurious_retries = 0;
+                pull.update_status(
+                    Status::Queued,
+                    &self.config,
+                    &self.github,
+                    self.project_board.as_ref(),
+                )
+                .await?;
+                pull.create_comment(
+                    &self.config,
+                    &self.github,
+                    CommentEvent::AutoApproved,
+                    &format!(
+                        ":robot: Approved automatically as {}, queued for landing",
+                        reason
+                    ),
+                )
+                .await?;
+                AuditRecord::AutoApproval {
+                    approved: true,
+                    reason,
+                }
+            }
+        };
+        self.audit(number, record);
+
+        Ok(())
+    }
+
+    fn pull_from_merge_oid(&mut self, oid: &github::Oid) -> Option<&mut PullRequestState> {
+        self.pulls
+            .iter_mut()
+            .find(|(_n, pr)| match &pr.status {
+                Status::Testing { merge_oid, .. } | Status::Trying { merge_oid, .. } => {
+                    merge_oid == oid
+                }
+                Status::InReview | Status::Queued | Status::Failed { .. } => false,
+            })
+            .map(|(_n, pr)| pr)
+    }
+
+    fn handle_check_run_event(&mut self, event: &github::CheckRunEvent) {
+        info!("Handling CheckRunEvent");
+
+        // Skip the event if it hasn't completed
+        let conclusion = match (
+            event.action,
+            event.check_run.status,
+            event.check_run.conclusion,
+        ) {
+            (
+                github::CheckRunEventAction::Completed,
+                github::CheckStatus::Completed,
+                Some(conclusion),
+            ) => conclusion,
+            _ => return,
+        };
+
+        let output = &event.check_run.output;
+        let annotations = output.annotations.iter().flatten().flat_map(|annotation| {
+            annotation
+                .title
+                .iter()
+                .chain(&annotation.message)
+                .chain(&annotation.raw_details)
+        });
+        let texts: Vec<&str> = output
+            .title
+            .iter()
+            .chain(&output.summary)
+            .chain(&output.text)
+            .chain(annotations)
+            .map(String::as_str)
+            .collect();
+        let known_issue =
+            self.known_issue(&event.check_run.name, conclusion, texts.iter().copied());
+        let spurious = is_spurious_failure(&self.config, conclusion, texts.iter().copied())
+            || known_issue.as_ref().map_or(false, |issue| issue.retry);
+
+        let duration = self
+            .pull_from_merge_oid(&event.check_run.head_sha)
+            .and_then(|pr| {
+                pr.add_build_result(
+                    &event.check_run.name,
+                    &event.check_run.details_url,
+                    output
+                        .summary
+                        .as_deref()
+                        .or_else(|| output.title.as_deref()),
+                    conclusion,
+                    spurious,
+                    known_issue.map(|issue| issue.issue_url),
+                )
+            });
+        self.record_check_duration(&event.check_run.name, duration);
+    }
+
+    /// A check suite's conclusion is only recorded for the Github Apps configured as check suites,
+    /// whose suites are gated on as a whole rather than by their individual checks
+    /// An app can report several check suites on a commit, e.g. one for each workflow of Github
+    /// Actions, all of which are gated on under the app's slug. Any of them failing fails the app,
+    /// which it only passes once every one of them has succeeded.
+    async fn handle_check_suite_event(&mut self, event: &github::CheckSuiteEvent) -> Result<()> {
+        info!("Handling CheckSuiteEvent");
+
+        let suite = &event.check_suite;
+        if !self.config.is_check_suite(&suite.app.slug) {
+            return Ok(());
+        }
+
+        // Skip the event if it hasn't completed
+        let mut conclusion = match (&event.action, suite.status, suite.conclusion) {
+            (
+                github::CheckSuiteEventAction::Completed,
+                github::CheckStatus::Completed,
+                Some(conclusion),
+            ) => conclusion,
+            _ => return Ok(()),
+        };
+
+        // A failure of one of the app's suites stands until the commit is tested again
+        let failed = match self.pull_from_merge_oid(&suite.head_sha) {
+            Some(pr) => pr
+                .build_result(&suite.app.slug)
+                .map_or(false, |result| !result.passed),
+            None => return Ok(()),
+        };
+        if failed {
+            return Ok(());
+        }
+
+        if matches!(conclusion, github::Conclusion::Success) {
+            let suites = self
+                .github
+                .checks()
+                .list_check_suites_for_ref(
+                    self.config.owner(),
+                    self.config.name(),
+                    &suite.head_sha.to_string(),
+                )
+                .await?
+                .into_inner()
+                .check_suites;
+            let mut suites = suites
+                .iter()
+                .filter(|other| other.app.slug == suite.app.slug);
+
+            if suites
+                .clone()
+                .any(|other| !matches!(other.status, github::CheckStatus::Completed))
+            {
+                info!(
+                    "waiting on the rest of the '{}' check suites of {}",
+                    suite.app.slug, suite.head_sha
+                );
+                return Ok(());
+            }
+            if let Some(failure) = suites.find_map(|other| {
+                other
+                    .conclusion
+                    .filter(|conclusion| !matches!(conclusion, github::Conclusion::Success))
+            }) {
+                conclusion = failure;
+            }
+        }
+
+        let known_issue = self.known_issue(&suite.app.slug, conclusion, iter::empty());
+        let spurious = is_spurious_failure(&self.config, conclusion, iter::empty())
+            || known_issue.as_ref().map_or(false, |issue| issue.retry);
+        let details_url = format!(
+            "https://github.com/{}/{}/commit/{}/checks?check_suite_id={}",
+            self.config.owner(),
+            self.config.name(),
+            suite.head_sha,
+            suite.id
+        );
+
+        let duration = self.pull_from_merge_oid(&suite.head_sha).and_then(|pr| {
+            pr.add_build_result(
+                &suite.app.slug,
+                &details_url,
+                None,
+                conclusion,
+                spurious,
+                known_issue.map(|issue| issue.issue_url),
+            )
+        });
+        self.record_check_duration(&suite.app.slug, duration);
+
+        Ok(())
+    }
+
+    /// Commit statuses are reported by CI systems which don't use check runs, e.g. Jenkins,
+    /// Buildkite or Travis, and are recorded alongside check runs under their context, which the
+    /// repo's `status` configs refer to
+    fn handle_status_event(&mut self, event: &github::StatusEvent) {
+        info!("Handling StatusEvent");
+
+        let conclusion = match status_conclusion(&event.state) {
+            Some(conclusion) => conclusion,
+            // A status which goes back to pending after reporting a result is being rerun, e.g. a
+            // rebuilt Jenkins job, so its new result is waited on instead
+            None => {
+                if let Some(pr) = self.pull_from_merge_oid(&event.sha) {
+                    pr.remove_build_result(&event.context);
+                }
+                return;
+            }
+        };
+
+        let known_issue = self.known_issue(
+            &event.context,
+            conclusion,
+            event.description.as_deref().into_iter(),
+        );
+        let spurious = is_spurious_failure(
+            &self.config,
+            conclusion,
+            event.description.as_deref().into_iter(),
+        ) || known_issue.as_ref().map_or(false, |issue| issue.retry);
 
-        Ok(())
+        let duration = self.pull_from_merge_oid(&event.sha).and_then(|pr| {
+            pr.add_build_result(
+                &event.context,
+                &event.target_url.as_deref().unwrap_or(""),
+                event.description.as_deref(),
+                conclusion,
+                spurious,
+                known_issue.map(|issue| issue.issue_url),
+            )
+        });
+        self.record_check_duration(&event.context, duration);
     }
 
-    fn pull_from_merge_oid(&mut self, oid: &github::Oid) -> Option<&mut PullRequestState> {
-        self.pulls
-            .iter_mut()
-            .find(|(_n, pr)| match &pr.status {
-                Status::Testing { merge_oid, .. } => merge_oid == oid,
-                Status::InReview | Status::Queued => false,
-            })
-            .map(|(_n, pr)| pr)
+    fn handle_workflow_run_event(&mut self, event: &github::WorkflowRunEvent) {
+        info!("Handling WorkflowRunEvent");
+
+        let run = &event.workflow_run;
+        let name = match (event.action, &run.name) {
+            (github::WorkflowRunEventAction::Completed, Some(name)) => name,
+            _ => return,
+        };
+        self.add_workflow_result(
+            name,
+            &run.html_url,
+            &run.head_sha,
+            run.conclusion.as_deref(),
+        );
     }
 
-    fn handle_check_run_event(&mut self, event: &github::CheckRunEvent) {
-        info!("Handling CheckRunEvent");
+    fn handle_workflow_job_event(&mut self, event: &github::WorkflowJobEvent) {
+        info!("Handling WorkflowJobEvent");
+
+        let job = &event.workflow_job;
+        if !matches!(event.action, github::WorkflowJobEventAction::Completed) {
+            return;
+        }
+        self.add_workflow_result(
+            &job.name,
+            job.html_url.as_deref().unwrap_or(""),
+            &job.head_sha,
+            job.conclusion.as_deref(),
+        );
+    }
 
+    /// Record the result of a completed GitHub Actions workflow run or job as a test result, named
+    /// after the workflow or job so that it can be listed in the repo's checks
+    fn add_workflow_result(
+        &mut self,
+        name: &str,
+        url: &str,
+        head_sha: &github::Oid,
+        conclusion: Option<&str>,
+    ) {
         // Skip the event if it hasn't completed
-        let conclusion = match (
-            event.action,
-            event.check_run.status,
-            event.check_run.conclusion,
-        ) {
-            (
-                github::CheckRunEventAction::Completed,
-                github::CheckStatus::Completed,
-                Some(conclusion),
-            ) => conclusion,
-            _ => return,
+        let conclusion = match conclusion {
+            Some(conclusion) => workflow_conclusion(conclusion),
+            None => return,
         };
 
-        if let Some(pr) = self.pull_from_merge_oid(&event.check_run.head_sha) {
+        let known_issue = self.known_issue(name, conclusion, iter::empty());
+        let spurious = is_spurious_failure(&self.config, conclusion, iter::empty())
+            || known_issue.as_ref().map_or(false, |issue| issue.retry);
+
+        let duration = self.pull_from_merge_oid(head_sha).and_then(|pr| {
             pr.add_build_result(
-                &event.check_run.name,
-                &event.check_run.details_url,
+                name,
+                url,
+                None,
                 conclusion,
-            );
+                spurious,
+                known_issue.map(|issue| issue.issue_url),
+            )
+        });
+        self.record_check_duration(name, duration);
+    }
+
+    /// Record how long a check took to pass, for estimating how long queued PRs will wait
+    fn record_check_duration(&mut self, name: &str, duration: Option<std::time::Duration>) {
+        if let Some(duration) = duration {
+            if let Err(e) = self.check_durations.record(name, duration) {
+                warn!("unable to record the duration of {}: {:?}", name, e);
+            }
         }
     }
 
-    // XXX This currently shoehorns github's statuses to fit into the new checks api. We should
-    // probably introduce a few types to distinguish between the two
-    fn handle_status_event(&mut self, event: &github::StatusEvent) {
-        // Skip the event if it hasn't completed
-        let conclusion = match event.state {
-            github::StatusEventState::Pending => return,
-            github::StatusEventState::Success => github::Conclusion::Success,
-            github::StatusEventState::Failure => github::Conclusion::Failure,
-            github::StatusEventState::Error => github::Conclusion::Failure,
+    /// Find the known issue, if any, tracking a failed check by fingerprinting its output
+    fn known_issue<'a>(
+        &self,
+        name: &str,
+        conclusion: github::Conclusion,
+        output: impl Iterator<Item = &'a str>,
+    ) -> Option<KnownIssue> {
+        if matches!(conclusion, github::Conclusion::Success) {
+            return None;
+        }
+
+        let known_issues = match KnownIssues::load(&self.git_repository.state_dir()) {
+            Ok(known_issues) => known_issues,
+            Err(e) => {
+                warn!("unable to load known issues: {:?}", e);
+                return None;
+            }
         };
+        let fingerprint = fingerprint(name, output);
+        let known_issue = known_issues.find(&fingerprint).cloned();
+        info!(
+            "{} failed with fingerprint '{}', known issue: {:?}",
+            name,
+            fingerprint,
+            known_issue.as_ref().map(|issue| &issue.issue_url)
+        );
 
-        if let Some(pr) = self.pull_from_merge_oid(&event.sha) {
-            pr.add_build_result(
-                &event.context,
-                &event.target_url.as_deref().unwrap_or(""),
-                conclusion,
-            );
+        known_issue
+    }
+
+    /// Look up whether the dependencies of queued PRs which aren't open were merged, since they
+    /// may have been closed while bors wasn't running or before they were depended on
+    async fn resolve_dependencies(&mut self) -> Result<()> {
+        let unresolved: BTreeSet<u64> = self
+            .pulls
+            .values()
+            .filter(|pull| pull.status.is_queued())
+            .flat_map(PullRequestState::unlanded_dependencies)
+            .filter(|number| !self.pulls.contains_key(number) && !self.unmerged.contains(number))
+            .collect();
+
+        for number in unresolved {
+            let pull = self
+                .github
+                .pulls()
+                .get(self.config.owner(), self.config.name(), number)
+                .await?
+                .into_inner();
+
+            if pull.merged == Some(true) {
+                mark_landed(&mut self.pulls, number);
+            } else if pull.closed_at.is_some() {
+                self.unmerged.insert(number);
+            }
         }
+
+        Ok(())
     }
 
     async fn process_merge_queue(&mut self) -> Result<()> {
+        if self.paused {
+            return Ok(());
+        }
+
+        self.resolve_protected_checks().await;
+        if let Err(e) = self.resolve_dependencies().await {
+            warn!(
+                "{}/{} - Unable to check whether dependencies landed: {:?}",
+                self.config.owner(),
+                self.config.name(),
+                e
+            );
+        }
+
         self.merge_queue
             .process_queue(
                 &self.config,
@@ -364,8 +2373,49 @@ impl EventProcessor {
                 &mut self.git_repository,
                 self.project_board.as_ref(),
                 &mut self.pulls,
+                self.draining,
             )
-            .await
+            .await?;
+
+        if let Some(wait) = self.merge_queue.land_window_wait(&self.config) {
+            self.schedule_queue_wakeup(wait);
+        }
+        if let Some(wait) = grace_period_wait(&self.config, &self.pulls) {
+            self.schedule_queue_wakeup(wait);
+        }
+
+        self.merge_queue
+            .update_position_comments(
+                &self.config,
+                &self.github,
+                &self.pulls,
+                &self.check_durations,
+            )
+            .await;
+
+        if self.draining && self.merge_queue.is_idle() && !self.drain_waiters.is_empty() {
+            info!(
+                "{}/{} - Merge queue drained",
+                self.config.owner(),
+                self.config.name()
+            );
+
+            for waiter in self.drain_waiters.drain(..) {
+                let _ = waiter.send(Ok(()));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn process_try_builds(&mut self) -> Result<()> {
+        crate::try_build::process_try_builds(
+            &self.config,
+            &self.github,
+            self.project_board.as_ref(),
+            &mut self.pulls,
+        )
+        .await
     }
 
     fn command_context<'a>(
@@ -373,13 +2423,18 @@ impl EventProcessor {
         sender: &'a str,
         pr_number: u64,
     ) -> Option<CommandContext<'a>> {
-        if let Some(pr) = self.pulls.get_mut(&pr_number) {
+        if self.pulls.contains_key(&pr_number) {
             Some(CommandContext {
-                pull_request: pr,
+                pulls: &mut self.pulls,
+                number: pr_number,
+                git_repository: &mut self.git_repository,
                 github: &self.github,
                 config: &self.config,
                 project_board: self.project_board.as_ref(),
+                tree_closed: self.merge_queue.tree_closed_mut(),
+                check_durations: &self.check_durations,
                 sender,
+                admin_request: None,
             })
         } else {
             None
@@ -389,38 +2444,135 @@ impl EventProcessor {
     async fn process_comment(
         &mut self,
         user: &str,
+        author_association: Option<&str>,
         pr_number: u64,
         comment: Option<&str>,
         node_id: &NodeId,
     ) -> Result<()> {
         info!("comment: {:#?}", comment);
 
+        if self.config.ignores_user(user) {
+            info!("ignoring comment from ignored user {}", user);
+            self.audit(
+                pr_number,
+                AuditRecord::CommentIgnored {
+                    user: user.to_owned(),
+                    reason: "the user is ignored".to_owned(),
+                },
+            );
+            return Ok(());
+        }
+
+        // Ignore comments from users who aren't sufficiently associated with the repo, without
+        // reacting or replying, so that drive-by accounts can't use bors to post comments
+        if let Some(min) = self.config.min_author_association() {
+            let association = author_association.and_then(|a| a.parse::<AuthorAssociation>().ok());
+            if association.map(|a| a < min).unwrap_or(true) {
+                info!(
+                    "ignoring comment from {} with author association {:?}",
+                    user, author_association
+                );
+                self.audit(
+                    pr_number,
+                    AuditRecord::CommentIgnored {
+                        user: user.to_owned(),
+                        reason: format!(
+                            "author association {:?} is below the minimum",
+                            author_association
+                        ),
+                    },
+                );
+                return Ok(());
+            }
+        }
+
         match comment.and_then(Command::from_comment) {
             Some(Ok(command)) => {
                 info!("Valid Command");
 
+                // Closed PRs, including landed ones, are no longer tracked. The commands which act
+                // on PRs once they've closed are run against a snapshot fetched from Github, which
+                // is only tracked while the command runs.
+                let snapshot = !self.pulls.contains_key(&pr_number);
+                if snapshot && !command.applies_to_closed() {
+                    info!("ignoring command on untracked pr #{}", pr_number);
+                    return Ok(());
+                }
+
                 self.github
                     .add_reaction(node_id, github::ReactionType::Rocket)
                     .await?;
 
+                if snapshot {
+                    let pull = self
+                        .github
+                        .pulls()
+                        .get(self.config.owner(), self.config.name(), pr_number)
+                        .await?
+                        .into_inner();
+                    self.pulls
+                        .insert(pr_number, PullRequestState::from_pull_request(&pull));
+                }
+
                 let mut ctx = self.command_context(user, pr_number).unwrap();
                 // Check if the user is authorized before executing the command
-                if command.is_authorized(&ctx).await? {
-                    command.execute(&mut ctx).await?;
+                let result = match command.is_authorized(&ctx).await {
+                    Ok(true) => command.execute(&mut ctx).await.map(|()| true),
+                    other => other,
+                };
+                let admin_request = ctx.admin_request.take();
+                if snapshot {
+                    self.pulls.remove(&pr_number);
+                }
+                let authorized = result?;
+
+                if let Some(admin_command) = admin_request {
+                    if let Err(e) = self.handle_admin_command(admin_command).await {
+                        warn!(
+                            "{}/{} - admin command from #{} failed: {:?}",
+                            self.config.owner(),
+                            self.config.name(),
+                            pr_number,
+                            e
+                        );
+                        self.github
+                            .issues()
+                            .create_comment(
+                                self.config.owner(),
+                                self.config.name(),
+                                pr_number,
+                                &format!(":exclamation: @{} {}", user, e),
+                            )
+                            .await?;
+                    }
                 }
+                self.audit(
+                    pr_number,
+                    AuditRecord::Command {
+                        user: user.to_owned(),
+                        command: command.cmd().to_owned(),
+                        authorized,
+                    },
+                );
             }
             Some(Err(_)) => {
                 info!("Invalid Command");
+                let version = self
+                    .pulls
+                    .get(&pr_number)
+                    .map(|pr| pr.state_version)
+                    .unwrap_or(0);
+                let marker = CommentMarker::new(CommentEvent::InvalidCommand, pr_number, version);
                 self.github
                     .issues()
                     .create_comment(
                         self.config.repo().owner(),
                         self.config.repo().name(),
                         pr_number,
-                        &format!(
+                        &marker.sign(&format!(
                             ":exclamation: Invalid command\n\n{}",
                             Command::help(&self.config, self.project_board.as_ref())
-                        ),
+                        )),
                     )
                     .await?;
             }
@@ -434,7 +2586,25 @@ impl EventProcessor {
 
     async fn handle_pull_request_review_event(&mut self, e: &PullRequestReviewEvent) -> Result<()> {
         let pr_number = e.pull_request.number;
+        if self.config.ignores_user(&e.review.user.login) {
+            info!(
+                "ignoring review on #{} from ignored user {}",
+                pr_number, e.review.user.login
+            );
+            return Ok(());
+        }
+
         if let Some(pr) = self.pulls.get_mut(&pr_number) {
+            match e.review.state {
+                ReviewState::Approved => {
+                    pr.approved_by.insert(e.review.user.login.clone());
+                }
+                ReviewState::ChangesRequested | ReviewState::Dismissed => {
+                    pr.approved_by.remove(&e.review.user.login);
+                }
+                ReviewState::Commented => {}
+            }
+
             pr.approved = self
                 .github
                 .get_review_decision(
@@ -448,6 +2618,7 @@ impl EventProcessor {
         if e.action.is_submitted() {
             self.process_comment(
                 &e.sender.login,
+                Some(&e.review.author_association),
                 e.pull_request.number,
                 e.review.body(),
                 &e.review.node_id,
@@ -467,7 +2638,7 @@ impl EventProcessor {
             .await?;
         info!("{} Open PullRequests", pulls.len());
 
-        // TODO: Scrape the comments/Reviews of each PR to pull out reviewer/approval data
+        // TODO: Scrape the comments of each PR to pull out reviewer/approval data
 
         self.pulls.clear();
         self.pulls
@@ -501,33 +2672,379 @@ impl EventProcessor {
 
         self.project_board = Some(board);
 
+        self.sync_protected_checks().await;
+
+        let state_dir = self.git_repository.state_dir();
+        if self.config.contributor_stats() && !Contributors::is_recorded(&state_dir) {
+            if let Err(e) =
+                contributors::backfill(&self.github, self.config.repo(), &state_dir).await
+            {
+                warn!(
+                    "{}/{} - Unable to backfill contributor stats: {:?}",
+                    self.config.owner(),
+                    self.config.name(),
+                    e
+                );
+            }
+        }
+
         info!("Done Synchronizing");
         Ok(())
     }
+
+    /// Fetch the status checks required by the protection rules of the configured
+    /// `branch-protection` branch, and of the base refs of the PRs, which are gated on along with
+    /// the configured checks
+    async fn sync_protected_checks(&mut self) {
+        let branch = match self.config.branch_protection() {
+            Some(branch) => branch.to_owned(),
+            None => return,
+        };
+
+        let mut refs: BTreeSet<String> = self
+            .pulls
+            .values()
+            .map(|pull| pull.base_ref_name.clone())
+            .chain(self.config.protected_refs().map(str::to_owned))
+            .collect();
+        refs.insert(branch);
+        for base_ref in refs {
+            self.fetch_protected_checks(&base_ref).await;
+        }
+    }
+
+    /// Fetch the status checks required by the protection rules of the base refs of queued PRs
+    /// which haven't been fetched yet, e.g. of a PR opened against a new release branch
+    async fn resolve_protected_checks(&mut self) {
+        if self.config.branch_protection().is_none() {
+            return;
+        }
+
+        let refs: BTreeSet<String> = self
+            .pulls
+            .values()
+            .filter(|pull| pull.status.is_queued())
+            .map(|pull| pull.base_ref_name.clone())
+            .filter(|base_ref| {
+                self.config
+                    .protected_refs()
+                    .all(|known| known != base_ref.as_str())
+            })
+            .collect();
+        for base_ref in refs {
+            self.fetch_protected_checks(&base_ref).await;
+        }
+    }
+
+    /// Fetch the status checks required by the protection rules of `base_ref`. Rules which can't be
+    /// fetched are logged, keeping the checks fetched before.
+    async fn fetch_protected_checks(&mut self, base_ref: &str) {
+        let contexts = match self
+            .github
+            .repos()
+            .get_required_status_checks(self.config.owner(), self.config.name(), base_ref)
+            .await
+        {
+            Ok(response) => response.into_inner().contexts,
+            // The branch isn't protected, or doesn't require any status checks
+            Err(github::client::Error::GithubClientError(status, _)) if status.as_u16() == 404 => {
+                Vec::new()
+            }
+            Err(e) => {
+                warn!(
+                    "{}/{} - Unable to fetch the branch protection of '{}': {:?}",
+                    self.config.owner(),
+                    self.config.name(),
+                    base_ref,
+                    e
+                );
+                return;
+            }
+        };
+
+        info!(
+            "{}/{} - Branch protection of '{}' requires checks: {:?}",
+            self.config.owner(),
+            self.config.name(),
+            base_ref,
+            contexts
+        );
+        self.config.set_protected_checks(base_ref, contexts);
+    }
+
+    /// Replace the repo's config with one reloaded from the config file, layering the repo's
+    /// `bors.toml` over it again and refetching the checks required by branch protection. The
+    /// queue and PRs are left as they are, though the merge queue is processed again in case,
+    /// e.g., a timeout was shortened. Settings only read on startup, e.g. `audit-log` or
+    /// `webhook-id`, only take effect after a restart.
+    async fn reconfigure(&mut self, config: RepoConfig) -> Result<()> {
+        if config.repo() != self.config.repo() {
+            return Err(anyhow!(
+                "config for {}/{} can't replace that of {}/{}",
+                config.owner(),
+                config.name(),
+                self.config.owner(),
+                self.config.name()
+            ));
+        }
+
+        info!(
+            "{}/{} - Reconfiguring",
+            self.config.owner(),
+            self.config.name()
+        );
+
+        // The new config only replaces the current one once it's been layered with the repo's
+        // `bors.toml`, so that a failure to fetch it leaves the repo configured as it was
+        let mut config = config;
+        config.keep_protected_checks(&self.config);
+        if let Some(layered) = self.with_in_repo_config(&config).await? {
+            config = layered;
+        }
+        self.config = config;
+        self.sync_protected_checks().await;
+        self.process_merge_queue().await
+    }
+
+    /// Layer the settings of the `bors.toml` on the repo's default branch over its server-side
+    /// config, if it's configured to read them. A `bors.toml` which can't be parsed is ignored,
+    /// leaving the config as it was.
+    async fn load_in_repo_config(&mut self) -> Result<()> {
+        if let Some(layered) = self.with_in_repo_config(&self.config).await? {
+            self.config = layered;
+        }
+        Ok(())
+    }
+
+    /// `config` with the settings of the `bors.toml` on the repo's default branch layered over it,
+    /// if it's configured to read them. `None` is returned if `config` is to be used as it is, e.g.
+    /// if the `bors.toml` can't be parsed.
+    async fn with_in_repo_config(&self, config: &RepoConfig) -> Result<Option<RepoConfig>> {
+        if !config.in_repo_config() {
+            return Ok(None);
+        }
+
+        let contents = match self
+            .github
+            .repos()
+            .get_raw_contents(config.owner(), config.name(), IN_REPO_CONFIG_PATH, None)
+            .await
+        {
+            Ok(response) => Some(response.into_inner()),
+            Err(github::client::Error::GithubClientError(status, _)) if status.as_u16() == 404 => {
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let layered = match &contents {
+            Some(contents) => config.with_in_repo_config(contents),
+            None => config.without_in_repo_config(),
+        };
+        match layered {
+            Ok(layered) => {
+                if contents.is_some() {
+                    info!(
+                        "{}/{} - Loaded settings from {}",
+                        config.owner(),
+                        config.name(),
+                        IN_REPO_CONFIG_PATH
+                    );
+                }
+                Ok(Some(layered))
+            }
+            Err(e) => {
+                warn!(
+                    "{}/{} - Ignoring invalid {}: {:?}",
+                    config.owner(),
+                    config.name(),
+                    IN_REPO_CONFIG_PATH,
+                    e
+                );
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Check if a push to the repo's default branch might have changed its `bors.toml`. A forced push
+/// doesn't list every commit it replaced, so it's assumed to have.
+/// Where the threshold set by `/treeclosed` is kept, so that a closed tree stays closed across
+/// restarts and not only across lease handoffs
+fn tree_closed_store(state_dir: &Path) -> Store {
+    Store::new(state_dir, "tree-closed.json")
+}
+
+fn touches_in_repo_config(event: &github::PushEvent) -> bool {
+    let default_branch = format!("refs/heads/{}", event.repository.default_branch);
+    event.git_ref == default_branch
+        && (event.forced
+            || event.commits.iter().any(|commit| {
+                commit
+                    .added
+                    .iter()
+                    .chain(&commit.modified)
+                    .chain(&commit.removed)
+                    .any(|path| path == IN_REPO_CONFIG_PATH)
+            }))
+}
+
+/// Check if a failed test's output matches one of the repo's configured spurious failure patterns
+fn is_spurious_failure<'a>(
+    config: &RepoConfig,
+    conclusion: github::Conclusion,
+    output: impl Iterator<Item = &'a str>,
+) -> bool {
+    match (conclusion, config.spurious_failures()) {
+        (github::Conclusion::Success, _) | (_, None) => false,
+        (_, Some(spurious_failures)) => spurious_failures.matches(output),
+    }
+}
+
+/// Map the state of a commit status onto a check run's conclusion, or `None` if it's still pending
+fn status_conclusion(state: &github::StatusEventState) -> Option<github::Conclusion> {
+    match state {
+        github::StatusEventState::Pending => None,
+        github::StatusEventState::Success => Some(github::Conclusion::Success),
+        github::StatusEventState::Failure | github::StatusEventState::Error => {
+            Some(github::Conclusion::Failure)
+        }
+    }
+}
+
+/// Map the conclusion of a GitHub Actions workflow run or job onto a check run's conclusion.
+/// Conclusions which don't have an equivalent, e.g. `stale` or `startup_failure`, are failures.
+fn workflow_conclusion(conclusion: &str) -> github::Conclusion {
+    match conclusion {
+        "success" => github::Conclusion::Success,
+        "neutral" | "skipped" => github::Conclusion::Neutral,
+        "cancelled" => github::Conclusion::Cancelled,
+        "timed_out" => github::Conclusion::TimedOut,
+        "action_required" => github::Conclusion::ActionRequired,
+        _ => github::Conclusion::Failure,
+    }
 }
 
 pub struct CommandContext<'a> {
-    pull_request: &'a mut PullRequestState,
+    /// All of the open PRs, including the one the command was issued on
+    pulls: &'a mut HashMap<u64, PullRequestState>,
+    /// The PR the command was issued on
+    number: u64,
+    git_repository: &'a mut GitRepository,
     github: &'a GithubClient,
     config: &'a RepoConfig,
     project_board: Option<&'a ProjectBoard>,
+    tree_closed: &'a mut Option<u32>,
+    check_durations: &'a CheckDurations,
     sender: &'a str,
+    /// An admin command the command asked to be run once it's done, against the whole repo
+    admin_request: Option<AdminCommand>,
 }
 
 impl<'a> CommandContext<'a> {
     pub fn pr(&self) -> &PullRequestState {
-        &self.pull_request
+        &self.pulls[&self.number]
     }
 
-    #[allow(unused)]
     pub fn pr_mut(&mut self) -> &mut PullRequestState {
-        &mut self.pull_request
+        self.pulls.get_mut(&self.number).expect("PR should exist")
+    }
+
+    /// Any open PR in the repo
+    pub fn pull(&self, number: u64) -> Option<&PullRequestState> {
+        self.pulls.get(&number)
+    }
+
+    pub fn pull_mut(&mut self, number: u64) -> Option<&mut PullRequestState> {
+        self.pulls.get_mut(&number)
+    }
+
+    /// Start tracking a PR bors just opened, ahead of its Opened event
+    pub fn track_pull(&mut self, pull: PullRequestState) {
+        self.pulls.insert(pull.number, pull);
+    }
+
+    /// The PR's 1-based position in the queue, if it's queued
+    pub fn queue_position(&self) -> Option<usize> {
+        queue_order(self.config, self.pulls)
+            .iter()
+            .position(|number| *number == self.number)
+            .map(|position| position + 1)
+    }
+
+    /// Estimate of how long until the PR starts being tested, if it's queued
+    pub fn estimated_wait(&self) -> Option<std::time::Duration> {
+        estimated_waits(self.config, self.pulls, self.check_durations).remove(&self.number)
+    }
+
+    /// The PR's dependencies which are still open and so haven't landed yet
+    pub fn unlanded_dependencies(&self) -> Vec<u64> {
+        self.pr().unlanded_dependencies()
+    }
+
+    /// The PRs which the PR is stacked on top of, ordered from the bottom of the stack up. A PR
+    /// is stacked on another when its base branch is the other PR's head branch.
+    pub fn stack(&self) -> Vec<u64> {
+        let mut stack = Vec::new();
+        let mut top = self.pr();
+
+        while let Some(below) = self.pulls.values().find(|pull| {
+            pull.head_repo.as_ref() == Some(self.config.repo())
+                && pull.head_ref_name == top.base_ref_name
+                && pull.number != self.number
+                && !stack.contains(&pull.number)
+        }) {
+            stack.push(below.number);
+            top = below;
+        }
+
+        stack.reverse();
+        stack
+    }
+
+    /// Push `new_head` to the PR's branch in `head_repo`, retrying failures which may be transient.
+    /// Returns why the update failed, if it did.
+    pub async fn update_pr_in_place(
+        &mut self,
+        head_repo: &Repo,
+        new_head: &Oid,
+    ) -> Option<PushFailure> {
+        update_in_place(
+            self.config,
+            self.git_repository,
+            head_repo,
+            &self.pulls[&self.number],
+            new_head,
+        )
+        .await
+    }
+
+    /// Act on another open PR, e.g. one lower in a stack, in place of the PR commented on, until
+    /// focusing back on it
+    pub fn focus(&mut self, number: u64) {
+        self.number = number;
+    }
+
+    pub fn git_repository(&mut self) -> &mut GitRepository {
+        &mut self.git_repository
     }
 
     pub fn github(&self) -> &GithubClient {
         &self.github
     }
 
+    /// The priority threshold below which PRs aren't tested, if the tree is closed
+    pub fn tree_closed(&self) -> Option<u32> {
+        *self.tree_closed
+    }
+
+    /// Close or reopen the tree, recording it so that it's still closed after a restart
+    pub fn set_tree_closed(&mut self, tree_closed: Option<u32>) -> Result<()> {
+        tree_closed_store(&self.git_repository.state_dir()).save(&tree_closed)?;
+        *self.tree_closed = tree_closed;
+        Ok(())
+    }
+
     pub fn config(&self) -> &RepoConfig {
         &self.config
     }
@@ -540,9 +3057,15 @@ impl<'a> CommandContext<'a> {
         &self.sender
     }
 
+    /// Run `command` against the repo once the comment's command has finished, e.g. to act on the
+    /// merge queue which isn't reachable from a PR's context
+    pub fn request_admin(&mut self, command: AdminCommand) {
+        self.admin_request = Some(command);
+    }
+
     #[allow(unused)]
     pub fn sender_is_author(&self) -> bool {
-        if let Some(author) = &self.pull_request.author {
+        if let Some(author) = &self.pr().author {
             author == self.sender
         } else {
             false
@@ -550,35 +3073,86 @@ impl<'a> CommandContext<'a> {
     }
 
     pub async fn create_pr_comment(&self, body: &str) -> Result<()> {
-        self.github()
-            .issues()
-            .create_comment(
-                self.config().owner(),
-                self.config().name(),
-                self.pr().number,
-                body,
-            )
-            .await?;
+        self.create_comment_on(self.number, body).await
+    }
+
+    /// Comment on any open PR in the repo
+    pub async fn create_comment_on(&self, number: u64, body: &str) -> Result<()> {
+        if let Some(pull) = self.pulls.get(&number) {
+            pull.create_comment(self.config, self.github, CommentEvent::Command, body)
+                .await?;
+        }
+
         Ok(())
     }
 
     pub async fn update_pr_status(&mut self, status: Status) -> Result<()> {
-        self.pull_request
-            .update_status(status, self.config, self.github, self.project_board)
-            .await?;
+        self.update_status_of(self.number, status).await
+    }
+
+    /// Update the status of any open PR in the repo
+    pub async fn update_status_of(&mut self, number: u64, status: Status) -> Result<()> {
+        if let Some(pull) = self.pulls.get_mut(&number) {
+            pull.update_status(status, self.config, self.github, self.project_board)
+                .await?;
+        }
 
         Ok(())
     }
 
     pub async fn set_label(&mut self, label: &str) -> Result<()> {
-        self.pull_request
+        self.pulls
+            .get_mut(&self.number)
+            .expect("PR should exist")
             .add_label(self.config, self.github, label)
             .await
     }
 
     pub async fn remove_label(&mut self, label: &str) -> Result<()> {
-        self.pull_request
+        self.pulls
+            .get_mut(&self.number)
+            .expect("PR should exist")
             .remove_label(self.config, self.github, label)
             .await
     }
 }
+
+#[cfg(test)]
+mod scenario;
+
+#[cfg(test)]
+mod test {
+    use super::AdminCommand;
+
+    fn admin_command(body: &str) -> AdminCommand {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn drain() {
+        assert!(matches!(
+            admin_command(r#"{"command": "drain"}"#),
+            AdminCommand::Drain
+        ));
+    }
+
+    #[test]
+    fn skip_soak() {
+        assert!(matches!(
+            admin_command(r#"{"command": "skip-soak"}"#),
+            AdminCommand::SkipSoak
+        ));
+    }
+
+    #[test]
+    fn prioritize_label() {
+        match admin_command(r#"{"command": "prioritize-label", "label": "release", "priority": 5}"#)
+        {
+            AdminCommand::PrioritizeLabel { label, priority } => {
+                assert_eq!(label, "release");
+                assert_eq!(priority, 5);
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+    }
+}
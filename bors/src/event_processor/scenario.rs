@@ -0,0 +1,526 @@
+//! Scenarios driving the event processor end to end, e.g. "PR opened → /land → CI passes →
+//! landed", with webhooks as Github would deliver them. Calls to Github's API are served by a mock
+//! which records them, and pushes go to a local bare repo standing in for the repo on Github.
+//!
+//! A scenario is a script of steps, one of:
+//! `open <N> [by <user>] [on <M>]`, which pushes a commit to branch `pr-N` and opens a PR of it
+//! into `master`, or into PR M's branch; `comment <N> <user> <body>`; and `pass <context>` or
+//! `fail <context>`, which report a commit status on the merge commit being tested. A scenario can
+//! check its progress with `expect <N> <in-review|queued|testing|failed|landed|closed>`,
+//! `expect-call <METHOD> <path>` and `expect-comment <N> <text>`, each of which stops the
+//! scenario if it doesn't hold.
+
+use super::{EventProcessor, Request};
+use crate::{
+    config::{GitConfig, RepoConfig},
+    git::GitRepository,
+    graphql::GithubClient,
+    state::Status,
+    Result,
+};
+use anyhow::{anyhow, Context};
+use github::{Event, EventType};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+const PR_JSON: &str = include_str!("../../../github/test-input/pr.json");
+const ISSUE_COMMENT_EVENT_JSON: &str =
+    include_str!("../../../github/test-input/issue-comment-event.json");
+const STATUS_EVENT_JSON: &str = include_str!("../../../github/test-input/status-event.json");
+
+/// A call made to the mock Github
+#[derive(Debug)]
+struct ApiCall {
+    method: Method,
+    /// The path called, relative to the API's root and without any query
+    path: String,
+    body: Value,
+}
+
+type ApiCalls = Arc<Mutex<Vec<ApiCall>>>;
+
+struct Scenario {
+    /// Directory holding the remote and every clone of it, removed once the scenario is over
+    root: PathBuf,
+    /// Bare repo standing in for the repo on Github
+    remote: PathBuf,
+    /// Clone from which PRs' authors push their branches
+    author: PathBuf,
+    processor: EventProcessor,
+    calls: ApiCalls,
+    deliveries: u64,
+}
+
+impl Scenario {
+    /// Serve a repo configured by `config` against a remote whose `master` holds a single commit.
+    /// `name` distinguishes the scenario's directory from those of scenarios run alongside it.
+    async fn new(name: &str, config: &str) -> Result<Self> {
+        let config: RepoConfig = toml::from_str(config)?;
+
+        let root =
+            std::env::temp_dir().join(format!("bors-scenario-{}-{}", std::process::id(), name));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&root)?;
+
+        let remote = root.join("remote.git");
+        git(&root, &["init", "--bare", "-b", "master", "remote.git"])?;
+        git(
+            &remote,
+            &["config", "uploadpack.allowAnySHA1InWant", "true"],
+        )?;
+
+        let author = root.join("author");
+        git(&root, &["init", "-b", "master", "author"])?;
+        fs::write(author.join("README"), "Hello World\n")?;
+        git(&author, &["add", "README"])?;
+        git(&author, &["commit", "-m", "Initial commit"])?;
+        git(&author, &["remote", "add", "origin", path_str(&remote)?])?;
+        git(&author, &["push", "origin", "master"])?;
+
+        let clone = root.join("repos").join(config.owner()).join(config.name());
+        git(&root, &["clone", path_str(&remote)?, path_str(&clone)?])?;
+        let git_config = GitConfig {
+            ssh_key_file: root.join("id_rsa"),
+            user: "bors".to_owned(),
+            email: "bors@example.com".to_owned(),
+        };
+        let git_repository = GitRepository::at(clone, &git_config, config.repo());
+
+        let calls = ApiCalls::default();
+        let base_url = serve_mock_github(calls.clone(), remote.clone());
+        let github = Arc::new(GithubClient::with_base_url(&base_url));
+        let (_sender, processor) =
+            EventProcessor::with_git_repository(config, github, git_repository, None)?;
+
+        Ok(Self {
+            root,
+            remote,
+            author,
+            processor,
+            calls,
+            deliveries: 0,
+        })
+    }
+
+    async fn run(&mut self, steps: &[&str]) -> Result<()> {
+        for step in steps {
+            self.step(step)
+                .await
+                .with_context(|| format!("'{}' failed, calls made:\n{}", step, self.calls()))?;
+        }
+
+        Ok(())
+    }
+
+    async fn step(&mut self, step: &str) -> Result<()> {
+        let words: Vec<&str> = step.split_whitespace().collect();
+        let number = |i: usize| -> Result<u64> {
+            words
+                .get(i)
+                .ok_or_else(|| anyhow!("missing PR number"))?
+                .parse()
+                .context("parsing PR number")
+        };
+
+        match words.as_slice() {
+            ["open", _, rest @ ..] => {
+                let mut author = "Codertocat";
+                let mut base = "master".to_owned();
+                for option in rest.chunks(2) {
+                    match option {
+                        ["by", user] => author = user,
+                        ["on", pr] => base = format!("pr-{}", pr),
+                        _ => return Err(anyhow!("unknown option {:?}", option)),
+                    }
+                }
+                self.open(number(1)?, author, &base).await
+            }
+            ["comment", _, user, ..] => {
+                let body = words[3..].join(" ");
+                self.comment(number(1)?, user, &body).await
+            }
+            ["pass", context] => self.report_status(context, "success").await,
+            ["fail", context] => self.report_status(context, "failure").await,
+            ["expect", _, state] => {
+                let number = number(1)?;
+                let actual = match self.processor.pulls.get(&number) {
+                    Some(pull) => pull.status.as_str(),
+                    None if self.has_landed(number) => "landed",
+                    None => "closed",
+                };
+                if actual != *state {
+                    return Err(anyhow!(
+                        "expected #{} to be {}, but it is {}",
+                        number,
+                        state,
+                        actual
+                    ));
+                }
+                Ok(())
+            }
+            ["expect-call", method, path] => {
+                let calls = self.calls.lock().unwrap();
+                if !calls
+                    .iter()
+                    .any(|call| call.method.as_str() == *method && call.path == *path)
+                {
+                    return Err(anyhow!("expected a call to {} {}", method, path));
+                }
+                Ok(())
+            }
+            ["expect-comment", _, ..] => {
+                let number = number(1)?;
+                let text = words[2..].join(" ");
+                let path = format!(
+                    "repos/{}/{}/issues/{}/comments",
+                    self.processor.config.owner(),
+                    self.processor.config.name(),
+                    number
+                );
+                let calls = self.calls.lock().unwrap();
+                let commented = calls.iter().any(|call| {
+                    call.method == Method::POST
+                        && call.path == path
+                        && call.body["body"]
+                            .as_str()
+                            .map_or(false, |body| body.contains(&text))
+                });
+                if !commented {
+                    return Err(anyhow!("expected a comment on #{} with '{}'", number, text));
+                }
+                Ok(())
+            }
+            _ => Err(anyhow!("unknown step")),
+        }
+    }
+
+    /// Push a commit adding `pr-N.txt` to branch `pr-N`, on top of `base`, and open a PR of it
+    async fn open(&mut self, number: u64, author: &str, base: &str) -> Result<()> {
+        let branch = format!("pr-{}", number);
+        let file = format!("{}.txt", branch);
+        git(&self.author, &["fetch", "origin"])?;
+        git(
+            &self.author,
+            &["checkout", "-B", &branch, &format!("origin/{}", base)],
+        )?;
+        fs::write(self.author.join(&file), format!("Change {}\n", number))?;
+        git(&self.author, &["add", &file])?;
+        git(
+            &self.author,
+            &["commit", "-m", &format!("Change {}", number)],
+        )?;
+        git(&self.author, &["push", "origin", &branch])?;
+
+        let mut pull: Value = serde_json::from_str(PR_JSON)?;
+        pull["number"] = json!(number);
+        pull["id"] = json!(number);
+        pull["title"] = json!(format!("Change {}", number));
+        pull["body"] = json!("");
+        pull["draft"] = json!(false);
+        pull["user"]["login"] = json!(author);
+        pull["head"]["ref"] = json!(branch);
+        pull["head"]["label"] = json!(format!("{}:{}", self.processor.config.owner(), branch));
+        pull["head"]["sha"] = json!(git(&self.author, &["rev-parse", "HEAD"])?);
+        pull["base"]["ref"] = json!(base);
+        pull["base"]["sha"] = json!(git(&self.author, &["rev-parse", "HEAD^"])?);
+
+        let event = json!({
+            "action": "opened",
+            "number": number,
+            "repository": pull["base"]["repo"],
+            "sender": pull["user"],
+            "pull_request": pull,
+        });
+        self.deliver(EventType::PullRequest, event).await
+    }
+
+    async fn comment(&mut self, number: u64, user: &str, body: &str) -> Result<()> {
+        let events: Vec<Value> = serde_json::from_str(ISSUE_COMMENT_EVENT_JSON)?;
+        let mut event = events[0].clone();
+        event["action"] = json!("created");
+        event["issue"]["number"] = json!(number);
+        event["issue"]["pull_request"] = json!({
+            "url": "",
+            "html_url": "",
+            "diff_url": "",
+            "patch_url": "",
+        });
+        event["comment"]["body"] = json!(body);
+        event["comment"]["user"]["login"] = json!(user);
+        event["comment"]["author_association"] = json!("MEMBER");
+        event["sender"]["login"] = json!(user);
+        self.deliver(EventType::IssueComment, event).await
+    }
+
+    /// Report `context`'s status on the merge commit of the tip of the batch being tested
+    async fn report_status(&mut self, context: &str, state: &str) -> Result<()> {
+        let tip = self
+            .processor
+            .merge_queue
+            .head()
+            .last()
+            .ok_or_else(|| anyhow!("nothing is being tested"))?;
+        let merge_oid = match &self.processor.pulls[tip].status {
+            Status::Testing { merge_oid, .. } => merge_oid.to_string(),
+            status => return Err(anyhow!("#{} is {}, not testing", tip, status.as_str())),
+        };
+
+        let mut event: Value = serde_json::from_str(STATUS_EVENT_JSON)?;
+        event["sha"] = json!(merge_oid);
+        event["context"] = json!(context);
+        event["state"] = json!(state);
+        self.deliver(EventType::Status, event).await
+    }
+
+    /// Deliver a webhook, then handle any requests the processor made of itself while handling it
+    async fn deliver(&mut self, event_type: EventType, event: Value) -> Result<()> {
+        let payload = serde_json::to_vec(&event)?;
+        self.deliveries += 1;
+        let request = Request::Webhook {
+            event: Event::from_json(event_type, &payload)?,
+            payload,
+            delivery_id: format!("delivery-{}", self.deliveries),
+        };
+        // Boxed, since handling a request needs more than a test thread's stack
+        Box::pin(self.processor.handle_request(request)).await?;
+
+        while let Ok(request) = self.processor.requests_rx.try_recv() {
+            Box::pin(self.processor.handle_request(request)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether PR `number`'s change has made it onto the remote's `master`
+    fn has_landed(&self, number: u64) -> bool {
+        let file = format!("master:pr-{}.txt", number);
+        git(&self.remote, &["cat-file", "-e", &file]).is_ok()
+    }
+
+    fn calls(&self) -> String {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|call| match call.body.get("operationName") {
+                // Queries are long and only differ by their operation and its variables
+                Some(operation) => format!(
+                    "  {} {} {} {}\n",
+                    call.method, call.path, operation, call.body["variables"]
+                ),
+                None => format!("  {} {} {}\n", call.method, call.path, call.body),
+            })
+            .collect()
+    }
+}
+
+impl Drop for Scenario {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Serve a mock of Github's API on a local port, recording each call made to it in `calls`.
+/// Updates to refs are applied to `remote`. Returns the API's base URL.
+fn serve_mock_github(calls: ApiCalls, remote: PathBuf) -> String {
+    let make_service = make_service_fn(move |_| {
+        let calls = calls.clone();
+        let remote = remote.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                respond(calls.clone(), remote.clone(), request)
+            }))
+        }
+    });
+
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let base_url = format!("http://{}/", server.local_addr());
+    tokio::spawn(server);
+    base_url
+}
+
+async fn respond(
+    calls: ApiCalls,
+    remote: PathBuf,
+    request: hyper::Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().trim_start_matches('/').to_owned();
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .ok()
+        .and_then(|body| serde_json::from_slice(&body).ok())
+        .unwrap_or(Value::Null);
+
+    let segments: Vec<&str> = path.split('/').collect();
+    let (status, response) = match (&method, segments.as_slice()) {
+        (&Method::POST, ["graphql"]) => (StatusCode::OK, Some(json!({ "data": {} }))),
+        (&Method::POST, ["repos", _, _, "issues", _, "comments"])
+        | (&Method::PATCH, ["repos", _, _, "issues", "comments", _]) => {
+            (StatusCode::CREATED, Some(comment(&body)))
+        }
+        (&Method::POST, ["repos", _, _, "statuses", _]) => {
+            (StatusCode::CREATED, Some(repo_status(&body)))
+        }
+        (&Method::GET, ["repos", _, _, "collaborators", _]) => (StatusCode::NO_CONTENT, None),
+        (&Method::GET, ["repos", _, _, "collaborators", _, "permission"]) => {
+            (StatusCode::OK, Some(json!({ "permission": "write" })))
+        }
+        (&Method::PATCH, ["repos", _, _, "git", "refs", "heads", branch]) => {
+            let sha = body["sha"].as_str().unwrap_or_default();
+            let branch = format!("refs/heads/{}", branch);
+            match git(&remote, &["update-ref", &branch, sha]) {
+                Ok(_) => (StatusCode::OK, Some(json!({}))),
+                Err(e) => (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some(json!({ "message": e.to_string() })),
+                ),
+            }
+        }
+        _ => (
+            StatusCode::NOT_FOUND,
+            Some(json!({ "message": "Not Found" })),
+        ),
+    };
+
+    calls.lock().unwrap().push(ApiCall { method, path, body });
+
+    let mut response = Response::new(match response {
+        Some(response) => Body::from(response.to_string()),
+        None => Body::empty(),
+    });
+    *response.status_mut() = status;
+    Ok(response)
+}
+
+/// The comment Github responds with when `request` posts or edits one
+fn comment(request: &Value) -> Value {
+    let events: Vec<Value> = serde_json::from_str(ISSUE_COMMENT_EVENT_JSON).unwrap();
+    let mut comment = events[0]["comment"].clone();
+    comment["body"] = request["body"].clone();
+    comment
+}
+
+/// The status Github responds with when `request` creates one
+fn repo_status(request: &Value) -> Value {
+    let events: Vec<Value> = serde_json::from_str(ISSUE_COMMENT_EVENT_JSON).unwrap();
+    let comment = &events[0]["comment"];
+    json!({
+        "id": 1,
+        "node_id": "MDY6U3RhdHVzMQ==",
+        "url": "",
+        "state": request["state"],
+        "target_url": request["target_url"],
+        "description": request["description"],
+        "context": request["context"],
+        "creator": comment["user"],
+        "created_at": comment["created_at"],
+        "updated_at": comment["updated_at"],
+    })
+}
+
+/// Run git in `dir`, returning its trimmed output
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(&[
+            "-c",
+            "user.name=Codertocat",
+            "-c",
+            "user.email=octocat@github.com",
+        ])
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("'{}' isn't valid UTF-8", path.display()))
+}
+
+mod test {
+    use super::Scenario;
+
+    const CONFIG: &str = r#"
+        owner = "Codertocat"
+        name = "Hello-World"
+
+        [status.ci]
+        context = "ci"
+    "#;
+
+    #[tokio::test]
+    async fn lands_a_pr_once_its_checks_pass() {
+        let mut scenario = Scenario::new("land", CONFIG).await.unwrap();
+        scenario
+            .run(&[
+                "open 1 by alice",
+                "comment 1 bob /land",
+                "expect 1 testing",
+                "pass ci",
+                "expect 1 landed",
+                "expect-call PATCH repos/Codertocat/Hello-World/git/refs/heads/master",
+            ])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_a_pr_whose_checks_fail() {
+        let mut scenario = Scenario::new("fail", CONFIG).await.unwrap();
+        scenario
+            .run(&[
+                "open 1 by alice",
+                "comment 1 bob /land",
+                "expect 1 testing",
+                "fail ci",
+                "expect 1 failed",
+                "expect-comment 1 Test Failed on attempt #1 - ci",
+            ])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn gates_every_pr_in_a_stack() {
+        let config = format!("block-self-approval = true\n{}", CONFIG);
+        let mut scenario = Scenario::new("stack", &config).await.unwrap();
+        scenario
+            .run(&[
+                "open 1 by alice",
+                "open 2 by bob on 1",
+                "comment 2 alice /land stack",
+                "expect 1 in-review",
+                "expect 2 in-review",
+                "expect-comment 1 PRs can't be approved by their own author",
+                "expect-comment 2 these PRs in it can't be queued yet: #1",
+                "comment 2 carol /land stack",
+                "expect 1 testing",
+                "expect 2 queued",
+            ])
+            .await
+            .unwrap();
+    }
+}
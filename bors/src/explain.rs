@@ -0,0 +1,240 @@
+//! Reconstruction of the trail of events and decisions which led a PR to its current state, from
+//! the repo's audit log
+
+use crate::{
+    audit::{AuditEntry, AuditLog, AuditRecord},
+    git, Config, Result,
+};
+use anyhow::anyhow;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct ExplainOptions {
+    /// repo, as <owner>/<name>, the PR belongs to
+    repo: String,
+
+    /// number of the PR to explain
+    pr: u64,
+}
+
+pub fn run_explain(config: Config, options: &ExplainOptions) -> Result<()> {
+    let repo = config
+        .repo
+        .iter()
+        .find(|repo| format!("{}/{}", repo.owner(), repo.name()) == options.repo)
+        .ok_or_else(|| anyhow!("no config for repo '{}'", options.repo))?;
+
+    let entries = AuditLog::read(&git::state_dir(repo.repo())?, options.pr)?;
+    if entries.is_empty() {
+        let hint = if repo.audit_log() {
+            ""
+        } else {
+            ", the audit log isn't enabled for this repo"
+        };
+        return Err(anyhow!(
+            "nothing has been recorded about {}#{}{}",
+            options.repo,
+            options.pr,
+            hint
+        ));
+    }
+
+    for line in explain(options.pr, &entries) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Describe each entry in turn, timed relative to the first, followed by a summary of where the
+/// PR stands and why
+fn explain(pr: u64, entries: &[AuditEntry]) -> Vec<String> {
+    let start = entries.first().map(|entry| entry.timestamp).unwrap_or(0);
+    let mut lines: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "[{:>8}] {}",
+                elapsed(entry.timestamp.saturating_sub(start)),
+                describe(&entry.record)
+            )
+        })
+        .collect();
+
+    lines.push(String::new());
+    lines.push(conclusion(pr, entries));
+    lines
+}
+
+fn describe(record: &AuditRecord) -> String {
+    match record {
+        AuditRecord::Event {
+            delivery_id,
+            description,
+        } => format!("received {} (delivery {})", description, delivery_id),
+        AuditRecord::CommentIgnored { user, reason } => {
+            format!("ignored comment from {}: {}", user, reason)
+        }
+        AuditRecord::Command {
+            user,
+            command,
+            authorized: true,
+        } => format!("executed `{}` from {}", command, user),
+        AuditRecord::Command {
+            user,
+            command,
+            authorized: false,
+        } => format!(
+            "rejected `{}` from {}: insufficient privileges",
+            command, user
+        ),
+        AuditRecord::Status { from, to, reason } => match reason {
+            Some(reason) => format!("status {} -> {}: {}", from, to, reason),
+            None => format!("status {} -> {}", from, to),
+        },
+        AuditRecord::Held {
+            reason: Some(reason),
+        } => format!("passed over in the queue: {}", reason),
+        AuditRecord::Held { reason: None } => "no longer passed over in the queue".to_owned(),
+        AuditRecord::Admin { action } => format!("administrator: {}", action),
+        AuditRecord::AutoApproval {
+            approved: true,
+            reason,
+        } => format!("approved automatically: {}", reason),
+        AuditRecord::AutoApproval {
+            approved: false,
+            reason,
+        } => format!("not approved automatically: {}", reason),
+    }
+}
+
+fn conclusion(pr: u64, entries: &[AuditEntry]) -> String {
+    let last_status = entries
+        .iter()
+        .rposition(|entry| matches!(entry.record, AuditRecord::Status { .. }));
+
+    // Only holds since the PR was last queued are relevant
+    let held = entries[last_status.map(|idx| idx + 1).unwrap_or(0)..]
+        .iter()
+        .rev()
+        .find_map(|entry| match &entry.record {
+            AuditRecord::Held { reason } => Some(reason.as_deref()),
+            _ => None,
+        })
+        .flatten();
+
+    let status = last_status.and_then(|idx| match &entries[idx].record {
+        AuditRecord::Status { to, reason, .. } => Some((to.as_str(), reason.as_deref())),
+        _ => None,
+    });
+
+    let mut summary = match status {
+        None => format!(
+            "#{} hasn't changed status since it was first recorded, it was never queued",
+            pr
+        ),
+        Some(("landed", _)) => format!("#{} landed", pr),
+        Some(("closed", _)) => format!("#{} was closed without landing", pr),
+        Some(("failed", reason)) => format!(
+            "#{} failed ({}) and must be retried or approved again",
+            pr,
+            reason.unwrap_or("unknown reason")
+        ),
+        Some(("queued", _)) => match held {
+            Some(reason) => format!("#{} is queued but is being passed over: {}", pr, reason),
+            None => format!("#{} is queued, waiting for its turn to be tested", pr),
+        },
+        Some(("testing", _)) => format!("#{} is being tested", pr),
+        Some(("trying", _)) => format!("#{} is running a try build", pr),
+        Some((status, _)) => format!("#{} is {} and isn't queued for landing", pr, status),
+    };
+
+    let last_command = entries.iter().rev().find_map(|entry| match &entry.record {
+        AuditRecord::Command {
+            user,
+            command,
+            authorized,
+        } => Some((user, command, *authorized)),
+        _ => None,
+    });
+    if let Some((user, command, false)) = last_command {
+        summary.push_str(&format!(
+            "; the last command, `{}` from {}, was rejected",
+            command, user
+        ));
+    }
+
+    summary
+}
+
+/// Format a number of seconds, e.g. "+1h05m"
+fn elapsed(seconds: u64) -> String {
+    if seconds < 60 {
+        format!("+{}s", seconds)
+    } else if seconds < 60 * 60 {
+        format!("+{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("+{}h{:02}m", seconds / (60 * 60), seconds / 60 % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::explain;
+    use crate::audit::AuditEntry;
+
+    fn entries(lines: &str) -> Vec<AuditEntry> {
+        lines
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn held_back_pr() {
+        let entries = entries(
+            r#"
+            {"timestamp":100,"pr":7,"kind":"event","delivery_id":"a","description":"comment Created by alice"}
+            {"timestamp":100,"pr":7,"kind":"command","user":"alice","command":"/land","authorized":true}
+            {"timestamp":101,"pr":7,"kind":"status","from":"in-review","to":"queued","reason":null}
+            {"timestamp":101,"pr":7,"kind":"held","reason":"waiting on #3"}
+            {"timestamp":4000,"pr":7,"kind":"event","delivery_id":"b","description":"comment Created by mallory"}
+            {"timestamp":4000,"pr":7,"kind":"command","user":"mallory","command":"/cancel","authorized":false}
+            "#,
+        );
+
+        assert_eq!(
+            explain(7, &entries),
+            vec![
+                "[     +0s] received comment Created by alice (delivery a)",
+                "[     +0s] executed `/land` from alice",
+                "[     +1s] status in-review -> queued",
+                "[     +1s] passed over in the queue: waiting on #3",
+                "[  +1h05m] received comment Created by mallory (delivery b)",
+                "[  +1h05m] rejected `/cancel` from mallory: insufficient privileges",
+                "",
+                "#7 is queued but is being passed over: waiting on #3; the last command, \
+                 `/cancel` from mallory, was rejected",
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_from_earlier_queueing_is_stale() {
+        let entries = entries(
+            r#"
+            {"timestamp":0,"pr":2,"kind":"status","from":"in-review","to":"queued","reason":null}
+            {"timestamp":1,"pr":2,"kind":"held","reason":"held back by the closed tree"}
+            {"timestamp":2,"pr":2,"kind":"status","from":"queued","to":"in-review","reason":null}
+            {"timestamp":3,"pr":2,"kind":"status","from":"in-review","to":"queued","reason":null}
+            "#,
+        );
+
+        assert_eq!(
+            explain(2, &entries).last().unwrap(),
+            "#2 is queued, waiting for its turn to be tested"
+        );
+    }
+}
@@ -1,29 +1,168 @@
-use crate::{config::GitConfig, state::Repo, Result};
+use crate::{
+    config::{GitConfig, MergeStrategy},
+    state::Repo,
+    Result,
+};
 use anyhow::{anyhow, Context};
 use github::Oid;
 use log::info;
 use std::{
+    io::Write,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Output, Stdio},
 };
 
 const REPOS_DIR: &str = "repos";
 
+/// Device names which Windows reserves in every directory, regardless of extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The quirks of the host's filesystem which affect where repos are cloned to and which branches
+/// can be created in them. Kept separate from the host so that each combination can be tested on
+/// any platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Filesystem {
+    /// Paths are separated by `\`, limited in length and can't contain some characters, and each
+    /// local branch is stored as a file subject to the same rules
+    windows: bool,
+    /// Paths which only differ by case refer to the same file
+    case_insensitive: bool,
+}
+
+impl Filesystem {
+    pub fn host() -> Self {
+        Self {
+            windows: cfg!(windows),
+            case_insensitive: cfg!(any(windows, target_os = "macos")),
+        }
+    }
+
+    /// The directory `repo` is cloned to under `root`. Github treats owners and names without
+    /// regard to case so, where the filesystem does too, they're lowercased in order for repos
+    /// configured with different casings to consistently share a clone.
+    fn repo_dir(&self, root: &Path, repo: &Repo) -> PathBuf {
+        let (owner, name) = if self.case_insensitive {
+            (repo.owner().to_lowercase(), repo.name().to_lowercase())
+        } else {
+            (repo.owner().to_owned(), repo.name().to_owned())
+        };
+        root.join(REPOS_DIR).join(owner).join(name)
+    }
+
+    /// The command git runs to connect over ssh. Git runs it with a POSIX shell, including on
+    /// Windows, so the key's path is quoted and uses `/` as its separator.
+    fn ssh_command(&self, ssh_key_file: &Path) -> String {
+        let mut path = ssh_key_file.display().to_string();
+        if self.windows {
+            path = path.replace('\\', "/");
+        }
+        format!(
+            "ssh -i '{}' -S none -o 'IdentitiesOnly true'",
+            path.replace('\'', r"'\''")
+        )
+    }
+
+    /// Check that a local branch named `branch` can be created, since git allows names which the
+    /// filesystem can't store
+    fn check_branch(&self, branch: &str) -> Result<()> {
+        if !self.windows {
+            return Ok(());
+        }
+
+        for component in branch.split('/') {
+            let stem = component.split('.').next().unwrap_or_default();
+            if component.contains(|c| matches!(c, '"' | '<' | '>' | '|'))
+                || component.ends_with('.')
+                || component.ends_with(' ')
+                || WINDOWS_RESERVED_NAMES
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(stem))
+            {
+                return Err(anyhow!(
+                    "branch '{}' can't be created on this host's filesystem",
+                    branch
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Why pushing to a remote failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PushFailure {
+    /// The remote refused the push, e.g. because a PR doesn't allow edits from maintainers
+    PermissionDenied,
+    /// The branch no longer pointed where it was expected to, e.g. it was pushed to concurrently
+    Stale,
+    /// Anything else, e.g. a network error, which may succeed if tried again
+    Transient,
+}
+
+impl PushFailure {
+    /// Classify the error returned by a failed push from what git reported
+    pub fn classify(error: &anyhow::Error) -> Self {
+        let message = error.to_string().to_ascii_lowercase();
+
+        if message.contains("stale info") || message.contains("fetch first") {
+            PushFailure::Stale
+        // bors' own ssh key being refused isn't something the PR's author can fix
+        } else if message.contains("publickey") {
+            PushFailure::Transient
+        } else if [
+            "permission",
+            "denied",
+            "error: 403",
+            "repository not found",
+            "hook declined",
+        ]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+        {
+            PushFailure::PermissionDenied
+        } else {
+            PushFailure::Transient
+        }
+    }
+}
+
+/// How the commits a PR lands as are annotated
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommitAnnotations {
+    /// Message of the commit a squashed PR lands as, replacing those of its commits
+    pub squash_message: Option<String>,
+    /// Trailers added to the PR's tip commit after its `Closes` trailer, as `(key, value)` pairs
+    pub trailers: Vec<(String, String)>,
+    /// Credit the authors of a squashed PR's commits, other than the author of its first commit
+    /// who the squashed commit is attributed to, with `Co-authored-by` trailers
+    pub co_authors: bool,
+}
+
 #[derive(Debug)]
 pub struct GitRepository {
     directory: PathBuf,
     github_repo: Repo,
     git_config: GitConfig,
+    filesystem: Filesystem,
+
+    /// Repo whose base branches PRs are rebased onto, when they're newer than this repo's own
+    upstream: Option<Repo>,
 }
 
 impl GitRepository {
-    pub fn from_config(git_config: &GitConfig, repo: &Repo) -> Result<Self> {
+    pub fn from_config(
+        git_config: &GitConfig,
+        repo: &Repo,
+        upstream: Option<&Repo>,
+    ) -> Result<Self> {
         let github_repo = repo.clone();
         let git_config = git_config.clone();
-        let mut directory = std::env::current_dir()?;
-        directory.push(REPOS_DIR);
-        directory.push(github_repo.owner());
-        directory.push(github_repo.name());
+        let filesystem = Filesystem::host();
+        let directory = filesystem.repo_dir(&std::env::current_dir()?, &github_repo);
 
         if !Git::new().current_dir(&directory).is_git_repo()? {
             info!(
@@ -32,7 +171,7 @@ impl GitRepository {
                 directory.display()
             );
             Git::new()
-                .with_ssh(&git_config.ssh_key_file)
+                .with_ssh(&git_config.ssh_key_file, filesystem)
                 .clone(&directory, &github_repo)?;
         } else {
             info!("using existing on-disk repo at {}", directory.display());
@@ -51,9 +190,30 @@ impl GitRepository {
             directory,
             github_repo,
             git_config,
+            filesystem,
+            upstream: upstream.cloned(),
         })
     }
 
+    /// An existing clone at `directory` whose `origin` may be any remote, e.g. a local one standing
+    /// in for Github in tests
+    #[cfg(test)]
+    pub fn at(directory: PathBuf, git_config: &GitConfig, repo: &Repo) -> Self {
+        Self {
+            directory,
+            github_repo: repo.clone(),
+            git_config: git_config.clone(),
+            filesystem: Filesystem::host(),
+            upstream: None,
+        }
+    }
+
+    /// Directory inside the clone's git directory for state bors keeps about the repo, so that it
+    /// stays with the clone wherever bors is run from
+    pub fn state_dir(&self) -> PathBuf {
+        state_dir_in(&self.directory)
+    }
+
     pub fn push_branch(&mut self, branch: &str) -> Result<()> {
         self.git().push_branch(branch, true)
     }
@@ -74,18 +234,195 @@ impl GitRepository {
         head_oid: &Oid,
         branch: &str,
         pr_number: u64,
-        fixup_all: bool,
+        annotations: &CommitAnnotations,
+        strategy: MergeStrategy,
     ) -> Result<Option<Oid>> {
         // Fetch base ref and head_oid
+        self.fetch(base_ref, head_oid)?;
+        let mut base_oid = self.git().ref_to_oid(&format!("origin/{}", base_ref))?;
+
+        // Build on upstream's base ref as long as it includes everything already landed here. If
+        // it doesn't, PRs keep being built on this repo's base ref until those have been synced
+        // upstream, so that the base ref can always be fast-forwarded when a PR lands.
+        if let Some(upstream) = self.upstream.clone() {
+            let upstream_oid = self.fetch_upstream(&upstream, base_ref)?;
+            if self.git().is_ancestor(&base_oid, &upstream_oid)? {
+                base_oid = upstream_oid;
+            } else {
+                info!(
+                    "'{}' has diverged from upstream, waiting for it to be synced",
+                    base_ref
+                );
+            }
+        }
+
+        self.rebase(
+            &base_oid,
+            head_oid,
+            branch,
+            pr_number,
+            annotations,
+            strategy,
+        )
+    }
+
+    /// Rebase a PR on top of `onto`, the tip of a batch of already rebased PRs
+    pub fn fetch_and_rebase_onto(
+        &mut self,
+        onto: &Oid,
+        head_oid: &Oid,
+        branch: &str,
+        pr_number: u64,
+        annotations: &CommitAnnotations,
+        strategy: MergeStrategy,
+    ) -> Result<Option<Oid>> {
+        self.git().fetch(&[head_oid.to_string()])?;
+        self.rebase(onto, head_oid, branch, pr_number, annotations, strategy)
+    }
+
+    /// Rebase a PR's commits, as they are, onto the tip of `base_ref`. Returns the rebased head, or
+    /// `None` if the commits conflict with `base_ref`.
+    pub fn fetch_and_rebase_as_is(
+        &mut self,
+        base_ref: &str,
+        head_oid: &Oid,
+        branch: &str,
+    ) -> Result<Option<Oid>> {
         self.fetch(base_ref, head_oid)?;
         let base_oid = self.git().ref_to_oid(&format!("origin/{}", base_ref))?;
-        self.rebase(&base_oid, head_oid, branch, pr_number, fixup_all)
+
+        self.create_branch(branch, head_oid)?;
+        if let Err(e) = self.git().rebase(&base_oid, false, None) {
+            info!("Rebase failed: {}", e);
+            self.git().rebase_abort()?;
+            return Ok(None);
+        }
+
+        self.git().head_oid().map(Some)
+    }
+
+    /// The commits PR `pr_number` landed on `base_ref`, oldest first, or none if it hasn't landed
+    /// there. These are the first-parent commits after the previous PR which landed, up to and
+    /// including the one whose `Closes` trailer names this PR.
+    pub fn landed_commits(&mut self, base_ref: &str, pr_number: u64) -> Result<Vec<Oid>> {
+        let base_oid = self.fetch_base(base_ref)?;
+
+        let closes = format!("^Closes: {}$", self.closes(pr_number).replace('.', "\\."));
+        let tip = match self
+            .git()
+            .last_commit_matching(&base_oid.to_string(), &closes)?
+        {
+            Some(tip) => tip,
+            None => return Ok(Vec::new()),
+        };
+        let previous = self
+            .git()
+            .last_commit_matching(&format!("{}^", tip), "^Closes: ")?;
+
+        let range = match previous {
+            Some(previous) => format!("{}..{}", previous, tip),
+            None => format!("{}^..{}", tip, tip),
+        };
+        self.git().first_parent_commits(&range)
+    }
+
+    /// Cherry-pick `commits` onto `onto`, recording where each of them was picked from. Returns
+    /// the new head, or `None` if the commits conflict with `onto`.
+    pub fn cherry_pick_onto(
+        &mut self,
+        onto: &Oid,
+        commits: &[Oid],
+        branch: &str,
+    ) -> Result<Option<Oid>> {
+        self.create_branch(branch, onto)?;
+        if let Err(e) = self.git().cherry_pick(commits) {
+            info!("Cherry-pick failed: {}", e);
+            self.git().cherry_pick_abort()?;
+            return Ok(None);
+        }
+
+        self.git().head_oid().map(Some)
+    }
+
+    /// Revert `commits`, newest first, on top of `onto`. Returns the new head, or `None` if the
+    /// commits can no longer be reverted cleanly.
+    pub fn revert_onto(
+        &mut self,
+        onto: &Oid,
+        commits: &[Oid],
+        branch: &str,
+    ) -> Result<Option<Oid>> {
+        self.create_branch(branch, onto)?;
+        let newest_first: Vec<_> = commits.iter().rev().cloned().collect();
+        if let Err(e) = self.git().revert(&newest_first) {
+            info!("Revert failed: {}", e);
+            self.git().revert_abort()?;
+            return Ok(None);
+        }
+
+        self.git().head_oid().map(Some)
+    }
+
+    /// Fetch `base_ref`, returning the oid it points to
+    pub fn fetch_base(&mut self, base_ref: &str) -> Result<Oid> {
+        self.git().fetch(&[base_ref])?;
+        self.git().ref_to_oid(&format!("origin/{}", base_ref))
+    }
+
+    /// The contents of the file at `path` in the commit `oid`, or `None` if there's no such file
+    pub fn file_contents(&mut self, oid: &Oid, path: &str) -> Result<Option<String>> {
+        match self.git().blob_at(oid, path)? {
+            Some(blob) => self.git().blob_contents(&blob).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Check if `ancestor` is reachable from `oid`
+    pub fn is_ancestor(&mut self, ancestor: &Oid, oid: &Oid) -> Result<bool> {
+        self.git().is_ancestor(ancestor, oid)
+    }
+
+    /// Point `branch` in the remote repository at `oid`, regardless of where it previously pointed
+    pub fn force_push(&mut self, branch: &str, oid: &Oid) -> Result<()> {
+        self.git().force_push(branch, oid)
+    }
+
+    /// Delete `branch` from the remote repository
+    pub fn delete_remote_branch(&mut self, branch: &str) -> Result<()> {
+        self.git().delete_remote_branch(branch)
+    }
+
+    /// Point `branch` at `oid`, discarding whatever it previously pointed to
+    pub fn reset_branch(&mut self, branch: &str, oid: &Oid) -> Result<()> {
+        self.create_branch(branch, oid)
+    }
+
+    fn create_branch(&mut self, branch: &str, oid: &Oid) -> Result<()> {
+        self.filesystem.check_branch(branch)?;
+        self.git().create_branch(branch, oid)
+    }
+
+    /// Fetch `branch` from `repo`, returning the oid it points to
+    pub fn fetch_remote_branch(&mut self, repo: &Repo, branch: &str) -> Result<Oid> {
+        self.git()
+            .fetch_from(&repo.to_github_ssh_url(), &[branch])?;
+        self.git().ref_to_oid("FETCH_HEAD")
     }
 
     fn fetch(&mut self, base_ref: &str, oid: &Oid) -> Result<()> {
         self.git().fetch(&[base_ref, &oid.to_string()])
     }
 
+    /// Fetch `base_ref` from `upstream`, returning the oid it points to
+    fn fetch_upstream(&mut self, upstream: &Repo, base_ref: &str) -> Result<Oid> {
+        let tracking_ref = format!("refs/remotes/upstream/{}", base_ref);
+        self.git().fetch_from(
+            &upstream.to_github_ssh_url(),
+            &[format!("+refs/heads/{}:{}", base_ref, tracking_ref)],
+        )?;
+        self.git().ref_to_oid(&tracking_ref)
+    }
+
     // None represents a Merge conflict
     fn rebase(
         &mut self,
@@ -93,10 +430,86 @@ impl GitRepository {
         head_oid: &Oid,
         branch: &str,
         pr_number: u64,
-        fixup_all: bool,
+        annotations: &CommitAnnotations,
+        strategy: MergeStrategy,
     ) -> Result<Option<Oid>> {
+        let co_authors = if strategy == MergeStrategy::Squash && annotations.co_authors {
+            let mut authors = self
+                .git()
+                .commit_authors(&format!("{}..{}", base_oid, head_oid))?
+                .into_iter();
+            let first = authors.next();
+            authors
+                .filter(|author| Some(author) != first.as_ref())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let applied = match strategy {
+            MergeStrategy::Rebase => self.rebase_commits(base_oid, head_oid, branch, false)?,
+            MergeStrategy::Squash => self.rebase_commits(base_oid, head_oid, branch, true)?,
+            MergeStrategy::Merge => self.merge_commits(base_oid, head_oid, branch, pr_number)?,
+        };
+
+        if !applied {
+            return Ok(None);
+        }
+
+        let head_oid = self.git().head_oid()?;
+
+        // If the head_oid and base_oid's match after the rebase then it means that the rebased
+        // commits resulted in no-ops
+        if head_oid == *base_oid {
+            Ok(None)
+        } else {
+            let message = match (strategy, &annotations.squash_message) {
+                (MergeStrategy::Squash, Some(message)) => message.clone(),
+                _ => self.git().message("HEAD")?,
+            };
+
+            // Amend the tip commit to annotate that it closes the PR, along with who approved,
+            // reviewed and wrote it
+            let mut trailers = vec![("Closes".to_owned(), self.closes(pr_number))];
+            trailers.extend(annotations.trailers.iter().cloned());
+            trailers.extend(
+                co_authors
+                    .into_iter()
+                    .map(|author| ("Co-authored-by".to_owned(), author)),
+            );
+            let message = self.git().interpret_trailers(&message, &trailers)?;
+            self.git().amend_message(&message)?;
+            let head_oid = self.git().head_oid()?;
+
+            Ok(Some(head_oid))
+        }
+    }
+
+    /// How a landed commit's `Closes` trailer refers to PR `pr_number`. The PR is qualified with its
+    /// repo when syncing upstream, where `#N` would refer to upstream's PRs instead.
+    fn closes(&self, pr_number: u64) -> String {
+        match &self.upstream {
+            Some(_) => format!(
+                "{}/{}#{}",
+                self.github_repo.owner(),
+                self.github_repo.name(),
+                pr_number
+            ),
+            None => format!("#{}", pr_number),
+        }
+    }
+
+    /// Rebase the PR's commits onto `base_oid`, first squashing them down to a single commit if
+    /// `fixup_all` is set. Returns `false` if the rebase failed due to a conflict.
+    fn rebase_commits(
+        &mut self,
+        base_oid: &Oid,
+        head_oid: &Oid,
+        branch: &str,
+        fixup_all: bool,
+    ) -> Result<bool> {
         // First create the branch to work on for the rebase
-        self.git().create_branch(branch, head_oid)?;
+        self.create_branch(branch, head_oid)?;
 
         if fixup_all {
             // Get the first commit in the PR
@@ -118,25 +531,31 @@ impl GitRepository {
             // the rebase failed, probably due to a merge conflict so we need to reset the state of
             // the tree and abort the rebase
             self.git().rebase_abort()?;
-            Ok(None)
+            Ok(false)
         } else {
-            let head_oid = self.git().head_oid()?;
+            Ok(true)
+        }
+    }
 
-            // If the head_oid and base_oid's match after the rebase then it means that the rebased
-            // commits resulted in no-ops
-            if head_oid == *base_oid {
-                Ok(None)
-            } else {
-                // Amend the tip commit to annotate that it closes the PR
-                let editor = format!(
-                    "git interpret-trailers --trailer \"Closes: #{}\" --in-place",
-                    pr_number
-                );
-                self.git().amend(&editor)?;
-                let head_oid = self.git().head_oid()?;
+    /// Merge the PR's commits into `base_oid` with a merge commit, preserving its history as-is.
+    /// Returns `false` if the merge failed due to a conflict.
+    fn merge_commits(
+        &mut self,
+        base_oid: &Oid,
+        head_oid: &Oid,
+        branch: &str,
+        pr_number: u64,
+    ) -> Result<bool> {
+        self.create_branch(branch, base_oid)?;
 
-                Ok(Some(head_oid))
-            }
+        let message = format!("Merge #{}", pr_number);
+        if let Err(e) = self.git().merge(head_oid, &message) {
+            info!("Merge failed: {}", e);
+
+            self.git().merge_abort()?;
+            Ok(false)
+        } else {
+            Ok(true)
         }
     }
 
@@ -145,8 +564,37 @@ impl GitRepository {
             .current_dir(&self.directory)
             .with_user(&self.git_config.user)
             .with_email(&self.git_config.email)
-            .with_ssh(&self.git_config.ssh_key_file)
+            .with_ssh(&self.git_config.ssh_key_file, self.filesystem)
+    }
+}
+
+/// The state directory of `repo`'s clone, for commands run outside of `bors serve`. The repo
+/// must already have been cloned, by serving it, since the clone can't be made into a directory
+/// which already holds state.
+pub fn state_dir(repo: &Repo) -> Result<PathBuf> {
+    let directory = Filesystem::host().repo_dir(&std::env::current_dir()?, repo);
+    if !directory.join(".git").is_dir() {
+        return Err(anyhow!(
+            "{}/{} hasn't been cloned to '{}' yet, it must be served first",
+            repo.owner(),
+            repo.name(),
+            directory.display()
+        ));
     }
+
+    Ok(state_dir_in(&directory))
+}
+
+fn state_dir_in(directory: &Path) -> PathBuf {
+    directory.join(".git").join("bors")
+}
+
+/// Check that `git_config`'s SSH key is accepted by Github and can read `repo`, without needing a
+/// clone of it
+pub fn check_access(git_config: &GitConfig, repo: &Repo) -> Result<()> {
+    Git::new()
+        .with_ssh(&git_config.ssh_key_file, Filesystem::host())
+        .ls_remote(repo)
 }
 
 struct Git {
@@ -166,6 +614,11 @@ impl Git {
             // Don't try and open an editor for things like `rebase -i`
             .env("GIT_EDITOR", "cat");
 
+        // Let checkouts on Windows exceed the legacy 260 character path limit
+        if cfg!(windows) {
+            inner.args(&["-c", "core.longpaths=true"]);
+        }
+
         Self { inner }
     }
 
@@ -178,16 +631,14 @@ impl Git {
         self
     }
 
-    pub fn with_ssh(mut self, ssh_key_file: &Path) -> Self {
+    pub fn with_ssh(mut self, ssh_key_file: &Path, filesystem: Filesystem) -> Self {
         let path = if ssh_key_file.is_absolute() {
             ssh_key_file.to_path_buf()
         } else {
             std::env::current_dir().unwrap().join(ssh_key_file)
         };
-        self.inner.env(
-            "GIT_SSH_COMMAND",
-            format!("ssh -i {} -S none -o 'IdentitiesOnly true'", path.display()),
-        );
+        self.inner
+            .env("GIT_SSH_COMMAND", filesystem.ssh_command(&path));
         self
     }
 
@@ -203,14 +654,28 @@ impl Git {
         self
     }
 
-    pub fn with_editor(mut self, editor: &str) -> Self {
-        self.inner.env("GIT_EDITOR", editor);
-        self
-    }
-
     fn run(mut self) -> Result<String> {
         let output = self.inner.output()?;
+        Self::check(output)
+    }
+
+    /// Run the command with `input` written to its stdin
+    fn run_with_input(mut self, input: &str) -> Result<String> {
+        let mut child = self
+            .inner
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input.as_bytes())?;
+        Self::check(child.wait_with_output()?)
+    }
 
+    fn check(output: Output) -> Result<String> {
         if !output.status.success() {
             return Err(anyhow!(
                 "failed to run git command:\n{}",
@@ -235,7 +700,19 @@ impl Git {
         self.inner.args(&["remote", "get-url", "origin"]);
         let output = self.run()?;
 
-        Ok(output.trim() == github_repo.to_github_ssh_url())
+        // Github doesn't distinguish owners or names by case
+        Ok(output
+            .trim()
+            .eq_ignore_ascii_case(&github_repo.to_github_ssh_url()))
+    }
+
+    pub fn ls_remote(mut self, github_repo: &Repo) -> Result<()> {
+        self.inner
+            .args(&["ls-remote", "--heads"])
+            .arg(github_repo.to_github_ssh_url());
+        self.run()
+            .with_context(|| format!("listing {}", github_repo.to_github_ssh_url()))?;
+        Ok(())
     }
 
     pub fn clone(mut self, path: &Path, github_repo: &Repo) -> Result<()> {
@@ -248,16 +725,44 @@ impl Git {
         Ok(())
     }
 
-    pub fn fetch<I, S>(mut self, refspec: I) -> Result<()>
+    pub fn fetch<I, S>(self, refspec: I) -> Result<()>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<std::ffi::OsStr>,
     {
-        self.inner.arg("fetch").arg("origin").args(refspec);
+        self.fetch_from("origin", refspec)
+    }
+
+    /// Fetch from `remote`, either the name of a remote or a URL
+    pub fn fetch_from<I, S>(mut self, remote: &str, refspec: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.inner.arg("fetch").arg(remote).args(refspec);
         self.run()?;
         Ok(())
     }
 
+    /// Check if `ancestor` is reachable from `oid`
+    pub fn is_ancestor(mut self, ancestor: &Oid, oid: &Oid) -> Result<bool> {
+        let output = self
+            .inner
+            .args(&["merge-base", "--is-ancestor"])
+            .arg(ancestor.to_string())
+            .arg(oid.to_string())
+            .output()?;
+
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(anyhow!(
+                "failed to run git command:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )),
+        }
+    }
+
     pub fn create_branch(mut self, branch_name: &str, oid: &Oid) -> Result<()> {
         self.inner
             .args(&["checkout", "-B", branch_name])
@@ -266,9 +771,13 @@ impl Git {
         Ok(())
     }
 
-    pub fn amend(mut self, editor: &str) -> Result<()> {
-        self.inner.args(&["commit", "--amend"]);
-        self.with_editor(editor).run()?;
+    /// Replace the message of the HEAD commit. Lines starting with '#' are kept, they're likely
+    /// markdown headings from a PR's description.
+    pub fn amend_message(mut self, message: &str) -> Result<()> {
+        self.inner
+            .args(&["commit", "--amend", "--cleanup=whitespace", "-m"])
+            .arg(message);
+        self.run()?;
         Ok(())
     }
 
@@ -295,6 +804,134 @@ impl Git {
         Ok(())
     }
 
+    pub fn merge(mut self, oid: &Oid, message: &str) -> Result<()> {
+        self.inner
+            .args(&["merge", "--no-ff", "--no-edit", "-m", message])
+            .arg(oid.to_string());
+        self.run()?;
+        Ok(())
+    }
+
+    pub fn merge_abort(mut self) -> Result<()> {
+        self.inner.args(&["merge", "--abort"]);
+        self.run()?;
+        Ok(())
+    }
+
+    /// Cherry-pick each of `commits` in turn, merge commits relative to their first parent
+    pub fn cherry_pick(mut self, commits: &[Oid]) -> Result<()> {
+        self.inner.args(&["cherry-pick", "-x", "-m", "1"]);
+        self.inner.args(commits.iter().map(ToString::to_string));
+        self.run()?;
+        Ok(())
+    }
+
+    pub fn cherry_pick_abort(mut self) -> Result<()> {
+        self.inner.args(&["cherry-pick", "--abort"]);
+        self.run()?;
+        Ok(())
+    }
+
+    /// The message of the commit `rev`
+    pub fn message(mut self, rev: &str) -> Result<String> {
+        self.inner.args(&["log", "-1", "--format=%B"]).arg(rev);
+        self.run()
+    }
+
+    /// Add `trailers` to `message`, merging them into any trailers it already ends with. The
+    /// trailers are passed as arguments rather than through a shell since their values, e.g.
+    /// commit authors' names, can contain anything.
+    pub fn interpret_trailers(
+        mut self,
+        message: &str,
+        trailers: &[(String, String)],
+    ) -> Result<String> {
+        self.inner.arg("interpret-trailers");
+        for (key, value) in trailers {
+            self.inner
+                .arg("--trailer")
+                .arg(format!("{}: {}", key, value));
+        }
+        self.run_with_input(message)
+    }
+
+    /// The distinct authors of the commits in `range`, as `Name <email>`, oldest first
+    pub fn commit_authors(mut self, range: &str) -> Result<Vec<String>> {
+        self.inner
+            .args(&["log", "--reverse", "--format=%an <%ae>"])
+            .arg(range);
+        let output = self.run()?;
+
+        let mut authors: Vec<String> = Vec::new();
+        for author in output.lines().map(str::trim) {
+            if !authors.iter().any(|a| a == author) {
+                authors.push(author.to_owned());
+            }
+        }
+        Ok(authors)
+    }
+
+    /// Revert each of `commits` in turn, merge commits relative to their first parent
+    pub fn revert(mut self, commits: &[Oid]) -> Result<()> {
+        self.inner.args(&["revert", "--no-edit", "-m", "1"]);
+        self.inner.args(commits.iter().map(ToString::to_string));
+        self.run()?;
+        Ok(())
+    }
+
+    pub fn revert_abort(mut self) -> Result<()> {
+        self.inner.args(&["revert", "--abort"]);
+        self.run()?;
+        Ok(())
+    }
+
+    /// The most recent commit along the first-parent history of `rev` with a line of its message
+    /// matching `pattern`
+    pub fn last_commit_matching(mut self, rev: &str, pattern: &str) -> Result<Option<Oid>> {
+        self.inner
+            .args(&["log", "--first-parent", "-1", "--format=%H"])
+            .arg(format!("--grep={}", pattern))
+            .arg(rev);
+        let output = self.run()?;
+        Ok(output.lines().next().map(|oid| Oid::from_str(oid.trim())))
+    }
+
+    /// The commits along the first-parent history in `range`, oldest first
+    pub fn first_parent_commits(mut self, range: &str) -> Result<Vec<Oid>> {
+        self.inner
+            .args(&["rev-list", "--first-parent", "--reverse"])
+            .arg(range);
+        let output = self.run()?;
+        Ok(output
+            .lines()
+            .map(|oid| Oid::from_str(oid.trim()))
+            .collect())
+    }
+
+    /// The blob of the file at `path` in the commit `oid`, if there is one
+    pub fn blob_at(mut self, oid: &Oid, path: &str) -> Result<Option<Oid>> {
+        self.inner
+            .arg("ls-tree")
+            .arg(oid.to_string())
+            .arg("--")
+            .arg(path);
+        let output = self.run()?;
+
+        // Each entry is `<mode> SP <type> SP <object> TAB <path>`
+        Ok(output.lines().find_map(|entry| {
+            let mut fields = entry.split('\t').next()?.split_whitespace().skip(1);
+            match (fields.next(), fields.next()) {
+                (Some("blob"), Some(object)) => Some(Oid::from_str(object)),
+                _ => None,
+            }
+        }))
+    }
+
+    pub fn blob_contents(mut self, blob: &Oid) -> Result<String> {
+        self.inner.args(&["cat-file", "blob"]).arg(blob.to_string());
+        self.run()
+    }
+
     pub fn get_first_commit(mut self, base_oid: &Oid, head_oid: &Oid) -> Result<Oid> {
         self.inner
             .arg("rev-list")
@@ -327,6 +964,20 @@ impl Git {
         Ok(())
     }
 
+    pub fn force_push(mut self, branch: &str, oid: &Oid) -> Result<()> {
+        self.inner
+            .args(&["push", "--force", "origin"])
+            .arg(format!("{}:refs/heads/{}", oid, branch));
+        self.run()?;
+        Ok(())
+    }
+
+    pub fn delete_remote_branch(mut self, branch: &str) -> Result<()> {
+        self.inner.args(&["push", "origin", "--delete", branch]);
+        self.run()?;
+        Ok(())
+    }
+
     pub fn push_to_remote(
         mut self,
         repo: &Repo,
@@ -343,3 +994,94 @@ impl Git {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Filesystem, PushFailure};
+    use crate::state::Repo;
+    use std::path::{Path, PathBuf};
+
+    const LINUX: Filesystem = Filesystem {
+        windows: false,
+        case_insensitive: false,
+    };
+    const MACOS: Filesystem = Filesystem {
+        windows: false,
+        case_insensitive: true,
+    };
+    const WINDOWS: Filesystem = Filesystem {
+        windows: true,
+        case_insensitive: true,
+    };
+
+    #[test]
+    fn repo_dirs() {
+        let repo = Repo::new("BMWill", "Bors-RS");
+        let root = Path::new("bors");
+
+        assert_eq!(
+            LINUX.repo_dir(root, &repo),
+            ["bors", "repos", "BMWill", "Bors-RS"]
+                .iter()
+                .collect::<PathBuf>()
+        );
+        assert_eq!(
+            MACOS.repo_dir(root, &repo),
+            ["bors", "repos", "bmwill", "bors-rs"]
+                .iter()
+                .collect::<PathBuf>()
+        );
+    }
+
+    #[test]
+    fn ssh_commands() {
+        assert_eq!(
+            LINUX.ssh_command(Path::new("/home/bors/it's/id_rsa")),
+            r"ssh -i '/home/bors/it'\''s/id_rsa' -S none -o 'IdentitiesOnly true'"
+        );
+        assert_eq!(
+            WINDOWS.ssh_command(Path::new(r"C:\Users\bors\My Keys\id_rsa")),
+            "ssh -i 'C:/Users/bors/My Keys/id_rsa' -S none -o 'IdentitiesOnly true'"
+        );
+    }
+
+    #[test]
+    fn branch_names() {
+        for branch in &["auto", "auto/pr-12", "try", "sync/main"] {
+            assert!(LINUX.check_branch(branch).is_ok());
+            assert!(WINDOWS.check_branch(branch).is_ok());
+        }
+        for branch in &["staging/con", "aux.tmp", "auto.", "try<1>", "a|b"] {
+            assert!(LINUX.check_branch(branch).is_ok());
+            assert!(WINDOWS.check_branch(branch).is_err(), "{}", branch);
+        }
+    }
+
+    #[test]
+    fn push_failures() {
+        let classify = |stderr: &str| {
+            PushFailure::classify(&anyhow::anyhow!("failed to run git command:\n{}", stderr))
+        };
+
+        assert_eq!(
+            classify("ERROR: Permission to alice/project.git denied to bors."),
+            PushFailure::PermissionDenied
+        );
+        assert_eq!(
+            classify(" ! [remote rejected] fix -> fix (protected branch hook declined)"),
+            PushFailure::PermissionDenied
+        );
+        assert_eq!(
+            classify(" ! [rejected]        fix -> fix (stale info)"),
+            PushFailure::Stale
+        );
+        assert_eq!(
+            classify("ssh: Could not resolve hostname github.com: Temporary failure"),
+            PushFailure::Transient
+        );
+        assert_eq!(
+            classify("fatal: the remote end hung up unexpectedly"),
+            PushFailure::Transient
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Authenticating as a Github App, exchanging short-lived JWTs signed by the app's private key for
+//! access tokens of its installations, which are refreshed before they expire
+
+use crate::{config::AppConfig, graphql::GithubClient, Result};
+use github::{Client, InstallationToken};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::{info, warn};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// How long a JWT is valid for. Github refuses JWTs valid for more than 10 minutes.
+const JWT_LIFETIME_SECONDS: i64 = 9 * 60;
+
+/// How far a JWT's issue time is backdated, to allow for the clock drifting from Github's
+const JWT_CLOCK_DRIFT_SECONDS: i64 = 60;
+
+/// How long before an installation token expires that it's replaced
+const REFRESH_MARGIN_SECONDS: i64 = 5 * 60;
+
+/// How long to wait before trying again when refreshing an installation token fails
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: u64,
+}
+
+#[derive(Debug)]
+pub struct GithubApp {
+    id: u64,
+    key: EncodingKey,
+}
+
+impl GithubApp {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let key = EncodingKey::from_rsa_pem(&config.private_key()?)?;
+        Ok(Self { id: config.id, key })
+    }
+
+    /// A client authenticated as the app itself, with a freshly signed JWT
+    fn client(&self) -> Result<Client> {
+        let now = chrono::Utc::now().timestamp();
+        let claims = Claims {
+            iat: now - JWT_CLOCK_DRIFT_SECONDS,
+            exp: now + JWT_LIFETIME_SECONDS,
+            iss: self.id,
+        };
+        let jwt = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+
+        let client = Client::builder()
+            .app_jwt(jwt)
+            .user_agent(USER_AGENT)
+            .build()?;
+        Ok(client)
+    }
+
+    /// The login of the bot user the app's installations act as
+    pub async fn login(&self) -> Result<String> {
+        let app = self
+            .client()?
+            .apps()
+            .get_authenticated()
+            .await?
+            .into_inner();
+        Ok(format!("{}[bot]", app.slug))
+    }
+
+    /// The id of the app's installation on the account owning the `owner/name` repo
+    pub async fn installation_id(&self, owner: &str, name: &str) -> Result<u64> {
+        let installation = self
+            .client()?
+            .apps()
+            .get_repository_installation(owner, name)
+            .await?
+            .into_inner();
+        Ok(installation.id)
+    }
+
+    pub async fn installation_token(&self, installation_id: u64) -> Result<InstallationToken> {
+        let token = self
+            .client()?
+            .apps()
+            .create_installation_access_token(installation_id)
+            .await?
+            .into_inner();
+        Ok(token)
+    }
+
+    /// Keep `client`, authenticated with `token`, supplied with fresh tokens of the installation
+    /// until the client is dropped
+    pub fn refresh(
+        self: Arc<Self>,
+        installation_id: u64,
+        client: &Arc<GithubClient>,
+        token: InstallationToken,
+    ) {
+        let client = Arc::downgrade(client);
+        tokio::spawn(self.refresh_loop(installation_id, client, token));
+    }
+
+    async fn refresh_loop(
+        self: Arc<Self>,
+        installation_id: u64,
+        client: Weak<GithubClient>,
+        mut token: InstallationToken,
+    ) {
+        loop {
+            let wait = token.expires_at.timestamp()
+                - chrono::Utc::now().timestamp()
+                - REFRESH_MARGIN_SECONDS;
+            tokio::time::delay_for(Duration::from_secs(wait.max(0) as u64)).await;
+
+            let client = match client.upgrade() {
+                Some(client) => client,
+                None => return,
+            };
+
+            match self.refresh_token(installation_id, &client).await {
+                Ok(fresh) => token = fresh,
+                Err(e) => {
+                    warn!(
+                        "unable to refresh token of installation {}: {}",
+                        installation_id, e
+                    );
+                    tokio::time::delay_for(REFRESH_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn refresh_token(
+        &self,
+        installation_id: u64,
+        client: &GithubClient,
+    ) -> Result<InstallationToken> {
+        let token = self.installation_token(installation_id).await?;
+        client.set_github_api_token(&token.token)?;
+        info!("refreshed token of installation {}", installation_id);
+        Ok(token)
+    }
+}
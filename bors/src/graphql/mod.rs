@@ -6,17 +6,38 @@
 //! [Github's v4 API Explorer](https://developer.github.com/v4/explorer/)
 //! [Github's v4 API Docs](https://developer.github.com/v4/)
 
-use crate::{state::PullRequestState, Result};
-use github::{client::Response, Client, NodeId, ReactionType};
+use crate::{config::GithubConfig, github_app::GithubApp, state::PullRequestState, Result};
+use github::{
+    client::{PaginationOptions, Response},
+    Client, NodeId, ReactionType,
+};
 use graphql_client::GraphQLQuery;
-use std::ops::Deref;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 mod query;
 
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
+/// How long the members of a team are cached for before they're fetched again
+const TEAM_MEMBERS_TTL: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Debug)]
-pub struct GithubClient(Client);
+pub struct GithubClient {
+    client: Client,
+
+    /// The lowercase logins of the members of each team, keyed by lowercase `org/team`, along
+    /// with when they were fetched
+    team_members: Mutex<HashMap<String, (Instant, HashSet<String>)>>,
+
+    /// The login of the app's bot user, if the client authenticates as an installation of a Github
+    /// App rather than as a user
+    app_login: Option<String>,
+}
 
 impl GithubClient {
     pub fn new(github_api_token: &str) -> Self {
@@ -25,7 +46,99 @@ impl GithubClient {
             .user_agent(USER_AGENT)
             .build()
             .unwrap();
-        Self(client)
+        Self {
+            client,
+            team_members: Mutex::new(HashMap::new()),
+            app_login: None,
+        }
+    }
+
+    /// A client of the API served at `base_url`, e.g. a mock of Github in tests
+    #[cfg(test)]
+    pub fn with_base_url(base_url: &str) -> Self {
+        let client = Client::builder()
+            .base_url(base_url)
+            .github_api_token("token")
+            .user_agent(USER_AGENT)
+            .build()
+            .unwrap();
+        Self {
+            client,
+            team_members: Mutex::new(HashMap::new()),
+            app_login: None,
+        }
+    }
+
+    /// A client authenticating as an installation of a Github App, which acts as `app_login`
+    pub fn for_installation(token: &str, app_login: String) -> Self {
+        Self {
+            app_login: Some(app_login),
+            ..Self::new(token)
+        }
+    }
+
+    pub fn app_login(&self) -> Option<&str> {
+        self.app_login.as_deref()
+    }
+
+    /// The lowercase logins of the members of `team`, given as `org/team`, including the members
+    /// of its child teams. Members are cached for a few minutes so that checking the permissions
+    /// of each command doesn't cost a request.
+    pub async fn team_members(&self, team: &str) -> Result<HashSet<String>> {
+        let key = team.to_lowercase();
+        if let Some((fetched, members)) = self.team_members.lock().unwrap().get(&key) {
+            if fetched.elapsed() < TEAM_MEMBERS_TTL {
+                return Ok(members.clone());
+            }
+        }
+
+        let mut parts = team.splitn(2, '/');
+        let (org, slug) = match (parts.next(), parts.next()) {
+            (Some(org), Some(slug)) => (org, slug),
+            _ => return Ok(HashSet::new()),
+        };
+
+        let mut members = HashSet::new();
+        let mut page = Some(1);
+        while let Some(current) = page {
+            let options = PaginationOptions {
+                page: Some(current),
+                per_page: Some(100),
+            };
+            let response = self
+                .client
+                .teams()
+                .list_members(org, slug, Some(options))
+                .await?;
+            page = response.pagination().next_page;
+
+            members.extend(
+                response
+                    .into_inner()
+                    .into_iter()
+                    .map(|user| user.login.to_lowercase()),
+            );
+        }
+
+        self.team_members
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), members.clone()));
+        Ok(members)
+    }
+
+    /// Check if `user` is a member of any of `teams`, each given as `org/team`
+    pub async fn is_team_member<'a, I>(&self, teams: I, user: &str) -> Result<bool>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let user = user.to_lowercase();
+        for team in teams {
+            if self.team_members(team).await?.contains(&user) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     pub async fn add_reaction(&self, id: &NodeId, reaction: ReactionType) -> Result<()> {
@@ -39,7 +152,7 @@ impl GithubClient {
             reaction: reaction.into(),
         });
 
-        let _: Response<ResponseData> = self.0.graphql().query(&q).await?;
+        let _: Response<ResponseData> = self.client.graphql().query(&q).await?;
 
         Ok(())
     }
@@ -61,7 +174,7 @@ impl GithubClient {
                 cursor: cursor.clone(),
             });
 
-            let response: ResponseData = self.0.graphql().query(&q).await?.into_inner();
+            let response: ResponseData = self.client.graphql().query(&q).await?.into_inner();
 
             let pull_requests = if let Some(repo) = response.repository {
                 repo.pull_requests
@@ -83,6 +196,32 @@ impl GithubClient {
         Ok(ret)
     }
 
+    /// Fetch the full state of a single PR, including its labels, reviews, and mergeability
+    pub async fn pull_request(
+        &self,
+        owner: &str,
+        name: &str,
+        number: u64,
+    ) -> Result<Option<PullRequestState>> {
+        use query::{
+            get_pull_request::{ResponseData, Variables},
+            GetPullRequest,
+        };
+
+        let q = GetPullRequest::build_query(Variables {
+            owner: owner.to_owned(),
+            name: name.to_owned(),
+            number: number as i64,
+        });
+
+        let response: ResponseData = self.client.graphql().query(&q).await?.into_inner();
+
+        Ok(response
+            .repository
+            .and_then(|r| r.pull_request)
+            .map(Into::into))
+    }
+
     pub async fn get_review_decision(&self, owner: &str, name: &str, number: u64) -> Result<bool> {
         use query::{
             get_review_decision::{PullRequestReviewDecision, ResponseData, Variables},
@@ -95,7 +234,7 @@ impl GithubClient {
             number: number as i64,
         });
 
-        let response: ResponseData = self.0.graphql().query(&q).await?.into_inner();
+        let response: ResponseData = self.client.graphql().query(&q).await?.into_inner();
 
         let d = response
             .repository
@@ -109,12 +248,187 @@ impl GithubClient {
 
         Ok(d)
     }
+
+    /// List the review threads of a PR which haven't been resolved
+    pub async fn unresolved_review_threads(
+        &self,
+        owner: &str,
+        name: &str,
+        number: u64,
+    ) -> Result<Vec<ReviewThread>> {
+        use query::{
+            list_review_threads::{ResponseData, Variables},
+            ListReviewThreads,
+        };
+
+        let mut ret = Vec::new();
+        let mut has_next_page = true;
+        let mut cursor = None;
+
+        while has_next_page {
+            let q = ListReviewThreads::build_query(Variables {
+                owner: owner.to_owned(),
+                name: name.to_owned(),
+                number: number as i64,
+                cursor: cursor.clone(),
+            });
+
+            let response: ResponseData = self.client.graphql().query(&q).await?.into_inner();
+
+            let threads = match response.repository.and_then(|r| r.pull_request) {
+                Some(pull_request) => pull_request.review_threads,
+                None => break,
+            };
+
+            has_next_page = threads.page_info.has_next_page;
+            cursor = threads.page_info.end_cursor;
+
+            let unresolved = threads
+                .nodes
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|thread| !thread.is_resolved)
+                .map(|thread| {
+                    let comment = thread
+                        .comments
+                        .nodes
+                        .and_then(|comments| comments.into_iter().flatten().next());
+                    ReviewThread {
+                        path: comment
+                            .as_ref()
+                            .map(|comment| comment.path.clone())
+                            .unwrap_or_default(),
+                        url: comment.map(|comment| comment.url).unwrap_or_default(),
+                    }
+                });
+            ret.extend(unresolved);
+        }
+
+        Ok(ret)
+    }
+
+    /// List the paths of all the files changed by a PR
+    pub async fn changed_files(&self, owner: &str, name: &str, number: u64) -> Result<Vec<String>> {
+        use query::{
+            list_changed_files::{ResponseData, Variables},
+            ListChangedFiles,
+        };
+
+        let mut ret = Vec::new();
+        let mut has_next_page = true;
+        let mut cursor = None;
+
+        while has_next_page {
+            let q = ListChangedFiles::build_query(Variables {
+                owner: owner.to_owned(),
+                name: name.to_owned(),
+                number: number as i64,
+                cursor: cursor.clone(),
+            });
+
+            let response: ResponseData = self.client.graphql().query(&q).await?.into_inner();
+
+            let files = match response
+                .repository
+                .and_then(|r| r.pull_request)
+                .and_then(|p| p.files)
+            {
+                Some(files) => files,
+                None => break,
+            };
+
+            has_next_page = files.page_info.has_next_page;
+            cursor = files.page_info.end_cursor;
+
+            ret.extend(
+                files
+                    .nodes
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|file| file.path),
+            );
+        }
+
+        Ok(ret)
+    }
+}
+
+/// The first comment of a review thread, identifying where the thread was started
+#[derive(Debug)]
+pub struct ReviewThread {
+    /// The file the thread was started on
+    pub path: String,
+    pub url: String,
+}
+
+/// Github clients scoped to each installation, each with its own token and rate limits, shared by
+/// all of the repos the installation serves
+#[derive(Debug)]
+pub struct GithubClients {
+    config: GithubConfig,
+
+    /// The Github App bors authenticates as, if it isn't using personal access tokens
+    app: Option<Arc<GithubApp>>,
+
+    /// Clients keyed by the owner their installation serves, or by "" for the default client
+    clients: HashMap<String, Arc<GithubClient>>,
+}
+
+impl GithubClients {
+    pub fn new(config: &GithubConfig) -> Result<Self> {
+        let app = match config.app() {
+            Some(app) => Some(Arc::new(GithubApp::new(app)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            app,
+            clients: HashMap::new(),
+        })
+    }
+
+    /// The client of the installation serving `owner`'s repos, of which `name` is one. When
+    /// authenticating as a Github App, the client's installation access token is refreshed in the
+    /// background before it expires.
+    pub async fn for_repo(&mut self, owner: &str, name: &str) -> Result<Arc<GithubClient>> {
+        let installation = self.config.installation(owner);
+        let token = installation.and_then(|installation| installation.github_api_token.as_ref());
+        let key = match (token, &self.app) {
+            (None, None) => "",
+            _ => owner,
+        };
+        if let Some(client) = self.clients.get(key) {
+            return Ok(client.clone());
+        }
+
+        let client = match (token, &self.app) {
+            (Some(token), _) => Arc::new(GithubClient::new(token)),
+            (None, Some(app)) => {
+                let installation_id = match installation.and_then(|i| i.installation_id) {
+                    Some(installation_id) => installation_id,
+                    None => app.installation_id(owner, name).await?,
+                };
+                let token = app.installation_token(installation_id).await?;
+                let login = app.login().await?;
+                let client = Arc::new(GithubClient::for_installation(&token.token, login));
+                app.clone().refresh(installation_id, &client, token);
+                client
+            }
+            (None, None) => Arc::new(GithubClient::new(&self.config.github_api_token)),
+        };
+
+        self.clients.insert(key.to_owned(), client.clone());
+        Ok(client)
+    }
 }
 
 impl Deref for GithubClient {
     type Target = Client;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.client
     }
 }
@@ -27,6 +27,7 @@ impl From<github::ReactionType> for add_reaction::ReactionContent {
 }
 
 type GitObjectID = github::Oid;
+type URI = String;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -59,6 +60,7 @@ impl From<list_pulls::ListPullsRepositoryPullRequestsNodes> for crate::state::Pu
             review_decision,
             maintainer_can_modify,
             mergeable,
+            reviews,
             labels,
             head_ref_name,
             head_ref_oid,
@@ -81,21 +83,28 @@ impl From<list_pulls::ListPullsRepositoryPullRequestsNodes> for crate::state::Pu
             })
             .collect();
 
-        let head_repo = if let Some(repo) = head_repository {
-            let mut iter = repo.name_with_owner.split('/');
-            match (iter.next(), iter.next()) {
-                (Some(owner), Some(name)) => Some(crate::state::Repo::new(owner, name)),
-                _ => None,
-            }
-        } else {
-            None
-        };
+        let approved_by = approved_by(
+            reviews
+                .into_iter()
+                .flat_map(|reviews| reviews.nodes.into_iter().flatten().flatten())
+                .map(|review| {
+                    (
+                        review.author.map(|a| a.login),
+                        matches!(review.state, list_pulls::PullRequestReviewState::APPROVED),
+                    )
+                }),
+        );
+
+        let head_repo =
+            head_repository.and_then(|repo| repo_from_name_with_owner(&repo.name_with_owner));
 
         let approved = match review_decision {
             Some(list_pulls::PullRequestReviewDecision::APPROVED) => true,
             _ => false,
         };
 
+        let depends_on = crate::state::parse_depends_on(&body);
+
         Self {
             number: number as u64,
             id: database_id.unwrap() as u64, // XXX ensure this is always populated
@@ -116,9 +125,28 @@ impl From<list_pulls::ListPullsRepositoryPullRequestsNodes> for crate::state::Pu
             labels,
             state: state.into(),
 
-            approved_by: std::collections::HashSet::new(),
+            approved_by,
             approved,
+            approver: None,
+            priority: 0,
+            retry: None,
+            next_attempt: None,
+            cooldown_until: None,
+            merge_strategy: None,
+            rollup: None,
+            depends_on,
+            landed_dependencies: Default::default(),
+            attempts: 0,
+            spurious_retries: 0,
+            superseded: None,
+            approved_oid: None,
+            reviewer: None,
+            delegate: None,
+            queued_at: None,
+            residency_alerted: false,
+            push_settling: false,
             status: crate::state::Status::InReview,
+            state_version: 0,
             project_card_id: None,
         }
     }
@@ -131,3 +159,177 @@ impl From<list_pulls::ListPullsRepositoryPullRequestsNodes> for crate::state::Pu
     response_derives = "Debug"
 )]
 pub struct GetReviewDecision;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/github-schema.graphql",
+    query_path = "src/graphql/list_review_threads.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListReviewThreads;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/github-schema.graphql",
+    query_path = "src/graphql/list_changed_files.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListChangedFiles;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/github-schema.graphql",
+    query_path = "src/graphql/get_pull_request.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetPullRequest;
+
+impl From<get_pull_request::PullRequestState> for github::PullRequestState {
+    fn from(state: get_pull_request::PullRequestState) -> Self {
+        match state {
+            get_pull_request::PullRequestState::OPEN => github::PullRequestState::Open,
+            get_pull_request::PullRequestState::CLOSED => github::PullRequestState::Closed,
+            get_pull_request::PullRequestState::MERGED => github::PullRequestState::Merged,
+
+            // Treat Other states as being closed
+            get_pull_request::PullRequestState::Other(_) => github::PullRequestState::Closed,
+        }
+    }
+}
+
+impl From<get_pull_request::GetPullRequestRepositoryPullRequest>
+    for crate::state::PullRequestState
+{
+    fn from(pull: get_pull_request::GetPullRequestRepositoryPullRequest) -> Self {
+        let get_pull_request::GetPullRequestRepositoryPullRequest {
+            number,
+            database_id,
+            author,
+            is_draft,
+            review_decision,
+            maintainer_can_modify,
+            mergeable,
+            reviews,
+            labels,
+            head_ref_name,
+            head_ref_oid,
+            body,
+            base_ref_name,
+            base_ref_oid,
+            title,
+            state,
+            head_repository,
+            ..
+        } = pull;
+
+        let labels = labels
+            .into_iter()
+            .flat_map(|nodes| {
+                nodes
+                    .nodes
+                    .into_iter()
+                    .flat_map(|labels| labels.into_iter().flat_map(|label| label.map(|l| l.name)))
+            })
+            .collect();
+
+        let approved_by = approved_by(
+            reviews
+                .into_iter()
+                .flat_map(|reviews| reviews.nodes.into_iter().flatten().flatten())
+                .map(|review| {
+                    (
+                        review.author.map(|a| a.login),
+                        matches!(
+                            review.state,
+                            get_pull_request::PullRequestReviewState::APPROVED
+                        ),
+                    )
+                }),
+        );
+
+        let head_repo =
+            head_repository.and_then(|repo| repo_from_name_with_owner(&repo.name_with_owner));
+
+        let approved = matches!(
+            review_decision,
+            Some(get_pull_request::PullRequestReviewDecision::APPROVED)
+        );
+
+        let depends_on = crate::state::parse_depends_on(&body);
+
+        Self {
+            number: number as u64,
+            id: database_id.unwrap() as u64, // XXX ensure this is always populated
+            author: author.map(|a| a.login),
+            title,
+            body,
+
+            head_ref_name,
+            head_ref_oid,
+            head_repo,
+
+            base_ref_name,
+            base_ref_oid,
+
+            is_draft,
+            maintainer_can_modify,
+            mergeable: matches!(mergeable, get_pull_request::MergeableState::MERGEABLE),
+            labels,
+            state: state.into(),
+
+            approved_by,
+            approved,
+            approver: None,
+            priority: 0,
+            retry: None,
+            next_attempt: None,
+            cooldown_until: None,
+            merge_strategy: None,
+            rollup: None,
+            depends_on,
+            landed_dependencies: Default::default(),
+            attempts: 0,
+            spurious_retries: 0,
+            superseded: None,
+            approved_oid: None,
+            reviewer: None,
+            delegate: None,
+            queued_at: None,
+            residency_alerted: false,
+            push_settling: false,
+            status: crate::state::Status::InReview,
+            state_version: 0,
+            project_card_id: None,
+        }
+    }
+}
+
+/// Parse a repository's `<owner>/<name>` into a `Repo`
+fn repo_from_name_with_owner(name_with_owner: &str) -> Option<crate::state::Repo> {
+    let mut iter = name_with_owner.split('/');
+    match (iter.next(), iter.next()) {
+        (Some(owner), Some(name)) => Some(crate::state::Repo::new(owner, name)),
+        _ => None,
+    }
+}
+
+/// Determine the set of users whose most recent review approved a PR, given a chronologically
+/// ordered list of `(author, approved)` pairs
+fn approved_by<I>(reviews: I) -> std::collections::HashSet<String>
+where
+    I: IntoIterator<Item = (Option<String>, bool)>,
+{
+    let mut approved_by = std::collections::HashSet::new();
+
+    for (author, approved) in reviews {
+        if let Some(author) = author {
+            if approved {
+                approved_by.insert(author);
+            } else {
+                approved_by.remove(&author);
+            }
+        }
+    }
+
+    approved_by
+}
@@ -0,0 +1,244 @@
+//! Known issues tracking recurring failures, e.g. of flaky infrastructure. Failed checks are
+//! fingerprinted and matched against each repo's known issues, registered with `bors known-issue`,
+//! so that failure comments can link the issue tracking them.
+
+use crate::{git, store::Store, Config, Result};
+use anyhow::anyhow;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use structopt::StructOpt;
+
+const KNOWN_ISSUES_STORE: &str = "known-issues.json";
+
+/// Maximum number of characters of a check's output included in its fingerprint
+const MAX_FINGERPRINT_EXCERPT: usize = 200;
+
+#[derive(StructOpt)]
+pub struct KnownIssueOptions {
+    /// repo, as <owner>/<name>, the known issues belong to
+    repo: String,
+
+    #[structopt(subcommand)]
+    action: KnownIssueAction,
+}
+
+#[derive(StructOpt)]
+enum KnownIssueAction {
+    #[structopt(name = "add")]
+    /// Register a known issue, matched against the fingerprints of failed checks
+    Add {
+        /// regex matched against fingerprints, e.g. "^ci/test: .*connection reset by peer"
+        pattern: String,
+
+        /// url of the issue tracking the failure
+        issue_url: String,
+
+        #[structopt(long)]
+        /// retry failures matching the issue, like spurious failures, instead of reporting them
+        retry: bool,
+    },
+
+    #[structopt(name = "remove")]
+    /// Remove the known issue tracked by an issue url
+    Remove {
+        /// url of the issue tracking the failure
+        issue_url: String,
+    },
+
+    #[structopt(name = "list")]
+    /// List the repo's known issues
+    List,
+}
+
+pub fn run_known_issue(config: Config, options: &KnownIssueOptions) -> Result<()> {
+    let repo = config
+        .repo
+        .iter()
+        .find(|repo| format!("{}/{}", repo.owner(), repo.name()) == options.repo)
+        .ok_or_else(|| anyhow!("no config for repo '{}'", options.repo))?;
+    let state_dir = git::state_dir(repo.repo())?;
+
+    let mut known_issues = KnownIssues::load(&state_dir)?;
+    match &options.action {
+        KnownIssueAction::Add {
+            pattern,
+            issue_url,
+            retry,
+        } => {
+            Regex::new(pattern)?;
+            known_issues
+                .issues
+                .retain(|issue| &issue.issue_url != issue_url);
+            known_issues.issues.push(KnownIssue {
+                pattern: pattern.clone(),
+                issue_url: issue_url.clone(),
+                retry: *retry,
+            });
+            known_issues.save(&state_dir)?;
+        }
+        KnownIssueAction::Remove { issue_url } => {
+            let before = known_issues.issues.len();
+            known_issues
+                .issues
+                .retain(|issue| &issue.issue_url != issue_url);
+            if known_issues.issues.len() == before {
+                return Err(anyhow!("no known issue is tracked by {}", issue_url));
+            }
+            known_issues.save(&state_dir)?;
+        }
+        KnownIssueAction::List => {
+            for issue in &known_issues.issues {
+                let retry = if issue.retry { " (retried)" } else { "" };
+                println!("{} {}{}", issue.issue_url, issue.pattern, retry);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KnownIssue {
+    /// Regex matched against the fingerprints of failed checks
+    pattern: String,
+
+    /// Url of the issue tracking the failure
+    pub issue_url: String,
+
+    /// Indicates if failures matching the issue are retried, like spurious failures
+    #[serde(default)]
+    pub retry: bool,
+}
+
+/// The known issues registered for a repo, stored as a JSON array
+#[derive(Debug, Default)]
+pub struct KnownIssues {
+    issues: Vec<KnownIssue>,
+}
+
+impl KnownIssues {
+    fn store(state_dir: &Path) -> Store {
+        Store::new(state_dir, KNOWN_ISSUES_STORE)
+    }
+
+    /// Read the known issues kept in `state_dir`, the repo's state directory. They're read afresh
+    /// each time a check fails so that changes take effect without restarting bors.
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            issues: Self::store(state_dir).load()?,
+        })
+    }
+
+    fn save(&self, state_dir: &Path) -> Result<()> {
+        Self::store(state_dir).save(&self.issues)
+    }
+
+    /// Find the first known issue matching a failure's fingerprint
+    pub fn find(&self, fingerprint: &str) -> Option<&KnownIssue> {
+        self.issues.iter().find(|issue| {
+            Regex::new(&issue.pattern)
+                .map(|pattern| pattern.is_match(fingerprint))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Fingerprint a failure by the name of its check and a normalized excerpt of its output, in which
+/// details which vary between occurrences, like numbers and commit hashes, are masked
+pub fn fingerprint<'a>(name: &str, output: impl Iterator<Item = &'a str>) -> String {
+    let mut excerpt = output
+        .flat_map(str::split_whitespace)
+        .map(normalize_word)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some((idx, _)) = excerpt.char_indices().nth(MAX_FINGERPRINT_EXCERPT) {
+        excerpt.truncate(idx);
+    }
+
+    format!("{}: {}", name, excerpt)
+}
+
+/// Mask hashes, and runs of digits, in a word of a check's output
+fn normalize_word(word: &str) -> String {
+    let is_hash = word.len() >= 7
+        && word.chars().all(|c| c.is_ascii_hexdigit())
+        && word.chars().any(|c| c.is_ascii_digit());
+    if is_hash {
+        return "<hash>".to_owned();
+    }
+
+    let mut normalized = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        if !c.is_ascii_digit() {
+            normalized.push(c);
+        } else if !normalized.ends_with('N') {
+            normalized.push('N');
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fingerprint, KnownIssue, KnownIssues};
+
+    #[test]
+    fn fingerprints_mask_varying_details() {
+        let first = fingerprint(
+            "ci/test",
+            vec![
+                "Build 1234 failed",
+                "  error: connection to 10.0.0.12 reset\n",
+            ]
+            .into_iter(),
+        );
+        let second = fingerprint(
+            "ci/test",
+            vec!["Build 98 failed", "error: connection to 10.0.3.7 reset"].into_iter(),
+        );
+
+        assert_eq!(
+            first,
+            "ci/test: build N failed error: connection to N.N.N.N reset"
+        );
+        assert_eq!(first, second);
+        assert_eq!(
+            fingerprint("lint", vec!["bad commit 3f2c9ab01"].into_iter()),
+            "lint: bad commit <hash>"
+        );
+    }
+
+    #[test]
+    fn matching_known_issues() {
+        let known_issues = KnownIssues {
+            issues: vec![
+                KnownIssue {
+                    pattern: "^ci/test: .*connection to N.N.N.N reset".to_owned(),
+                    issue_url: "https://github.com/o/r/issues/1".to_owned(),
+                    retry: true,
+                },
+                KnownIssue {
+                    pattern: "out of disk".to_owned(),
+                    issue_url: "https://github.com/o/r/issues/2".to_owned(),
+                    retry: false,
+                },
+            ],
+        };
+
+        let issue = |fingerprint: &str| {
+            known_issues
+                .find(fingerprint)
+                .map(|issue| issue.issue_url.as_str())
+        };
+        assert_eq!(
+            issue("ci/test: build N failed error: connection to N.N.N.N reset"),
+            Some("https://github.com/o/r/issues/1")
+        );
+        assert_eq!(issue("ci/lint: error: connection to N.N.N.N reset"), None);
+        assert_eq!(
+            issue("ci/test: runner out of disk space"),
+            Some("https://github.com/o/r/issues/2")
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Restricting when PRs may land to configured days and hours
+
+use crate::config::LandWindowConfig;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+
+/// Check if PRs may land at `now`
+pub fn is_open(window: &LandWindowConfig, now: DateTime<Utc>) -> bool {
+    let time = now.time();
+    window.allows_day(now.weekday())
+        && window.start() <= time
+        && window.end().map(|end| time < end).unwrap_or(true)
+}
+
+/// How long until the land window next opens after `now`, or `None` if it is currently open
+pub fn time_until_open(
+    window: &LandWindowConfig,
+    now: DateTime<Utc>,
+) -> Option<std::time::Duration> {
+    if is_open(window, now) {
+        return None;
+    }
+
+    // Windows open on a minute boundary, so check each minute over the following week. A window
+    // which never opens, e.g. because its end is before its start, is checked again in a day.
+    let minute = now.with_second(0)?.with_nanosecond(0)?;
+    let opens_at = (1..=8 * 24 * 60)
+        .map(|n| minute + Duration::minutes(n))
+        .find(|time| is_open(window, *time))
+        .unwrap_or_else(|| now + Duration::days(1));
+
+    (opens_at - now).to_std().ok()
+}
+
+/// Format a wait, rounded up to the minute, e.g. "2h05m"
+pub fn format_wait(wait: std::time::Duration) -> String {
+    let minutes = (wait.as_secs() + 59) / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_wait, time_until_open};
+    use crate::config::LandWindowConfig;
+    use chrono::{DateTime, Utc};
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn minutes_until_open(window: &LandWindowConfig, time: &str) -> Option<u64> {
+        time_until_open(window, at(time)).map(|wait| wait.as_secs() / 60)
+    }
+
+    #[test]
+    fn weekday_business_hours() {
+        let window: LandWindowConfig = toml::from_str(
+            r#"
+            days = ["mon", "tue", "wed", "thu", "fri"]
+            start = "09:00"
+            end = "18:00"
+            "#,
+        )
+        .unwrap();
+
+        // 2020-06-01 is a Monday
+        assert_eq!(minutes_until_open(&window, "2020-06-01T09:00:00Z"), None);
+        assert_eq!(minutes_until_open(&window, "2020-06-01T17:59:59Z"), None);
+        assert_eq!(
+            minutes_until_open(&window, "2020-06-01T08:30:00Z"),
+            Some(30)
+        );
+        assert_eq!(
+            minutes_until_open(&window, "2020-06-01T18:00:00Z"),
+            Some(15 * 60)
+        );
+        // Friday evening waits until Monday morning
+        assert_eq!(
+            minutes_until_open(&window, "2020-06-05T18:00:00Z"),
+            Some(63 * 60)
+        );
+    }
+
+    #[test]
+    fn always_open_by_default() {
+        let window: LandWindowConfig = toml::from_str("").unwrap();
+        assert_eq!(minutes_until_open(&window, "2020-06-06T03:00:00Z"), None);
+    }
+
+    #[test]
+    fn invalid_times() {
+        assert!(toml::from_str::<LandWindowConfig>(r#"start = "9am""#).is_err());
+        assert!(toml::from_str::<LandWindowConfig>(r#"days = ["someday"]"#).is_err());
+    }
+
+    #[test]
+    fn waits() {
+        assert_eq!(format_wait(std::time::Duration::from_secs(30)), "1m");
+        assert_eq!(
+            format_wait(std::time::Duration::from_secs(3 * 60 * 60)),
+            "3h00m"
+        );
+        assert_eq!(
+            format_wait(std::time::Duration::from_secs(125 * 60)),
+            "2h05m"
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Per-repo leases used to hand off between bors instances, e.g. during a blue/green deployment
+//!
+//! Only the instance holding a repo's lease mutates that repo. A new instance takes over a lease by
+//! overwriting it and then waits for the previous holder, which notices the loss the next time it
+//! tries to renew the lease, to stop and write out its in-flight queue state for the new instance
+//! to pick up.
+//!
+//! Each read-modify-write of a lease happens while holding its lock file, which is created
+//! exclusively, so that instances racing to take over or renew a lease can't both believe they
+//! hold it.
+
+use crate::{
+    config::{LeaseConfig, MergeStrategy, Rollup},
+    state::{AttemptOverrides, PullRequestState, Repo, Status, TestResult},
+    store::write_atomic,
+    Result,
+};
+use anyhow::anyhow;
+use github::Oid;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{self, OpenOptions},
+    io,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct LeaseRecord {
+    holder: String,
+    /// Unix timestamp, in seconds, after which the lease is no longer valid
+    expires_at: u64,
+}
+
+/// In-flight queue state handed from the previous holder of a lease to the next
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Handoff {
+    /// The instance which wrote this handoff
+    pub from: String,
+    pub queued: Vec<HandoffEntry>,
+    /// The batch at the head of the queue which was being tested, in order
+    pub testing: Vec<HandoffEntry>,
+    /// The priority threshold set via the `treeclosed` command, if the tree was closed
+    #[serde(default)]
+    pub tree_closed: Option<u32>,
+    /// Users each PR's approval rights are delegated to, keyed by PR, including PRs which aren't
+    /// queued
+    #[serde(default)]
+    pub delegates: HashMap<u64, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandoffEntry {
+    pub number: u64,
+    /// The PR's head at the time of the handoff. Entries are dropped if the PR has since changed.
+    pub head_oid: Oid,
+    pub priority: u32,
+    pub approver: Option<String>,
+    pub merge_oid: Option<Oid>,
+    #[serde(default)]
+    pub test_results: HashMap<String, TestResult>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub spurious_retries: u32,
+    #[serde(default)]
+    pub merge_strategy: Option<MergeStrategy>,
+    #[serde(default)]
+    pub rollup: Option<Rollup>,
+    #[serde(default)]
+    pub depends_on: BTreeSet<u64>,
+    #[serde(default)]
+    pub superseded: Option<u64>,
+    #[serde(default)]
+    pub queued_at: Option<SystemTime>,
+    #[serde(default)]
+    pub residency_alerted: bool,
+    #[serde(default)]
+    pub approved_oid: Option<Oid>,
+    #[serde(default)]
+    pub reviewer: Option<String>,
+    #[serde(default)]
+    pub overrides: AttemptOverrides,
+    #[serde(default)]
+    pub next_attempt: Option<AttemptOverrides>,
+}
+
+impl HandoffEntry {
+    pub fn from_pull(pull: &PullRequestState) -> Self {
+        let (merge_oid, test_results, overrides) = match &pull.status {
+            Status::Testing {
+                merge_oid,
+                test_results,
+                overrides,
+                ..
+            } => (
+                Some(merge_oid.clone()),
+                test_results.clone(),
+                overrides.clone(),
+            ),
+            _ => (None, HashMap::new(), AttemptOverrides::default()),
+        };
+
+        Self {
+            number: pull.number,
+            head_oid: pull.head_ref_oid.clone(),
+            priority: pull.priority,
+            approver: pull.approver.clone(),
+            merge_oid,
+            test_results,
+            attempts: pull.attempts,
+            spurious_retries: pull.spurious_retries,
+            merge_strategy: pull.merge_strategy,
+            rollup: pull.rollup,
+            depends_on: pull.depends_on.clone(),
+            superseded: pull.superseded,
+            queued_at: pull.queued_at,
+            residency_alerted: pull.residency_alerted,
+            approved_oid: pull.approved_oid.clone(),
+            reviewer: pull.reviewer.clone(),
+            overrides,
+            next_attempt: pull.next_attempt.clone(),
+        }
+    }
+
+    /// An entry for a PR which is to be returned to the queue, discarding any test state
+    pub fn requeued(pull: &PullRequestState) -> Self {
+        Self {
+            merge_oid: None,
+            test_results: HashMap::new(),
+            ..Self::from_pull(pull)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Lease {
+    holder: String,
+    duration: Duration,
+    lease_path: PathBuf,
+    lock_path: PathBuf,
+    handoff_path: PathBuf,
+}
+
+/// How long to wait for another instance to release a lease's lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Lease {
+    pub fn new(config: &LeaseConfig, repo: &Repo) -> Self {
+        let mut directory = config.directory.clone();
+        directory.push(repo.owner());
+
+        Self {
+            holder: config.holder.clone(),
+            duration: config.duration(),
+            lease_path: directory.join(format!("{}.lease", repo.name())),
+            lock_path: directory.join(format!("{}.lease.lock", repo.name())),
+            handoff_path: directory.join(format!("{}.handoff", repo.name())),
+        }
+    }
+
+    /// How often the lease should be renewed
+    pub fn renew_interval(&self) -> Duration {
+        self.duration / 3
+    }
+
+    /// Take over the lease, returning the previous holder if it may still be active
+    pub fn acquire(&self) -> Result<Option<String>> {
+        let previous = self.locked(|| {
+            let previous = self
+                .read()?
+                .filter(|record| record.holder != self.holder && record.expires_at > unix_now());
+
+            // Remove any stale handoff so that it isn't mistaken for the one the previous holder
+            // is about to write
+            match fs::remove_file(&self.handoff_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+
+            self.write()?;
+            Ok(previous)
+        })?;
+        info!("acquired lease {}", self.lease_path.display());
+
+        Ok(previous.map(|record| record.holder))
+    }
+
+    /// Renew the lease, returning `false` if it has been taken over by another instance
+    pub fn renew(&self) -> Result<bool> {
+        self.locked(|| match self.read()? {
+            Some(record) if record.holder != self.holder => Ok(false),
+            _ => {
+                self.write()?;
+                Ok(true)
+            }
+        })
+    }
+
+    /// Run `f` while holding the lease's lock. A lock left behind by an instance which stopped
+    /// while holding it is broken once it's older than the lease itself.
+    fn locked<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if let Some(parent) = self.lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&self.lock_path)
+            {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            let age = fs::metadata(&self.lock_path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if age.map_or(false, |age| age > self.duration) {
+                warn!("breaking stale lock {}", self.lock_path.display());
+                match fs::remove_file(&self.lock_path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => continue,
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "timed out waiting for lock {}",
+                    self.lock_path.display()
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let result = f();
+        fs::remove_file(&self.lock_path)?;
+        result
+    }
+
+    /// Wait for `previous` to hand off its in-flight state, giving up once its lease would have
+    /// expired
+    pub async fn wait_for_handoff(&self, previous: &str) -> Result<Option<Handoff>> {
+        let deadline = Instant::now() + self.duration;
+
+        loop {
+            if self.handoff_path.exists() {
+                let handoff: Handoff =
+                    serde_json::from_str(&fs::read_to_string(&self.handoff_path)?)?;
+                if handoff.from == previous {
+                    fs::remove_file(&self.handoff_path)?;
+                    return Ok(Some(handoff));
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+
+            tokio::time::delay_for(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub fn write_handoff(
+        &self,
+        queued: Vec<HandoffEntry>,
+        testing: Vec<HandoffEntry>,
+        tree_closed: Option<u32>,
+        delegates: HashMap<u64, String>,
+    ) -> Result<()> {
+        let handoff = Handoff {
+            from: self.holder.clone(),
+            queued,
+            testing,
+            tree_closed,
+            delegates,
+        };
+
+        write_atomic(&self.handoff_path, &serde_json::to_string(&handoff)?)
+    }
+
+    fn read(&self) -> Result<Option<LeaseRecord>> {
+        match fs::read_to_string(&self.lease_path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        let record = LeaseRecord {
+            holder: self.holder.clone(),
+            expires_at: unix_now() + self.duration.as_secs(),
+        };
+
+        write_atomic(&self.lease_path, &serde_json::to_string(&record)?)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lease;
+    use std::{fs, path::Path, time::Duration};
+
+    fn lease(directory: &Path, holder: &str) -> Lease {
+        Lease {
+            holder: holder.to_owned(),
+            duration: Duration::from_secs(60),
+            lease_path: directory.join("repo.lease"),
+            lock_path: directory.join("repo.lease.lock"),
+            handoff_path: directory.join("repo.handoff"),
+        }
+    }
+
+    #[test]
+    fn takeover() {
+        let directory = std::env::temp_dir().join(format!("bors-lease-{}", std::process::id()));
+        let blue = lease(&directory, "blue");
+        let green = lease(&directory, "green");
+
+        assert_eq!(blue.acquire().unwrap(), None);
+        assert!(blue.renew().unwrap());
+
+        assert_eq!(green.acquire().unwrap(), Some("blue".to_owned()));
+        assert!(!blue.renew().unwrap());
+        assert!(green.renew().unwrap());
+        assert!(!green.lock_path.exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,14 +1,46 @@
+mod approvals;
+mod audit;
+mod auto_approve;
+mod autolabel;
+mod check_archive;
+mod check_durations;
+mod codeowners;
 mod command;
+mod comment;
+mod commit_lint;
+mod commit_message;
 mod config;
+mod contributors;
+mod deliveries;
+mod description;
+mod doctor;
+mod enroll;
 mod event_processor;
+mod explain;
 mod git;
+mod github_app;
 mod graphql;
+mod known_issues;
+mod land_window;
+mod lease;
+mod pr_template;
 mod project_board;
+mod protected_paths;
 mod queue;
+mod selfcheck;
 mod service;
+mod simulate;
 mod state;
+mod store;
+mod stream;
+mod try_build;
+mod upstream;
 
 pub use anyhow::{Error, Result};
 pub use config::Config;
+pub use doctor::{run_doctor, DoctorOptions};
+pub use explain::{run_explain, ExplainOptions};
+pub use known_issues::{run_known_issue, KnownIssueOptions};
 pub use probot::{Server, ServerBuilder, Service};
 pub use service::{run_serve, ServeOptions};
+pub use simulate::{run_simulate, SimulateOptions};
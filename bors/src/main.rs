@@ -1,4 +1,7 @@
-use bors::{run_serve, Config, Result, ServeOptions};
+use bors::{
+    run_doctor, run_explain, run_known_issue, run_serve, run_simulate, Config, DoctorOptions,
+    ExplainOptions, KnownIssueOptions, Result, ServeOptions, SimulateOptions,
+};
 use log::info;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -18,6 +21,22 @@ enum Command {
     #[structopt(name = "serve")]
     /// Run the server
     Serve(ServeOptions),
+
+    #[structopt(name = "simulate")]
+    /// Simulate the merge queue offline, applying a scripted sequence of events to a fixture
+    Simulate(SimulateOptions),
+
+    #[structopt(name = "explain")]
+    /// Explain how a PR got to its current state from the repo's audit log
+    Explain(ExplainOptions),
+
+    #[structopt(name = "known-issue")]
+    /// Manage the known issues failed checks are matched against
+    KnownIssue(KnownIssueOptions),
+
+    #[structopt(name = "doctor")]
+    /// Check that bors has the credentials, access and webhooks it needs to serve each repo
+    Doctor(DoctorOptions),
 }
 
 #[tokio::main]
@@ -32,6 +51,10 @@ async fn main() -> Result<()> {
     let config = Config::from_file(&opts.config)?;
 
     match &opts.command {
-        Command::Serve(options) => run_serve(config, options).await,
+        Command::Serve(options) => run_serve(config, &opts.config, options).await,
+        Command::Simulate(options) => run_simulate(config, options),
+        Command::Explain(options) => run_explain(config, options),
+        Command::KnownIssue(options) => run_known_issue(config, options),
+        Command::Doctor(options) => run_doctor(config, options).await,
     }
 }
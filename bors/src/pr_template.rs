@@ -0,0 +1,129 @@
+//! Checks that a PR's title and description contain what the repo requires of them, e.g. a "Test
+//! Plan" section, before the PR can be queued for landing
+
+use crate::config::PrTemplateConfig;
+
+/// What's missing from a PR's title and description, each phrased to follow "is missing"
+pub fn missing(config: &PrTemplateConfig, title: &str, body: &str) -> Vec<String> {
+    let mut missing = Vec::new();
+
+    if let Some(pattern) = config.title_pattern() {
+        if !pattern.is_match(title) {
+            missing.push(format!("a title matching `{}`", pattern.as_str()));
+        }
+    }
+
+    let body = strip_comments(&body.replace("\r\n", "\n"));
+    for heading in config.required_sections() {
+        match section(&body, heading) {
+            None => missing.push(format!("a \"{}\" section", heading)),
+            Some(content) if content.trim().is_empty() => {
+                missing.push(format!("content in its \"{}\" section", heading))
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (name, pattern) in config.required_patterns() {
+        if !pattern.is_match(&body) {
+            missing.push(name.to_owned());
+        }
+    }
+
+    missing
+}
+
+/// The content of the section of a markdown document under `heading`, up to the next heading
+fn section<'a>(body: &'a str, heading: &str) -> Option<&'a str> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let text = heading_text(line);
+        match (start, text) {
+            (None, Some(text)) if text.eq_ignore_ascii_case(heading) => {
+                start = Some(offset + line.len())
+            }
+            (Some(start), Some(_)) => return Some(&body[start..offset]),
+            _ => {}
+        }
+        offset += line.len();
+    }
+    start.map(|start| &body[start..])
+}
+
+/// The text of a markdown heading, e.g. "Test Plan" for "## Test Plan:", if `line` is one
+fn heading_text(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if !line.starts_with('#') {
+        return None;
+    }
+
+    Some(
+        line.trim_start_matches('#')
+            .trim_end_matches('#')
+            .trim()
+            .trim_end_matches(':')
+            .trim(),
+    )
+}
+
+/// Remove HTML comments, e.g. the instructions left in a PR template
+fn strip_comments(body: &str) -> String {
+    let mut stripped = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("<!--") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + "-->".len()..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+#[cfg(test)]
+mod test {
+    use super::{missing, section};
+    use crate::config::PrTemplateConfig;
+
+    #[test]
+    fn sections() {
+        let body = "Intro\n\n## Summary\nFixes the parser\n\n### Test Plan:\nRan it\n# Notes\n";
+
+        assert_eq!(section(body, "summary"), Some("Fixes the parser\n\n"));
+        assert_eq!(section(body, "Test Plan"), Some("Ran it\n"));
+        assert_eq!(section(body, "Notes"), Some(""));
+        assert_eq!(section(body, "Risks"), None);
+    }
+
+    #[test]
+    fn missing_items() {
+        let config: PrTemplateConfig = toml::from_str(
+            r#"
+            required-sections = ["Test Plan", "Summary"]
+            title-pattern = "^(fix|feat): "
+
+            [required-patterns]
+            "a reference to an issue" = "(?i)\\bfixes #\\d+"
+            "#,
+        )
+        .unwrap();
+
+        let body =
+            "## Summary\r\nFixes #12\r\n\r\n## Test Plan\r\n<!-- How was this tested? -->\r\n";
+        assert_eq!(
+            missing(&config, "Parser fixes", body),
+            vec![
+                "a title matching `^(fix|feat): `",
+                "content in its \"Test Plan\" section",
+            ]
+        );
+
+        assert_eq!(
+            missing(&config, "fix: parser", "## Test Plan\nRan it"),
+            vec!["a \"Summary\" section", "a reference to an issue"]
+        );
+        assert!(missing(&config, "fix: parser", &format!("{}Ran it", body)).is_empty());
+    }
+}
@@ -6,6 +6,7 @@ const PROJECT_BOARD_NAME: &str = "bors";
 const REVIEW_COLUMN_NAME: &str = "In Review";
 const QUEUED_COLUMN_NAME: &str = "Queued";
 const TESTING_COLUMN_NAME: &str = "Testing";
+const FAILED_COLUMN_NAME: &str = "Failed";
 
 #[derive(Debug)]
 pub struct ProjectBoard {
@@ -13,6 +14,7 @@ pub struct ProjectBoard {
     review_column: ProjectColumn,
     queued_column: ProjectColumn,
     testing_column: ProjectColumn,
+    failed_column: ProjectColumn,
 }
 
 impl ProjectBoard {
@@ -56,6 +58,18 @@ impl ProjectBoard {
         Ok(())
     }
 
+    pub async fn move_to_failed(
+        &self,
+        github: &GithubClient,
+        pull: &PullRequestState,
+    ) -> Result<()> {
+        if let Some(card_id) = pull.project_card_id {
+            Self::move_card_to_column(github, card_id, self.failed_column.id).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn create_card(
         &self,
         github: &GithubClient,
@@ -98,15 +112,14 @@ impl ProjectBoard {
     ) -> Result<Self> {
         let board = Self::create_or_get_project_board(github, config).await?;
 
-        let (review_column, queued_column, testing_column) =
+        let (review_column, queued_column, testing_column, failed_column) =
             Self::create_or_get_columns(github, board.id).await?;
 
         Self::init_project_cards(
             github,
             open_pulls,
             review_column.id,
-            queued_column.id,
-            testing_column.id,
+            &[queued_column.id, testing_column.id, failed_column.id],
         )
         .await?;
 
@@ -115,6 +128,7 @@ impl ProjectBoard {
             review_column,
             queued_column,
             testing_column,
+            failed_column,
         })
     }
 
@@ -155,10 +169,12 @@ impl ProjectBoard {
         github::ProjectColumn,
         github::ProjectColumn,
         github::ProjectColumn,
+        github::ProjectColumn,
     )> {
         let mut review_column = None;
         let mut queued_column = None;
         let mut testing_column = None;
+        let mut failed_column = None;
 
         for column in github
             .projects()
@@ -170,6 +186,7 @@ impl ProjectBoard {
                 "In Review" => review_column = Some(column),
                 "Queued" => queued_column = Some(column),
                 "Testing" => testing_column = Some(column),
+                "Failed" => failed_column = Some(column),
                 // Delete columns which don't match
                 _ => {
                     github.projects().delete_column(column.id).await?;
@@ -187,7 +204,11 @@ impl ProjectBoard {
             Self::unwrap_or_create_column(testing_column, TESTING_COLUMN_NAME, project_id, github)
                 .await?;
 
-        Ok((review_column, queued_column, testing_column))
+        let failed_column =
+            Self::unwrap_or_create_column(failed_column, FAILED_COLUMN_NAME, project_id, github)
+                .await?;
+
+        Ok((review_column, queued_column, testing_column, failed_column))
     }
 
     async fn unwrap_or_create_column(
@@ -213,24 +234,20 @@ impl ProjectBoard {
         github: &GithubClient,
         open_pulls: &mut HashMap<u64, PullRequestState>,
         review_column_id: u64,
-        queued_column_id: u64,
-        testing_column_id: u64,
+        other_column_ids: &[u64],
     ) -> Result<()> {
         Self::assign_or_delete_cards_in_column(github, open_pulls, review_column_id, None).await?;
-        Self::assign_or_delete_cards_in_column(
-            github,
-            open_pulls,
-            queued_column_id,
-            Some(review_column_id),
-        )
-        .await?;
-        Self::assign_or_delete_cards_in_column(
-            github,
-            open_pulls,
-            testing_column_id,
-            Some(review_column_id),
-        )
-        .await?;
+
+        // All PRs start out in review, so cards in any other column are moved back to review
+        for column_id in other_column_ids {
+            Self::assign_or_delete_cards_in_column(
+                github,
+                open_pulls,
+                *column_id,
+                Some(review_column_id),
+            )
+            .await?;
+        }
 
         // Create cards for remaining PRs
         for (_n, pull) in open_pulls.iter_mut() {
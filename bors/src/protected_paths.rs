@@ -0,0 +1,164 @@
+//! Sensitive files, e.g. CI config, which need more approval than usual, from a particular team or
+//! from several users, before a PR changing them can be queued for landing
+
+use crate::{
+    codeowners::{approvers, pattern_regex},
+    config::{ProtectedPathsConfig, RepoConfig},
+    graphql::GithubClient,
+    state::PullRequestState,
+    Result,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The approvals `pull` lacks for the protected files it changes, given that it's queued by
+/// `approver`, each phrased to follow "needs"
+pub async fn missing_approvals(
+    config: &RepoConfig,
+    github: &GithubClient,
+    pull: &PullRequestState,
+    approver: &str,
+) -> Result<Vec<String>> {
+    if config.protected_paths().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let files = github
+        .changed_files(config.owner(), config.name(), pull.number)
+        .await?;
+
+    let mut team_members = HashMap::new();
+    for protected in config.protected_paths() {
+        match protected.team() {
+            Some(team) if changed_file(protected, &files).is_some() => {
+                let members = github.team_members(team).await?;
+                team_members.insert(team.to_owned(), members);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(missing(
+        config.protected_paths(),
+        &files,
+        &approvers(pull, approver),
+        &team_members,
+    ))
+}
+
+/// The approvals lacking for the protected files among `files`, given the lowercase logins of the
+/// `approvers` and of the members of each team, keyed by `org/team`
+fn missing(
+    protected_paths: &[ProtectedPathsConfig],
+    files: &[String],
+    approvers: &HashSet<String>,
+    team_members: &HashMap<String, HashSet<String>>,
+) -> Vec<String> {
+    let mut missing = Vec::new();
+    for protected in protected_paths {
+        let file = match changed_file(protected, files) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        if let Some(team) = protected.team() {
+            let approved = team_members
+                .get(team)
+                .map_or(false, |members| !members.is_disjoint(approvers));
+            if !approved {
+                missing.push(format!(
+                    "approval from a member of @{}, since it changes `{}`",
+                    team, file
+                ));
+            }
+        }
+
+        if approvers.len() < protected.approvals() {
+            missing.push(format!(
+                "{} approvals, rather than {}, since it changes `{}`",
+                protected.approvals(),
+                approvers.len(),
+                file
+            ));
+        }
+    }
+    missing
+}
+
+/// The first of `files` which is protected by `protected`
+fn changed_file<'a>(protected: &ProtectedPathsConfig, files: &'a [String]) -> Option<&'a str> {
+    let patterns: Vec<_> = protected.paths().map(pattern_regex).collect();
+    files
+        .iter()
+        .find(|file| patterns.iter().any(|pattern| pattern.is_match(file)))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod test {
+    use super::missing;
+    use crate::config::ProtectedPathsConfig;
+    use std::collections::{HashMap, HashSet};
+
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Config {
+        protected_paths: Vec<ProtectedPathsConfig>,
+    }
+
+    #[test]
+    fn protected_approvals() {
+        let config: Config = toml::from_str(
+            r#"
+            [[protected-paths]]
+            paths = ["ci/**", ".github/workflows/"]
+            team = "org/release"
+
+            [[protected-paths]]
+            paths = ["**/Cargo.lock"]
+            approvals = 2
+            "#,
+        )
+        .unwrap();
+        let set = |users: &[&str]| -> HashSet<String> {
+            users.iter().map(|user| (*user).to_owned()).collect()
+        };
+        let mut team_members = HashMap::new();
+        team_members.insert("org/release".to_owned(), set(&["alice"]));
+
+        let files = vec!["src/lib.rs".to_owned(), "bors/Cargo.lock".to_owned()];
+        assert_eq!(
+            missing(
+                &config.protected_paths,
+                &files,
+                &set(&["bob"]),
+                &team_members
+            ),
+            vec!["2 approvals, rather than 1, since it changes `bors/Cargo.lock`"]
+        );
+        assert!(missing(
+            &config.protected_paths,
+            &files,
+            &set(&["bob", "carol"]),
+            &team_members
+        )
+        .is_empty());
+
+        let files = vec!["ci/deploy.sh".to_owned()];
+        assert_eq!(
+            missing(
+                &config.protected_paths,
+                &files,
+                &set(&["bob"]),
+                &team_members
+            ),
+            vec!["approval from a member of @org/release, since it changes `ci/deploy.sh`"]
+        );
+        assert!(missing(
+            &config.protected_paths,
+            &files,
+            &set(&["alice"]),
+            &team_members
+        )
+        .is_empty());
+    }
+}
@@ -1,38 +1,69 @@
 use crate::{
-    config::RepoConfig,
-    git::GitRepository,
+    check_durations::CheckDurations,
+    command::pr_list,
+    comment::{link, CommentEvent},
+    commit_message::annotations,
+    config::{BaseAdvanced, RepoConfig, Rollup},
+    contributors::Contributors,
+    description::{Importance, StatusDescription},
+    git::{CommitAnnotations, GitRepository, PushFailure},
     graphql::GithubClient,
+    land_window::{format_wait, time_until_open},
     project_board::ProjectBoard,
-    state::{PullRequestState, Status},
+    state::{upsert_check_run, AttemptOverrides, PullRequestState, Repo, Status, TestResult},
+    upstream::sync_upstream,
     Result,
 };
-use log::info;
+use chrono::Utc;
+use github::{
+    client::{CheckRunOutput, CreateCheckRunRequest, ListWorkflowRunsOptions, PaginationOptions},
+    CheckStatus, Conclusion, Oid,
+};
+use log::{info, warn};
 use std::{
     cmp::{Ordering, Reverse},
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::{Duration, Instant},
 };
 
+/// Local branch queued PRs are test rebased on when checking them for merge conflicts
+const CONFLICT_CHECK_BRANCH: &str = "bors-conflict-check";
+
+/// Delay before retrying a failed in-place update, which grows with each retry
+const IN_PLACE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Progress of the soak checks on the batch at the head of the queue
+enum Soak {
+    Passed,
+    Running,
+    TimedOut,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct QueueEntry {
     number: u64,
 
-    /// Indicates if the PR is high priority or not
-    priority: bool,
+    /// The PR's priority, higher priority PRs are tested first
+    priority: u32,
+
+    /// Among PRs of the same priority, lower numbered PRs are tested first. A PR which superseded
+    /// another is ordered by the other's number.
+    queue_number: u64,
 }
 
 impl PartialOrd for QueueEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match Reverse(self.priority).cmp(&Reverse(other.priority)) {
-            Ordering::Equal => Some(self.number.cmp(&other.number)),
-            ord => Some(ord),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for QueueEntry {
     fn cmp(&self, other: &Self) -> Ordering {
         match Reverse(self.priority).cmp(&Reverse(other.priority)) {
-            Ordering::Equal => self.number.cmp(&other.number),
+            Ordering::Equal => {
+                (self.queue_number, self.number).cmp(&(other.queue_number, other.number))
+            }
             ord => ord,
         }
     }
@@ -40,265 +71,662 @@ impl Ord for QueueEntry {
 
 #[derive(Debug)]
 pub struct MergeQueue {
-    /// The current head of the queue, the PR that is currently being tested
-    head: Option<u64>,
+    /// The current head of the queue, the batch of PRs currently being tested in the order they
+    /// were rebased onto the 'auto' branch. The last PR's merge commit is the tip of the batch and
+    /// is the commit the test results are reported on.
+    head: Vec<u64>,
+
+    /// Halves of failed batches which are waiting to be retested in order to find the PR which
+    /// caused the failure, along with batches which are to be rebuilt and retested because their
+    /// base ref moved before they could land
+    bisect: VecDeque<Vec<u64>>,
+
+    /// The batch being tested speculatively on top of the head's merge commit, on the speculative
+    /// branch. If the head lands this becomes the new head, reusing any results it has received.
+    speculative: Vec<u64>,
+
+    /// Indicates if the head is being tested on the speculative branch, rather than the 'auto'
+    /// branch, which happens when a speculative batch is promoted to be the head. The two
+    /// branches swap roles so that pushing a new speculative batch never clobbers the head.
+    swapped: bool,
+
+    /// When the tree is closed, via the `treeclosed` command, PRs with a priority below this
+    /// threshold aren't tested
+    tree_closed: Option<u32>,
+
+    /// Merge commit of the head, if it has passed its tests and is waiting for the land window to
+    /// open
+    awaiting_land_window: Option<Oid>,
+
+    /// Merge commit of the head, if it has passed its primary checks and is waiting on its soak
+    /// checks, along with when it started waiting
+    soaking: Option<(Oid, Instant)>,
+
+    /// Merge commit of the head, if an administrator chose to land it without waiting on its soak
+    /// checks
+    soak_skipped: Option<Oid>,
+
+    /// The position last reported in each queued PR's queue position comment
+    reported_positions: HashMap<u64, usize>,
+
+    /// The head of each queued PR found to conflict with its base ref, so that each conflict is
+    /// only reported once. Base refs only move forward so a PR keeps conflicting until its head
+    /// changes.
+    reported_conflicts: HashMap<u64, Oid>,
 }
 
 impl MergeQueue {
     pub fn new() -> Self {
-        Self { head: None }
+        Self {
+            head: Vec::new(),
+            bisect: VecDeque::new(),
+            speculative: Vec::new(),
+            swapped: false,
+            tree_closed: None,
+            awaiting_land_window: None,
+            soaking: None,
+            soak_skipped: None,
+            reported_positions: HashMap::new(),
+            reported_conflicts: HashMap::new(),
+        }
+    }
+
+    /// The priority threshold below which PRs aren't tested, if the tree is closed
+    pub fn tree_closed(&self) -> Option<u32> {
+        self.tree_closed
+    }
+
+    pub fn tree_closed_mut(&mut self) -> &mut Option<u32> {
+        &mut self.tree_closed
+    }
+
+    /// The batch of PRs currently being tested
+    pub fn head(&self) -> &[u64] {
+        &self.head
+    }
+
+    /// Resume testing a batch which was started elsewhere, e.g. by another bors instance
+    pub fn restore_head(&mut self, batch: Vec<u64>) {
+        assert!(self.head.is_empty());
+        self.head = batch;
+    }
+
+    /// Stop testing the batch at the head of the queue, returning it
+    pub fn take_head(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.head)
+    }
+
+    /// The PRs the next head is built from, along with whether they're half of a failed batch.
+    /// Halves of a failed batch which are still being bisected take precedence over the rest of
+    /// the queue.
+    pub fn next_candidates(
+        &mut self,
+        config: &RepoConfig,
+        pulls: &HashMap<u64, PullRequestState>,
+    ) -> (Vec<u64>, bool) {
+        match self.bisect.pop_front() {
+            Some(half) => (half, true),
+            None => (queue_order(config, pulls), false),
+        }
+    }
+
+    /// Retest a failed `batch` in halves, ahead of the rest of the queue, starting with the first
+    pub fn push_bisected(&mut self, batch: &[u64]) {
+        let (first, second) = batch.split_at(batch.len() / 2);
+        self.bisect.push_front(second.to_vec());
+        self.bisect.push_front(first.to_vec());
+    }
+
+    /// The batch of PRs currently being tested speculatively behind the head
+    pub fn speculative(&self) -> &[u64] {
+        &self.speculative
+    }
+
+    /// How long until the land window opens, if the head has passed and is waiting for it
+    pub fn land_window_wait(&self, config: &RepoConfig) -> Option<std::time::Duration> {
+        if self.head.is_empty() {
+            return None;
+        }
+
+        self.awaiting_land_window.as_ref()?;
+        config
+            .land_window()
+            .and_then(|window| time_until_open(window, Utc::now()))
+    }
+
+    /// Land the head without waiting on its soak checks, returning the PRs in it, or `None` if it
+    /// isn't waiting on them
+    pub fn skip_soak(&mut self, pulls: &HashMap<u64, PullRequestState>) -> Option<&[u64]> {
+        let (merge_oid, _started_at) = self.soaking.as_ref()?;
+        let is_head = match self.head.last().map(|tip| &pulls[tip].status) {
+            Some(Status::Testing {
+                merge_oid: head_oid,
+                ..
+            }) => head_oid == merge_oid,
+            _ => false,
+        };
+        if !is_head {
+            return None;
+        }
+
+        self.soak_skipped = Some(merge_oid.clone());
+        Some(&self.head)
+    }
+
+    /// Keep each queued PR's queue position comment up to date, editing it whenever the PR's
+    /// position changes and one last time once the PR leaves the queue. Comments which can't be
+    /// edited are only logged, and tried again the next time the queue is processed.
+    pub async fn update_position_comments(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pulls: &HashMap<u64, PullRequestState>,
+        durations: &CheckDurations,
+    ) {
+        if !config.queue_position_comments() {
+            return;
+        }
+
+        let queue = queue_order(config, pulls);
+
+        let departed: Vec<u64> = self
+            .reported_positions
+            .keys()
+            .filter(|number| !queue.contains(*number))
+            .copied()
+            .collect();
+        for number in departed {
+            self.reported_positions.remove(&number);
+            let pull = match pulls.get(&number) {
+                Some(pull) => pull,
+                None => continue,
+            };
+
+            let body = if pull.status.is_testing() {
+                ":test_tube: This PR has left the queue and is being tested"
+            } else {
+                ":eject: This PR is no longer queued for landing"
+            };
+            if let Err(e) = pull
+                .upsert_comment(config, github, CommentEvent::QueuePosition, body)
+                .await
+            {
+                warn!(
+                    "unable to update the queue position of PR #{}: {:?}",
+                    number, e
+                );
+            }
+        }
+
+        let waits = estimated_waits(config, pulls, durations);
+
+        for (idx, number) in queue.iter().enumerate() {
+            let position = idx + 1;
+            if self.reported_positions.get(number) == Some(&position) {
+                continue;
+            }
+
+            let mut body = format!(
+                ":clipboard: This PR is at position {} in the queue",
+                position
+            );
+            let expected = durations.expected(config.checks(&pulls[number].base_ref_name));
+            if let (Some(expected), Some(wait)) = (expected, waits.get(number)) {
+                body.push_str(&format!(
+                    ", it's estimated to start testing in about {}, based on recent tests taking \
+                    {}",
+                    format_wait(*wait),
+                    format_wait(expected),
+                ));
+            }
+            if !self.head.is_empty() {
+                body.push_str(&format!("\n\nCurrently testing: {}", pr_list(&self.head)));
+            }
+
+            match pulls[number]
+                .upsert_comment(config, github, CommentEvent::QueuePosition, &body)
+                .await
+            {
+                Ok(()) => {
+                    self.reported_positions.insert(*number, position);
+                }
+                Err(e) => warn!(
+                    "unable to update the queue position of PR #{}: {:?}",
+                    number, e
+                ),
+            }
+        }
     }
 
-    async fn land_pr(
+    /// Check if there is nothing currently being tested
+    pub fn is_idle(&self) -> bool {
+        self.head.is_empty() && self.speculative.is_empty()
+    }
+
+    /// The branches that the head and the speculative batch are pushed to for testing
+    fn branches<'a>(&self, config: &'a RepoConfig) -> (&'a str, Option<&'a str>) {
+        match config.speculative_branch() {
+            Some(speculative) if self.swapped => (speculative, Some(config.staging_branch())),
+            speculative => (config.staging_branch(), speculative),
+        }
+    }
+
+    async fn land_batch(
         &mut self,
         config: &RepoConfig,
         github: &GithubClient,
         repo: &mut GitRepository,
         project_board: Option<&ProjectBoard>,
         pulls: &mut HashMap<u64, PullRequestState>,
-    ) -> Result<()> {
-        let head = self
-            .head
-            .take()
-            .expect("land_pr should only be called when there is a PR to land");
+    ) -> Result<bool> {
+        let batch = std::mem::take(&mut self.head);
+        assert!(
+            !batch.is_empty(),
+            "land_batch should only be called when there is a batch to land"
+        );
+
+        // The attempt has completed whether or not the batch lands
+        let (branch, _speculative) = self.branches(config);
+        delete_attempt_branch(config, repo, branch, *batch.last().unwrap());
 
-        let mut pull = pulls.get_mut(&head).expect("PR should exist");
-        let merge_oid = match &pull.status {
-            Status::Testing { merge_oid, .. } => merge_oid,
-            // XXX Fix this
+        let tip = pulls.get(batch.last().unwrap()).expect("PR should exist");
+        let (base_ref_name, merge_oid, test_results) = match &tip.status {
+            Status::Testing {
+                merge_oid,
+                test_results,
+                ..
+            } => (
+                tip.base_ref_name.clone(),
+                merge_oid.clone(),
+                test_results.clone(),
+            ),
             _ => unreachable!(),
         };
 
-        // Attempt to update the PR in-place
-        if let Some(head_repo) = pull.head_repo.as_ref() {
-            // Before 'merging' the PR into the base ref we first update the PR with the rebased
-            // commits that are to be imminently merged using the `maintainer_can_modify` feature.
-            // This is done so that when the commits are finally pushed to the base ref that Github
-            // will properly mark the PR as being 'merged'.
-            if config.maintainer_mode()
-                && repo
-                    .push_to_remote(
-                        &head_repo,
-                        &pull.head_ref_name,
-                        &pull.head_ref_oid,
-                        &merge_oid,
-                    )
-                    .is_err()
-            {
-                info!(
-                    "unable to update pr #{} in-place. maintainer_can_modify: {}",
-                    pull.number, pull.maintainer_can_modify
-                );
+        // If 'base_ref' was advanced outside of bors, e.g. pushed to directly, while the batch
+        // was being tested then it can only be landed by discarding those commits. This is
+        // checked before any PR is updated in-place, so that PRs aren't pushed commits which
+        // won't land. Should the check itself fail the batch is put back, to try landing it again.
+        let (base_oid, advanced) = match base_advanced(repo, &base_ref_name, &merge_oid) {
+            Ok(advanced) => advanced,
+            Err(e) => {
+                self.head = batch;
+                return Err(e);
+            }
+        };
+        if advanced && config.base_advanced() == BaseAdvanced::Fail {
+            info!(
+                "'{}' advanced to {} while batch {:?} was tested",
+                base_ref_name, base_oid, batch
+            );
+            fail_base_advanced(config, github, project_board, pulls, &batch, &base_ref_name)
+                .await?;
+            return Ok(false);
+        }
+        if advanced && config.base_advanced() == BaseAdvanced::Retest {
+            self.rebuild_batch(
+                config,
+                github,
+                project_board,
+                pulls,
+                batch,
+                &base_ref_name,
+                &base_oid,
+            )
+            .await?;
+            return Ok(false);
+        }
+        let force = advanced && config.base_advanced() == BaseAdvanced::LandAnyway;
+        if force {
+            warn!(
+                "force-updating '{}' from {} to land batch {:?}",
+                base_ref_name, base_oid, batch
+            );
+        }
 
-                pull.update_status(Status::InReview, config, github, project_board)
-                    .await?;
+        // Attempt to update each PR in-place
+        let mut update_failed = false;
+        for number in &batch {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            let merge_oid = match &pull.status {
+                Status::Testing { merge_oid, .. } => merge_oid.clone(),
+                // XXX Fix this
+                _ => unreachable!(),
+            };
 
-                let comment =
-                    ":exclamation: failed to update PR in-place; halting merge.\n\
-                    Make sure that that [\"Allow edits from maintainers\"]\
-                    (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/allowing-changes-to-a-pull-request-branch-created-from-a-fork) \
-                    is enabled before attempting to reland this PR.";
+            if let Some(head_repo) = pull.head_repo.as_ref() {
+                // Before 'merging' the PR into the base ref we first update the PR with the
+                // rebased commits that are to be imminently merged using the
+                // `maintainer_can_modify` feature. This is done so that when the commits are
+                // finally pushed to the base ref that Github will properly mark the PR as being
+                // 'merged'.
+                let failure = if config.maintainer_mode() {
+                    update_in_place(config, repo, head_repo, pull, &merge_oid).await
+                } else {
+                    None
+                };
+                if let Some(failure) = failure {
+                    info!(
+                        "unable to update pr #{} in-place ({:?}). maintainer_can_modify: {}",
+                        pull.number, failure, pull.maintainer_can_modify
+                    );
 
-                github
-                    .issues()
-                    .create_comment(config.owner(), config.name(), pull.number, &comment)
-                    .await?;
+                    pull.update_status(Status::InReview, config, github, project_board)
+                        .await?;
+                    pull.start_cooldown(config);
+
+                    // Only a refused push is something the PR's author can fix by allowing edits
+                    let comment = match failure {
+                        PushFailure::PermissionDenied => {
+                            ":exclamation: failed to update PR in-place; halting merge.\n\
+                            Make sure that that [\"Allow edits from maintainers\"]\
+                            (https://help.github.com/en/github/collaborating-with-issues-and-pull-requests/allowing-changes-to-a-pull-request-branch-created-from-a-fork) \
+                            is enabled before attempting to reland this PR."
+                                .to_owned()
+                        }
+                        PushFailure::Stale => {
+                            ":exclamation: failed to update PR in-place since it was pushed to \
+                            while it was being tested; halting merge. Review the new commits \
+                            before attempting to reland this PR."
+                                .to_owned()
+                        }
+                        PushFailure::Transient => format!(
+                            ":exclamation: failed to update PR in-place after {} attempts; \
+                            halting merge. This looks like a temporary problem, try relanding \
+                            this PR once it has cooled down.",
+                            config.in_place_update_retries() + 1
+                        ),
+                    };
+
+                    pull.create_comment(config, github, CommentEvent::UpdateFailed, &comment)
+                        .await?;
+                    pull.report_check_run(
+                        config,
+                        github,
+                        &pull.head_ref_oid,
+                        Some(Conclusion::Failure),
+                        None,
+                    )
+                    .await;
+
+                    update_failed = true;
+                }
+            }
+        }
 
-                return Ok(());
+        // The batch can't be landed without every one of its PRs, so return the rest of them to
+        // the queue to be retested without the ones that couldn't be updated
+        if update_failed {
+            for number in &batch {
+                let pull = pulls.get_mut(number).expect("PR should exist");
+                if pull.status.is_testing() {
+                    pull.update_status(Status::Queued, config, github, project_board)
+                        .await?;
+                }
             }
+
+            return Ok(false);
         }
 
-        // Finally 'merge' the PR by updating the 'base_ref' with `merge_oid`
-        github
+        // Finally 'merge' the whole batch at once by updating the 'base_ref' with the `merge_oid`
+        // of the batch's tip. This fails if 'base_ref' can't be fast-forwarded, e.g. if it was
+        // pushed to directly after it was checked above, in which case the batch is rebuilt and
+        // retested straight away. Any other failure requeues the batch to be retested once it
+        // has cooled down.
+        if let Err(e) = github
             .git()
             .update_ref(
                 config.owner(),
                 config.name(),
-                &format!("heads/{}", pull.base_ref_name),
+                &format!("heads/{}", base_ref_name),
                 &merge_oid,
-                false,
+                force,
             )
-            .await?;
+            .await
+        {
+            info!("unable to land batch {:?}: {}", batch, e);
+
+            let (moved_to, advanced) = match base_advanced(repo, &base_ref_name, &merge_oid) {
+                Ok(advanced) => advanced,
+                Err(e) => {
+                    self.head = batch;
+                    return Err(e);
+                }
+            };
+            if advanced {
+                self.rebuild_batch(
+                    config,
+                    github,
+                    project_board,
+                    pulls,
+                    batch,
+                    &base_ref_name,
+                    &moved_to,
+                )
+                .await?;
+                return Ok(false);
+            }
 
-        if let Some(board) = project_board {
-            board.delete_card(github, &mut pull).await?;
+            for number in &batch {
+                let pull = pulls.get_mut(number).expect("PR should exist");
+                pull.update_status(Status::Queued, config, github, project_board)
+                    .await?;
+                pull.start_cooldown(config);
+
+                let comment = format!(
+                    ":warning: Unable to fast-forward `{}` to the merge commit of attempt #{}, it \
+                    may have been updated outside of bors. This PR will be retested in {}s.{}",
+                    base_ref_name,
+                    pull.attempts,
+                    config.cooldown().as_secs(),
+                    batch_note(&batch, pull.number),
+                );
+                pull.create_comment(config, github, CommentEvent::LandFailed, &comment)
+                    .await?;
+            }
+
+            return Ok(false);
         }
 
-        // Actually remove the PR
-        pulls.remove(&head);
+        // The batch has landed at this point, so failing to sync it upstream is only logged
+        if let Err(e) = sync_upstream(config, github, repo, &base_ref_name, &merge_oid).await {
+            warn!("unable to sync batch {:?} upstream: {:?}", batch, e);
+        }
 
-        Ok(())
+        for number in &batch {
+            let mut pull = pulls.get_mut(number).expect("PR should exist");
+
+            if force {
+                let comment = format!(
+                    ":warning: `{}` was updated outside of bors while attempt #{} was being \
+                    tested. It was force-updated to land this PR, discarding its previous tip {} \
+                    and any commits only reachable from it.{}",
+                    base_ref_name,
+                    pull.attempts,
+                    base_oid,
+                    batch_note(&batch, pull.number),
+                );
+                pull.create_comment(config, github, CommentEvent::BaseAdvanced, &comment)
+                    .await?;
+            }
+
+            // When updated in-place the PR's head is now its merge commit
+            let head_oid = match &pull.status {
+                Status::Testing { merge_oid, .. }
+                    if config.maintainer_mode() && pull.head_repo.is_some() =>
+                {
+                    merge_oid.clone()
+                }
+                _ => pull.head_ref_oid.clone(),
+            };
+            pull.report_check_run(config, github, &head_oid, Some(Conclusion::Success), None)
+                .await;
+
+            // Optional checks never hold up a land, but their failures are still worth a look
+            if let Some(failures) = optional_failures(config, &base_ref_name, &test_results) {
+                let comment = format!(
+                    ":information_source: Landed, but some optional checks failed: {}",
+                    failures
+                );
+                pull.create_comment(config, github, CommentEvent::OptionalChecksFailed, &comment)
+                    .await?;
+            }
+
+            if config.contributor_stats() {
+                record_contributor(config, github, &repo.state_dir(), pull).await;
+            }
+
+            if let Some(board) = project_board {
+                board.delete_card(github, &mut pull).await?;
+            }
+
+            // Actually remove the PR
+            let pull = pulls.remove(number).expect("PR should exist");
+            retarget_stacked(config, github, &pull, pulls).await;
+        }
+
+        // Landing only fails a queued PR's rebase later, so failing to look for conflicts now is
+        // only logged
+        if config.detect_conflicts() {
+            if let Err(e) = self
+                .detect_conflicts(config, github, repo, pulls, &base_ref_name, &merge_oid)
+                .await
+            {
+                warn!("unable to check queued PRs for merge conflicts: {:?}", e);
+            }
+        }
+
+        Ok(true)
     }
 
-    pub async fn process_queue(
+    /// Test rebase each queued PR targeting `base_ref` onto `base_oid`, its newly landed tip,
+    /// letting the authors of those which conflict know that they need to be rebased
+    async fn detect_conflicts(
         &mut self,
         config: &RepoConfig,
         github: &GithubClient,
         repo: &mut GitRepository,
-        project_board: Option<&ProjectBoard>,
-        pulls: &mut HashMap<u64, PullRequestState>,
+        pulls: &HashMap<u64, PullRequestState>,
+        base_ref: &str,
+        base_oid: &Oid,
     ) -> Result<()> {
-        // Ensure that only ever 1 PR is in "Testing" at a time
-        assert!(pulls.iter().filter(|(_n, p)| p.status.is_testing()).count() <= 1);
+        self.reported_conflicts.retain(|number, head_oid| {
+            pulls.get(number).map(|pull| &pull.head_ref_oid) == Some(head_oid)
+        });
 
-        // Process the PR at the head of the queue
-        self.process_head(config, github, repo, project_board, pulls)
-            .await?;
+        for number in queue_order(config, pulls) {
+            let pull = &pulls[&number];
+            if pull.base_ref_name != base_ref || self.reported_conflicts.contains_key(&number) {
+                continue;
+            }
 
-        if self.head.is_none() {
-            self.process_next_head(config, github, repo, project_board, pulls)
+            let merge_oid = repo.fetch_and_rebase_onto(
+                base_oid,
+                &pull.head_ref_oid,
+                CONFLICT_CHECK_BRANCH,
+                pull.number,
+                &CommitAnnotations::default(),
+                pull.merge_strategy(config),
+            )?;
+            if merge_oid.is_some() {
+                continue;
+            }
+
+            info!("queued pr #{} conflicts with '{}'", number, base_ref);
+
+            let comment = format!(
+                ":lock: This PR no longer applies cleanly on top of `{}` and will fail to land \
+                with a merge conflict once it reaches the head of the queue. It needs to be \
+                rebased to resolve the conflict.",
+                base_ref
+            );
+            pull.create_comment(config, github, CommentEvent::ConflictDetected, &comment)
                 .await?;
+            self.reported_conflicts
+                .insert(number, pull.head_ref_oid.clone());
         }
 
         Ok(())
     }
 
-    async fn process_head(
+    pub async fn process_queue(
         &mut self,
         config: &RepoConfig,
         github: &GithubClient,
         repo: &mut GitRepository,
         project_board: Option<&ProjectBoard>,
         pulls: &mut HashMap<u64, PullRequestState>,
+        drain: bool,
     ) -> Result<()> {
-        // Early return if there isn't anything at the head of the Queue currently being tested
-        let head = if let Some(head) = self.head {
-            head
-        } else {
-            return Ok(());
-        };
-
-        // Early return if the PR that was currently being tested was closed for some reason
-        let pull = match pulls.get_mut(&head) {
-            Some(pull) => pull,
-            None => {
-                self.head = None;
-                return Ok(());
-            }
-        };
-
-        // Early return if the PR that was currently being tested had its state changed from
-        // `Status::Testing`, e.g. if the land was canceled.
-        let (merge_oid, tests_started_at, test_results) = match &pull.status {
-            Status::Testing {
-                merge_oid,
-                tests_started_at,
-                test_results,
-            } => (merge_oid, tests_started_at, test_results),
-            _ => {
-                self.head = None;
-                return Ok(());
-            }
-        };
+        // Ensure that only ever the head and a speculative batch are in "Testing" at a time
+        assert!(
+            pulls.iter().filter(|(_n, p)| p.status.is_testing()).count()
+                <= 2 * config.max_batch_size()
+        );
 
-        // Check if there were any test failures from configured checks
-        if let Some((name, result)) = config
-            .checks()
-            .filter_map(|name| test_results.get(name).map(|result| (name, result.clone())))
-            .find(|(_name, result)| !result.passed)
-        {
-            // Remove the PR from the Queue
-            // XXX Maybe mark as "Failed"?
-            pull.update_status(Status::InReview, config, github, project_board)
+        // Process the batch at the head of the queue. If it lands then the speculative batch
+        // becomes the new head and is processed in turn, as its tests may have already completed.
+        loop {
+            let landed = self
+                .process_head(config, github, repo, project_board, pulls)
                 .await?;
-            self.head.take();
 
-            // Create github status/check
-            github
-                .repos()
-                .create_status(
-                    config.owner(),
-                    config.name(),
-                    &pull.head_ref_oid.to_string(),
-                    &github::client::CreateStatusRequest {
-                        state: github::StatusEventState::Failure,
-                        target_url: Some(&result.details_url),
-                        description: None,
-                        context: "bors",
-                    },
-                )
-                .await?;
+            if self.head.is_empty() && !self.speculative.is_empty() {
+                if landed && self.promote_speculative(config, github, pulls).await? {
+                    continue;
+                }
 
-            // Report the Error
-            github
-                .issues()
-                .create_comment(
-                    config.owner(),
-                    config.name(),
-                    pull.number,
-                    &format!(
-                        ":broken_heart: Test Failed - [{}]({})",
-                        name, result.details_url
-                    ),
-                )
-                .await?;
+                self.discard_speculative(config, github, repo, project_board, pulls)
+                    .await?;
+            }
 
-        // Check if all tests have completed and passed
-        } else if config
-            .checks()
-            .map(|name| test_results.get(name))
-            .all(|result| result.map(|r| r.passed).unwrap_or(false))
-        {
-            // Create github status/check on the merge commit
-            github
-                .repos()
-                .create_status(
-                    config.owner(),
-                    config.name(),
-                    &merge_oid.to_string(),
-                    &github::client::CreateStatusRequest {
-                        state: github::StatusEventState::Success,
-                        target_url: None,
-                        description: None,
-                        context: "bors",
-                    },
-                )
-                .await?;
+            break;
+        }
+
+        // When draining, the current batch is allowed to finish but no new ones are started
+        if drain {
+            return Ok(());
+        }
 
-            self.land_pr(config, github, repo, project_board, pulls)
+        if self.head.is_empty() {
+            self.process_next_head(config, github, repo, project_board, pulls)
                 .await?;
+        }
 
-        // Check if the test has timed-out
-        } else if tests_started_at.elapsed() >= config.timeout() {
-            info!("PR #{} timed-out", pull.number);
+        self.process_speculative(config, github, repo, project_board, pulls)
+            .await
+    }
 
-            // Remove the PR from the Queue
-            // XXX Maybe mark as "Failed"?
-            pull.update_status(Status::InReview, config, github, project_board)
-                .await?;
-            self.head = None;
+    /// Make the speculative batch the new head of the queue after the previous head landed,
+    /// returning `false` if it is no longer valid and should be discarded instead
+    async fn promote_speculative(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pulls: &HashMap<u64, PullRequestState>,
+    ) -> Result<bool> {
+        if !all_testing(&self.speculative, pulls) {
+            return Ok(false);
+        }
 
-            github
-                .repos()
-                .create_status(
-                    config.owner(),
-                    config.name(),
-                    &pull.head_ref_oid.to_string(),
-                    &github::client::CreateStatusRequest {
-                        state: github::StatusEventState::Failure,
-                        target_url: None,
-                        description: Some("Timed-out"),
-                        context: "bors",
-                    },
-                )
-                .await?;
+        info!("promoting speculative batch {:?}", self.speculative);
+        self.head = std::mem::take(&mut self.speculative);
+        self.swapped = !self.swapped;
 
-            // Report the Error
-            github
-                .issues()
-                .create_comment(
-                    config.owner(),
-                    config.name(),
-                    pull.number,
-                    &format!(":boom: Tests timed-out"),
-                )
-                .await?;
+        // Only the commit statuses need updating, the check runs remain in progress
+        for number in &self.head {
+            let pull = &pulls[number];
+            let description = batch_description(&self.head, None, pull.attempts);
+            let summary = testing_summary(&self.head, pull.number);
+            create_pending_status(config, github, pull, &description, &summary).await?;
         }
 
-        Ok(())
+        Ok(true)
     }
 
-    async fn process_next_head(
+    /// Return the PRs in the speculative batch to the queue, e.g. after the head failed, since
+    /// their results no longer reflect what would be landed
+    async fn discard_speculative(
         &mut self,
         config: &RepoConfig,
         github: &GithubClient,
@@ -306,84 +734,1590 @@ impl MergeQueue {
         project_board: Option<&ProjectBoard>,
         pulls: &mut HashMap<u64, PullRequestState>,
     ) -> Result<()> {
-        assert!(self.head.is_none());
+        let batch = std::mem::take(&mut self.speculative);
+        if batch.is_empty() {
+            return Ok(());
+        }
 
-        let mut queue: Vec<_> = pulls
-            .iter_mut()
-            .map(|(_n, p)| p)
-            .filter(|p| p.status.is_queued())
-            .collect();
-        queue.sort_unstable_by_key(|p| QueueEntry {
-            number: p.number,
-            priority: p.has_label(config.labels().high_priority()),
-        });
-        let mut queue = queue.into_iter();
+        info!("discarding speculative batch {:?}", batch);
+        if let (_head, Some(branch)) = self.branches(config) {
+            let tip = *batch.last().expect("batch is non-empty");
+            cancel_ci(config, github, repo, branch, tip).await;
+        }
 
-        while let (None, Some(pull)) = (self.head, queue.next()) {
-            info!("Creating merge for pr #{}", pull.number);
+        for number in batch {
+            if let Some(pull) = pulls.get_mut(&number) {
+                if pull.status.is_testing() {
+                    pull.update_status(Status::Queued, config, github, project_board)
+                        .await?;
+                }
+            }
+        }
 
-            // Attempt to rebase the PR onto 'base_ref' and push to the 'auto' branch for
-            // testing
-            if let Some(merge_oid) = repo.fetch_and_rebase(
-                &pull.base_ref_name,
-                &pull.head_ref_oid,
-                "auto",
-                pull.number,
-                pull.has_label(config.labels().squash()),
-            )? {
-                repo.push_branch("auto")?;
-                info!("pushed 'auto' branch");
+        Ok(())
+    }
 
-                pull.update_status(Status::testing(merge_oid), config, github, project_board)
-                    .await?;
-                self.head = Some(pull.number);
-
-                // Create github status
-                github
-                    .repos()
-                    .create_status(
-                        config.owner(),
-                        config.name(),
-                        &pull.head_ref_oid.to_string(),
-                        &github::client::CreateStatusRequest {
-                            state: github::StatusEventState::Pending,
-                            target_url: None,
-                            description: None,
-                            context: "bors",
-                        },
-                    )
+    /// Start testing the next batch in the queue speculatively, on top of the head's merge commit,
+    /// if speculative testing is enabled
+    async fn process_speculative(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+    ) -> Result<()> {
+        let branch = match self.branches(config) {
+            (_head, Some(branch)) => branch,
+            (_head, None) => return Ok(()),
+        };
+
+        // Drop a speculative batch which had any of its PRs canceled or closed
+        if !all_testing(&self.speculative, pulls) {
+            self.discard_speculative(config, github, repo, project_board, pulls)
+                .await?;
+        }
+
+        // Nothing is tested speculatively while a failed batch is being bisected since the head
+        // is likely to fail again
+        if !self.speculative.is_empty() || !self.bisect.is_empty() {
+            return Ok(());
+        }
+
+        let (head, base_ref_name, merge_oid) = match self.head.last().map(|number| &pulls[number]) {
+            Some(pull) => match &pull.status {
+                Status::Testing { merge_oid, .. } => {
+                    (pull.number, pull.base_ref_name.clone(), merge_oid.clone())
+                }
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        let candidates = queue_order(config, pulls);
+        let batch = self
+            .create_batch(
+                config,
+                github,
+                repo,
+                project_board,
+                pulls,
+                candidates,
+                branch,
+                Some((&base_ref_name, &merge_oid)),
+            )
+            .await?;
+
+        self.speculative = self
+            .start_batch(
+                config,
+                github,
+                repo,
+                project_board,
+                pulls,
+                batch,
+                branch,
+                Some(head),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn process_head(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+    ) -> Result<bool> {
+        // Early return if there isn't anything at the head of the Queue currently being tested
+        let tip = if let Some(tip) = self.head.last() {
+            *tip
+        } else {
+            return Ok(false);
+        };
+
+        // Abandon the batch if any of its PRs were closed for some reason or had their state
+        // changed from `Status::Testing`, e.g. if the land was canceled. Any other PRs in the
+        // batch are returned to the queue to be retested.
+        if !all_testing(&self.head, pulls) {
+            let (branch, _speculative) = self.branches(config);
+            cancel_ci(config, github, repo, branch, tip).await;
+
+            for number in std::mem::take(&mut self.head) {
+                if let Some(pull) = pulls.get_mut(&number) {
+                    if pull.status.is_testing() {
+                        pull.update_status(Status::Queued, config, github, project_board)
+                            .await?;
+                    }
+                }
+            }
+
+            return Ok(false);
+        }
+
+        let tip_pull = &pulls[&tip];
+        let (base_ref, merge_oid, tests_started_at, test_results, overrides) =
+            match &tip_pull.status {
+                Status::Testing {
+                    merge_oid,
+                    tests_started_at,
+                    test_results,
+                    overrides,
+                } => (
+                    tip_pull.base_ref_name.clone(),
+                    merge_oid.clone(),
+                    *tests_started_at,
+                    test_results.clone(),
+                    overrides.clone(),
+                ),
+                _ => unreachable!(),
+            };
+
+        // Check if there were any test failures from configured checks, including soak checks
+        if let Some((name, result)) = overrides
+            .checks(config, &base_ref)
+            .into_iter()
+            .chain(config.soak_checks())
+            .filter_map(|name| test_results.get(name).map(|result| (name, result.clone())))
+            .find(|(_name, result)| !result.passed)
+        {
+            let (branch, _speculative) = self.branches(config);
+            delete_attempt_branch(config, repo, branch, tip);
+
+            // Failures caused by flaky infrastructure are retried a limited number of times,
+            // without bisecting the batch, before being reported
+            if result.spurious
+                && self
+                    .retry_spurious_failure(config, github, project_board, pulls, name, &result)
+                    .await?
+            {
+                return Ok(false);
+            }
+
+            // A failed batch is split up and retested in order to find the PR responsible
+            if self.head.len() > 1 {
+                let reason = format!("failed - {}", link(name, &result.details_url));
+                self.bisect_head(config, github, project_board, pulls, &reason)
                     .await?;
+                return Ok(false);
+            }
+
+            // Remove the PR from the Queue
+            let pull = pulls.get_mut(&tip).expect("PR should exist");
+            let reason = format!("{} failed on attempt #{}", name, pull.attempts);
+            pull.update_status(
+                Status::failed(reason, test_results.clone()),
+                config,
+                github,
+                project_board,
+            )
+            .await?;
+            self.head.clear();
+
+            // Create github status/check
+            let summary = format!(
+                "{} failed on the merge commit {}{}\n\n{}",
+                link(name, &result.details_url),
+                merge_oid,
+                result.known_issue_note(),
+                results_table(
+                    overrides
+                        .checks(config, &base_ref)
+                        .into_iter()
+                        .chain(config.soak_checks()),
+                    &test_results
+                ),
+            );
+            report_status(
+                config,
+                github,
+                &pull.head_ref_oid,
+                github::StatusEventState::Failure,
+                &StatusDescription::new(format!("Attempt #{} failed", pull.attempts))
+                    .detail(Importance::Medium, name),
+                Some(&result.details_url),
+                &summary,
+            )
+            .await?;
+
+            // Report the Error
+            let comment = format!(
+                ":broken_heart: Test Failed on attempt #{} - {}{}{}{}",
+                pull.attempts,
+                link(name, &result.details_url),
+                result.summary_note(),
+                result.known_issue_note(),
+                cc_reviewers(config, pull),
+            );
+            pull.create_comment(config, github, CommentEvent::TestFailed, &comment)
+                .await?;
+
+        // Check if all tests have completed and passed
+        } else if overrides
+            .checks(config, &base_ref)
+            .into_iter()
+            .map(|name| test_results.get(name))
+            .all(|result| result.map(|r| r.passed).unwrap_or(false))
+        {
+            match self
+                .check_soak(config, github, pulls, &merge_oid, &test_results)
+                .await?
+            {
+                Soak::Passed => {}
+                Soak::Running => return Ok(false),
+                Soak::TimedOut => {
+                    info!("batch {:?} timed-out waiting on soak checks", self.head);
+                    self.time_out_head(config, github, repo, project_board, pulls, test_results)
+                        .await?;
+                    return Ok(false);
+                }
+            }
+
+            // Outside of the land window the batch waits at the head of the queue, with its
+            // tests passed, until the window opens
+            if !self
+                .check_land_window(config, github, pulls, &merge_oid)
+                .await?
+            {
+                return Ok(false);
+            }
+
+            // Create github status/check on the merge commit
+            let summary = format!(
+                "Landing {}\n\n{}",
+                pr_list(&self.head),
+                results_table(
+                    overrides
+                        .checks(config, &base_ref)
+                        .into_iter()
+                        .chain(config.soak_checks()),
+                    &test_results
+                ),
+            );
+            report_status(
+                config,
+                github,
+                &merge_oid,
+                github::StatusEventState::Success,
+                &StatusDescription::new("All checks passed"),
+                None,
+                &summary,
+            )
+            .await?;
+
+            return self
+                .land_batch(config, github, repo, project_board, pulls)
+                .await;
+
+        // Check if any of the checks still running has timed-out
+        } else if let Some(name) = timed_out_check(
+            config,
+            &base_ref,
+            &overrides,
+            tests_started_at.elapsed(),
+            &test_results,
+        ) {
+            info!("batch {:?} timed-out waiting on {}", self.head, name);
+            self.time_out_head(config, github, repo, project_board, pulls, test_results)
+                .await?;
+        }
+
+        Ok(false)
+    }
+
+    /// Fail the batch at the head of the queue because its tests, or soak checks, timed-out
+    async fn time_out_head(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        test_results: HashMap<String, TestResult>,
+    ) -> Result<()> {
+        let tip = *self.head.last().expect("head should be non-empty");
+
+        let (branch, _speculative) = self.branches(config);
+        cancel_ci(config, github, repo, branch, tip).await;
+
+        if self.head.len() > 1 {
+            return self
+                .bisect_head(config, github, project_board, pulls, "timed-out")
+                .await;
+        }
+
+        let summary = match &pulls[&tip].status {
+            Status::Testing { overrides, .. } => format!(
+                "Checks timed-out on the merge commit\n\n{}",
+                results_table(
+                    overrides
+                        .checks(config, &pulls[&tip].base_ref_name)
+                        .into_iter()
+                        .chain(config.soak_checks()),
+                    &test_results
+                )
+            ),
+            _ => unreachable!(),
+        };
+
+        // Remove the PR from the Queue
+        let pull = pulls.get_mut(&tip).expect("PR should exist");
+        let reason = format!("Timed-out on attempt #{}", pull.attempts);
+        pull.update_status(
+            Status::failed(reason, test_results),
+            config,
+            github,
+            project_board,
+        )
+        .await?;
+        self.head.clear();
+
+        report_status(
+            config,
+            github,
+            &pull.head_ref_oid,
+            github::StatusEventState::Failure,
+            &StatusDescription::new(format!("Attempt #{} timed-out", pull.attempts)),
+            None,
+            &summary,
+        )
+        .await?;
+
+        // Report the Error
+        let comment = format!(
+            ":boom: Tests timed-out on attempt #{}{}",
+            pull.attempts,
+            cc_reviewers(config, pull)
+        );
+        pull.create_comment(config, github, CommentEvent::TimedOut, &comment)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check on the soak checks of the batch at the head of the queue, once its primary checks
+    /// have passed, letting its PRs know when it starts waiting on them
+    async fn check_soak(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        merge_oid: &Oid,
+        test_results: &HashMap<String, TestResult>,
+    ) -> Result<Soak> {
+        let soak = match config.soak() {
+            Some(soak) => soak,
+            None => return Ok(Soak::Passed),
+        };
+
+        if self.soak_skipped.as_ref() == Some(merge_oid)
+            || soak
+                .checks()
+                .all(|name| test_results.get(name).map_or(false, |result| result.passed))
+        {
+            self.soaking = None;
+            return Ok(Soak::Passed);
+        }
+
+        match &self.soaking {
+            Some((soaking, started_at)) if soaking == merge_oid => {
+                if started_at.elapsed() >= soak.timeout() {
+                    self.soaking = None;
+                    return Ok(Soak::TimedOut);
+                }
+                return Ok(Soak::Running);
+            }
+            _ => {}
+        }
+
+        info!("batch {:?} passed, waiting on soak checks", self.head);
+        self.soaking = Some((merge_oid.clone(), Instant::now()));
+
+        let summary = format!(
+            "Checks of {} passed, waiting up to {} on the soak checks\n\n{}",
+            pr_list(&self.head),
+            format_wait(soak.timeout()),
+            results_table(config.soak_checks(), test_results),
+        );
+        report_status(
+            config,
+            github,
+            merge_oid,
+            github::StatusEventState::Pending,
+            &StatusDescription::new("Waiting on soak checks"),
+            None,
+            &summary,
+        )
+        .await?;
+
+        for number in &self.head {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            let comment = format!(
+                ":hourglass_flowing_sand: Checks passed on attempt #{}, waiting up to {} on the \
+                soak checks{}",
+                pull.attempts,
+                format_wait(soak.timeout()),
+                batch_note(&self.head, pull.number),
+            );
+            pull.create_comment(config, github, CommentEvent::Soaking, &comment)
+                .await?;
+        }
+
+        Ok(Soak::Running)
+    }
+
+    /// Check if the batch at the head of the queue may land now, letting its PRs know the first
+    /// time it has to wait for the land window to open
+    async fn check_land_window(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        merge_oid: &Oid,
+    ) -> Result<bool> {
+        let wait = match config
+            .land_window()
+            .and_then(|window| time_until_open(window, Utc::now()))
+        {
+            Some(wait) => wait,
+            None => {
+                self.awaiting_land_window = None;
+                return Ok(true);
+            }
+        };
+
+        if self.awaiting_land_window.as_ref() == Some(merge_oid) {
+            return Ok(false);
+        }
+
+        info!(
+            "batch {:?} passed, waiting {} for the land window to open",
+            self.head,
+            format_wait(wait)
+        );
+        self.awaiting_land_window = Some(merge_oid.clone());
+
+        for number in &self.head {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            let comment = format!(
+                ":hourglass: Tests passed on attempt #{}. PRs only land during the land window, \
+                which opens in {}{}",
+                pull.attempts,
+                format_wait(wait),
+                batch_note(&self.head, pull.number),
+            );
+            pull.create_comment(config, github, CommentEvent::AwaitingLandWindow, &comment)
+                .await?;
+        }
+
+        Ok(false)
+    }
+
+    /// Return the PRs in the batch at the head of the queue to the queue to be retested after a
+    /// spurious failure, unless any of them has already been retried too many times
+    async fn retry_spurious_failure(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        name: &str,
+        result: &TestResult,
+    ) -> Result<bool> {
+        let max_retries = config.spurious_retries();
+
+        if self
+            .head
+            .iter()
+            .any(|number| pulls[number].spurious_retries >= max_retries)
+        {
+            return Ok(false);
+        }
+
+        let batch = std::mem::take(&mut self.head);
+        info!("retrying batch {:?} after spurious failure", batch);
+
+        for number in &batch {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            pull.spurious_retries += 1;
+            pull.update_status(Status::Queued, config, github, project_board)
+                .await?;
+
+            let comment = format!(
+                ":repeat: {} failed spuriously on attempt #{}; retrying ({} of {}){}{}",
+                link(name, &result.details_url),
+                pull.attempts,
+                pull.spurious_retries,
+                max_retries,
+                result.summary_note(),
+                result.known_issue_note(),
+            );
+            pull.create_comment(config, github, CommentEvent::SpuriousFailure, &comment)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Requeue a batch, whose tests passed, to be rebuilt on top of its base ref and retested
+    /// ahead of the rest of the queue because the base ref moved to `base_oid` before the batch
+    /// could land
+    #[allow(clippy::too_many_arguments)]
+    async fn rebuild_batch(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        batch: Vec<u64>,
+        base_ref: &str,
+        base_oid: &Oid,
+    ) -> Result<()> {
+        info!(
+            "'{}' moved to {} before batch {:?} landed; rebuilding it",
+            base_ref, base_oid, batch
+        );
+
+        for number in &batch {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            pull.update_status(Status::Queued, config, github, project_board)
+                .await?;
+
+            let comment = format!(
+                ":arrows_counterclockwise: Attempt #{} passed but `{}` moved to {} before it could \
+                land, so it can no longer be fast-forwarded to the tested merge commit. This PR is \
+                being rebased on top of the new `{}` and retested.{}",
+                pull.attempts,
+                base_ref,
+                base_oid,
+                base_ref,
+                batch_note(&batch, pull.number),
+            );
+            pull.create_comment(config, github, CommentEvent::BaseAdvanced, &comment)
+                .await?;
+        }
+
+        self.bisect.push_front(batch);
+        Ok(())
+    }
+
+    /// Split the failed batch at the head of the queue in two, returning its PRs to the queue so
+    /// that each half can be retested separately in order to find the PR responsible
+    async fn bisect_head(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        reason: &str,
+    ) -> Result<()> {
+        let batch = std::mem::take(&mut self.head);
+        info!("bisecting batch {:?}", batch);
+
+        for number in &batch {
+            let pull = pulls.get_mut(number).expect("PR should exist");
+            pull.update_status(Status::Queued, config, github, project_board)
+                .await?;
+
+            let comment = format!(
+                ":mag: Batch {} on attempt #{}; retesting it in smaller batches to find the cause{}",
+                reason,
+                pull.attempts,
+                batch_note(&batch, pull.number),
+            );
+            pull.create_comment(config, github, CommentEvent::Bisecting, &comment)
+                .await?;
+        }
+
+        self.push_bisected(&batch);
+
+        Ok(())
+    }
+
+    async fn process_next_head(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+    ) -> Result<()> {
+        assert!(self.head.is_empty());
+
+        // If none of a bisected half's PRs could be tested, e.g. if they were all canceled, move
+        // on to the next one
+        let (branch, _speculative) = self.branches(config);
+        let batch = loop {
+            let (candidates, bisecting) = self.next_candidates(config, pulls);
+
+            let batch = self
+                .create_batch(
+                    config,
+                    github,
+                    repo,
+                    project_board,
+                    pulls,
+                    candidates,
+                    branch,
+                    None,
+                )
+                .await?;
+
+            if !batch.is_empty() || !bisecting {
+                break batch;
+            }
+        };
+
+        self.head = self
+            .start_batch(
+                config,
+                github,
+                repo,
+                project_board,
+                pulls,
+                batch,
+                branch,
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Push the tip of `batch` to `branch` for testing and mark each of its PRs as being tested.
+    /// `behind` is the tip of the head when the batch is being tested speculatively.
+    #[allow(clippy::too_many_arguments)]
+    async fn start_batch(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        batch: Vec<(u64, Oid)>,
+        branch: &str,
+        behind: Option<u64>,
+    ) -> Result<Vec<u64>> {
+        let tip = match batch.last() {
+            Some((_number, merge_oid)) => merge_oid.clone(),
+            None => return Ok(Vec::new()),
+        };
+        let numbers: Vec<u64> = batch.iter().map(|(number, _merge_oid)| *number).collect();
+
+        repo.reset_branch(branch, &tip)?;
+        if config.unique_staging_branches() {
+            let attempt_branch = config.attempt_branch(branch, *numbers.last().unwrap());
+            repo.force_push(&attempt_branch, &tip)?;
+            info!("pushed '{}' branch", attempt_branch);
+        } else {
+            repo.push_branch(branch)?;
+            info!("pushed '{}' branch", branch);
+        }
+
+        for (number, merge_oid) in batch {
+            let pull = pulls.get_mut(&number).expect("PR should exist");
+            pull.attempts += 1;
+            info!("starting attempt #{} of pr #{}", pull.attempts, pull.number);
+            let overrides = pull.next_attempt.take().unwrap_or_default();
+            pull.update_status(
+                Status::testing(merge_oid, overrides),
+                config,
+                github,
+                project_board,
+            )
+            .await?;
+
+            let description = batch_description(&numbers, behind, pull.attempts);
+            let summary = testing_summary(&numbers, pull.number);
+            create_pending_status(config, github, pull, &description, &summary).await?;
+        }
+
+        Ok(numbers)
+    }
+
+    /// Build up a batch by rebasing the queued PRs in `candidates`, in order, on top of one
+    /// another on `branch`. Returns each PR in the batch along with its rebased commit.
+    ///
+    /// A speculative batch is built on top of `onto`, the base ref and merge commit of the head,
+    /// rather than on top of the base ref itself.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_batch(
+        &mut self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        repo: &mut GitRepository,
+        project_board: Option<&ProjectBoard>,
+        pulls: &mut HashMap<u64, PullRequestState>,
+        candidates: Vec<u64>,
+        branch: &str,
+        onto: Option<(&str, &Oid)>,
+    ) -> Result<Vec<(u64, Oid)>> {
+        let mut batch: Vec<(u64, Oid)> = Vec::new();
+
+        let candidates = batch_order(self.tree_closed, config, pulls, candidates);
+        let onto_base = onto.map(|(base_ref_name, _oid)| base_ref_name);
+        for number in candidates {
+            let numbers: Vec<u64> = batch.iter().map(|(number, _)| *number).collect();
+            match admission(self.tree_closed, config, pulls, &numbers, onto_base, number) {
+                Admission::Admit => {}
+                Admission::Skip => continue,
+                Admission::Stop => break,
+            }
+
+            let pull = pulls.get_mut(&number).expect("PR should exist");
+
+            // An approval pinned to a reviewed head doesn't carry over to changes pushed since
+            if let Some(approved_oid) = pull.approved_oid.clone() {
+                if approved_oid != pull.head_ref_oid {
+                    info!(
+                        "pr #{} was approved at {} but its head is now {}",
+                        pull.number, approved_oid, pull.head_ref_oid
+                    );
+
+                    pull.approver = None;
+                    pull.approved_oid = None;
+                    pull.reviewer = None;
+                    pull.update_status(Status::InReview, config, github, project_board)
+                        .await?;
+
+                    let comment = format!(
+                        ":warning: This PR's head changed from `{}`, the commit which was \
+                        approved, to `{}`. Its latest changes need to be reviewed and approved \
+                        again before it can land.",
+                        approved_oid, pull.head_ref_oid,
+                    );
+                    pull.create_comment(config, github, CommentEvent::StaleApproval, &comment)
+                        .await?;
+                    continue;
+                }
+            }
+
+            info!("Creating merge for pr #{}", pull.number);
+
+            let strategy = pull.merge_strategy(config);
+            let tip = batch
+                .last()
+                .map(|(_number, merge_oid)| merge_oid.clone())
+                .or_else(|| onto.map(|(_base_ref_name, oid)| oid.clone()));
+
+            if let Some(tip) = tip {
+                // Attempt to rebase the PR on top of the rest of the batch. A PR which conflicts
+                // with the batch, but not necessarily with 'base_ref', is left in the queue.
+                if let Some(merge_oid) = repo.fetch_and_rebase_onto(
+                    &tip,
+                    &pull.head_ref_oid,
+                    branch,
+                    pull.number,
+                    &annotations(config, pull),
+                    strategy,
+                )? {
+                    batch.push((number, merge_oid));
+                } else {
+                    info!(
+                        "pr #{} conflicts with the current batch; leaving it queued",
+                        pull.number
+                    );
+                }
+
+            // Attempt to rebase the PR onto 'base_ref'
+            } else if let Some(merge_oid) = repo.fetch_and_rebase(
+                &pull.base_ref_name,
+                &pull.head_ref_oid,
+                branch,
+                pull.number,
+                &annotations(config, pull),
+                strategy,
+            )? {
+                batch.push((number, merge_oid));
             } else {
                 pull.update_status(Status::InReview, config, github, project_board)
                     .await?;
 
-                github
-                    .repos()
-                    .create_status(
-                        config.owner(),
-                        config.name(),
-                        &pull.head_ref_oid.to_string(),
-                        &github::client::CreateStatusRequest {
-                            state: github::StatusEventState::Error,
-                            target_url: None,
-                            description: Some("Merge Conflict"),
-                            context: "bors",
-                        },
-                    )
-                    .await?;
+                report_status(
+                    config,
+                    github,
+                    &pull.head_ref_oid,
+                    github::StatusEventState::Error,
+                    &StatusDescription::new("Merge Conflict"),
+                    None,
+                    &format!(
+                        "The PR couldn't be merged into `{}`, it needs to be rebased",
+                        pull.base_ref_name
+                    ),
+                )
+                .await?;
+
+                pull.create_comment(
+                    config,
+                    github,
+                    CommentEvent::MergeConflict,
+                    ":lock: Merge Conflict",
+                )
+                .await?;
+            }
+        }
+
+        Ok(batch)
+    }
+}
+
+/// Find a check which hasn't reported a result within its timeout, `elapsed` since testing of PRs
+/// into `base_ref` started
+pub fn timed_out_check<'a>(
+    config: &'a RepoConfig,
+    base_ref: &str,
+    overrides: &'a AttemptOverrides,
+    elapsed: std::time::Duration,
+    test_results: &HashMap<String, TestResult>,
+) -> Option<&'a str> {
+    overrides.checks(config, base_ref).into_iter().find(|name| {
+        !test_results.contains_key(*name)
+            && elapsed >= overrides.check_timeout(config, base_ref, name)
+    })
+}
+
+/// Check if a queued PR can be tested now
+fn is_ready_to_test(
+    tree_closed: Option<u32>,
+    config: &RepoConfig,
+    pull: &PullRequestState,
+) -> bool {
+    pull.status.is_queued() && hold_reason(tree_closed, config, pull).is_none()
+}
 
-                github
-                    .issues()
-                    .create_comment(
-                        config.owner(),
-                        config.name(),
-                        pull.number,
-                        ":lock: Merge Conflict",
+/// Explain why a queued PR is being passed over, if it is. PRs whose dependencies haven't landed
+/// yet are held back, as are PRs below the threshold while the tree is closed, PRs which are
+/// cooling down after a failed land and PRs whose latest pushes haven't settled.
+pub fn hold_reason(
+    tree_closed: Option<u32>,
+    config: &RepoConfig,
+    pull: &PullRequestState,
+) -> Option<String> {
+    let waiting_on = pull.unlanded_dependencies();
+    if !waiting_on.is_empty() {
+        Some(format!("waiting on {}", pr_list(&waiting_on)))
+    } else if is_tree_closed_for(tree_closed, config, pull) {
+        Some("held back by the closed tree".to_owned())
+    } else if pull.cooldown_remaining().is_some() {
+        Some("cooling down after a failed land".to_owned())
+    } else if pull.grace_remaining(config).is_some() {
+        Some("in its grace period after being queued".to_owned())
+    } else if pull.push_settling {
+        Some("waiting for pushes to it to settle".to_owned())
+    } else {
+        None
+    }
+}
+
+/// Estimate how long until the PR at `position` in the queue starts being tested, given that
+/// batches of up to `batch_size` PRs take `average` to test and the batch being tested is expected
+/// to finish in `head_remaining`
+fn estimated_wait(
+    position: usize,
+    batch_size: usize,
+    average: Duration,
+    head_remaining: Duration,
+) -> Duration {
+    let batches_ahead = (position.saturating_sub(1) / batch_size.max(1)) as u32;
+    head_remaining + average * batches_ahead
+}
+
+/// Estimate how long until each queued PR starts being tested, from how long the checks of the
+/// batch being tested, or else of the next PR to be tested, usually take to pass and how long the
+/// batch at the head of the queue has been testing
+pub fn estimated_waits(
+    config: &RepoConfig,
+    pulls: &HashMap<u64, PullRequestState>,
+    durations: &CheckDurations,
+) -> HashMap<u64, Duration> {
+    let queue = queue_order(config, pulls);
+    let base_ref = match pulls
+        .values()
+        .find(|pull| pull.status.is_testing())
+        .or_else(|| queue.first().map(|number| &pulls[number]))
+    {
+        Some(pull) => &pull.base_ref_name,
+        None => return HashMap::new(),
+    };
+    let expected = match durations.expected(config.checks(base_ref)) {
+        Some(expected) => expected,
+        None => return HashMap::new(),
+    };
+
+    // The head started testing before any speculative batch behind it
+    let head_remaining = pulls
+        .values()
+        .filter_map(|pull| match &pull.status {
+            Status::Testing {
+                tests_started_at, ..
+            } => Some(*tests_started_at),
+            _ => None,
+        })
+        .min()
+        .map(|started_at| {
+            expected
+                .checked_sub(started_at.elapsed())
+                .unwrap_or_default()
+        })
+        .unwrap_or_default();
+
+    queue
+        .into_iter()
+        .enumerate()
+        .map(|(idx, number)| {
+            let wait = estimated_wait(idx + 1, config.max_batch_size(), expected, head_remaining);
+            (number, wait)
+        })
+        .collect()
+}
+
+/// How long until the first of the queued PRs which are in their grace period can be tested
+pub fn grace_period_wait(
+    config: &RepoConfig,
+    pulls: &HashMap<u64, PullRequestState>,
+) -> Option<std::time::Duration> {
+    pulls
+        .values()
+        .filter(|pull| pull.status.is_queued())
+        .filter_map(|pull| pull.grace_remaining(config))
+        .min()
+}
+
+/// Reorder `candidates` for building a batch. The first PR ready to be tested leads the batch and
+/// is followed by the rest in order of priority. Among PRs of the same priority, those flagged
+/// `rollup=always` are pulled into batches ahead of the others.
+pub fn batch_order(
+    tree_closed: Option<u32>,
+    config: &RepoConfig,
+    pulls: &HashMap<u64, PullRequestState>,
+    mut candidates: Vec<u64>,
+) -> Vec<u64> {
+    let first = candidates.iter().position(|number| {
+        pulls
+            .get(number)
+            .map(|pull| is_ready_to_test(tree_closed, config, pull))
+            .unwrap_or(false)
+    });
+
+    if let Some(first) = first {
+        // A stable sort, so PRs otherwise remain in queue order
+        candidates[first + 1..].sort_by_key(|number| {
+            pulls
+                .get(number)
+                .map(|pull| {
+                    (
+                        Reverse(pull.queue_priority(config)),
+                        pull.rollup(config) != Rollup::Always,
                     )
-                    .await?;
+                })
+                .unwrap_or((Reverse(0), true))
+        });
+    }
+
+    candidates
+}
+
+/// Check if `pull` can join the batch starting with `first`. Solo PRs are always tested on their
+/// own and a batch can only contain PRs which target the same base ref.
+fn can_join_batch(config: &RepoConfig, first: &PullRequestState, pull: &PullRequestState) -> bool {
+    !first.is_solo(config) && !pull.is_solo(config) && first.base_ref_name == pull.base_ref_name
+}
+
+/// What becomes of a candidate PR while a batch is being built
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    /// The PR joins the batch, provided it can be rebased on top of the rest of it
+    Admit,
+    /// The PR isn't ready to be tested and is passed over
+    Skip,
+    /// The batch is complete, leaving the PR and those behind it for a later batch
+    Stop,
+}
+
+/// Decide whether `number`, the next of a batch's candidates in `batch_order`, joins `batch`, the
+/// PRs already in it. A speculative batch only takes PRs into `onto_base`, the base ref of the
+/// head it's built on top of.
+pub fn admission(
+    tree_closed: Option<u32>,
+    config: &RepoConfig,
+    pulls: &HashMap<u64, PullRequestState>,
+    batch: &[u64],
+    onto_base: Option<&str>,
+    number: u64,
+) -> Admission {
+    if batch.len() >= config.max_batch_size() {
+        return Admission::Stop;
+    }
+
+    let pull = match pulls.get(&number) {
+        Some(pull) if is_ready_to_test(tree_closed, config, pull) => pull,
+        _ => return Admission::Skip,
+    };
+
+    // Stop at the first PR which can't join the batch so that it isn't passed over by the PRs
+    // queued behind it
+    match batch.first() {
+        Some(first) if !can_join_batch(config, &pulls[first], pull) => Admission::Stop,
+        None if onto_base.map_or(false, |base_ref_name| pull.base_ref_name != base_ref_name) => {
+            Admission::Stop
+        }
+        _ => Admission::Admit,
+    }
+}
+
+/// The numbers of the queued PRs, in the order they're to be tested
+pub fn queue_order(config: &RepoConfig, pulls: &HashMap<u64, PullRequestState>) -> Vec<u64> {
+    let mut queue: Vec<_> = pulls
+        .values()
+        .filter(|p| p.status.is_queued())
+        .map(|p| QueueEntry {
+            number: p.number,
+            priority: p.queue_priority(config),
+            queue_number: p.queue_number(),
+        })
+        .collect();
+    queue.sort_unstable();
+
+    let fairness = match config.fairness() {
+        Some(fairness) => fairness,
+        None => return queue.into_iter().map(|entry| entry.number).collect(),
+    };
+    interleave(
+        queue
+            .into_iter()
+            .map(|entry| {
+                let author = pulls[&entry.number].author.as_deref().unwrap_or_default();
+                (entry.number, entry.priority, fairness.group(author))
+            })
+            .collect(),
+    )
+}
+
+/// Reorder a sorted queue of `(number, priority, group)` entries so that, among PRs of the same
+/// priority, each group's PRs take turns with those of the other groups
+fn interleave(queue: Vec<(u64, u32, &str)>) -> Vec<u64> {
+    let mut turns: HashMap<(u32, &str), usize> = HashMap::new();
+    let mut order: Vec<_> = queue
+        .into_iter()
+        .enumerate()
+        .map(|(position, (number, priority, group))| {
+            let turn = turns.entry((priority, group)).or_insert(0);
+            *turn += 1;
+            (Reverse(priority), *turn, position, number)
+        })
+        .collect();
+    order.sort_unstable();
+
+    order.into_iter().map(|(.., number)| number).collect()
+}
+
+/// Stop testing a batch, whose last PR is `tip`, which was dequeued by canceling any GitHub
+/// Actions workflow runs still in progress on the branch it was pushed to, and then deleting the
+/// branch if it was pushed to one of its own. Only GitHub Actions runs can be canceled through
+/// Github, so other CI systems, whether they report commit statuses or check runs, keep testing
+/// until they finish and their results are then ignored. Failures are only logged since they
+/// don't affect the state of the queue.
+async fn cancel_ci(
+    config: &RepoConfig,
+    github: &GithubClient,
+    repo: &mut GitRepository,
+    branch: &str,
+    tip: u64,
+) {
+    let attempt_branch = config.attempt_branch(branch, tip);
+
+    let mut runs = Vec::new();
+    let mut page = Some(1);
+    while let Some(current) = page {
+        let options = ListWorkflowRunsOptions {
+            branch: Some(&attempt_branch),
+            pagination_options: PaginationOptions {
+                page: Some(current),
+                per_page: Some(100),
+            },
+            ..Default::default()
+        };
+
+        match github
+            .actions()
+            .list_workflow_runs(config.owner(), config.name(), &options)
+            .await
+        {
+            Ok(response) => {
+                page = response.pagination().next_page;
+                runs.extend(response.into_inner().workflow_runs);
+            }
+            Err(e) => {
+                warn!(
+                    "unable to list workflow runs on '{}': {}",
+                    attempt_branch, e
+                );
+                break;
             }
         }
+    }
 
-        Ok(())
+    for run in runs {
+        if run.status.as_deref() == Some("completed") {
+            continue;
+        }
+
+        info!("canceling workflow run {} on '{}'", run.id, attempt_branch);
+        if let Err(e) = github
+            .actions()
+            .cancel_workflow_run(config.owner(), config.name(), run.id)
+            .await
+        {
+            warn!("unable to cancel workflow run {}: {}", run.id, e);
+        }
+    }
+
+    // The shared staging and speculative branches are left in place for the next batch
+    delete_attempt_branch(config, repo, branch, tip);
+}
+
+/// Update a PR in-place with the rebased commits about to land, retrying failures which may be
+/// transient. The PR's branch is fetched again before each retry in case it was pushed to in the
+/// meantime. Returns why the update failed, if it did.
+pub async fn update_in_place(
+    config: &RepoConfig,
+    repo: &mut GitRepository,
+    head_repo: &Repo,
+    pull: &PullRequestState,
+    merge_oid: &Oid,
+) -> Option<PushFailure> {
+    let mut retries = 0;
+    loop {
+        let error = match repo.push_to_remote(
+            head_repo,
+            &pull.head_ref_name,
+            &pull.head_ref_oid,
+            merge_oid,
+        ) {
+            Ok(()) => return None,
+            Err(e) => e,
+        };
+
+        let failure = PushFailure::classify(&error);
+        warn!(
+            "updating pr #{} in-place failed ({:?}): {}",
+            pull.number, failure, error
+        );
+        if failure != PushFailure::Transient || retries >= config.in_place_update_retries() {
+            return Some(failure);
+        }
+
+        retries += 1;
+        tokio::time::delay_for(IN_PLACE_RETRY_DELAY * retries).await;
+
+        match repo.fetch_remote_branch(head_repo, &pull.head_ref_name) {
+            Ok(oid) if oid != pull.head_ref_oid => return Some(PushFailure::Stale),
+            Ok(_) => {}
+            // Most likely the same problem the push ran into, so try pushing again regardless
+            Err(e) => warn!("unable to fetch pr #{}'s branch: {}", pull.number, e),
+        }
+    }
+}
+
+/// Delete the branch a completed attempt was pushed to, if each attempt is pushed to a branch of
+/// its own. Failures are only logged since they don't affect the state of the queue.
+fn delete_attempt_branch(config: &RepoConfig, repo: &mut GitRepository, branch: &str, tip: u64) {
+    if !config.unique_staging_branches() {
+        return;
+    }
+
+    let attempt_branch = config.attempt_branch(branch, tip);
+    if let Err(e) = repo.delete_remote_branch(&attempt_branch) {
+        warn!("unable to delete '{}' branch: {}", attempt_branch, e);
+    }
+}
+
+/// Count a landed PR towards its author's contributor stats, celebrating their first landed PR if
+/// configured to. Failing to update the stats, or to celebrate, is only logged since they don't
+/// affect landing.
+async fn record_contributor(
+    config: &RepoConfig,
+    github: &GithubClient,
+    state_dir: &Path,
+    pull: &PullRequestState,
+) {
+    let author = match &pull.author {
+        Some(author) => author,
+        None => return,
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let landed = Contributors::load(state_dir).and_then(|mut contributors| {
+        let landed = contributors.record_land(author, now);
+        contributors.save(state_dir)?;
+        Ok(landed)
+    });
+    let landed = match landed {
+        Ok(landed) => landed,
+        Err(e) => {
+            warn!("unable to record contributor stats: {:?}", e);
+            return;
+        }
+    };
+
+    if let (1, Some(message)) = (landed, config.first_land_message()) {
+        let comment = format!("@{} {}", author, message);
+        if let Err(e) = pull
+            .create_comment(config, github, CommentEvent::FirstLand, &comment)
+            .await
+        {
+            warn!("unable to celebrate the first land of {}: {:?}", author, e);
+        }
+    }
+}
+
+/// Retarget any PRs stacked on top of `landed` onto its base branch now that it has landed
+async fn retarget_stacked(
+    config: &RepoConfig,
+    github: &GithubClient,
+    landed: &PullRequestState,
+    pulls: &mut HashMap<u64, PullRequestState>,
+) {
+    if landed.head_repo.as_ref() != Some(config.repo()) {
+        return;
+    }
+
+    for pull in pulls.values_mut() {
+        if pull.base_ref_name != landed.head_ref_name {
+            continue;
+        }
+
+        info!(
+            "retargeting pr #{} from '{}' to '{}'",
+            pull.number, pull.base_ref_name, landed.base_ref_name
+        );
+        let update = github::client::UpdatePullRequest {
+            base: Some(landed.base_ref_name.clone()),
+            ..Default::default()
+        };
+        match github
+            .pulls()
+            .update(config.owner(), config.name(), pull.number, update)
+            .await
+        {
+            Ok(_) => pull.base_ref_name = landed.base_ref_name.clone(),
+            Err(e) => warn!("unable to retarget pr #{}: {}", pull.number, e),
+        }
+    }
+}
+
+/// Fetch the tip of `base_ref`, checking whether it has advanced past the base `merge_oid` was
+/// built on
+fn base_advanced(repo: &mut GitRepository, base_ref: &str, merge_oid: &Oid) -> Result<(Oid, bool)> {
+    let base_oid = repo.fetch_base(base_ref)?;
+    let advanced = !repo.is_ancestor(&base_oid, merge_oid)?;
+    Ok((base_oid, advanced))
+}
+
+/// Fail each of a batch's PRs, whose tests passed, because `base_ref` was advanced outside of bors
+/// while the batch was being tested
+async fn fail_base_advanced(
+    config: &RepoConfig,
+    github: &GithubClient,
+    project_board: Option<&ProjectBoard>,
+    pulls: &mut HashMap<u64, PullRequestState>,
+    batch: &[u64],
+    base_ref: &str,
+) -> Result<()> {
+    for number in batch {
+        let pull = pulls.get_mut(number).expect("PR should exist");
+        let test_results = match &pull.status {
+            Status::Testing { test_results, .. } => test_results.clone(),
+            _ => HashMap::new(),
+        };
+
+        let reason = format!("`{}` was updated outside of bors", base_ref);
+        pull.update_status(
+            Status::failed(reason, test_results),
+            config,
+            github,
+            project_board,
+        )
+        .await?;
+
+        let comment = format!(
+            ":rotating_light: Attempt #{} passed but `{}` was updated outside of bors while it was \
+            being tested, so it can't be landed without discarding those commits. `{}` was left \
+            untouched; approve this PR again to retest it on top of the updated `{}`.{}{}",
+            pull.attempts,
+            base_ref,
+            base_ref,
+            base_ref,
+            batch_note(batch, pull.number),
+            cc_reviewers(config, pull),
+        );
+        pull.create_comment(config, github, CommentEvent::BaseAdvanced, &comment)
+            .await?;
+        pull.report_check_run(
+            config,
+            github,
+            &pull.head_ref_oid,
+            Some(Conclusion::Failure),
+            None,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Check if `pull` is held back from being tested because the tree is closed
+pub fn is_tree_closed_for(
+    tree_closed: Option<u32>,
+    config: &RepoConfig,
+    pull: &PullRequestState,
+) -> bool {
+    tree_closed
+        .map(|threshold| pull.queue_priority(config) < threshold)
+        .unwrap_or(false)
+}
+
+/// Check if every PR in `batch` is still being tested
+fn all_testing(batch: &[u64], pulls: &HashMap<u64, PullRequestState>) -> bool {
+    batch.iter().all(|number| {
+        pulls
+            .get(number)
+            .map(|pull| pull.status.is_testing())
+            .unwrap_or(false)
+    })
+}
+
+/// Describes how a batch is being tested, for its PRs' pending commit statuses
+fn batch_description(batch: &[u64], behind: Option<u64>, attempt: u32) -> StatusDescription {
+    let description = StatusDescription::new(format!("Attempt #{}", attempt));
+    match behind {
+        Some(head) => description.detail(
+            Importance::High,
+            format!("testing speculatively behind #{}", head),
+        ),
+        None if batch.len() > 1 => description.detail(
+            Importance::High,
+            format!("testing in a batch of {} PRs", batch.len()),
+        ),
+        None => description,
+    }
+}
+
+/// Describes what is being tested, for the summary of a PR's pending "bors" check run
+fn testing_summary(batch: &[u64], number: u64) -> String {
+    format!(
+        "Testing the merge commit of #{}{}",
+        number,
+        batch_note(batch, number)
+    )
+}
+
+/// Markdown table of the results reported by each of `checks`, for the summaries of "bors" check
+/// runs
+fn results_table<'a>(
+    checks: impl Iterator<Item = &'a str>,
+    test_results: &HashMap<String, TestResult>,
+) -> String {
+    let mut table = "| Check | Result |\n| --- | --- |\n".to_owned();
+    for name in checks {
+        let result = match test_results.get(name) {
+            Some(result) if result.passed => {
+                format!(":white_check_mark: {}", link("passed", &result.details_url))
+            }
+            Some(result) => format!(":x: {}", link("failed", &result.details_url)),
+            None => ":hourglass: no result".to_owned(),
+        };
+        table.push_str(&format!("| {} | {} |\n", name.replace('|', "\\|"), result));
+    }
+    table
+}
+
+pub async fn create_pending_status(
+    config: &RepoConfig,
+    github: &GithubClient,
+    pull: &PullRequestState,
+    description: &StatusDescription,
+    summary: &str,
+) -> Result<()> {
+    report_status(
+        config,
+        github,
+        &pull.head_ref_oid,
+        github::StatusEventState::Pending,
+        description,
+        None,
+        summary,
+    )
+    .await
+}
+
+/// Report the "bors" status of `oid`, as a commit status or, if the repo is configured with a
+/// `check-run`, as that check run whose output also carries `summary`. Check runs are reported on
+/// a best-effort basis, like `PullRequestState::report_check_run`.
+pub async fn report_status(
+    config: &RepoConfig,
+    github: &GithubClient,
+    oid: &Oid,
+    state: github::StatusEventState,
+    description: &StatusDescription,
+    target_url: Option<&str>,
+    summary: &str,
+) -> Result<()> {
+    let description = description.build();
+    let name = match config.check_run() {
+        Some(name) => name,
+        None => {
+            github
+                .repos()
+                .create_status(
+                    config.owner(),
+                    config.name(),
+                    &oid.to_string(),
+                    &github::client::CreateStatusRequest {
+                        state,
+                        target_url,
+                        description: Some(&description),
+                        context: "bors",
+                    },
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let (status, conclusion) = match state {
+        github::StatusEventState::Pending => (Some(CheckStatus::InProgress), None),
+        github::StatusEventState::Success => (None, Some(Conclusion::Success)),
+        github::StatusEventState::Failure | github::StatusEventState::Error => {
+            (None, Some(Conclusion::Failure))
+        }
+    };
+    let request = CreateCheckRunRequest {
+        name,
+        head_sha: &oid.to_string(),
+        details_url: target_url,
+        external_id: None,
+        status,
+        conclusion,
+        output: Some(CheckRunOutput {
+            title: &description,
+            summary,
+            text: None,
+        }),
+    };
+    if let Err(e) = upsert_check_run(config, github, &request).await {
+        warn!(
+            "unable to report the '{}' check run of {}: {:?}",
+            name, oid, e
+        );
+    }
+    Ok(())
+}
+
+/// Link the optional checks which failed, if any did
+pub fn optional_failures(
+    config: &RepoConfig,
+    base_ref: &str,
+    test_results: &HashMap<String, TestResult>,
+) -> Option<String> {
+    let failures: Vec<_> = config
+        .optional_checks(base_ref)
+        .filter_map(|name| test_results.get(name).map(|result| (name, result)))
+        .filter(|(_name, result)| !result.passed)
+        .map(|(name, result)| link(name, &result.details_url))
+        .collect();
+
+    if failures.is_empty() {
+        None
+    } else {
+        Some(failures.join(", "))
+    }
+}
+
+/// Returns a line listing the other PRs a PR was tested alongside, if it was part of a batch
+fn batch_note(batch: &[u64], number: u64) -> String {
+    let others: Vec<_> = batch
+        .iter()
+        .filter(|n| **n != number)
+        .map(|n| format!("#{}", n))
+        .collect();
+
+    if others.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nTested in a batch with {}", others.join(", "))
+    }
+}
+
+/// Returns a line cc'ing the PR's author and approving reviewers if the repo is configured to do so
+fn cc_reviewers(config: &RepoConfig, pull: &PullRequestState) -> String {
+    if config.cc_reviewers() {
+        pull.cc_reviewers()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{estimated_wait, interleave, results_table};
+    use crate::state::TestResult;
+    use std::collections::HashMap;
+
+    #[test]
+    fn authors_take_turns() {
+        let queue = vec![
+            (1, 1, "carol"),
+            (2, 0, "alice"),
+            (3, 0, "alice"),
+            (4, 0, "alice"),
+            (5, 0, "bob"),
+            (6, 0, "infra"),
+            (7, 0, "bob"),
+        ];
+
+        assert_eq!(interleave(queue), vec![1, 2, 5, 6, 3, 7, 4]);
+    }
+
+    #[test]
+    fn estimating_waits() {
+        let minutes = |m: u64| std::time::Duration::from_secs(m * 60);
+
+        assert_eq!(estimated_wait(1, 1, minutes(30), minutes(10)), minutes(10));
+        assert_eq!(estimated_wait(3, 1, minutes(30), minutes(10)), minutes(70));
+        assert_eq!(estimated_wait(4, 4, minutes(30), minutes(0)), minutes(0));
+        assert_eq!(estimated_wait(5, 4, minutes(30), minutes(0)), minutes(30));
+    }
+
+    #[test]
+    fn results_tables() {
+        let result = |passed| TestResult {
+            passed,
+            details_url: "https://ci.example.com/1".to_owned(),
+            summary: None,
+            spurious: false,
+            known_issue: None,
+        };
+        let mut test_results = HashMap::new();
+        test_results.insert("ci/test".to_owned(), result(true));
+        test_results.insert("ci/lint".to_owned(), result(false));
+
+        assert_eq!(
+            results_table(
+                vec!["ci/test", "ci/lint", "ci|doc"].into_iter(),
+                &test_results
+            ),
+            "| Check | Result |\n\
+            | --- | --- |\n\
+            | ci/test | :white_check_mark: [passed](https://ci.example.com/1) |\n\
+            | ci/lint | :x: [failed](https://ci.example.com/1) |\n\
+            | ci\\|doc | :hourglass: no result |\n"
+        );
     }
 }
@@ -0,0 +1,293 @@
+//! Health checks of a repo's bors installation, reported by the `selfcheck` command so that admins
+//! can tell whether bors has everything it needs before PRs start failing to land
+
+use crate::{
+    comment::sanitize,
+    config::{RepoConfig, Role},
+    event_processor::CommandContext,
+    graphql::GithubClient,
+    Result,
+};
+use github::client::PaginationOptions;
+use std::collections::HashSet;
+
+/// Number of recent webhook deliveries inspected
+const RECENT_DELIVERIES: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The check doesn't apply to the repo's configuration
+    Skipped,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Pass => "ok",
+            Outcome::Fail => "FAIL",
+            Outcome::Skipped => "skip",
+        }
+    }
+
+    fn emoji(self) -> &'static str {
+        match self {
+            Outcome::Pass => ":white_check_mark:",
+            Outcome::Fail => ":x:",
+            Outcome::Skipped => ":heavy_minus_sign:",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SelfCheck {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl SelfCheck {
+    pub fn new(name: &'static str, outcome: Outcome, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+
+    /// Errors, e.g. a request Github refused, fail the check
+    pub fn from_result(name: &'static str, result: Result<(Outcome, String)>) -> Self {
+        match result {
+            Ok((outcome, detail)) => Self::new(name, outcome, detail),
+            Err(e) => Self::new(name, Outcome::Fail, sanitize(&format!("error: {}", e))),
+        }
+    }
+}
+
+/// Run each of the checks against the repo, in the order they're reported
+pub async fn run(ctx: &mut CommandContext<'_>) -> Vec<SelfCheck> {
+    vec![
+        SelfCheck::from_result(
+            "Webhook deliveries",
+            webhook_deliveries(ctx.github(), ctx.config()).await,
+        ),
+        SelfCheck::from_result(
+            "Token permissions",
+            token_permissions(ctx.github(), ctx.config()).await,
+        ),
+        SelfCheck::from_result("Staging branch", staging_branch(ctx).await),
+        SelfCheck::from_result("Configured checks", configured_checks(ctx).await),
+        SelfCheck::from_result("Project board", project_board(ctx).await),
+    ]
+}
+
+/// Format the results of the checks as a markdown table. Details are expected to have had any text
+/// which isn't controlled by bors sanitized already.
+pub fn report(checks: &[SelfCheck]) -> String {
+    let mut report = String::from("| Check | Result | Details |\n| --- | --- | --- |\n");
+    for check in checks {
+        report.push_str(&format!(
+            "| {} | {} | {} |\n",
+            check.name,
+            check.outcome.emoji(),
+            check.detail,
+        ));
+    }
+    report
+}
+
+/// Check that Github has recently been able to deliver webhooks to bors
+pub async fn webhook_deliveries(
+    github: &GithubClient,
+    config: &RepoConfig,
+) -> Result<(Outcome, String)> {
+    let hook_id = match config.webhook_id() {
+        Some(hook_id) => hook_id,
+        None => {
+            return Ok((
+                Outcome::Skipped,
+                "set `webhook-id` to inspect recent deliveries".to_owned(),
+            ))
+        }
+    };
+
+    let deliveries = github
+        .repos()
+        .list_hook_deliveries(
+            config.owner(),
+            config.name(),
+            hook_id,
+            PaginationOptions {
+                page: None,
+                per_page: Some(RECENT_DELIVERIES),
+            },
+        )
+        .await?
+        .into_inner();
+
+    let failed = deliveries
+        .iter()
+        .filter(|delivery| !(200..300).contains(&delivery.status_code))
+        .count();
+    let outcome = match deliveries.first() {
+        Some(latest) if (200..300).contains(&latest.status_code) => Outcome::Pass,
+        _ => Outcome::Fail,
+    };
+
+    Ok((
+        outcome,
+        format!(
+            "{} of the last {} deliveries failed",
+            failed,
+            deliveries.len()
+        ),
+    ))
+}
+
+/// Check that the user bors acts as can push, report statuses and comment on the repo, all of
+/// which need write access. An installation of a Github App isn't a user, its permissions are those
+/// granted to the app.
+pub async fn token_permissions(
+    github: &GithubClient,
+    config: &RepoConfig,
+) -> Result<(Outcome, String)> {
+    if let Some(login) = github.app_login() {
+        return Ok((
+            Outcome::Skipped,
+            format!(
+                "'{}' is a Github App, whose permissions are set in its settings",
+                login
+            ),
+        ));
+    }
+
+    let login = github.users().get_authenticated().await?.into_inner().login;
+    let level = github
+        .repos()
+        .get_collaborator_permission_level(config.owner(), config.name(), &login)
+        .await?
+        .into_inner();
+
+    let role = Role::from_permission_level(&level);
+    let outcome = if role >= Role::Write {
+        Outcome::Pass
+    } else {
+        Outcome::Fail
+    };
+
+    Ok((
+        outcome,
+        format!(
+            "'{}' has '{}' permission, '{}' is needed",
+            login,
+            role.as_str(),
+            Role::Write.as_str()
+        ),
+    ))
+}
+
+/// Check that bors can push branches by pushing the PR's base to a scratch branch next to the
+/// staging branch, which is deleted again straight away
+async fn staging_branch(ctx: &mut CommandContext<'_>) -> Result<(Outcome, String)> {
+    let base_ref = ctx.pr().base_ref_name.clone();
+    let branch = format!("{}-selfcheck", ctx.config().staging_branch());
+
+    let base_oid = ctx.git_repository().fetch_base(&base_ref)?;
+    ctx.git_repository().force_push(&branch, &base_oid)?;
+    ctx.git_repository().delete_remote_branch(&branch)?;
+
+    Ok((Outcome::Pass, format!("pushed and deleted '{}'", branch)))
+}
+
+/// Check that each of the required checks has been reported on the tip of the PR's base branch,
+/// which was the last commit to be tested and landed
+async fn configured_checks(ctx: &CommandContext<'_>) -> Result<(Outcome, String)> {
+    let config = ctx.config();
+    let base_ref = &ctx.pr().base_ref_name;
+
+    let configured: Vec<_> = config.checks(base_ref).collect();
+    if configured.is_empty() {
+        return Ok((Outcome::Skipped, "no checks are required".to_owned()));
+    }
+
+    let mut observed: HashSet<String> = ctx
+        .github()
+        .checks()
+        .list_check_runs_for_ref(config.owner(), config.name(), base_ref)
+        .await?
+        .into_inner()
+        .check_runs
+        .into_iter()
+        .map(|check_run| check_run.name)
+        .collect();
+    observed.extend(
+        ctx.github()
+            .repos()
+            .list_statuses(
+                config.owner(),
+                config.name(),
+                base_ref,
+                PaginationOptions {
+                    page: None,
+                    per_page: Some(100),
+                },
+            )
+            .await?
+            .into_inner()
+            .into_iter()
+            .map(|status| status.context),
+    );
+
+    let missing: Vec<_> = configured
+        .iter()
+        .filter(|check| !observed.contains(**check))
+        .map(|check| format!("'{}'", sanitize(check)))
+        .collect();
+    if missing.is_empty() {
+        Ok((
+            Outcome::Pass,
+            format!("all {} seen on '{}'", configured.len(), base_ref),
+        ))
+    } else {
+        Ok((
+            Outcome::Fail,
+            format!("not seen on '{}': {}", base_ref, missing.join(", ")),
+        ))
+    }
+}
+
+/// Check that the merge queue's project board can still be read
+async fn project_board(ctx: &CommandContext<'_>) -> Result<(Outcome, String)> {
+    let board = match ctx.project_board() {
+        Some(board) => board.board(),
+        None => return Ok((Outcome::Fail, "hasn't been set up yet".to_owned())),
+    };
+
+    let project = ctx.github().projects().get(board.id).await?.into_inner();
+
+    Ok((Outcome::Pass, project.html_url))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{report, Outcome, SelfCheck};
+
+    #[test]
+    fn report_table() {
+        let checks = vec![
+            SelfCheck::new("Token permissions", Outcome::Pass, "'bors' has 'write'"),
+            SelfCheck::new("Configured checks", Outcome::Fail, "not seen: 'ci/test'"),
+            SelfCheck::new("Webhook deliveries", Outcome::Skipped, "no webhook"),
+        ];
+
+        assert_eq!(
+            report(&checks),
+            "| Check | Result | Details |\n\
+            | --- | --- | --- |\n\
+            | Token permissions | :white_check_mark: | 'bors' has 'write' |\n\
+            | Configured checks | :x: | not seen: 'ci/test' |\n\
+            | Webhook deliveries | :heavy_minus_sign: | no webhook |\n"
+        );
+    }
+}
@@ -1,5 +1,7 @@
-use crate::{event_processor::EventProcessor, Config, Result};
-use probot::{Installation, Server};
+use crate::{enroll::Enroller, Config, Result};
+use log::{error, info, warn};
+use probot::Server;
+use std::path::Path;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -13,21 +15,34 @@ pub struct ServeOptions {
 }
 
 //TODO Make sure to join and await on all of the JoinHandles of the tasks that get spawned
-pub async fn run_serve(config: Config, options: &ServeOptions) -> Result<()> {
-    let Config { repo, github, git } = config;
+pub async fn run_serve(
+    mut config: Config,
+    config_path: &Path,
+    options: &ServeOptions,
+) -> Result<()> {
     let mut builder = Server::builder();
+    let repos = std::mem::take(&mut config.repo);
+    let app = config.github.app().cloned();
+    if let Some(admin) = &config.admin {
+        builder.admin_token(admin.token.clone());
+    }
+    let enroller = Enroller::new(config, builder.registry())?;
 
-    for repo in repo {
-        let mut installation = Installation::new(repo.owner(), repo.name());
-        if let Some(secret) = repo.secret() {
-            installation.with_secret(secret);
-        }
+    for repo in repos {
+        enroller.enroll(repo).await?;
+    }
 
-        let (tx, event_processor) = EventProcessor::new(repo, &github, &git)?;
-        tokio::spawn(event_processor.start());
-        installation.with_service(Box::new(tx));
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(config_path.to_owned(), enroller.clone()));
 
-        builder.add_installation(installation);
+    if let Some(app) = app.filter(|app| app.auto_enroll) {
+        match app.webhook_secret {
+            Some(secret) => {
+                builder.app_secret(secret);
+            }
+            None => warn!("no app webhook secret configured; unsigned deliveries will be accepted"),
+        }
+        builder.add_app_service(Box::new(enroller));
     }
 
     if let Some(smee_uri) = &options.smee {
@@ -38,3 +53,20 @@ pub async fn run_serve(config: Config, options: &ServeOptions) -> Result<()> {
     builder.serve(addr).await?;
     Ok(())
 }
+
+/// Reload the config file whenever bors receives a SIGHUP, applying it to the repos being served
+/// without dropping their queues
+#[cfg(unix)]
+async fn reload_on_hangup(path: std::path::PathBuf, enroller: Enroller) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        info!("reloading {}", path.display());
+        match Config::from_file(&path) {
+            Ok(config) => enroller.reload(config).await?,
+            Err(e) => error!("unable to reload {}: {:?}", path.display(), e),
+        }
+    }
+    Ok(())
+}
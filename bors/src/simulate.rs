@@ -0,0 +1,699 @@
+//! Offline simulation of the merge queue, used to check how a config's policies (priorities,
+//! batching, tree closures and dependencies) play out before deploying it
+//!
+//! Scenarios, both fixtures and the tests below, drive `MergeQueue`'s candidate selection,
+//! admission and bisection directly. They don't go through the event processor; scenarios which
+//! do, handling webhooks and comments against a mock Github and a local git remote, are scripted
+//! in `event_processor::scenario`.
+
+use crate::{
+    command::pr_list,
+    config::{RepoConfig, Rollup},
+    queue::{admission, batch_order, hold_reason, queue_order, Admission, MergeQueue},
+    state::{mark_landed, AttemptOverrides, PullRequestState, Status},
+    Config, Result,
+};
+use anyhow::{anyhow, Context};
+use github::Oid;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct SimulateOptions {
+    #[structopt(parse(from_os_str))]
+    /// fixture describing the PRs in the queue and the events to apply to it
+    fixture: PathBuf,
+
+    #[structopt(long)]
+    /// repo, as <owner>/<name>, whose config to simulate. Required if more than one is configured
+    repo: Option<String>,
+}
+
+/// The initial state of the queue along with a script of events to apply to it
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Fixture {
+    /// Priority threshold below which PRs aren't tested, as set by the `treeclosed` command
+    tree_closed: Option<u32>,
+
+    /// Events applied in order, one of:
+    /// `open <N>`, `queue <N>` or `land <N>`, `cancel <N>`, `close <N>`, `priority <N> <P>`,
+    /// `label <N> <label>`, `unlabel <N> <label>`, `rollup <N> <always|maybe|never>`,
+    /// `treeclosed <P>`, `treeclosed-`, `pass` or `fail`. A scenario can check its progress with
+    /// `expect <N> <in-review|queued|testing|failed|landed|closed>`, which stops the simulation
+    /// if PR N isn't in that state.
+    #[serde(default)]
+    events: Vec<String>,
+
+    #[serde(default)]
+    pull: Vec<FixturePull>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FixturePull {
+    number: u64,
+    #[serde(default)]
+    title: String,
+    #[serde(default = "default_base")]
+    base: String,
+    #[serde(default)]
+    priority: u32,
+    #[serde(default)]
+    labels: HashSet<String>,
+    #[serde(default)]
+    depends_on: BTreeSet<u64>,
+    rollup: Option<Rollup>,
+    /// Whether the PR starts out queued for landing, rather than in review
+    #[serde(default = "default_queued")]
+    queued: bool,
+}
+
+fn default_base() -> String {
+    "master".to_owned()
+}
+
+fn default_queued() -> bool {
+    true
+}
+
+impl FixturePull {
+    /// A PR which was just opened, and is in review
+    fn opened(number: u64) -> Self {
+        Self {
+            number,
+            title: String::new(),
+            base: default_base(),
+            priority: 0,
+            labels: HashSet::new(),
+            depends_on: BTreeSet::new(),
+            rollup: None,
+            queued: false,
+        }
+    }
+
+    fn into_pull(self) -> PullRequestState {
+        let oid = Oid::from_str(format!("{:040x}", self.number));
+
+        PullRequestState {
+            number: self.number,
+            id: self.number,
+            author: None,
+            title: self.title,
+            body: String::new(),
+            head_ref_oid: oid.clone(),
+            head_ref_name: format!("pr-{}", self.number),
+            head_repo: None,
+            base_ref_name: self.base,
+            base_ref_oid: oid,
+            state: github::PullRequestState::Open,
+            is_draft: false,
+            approved_by: HashSet::new(),
+            approved: true,
+            approver: None,
+            maintainer_can_modify: false,
+            mergeable: true,
+            labels: self.labels,
+            priority: self.priority,
+            retry: None,
+            next_attempt: None,
+            cooldown_until: None,
+            merge_strategy: None,
+            rollup: self.rollup,
+            depends_on: self.depends_on,
+            landed_dependencies: BTreeSet::new(),
+            attempts: 0,
+            spurious_retries: 0,
+            superseded: None,
+            approved_oid: None,
+            reviewer: None,
+            delegate: None,
+            queued_at: None,
+            residency_alerted: false,
+            push_settling: false,
+            status: if self.queued {
+                Status::Queued
+            } else {
+                Status::InReview
+            },
+            state_version: 0,
+            project_card_id: None,
+        }
+    }
+}
+
+pub fn run_simulate(config: Config, options: &SimulateOptions) -> Result<()> {
+    let repo = match &options.repo {
+        Some(name) => config
+            .repo
+            .iter()
+            .find(|repo| format!("{}/{}", repo.owner(), repo.name()) == *name)
+            .ok_or_else(|| anyhow!("no config for repo '{}'", name))?,
+        None if config.repo.len() == 1 => &config.repo[0],
+        None => {
+            return Err(anyhow!(
+                "more than one repo is configured, pick one with --repo"
+            ))
+        }
+    };
+
+    let contents = fs::read_to_string(&options.fixture)
+        .with_context(|| format!("reading fixture {}", options.fixture.display()))?;
+    let fixture: Fixture = toml::from_str(&contents)?;
+
+    let mut simulation = Simulation::new(repo, fixture.tree_closed, fixture.pull);
+    simulation.run(&fixture.events)?;
+
+    for line in simulation.log {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Drives a `MergeQueue` through the same decisions about what to test as the real one, but where
+/// tests pass or fail on command instead of running on CI and PRs are never rebased
+struct Simulation<'a> {
+    config: &'a RepoConfig,
+    pulls: HashMap<u64, PullRequestState>,
+    queue: MergeQueue,
+    landed: HashSet<u64>,
+    log: Vec<String>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(config: &'a RepoConfig, tree_closed: Option<u32>, pulls: Vec<FixturePull>) -> Self {
+        let mut queue = MergeQueue::new();
+        *queue.tree_closed_mut() = tree_closed;
+
+        Self {
+            config,
+            pulls: pulls
+                .into_iter()
+                .map(|pull| (pull.number, pull.into_pull()))
+                .collect(),
+            queue,
+            landed: HashSet::new(),
+            log: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, events: &[String]) -> Result<()> {
+        self.advance();
+
+        for event in events {
+            self.log.push(format!("> {}", event));
+            self.apply(event)?;
+            self.advance();
+        }
+
+        self.summarize();
+        Ok(())
+    }
+
+    fn apply(&mut self, event: &str) -> Result<()> {
+        let args: Vec<&str> = event.split_whitespace().collect();
+        let invalid = || anyhow!("invalid event '{}'", event);
+        let number = |idx: usize| -> Result<u64> {
+            args.get(idx)
+                .map(|arg| arg.trim_start_matches('#'))
+                .and_then(|arg| arg.parse().ok())
+                .ok_or_else(invalid)
+        };
+
+        match args.first().copied() {
+            Some("open") => {
+                let number = number(1)?;
+                if self.pulls.contains_key(&number) || self.landed.contains(&number) {
+                    return Err(anyhow!("PR #{} was already opened", number));
+                }
+                self.pulls
+                    .insert(number, FixturePull::opened(number).into_pull());
+            }
+            Some("queue") | Some("land") => self.pull_mut(number(1)?)?.status = Status::Queued,
+            Some("cancel") => {
+                let number = number(1)?;
+                self.pull_mut(number)?.status = Status::InReview;
+                self.dequeue(number);
+            }
+            Some("close") => {
+                let number = number(1)?;
+                self.pulls.remove(&number).ok_or_else(invalid)?;
+                self.dequeue(number);
+            }
+            Some("priority") => {
+                let priority = number(2)? as u32;
+                self.pull_mut(number(1)?)?.priority = priority;
+            }
+            Some("label") => {
+                let label = args.get(2).ok_or_else(invalid)?.to_string();
+                self.pull_mut(number(1)?)?.labels.insert(label);
+            }
+            Some("unlabel") => {
+                let label = args.get(2).ok_or_else(invalid)?;
+                self.pull_mut(number(1)?)?.labels.remove(*label);
+            }
+            Some("rollup") => {
+                let rollup = args
+                    .get(2)
+                    .and_then(|arg| Rollup::parse(arg))
+                    .ok_or_else(invalid)?;
+                self.pull_mut(number(1)?)?.rollup = Some(rollup);
+            }
+            Some("treeclosed") => *self.queue.tree_closed_mut() = Some(number(1)? as u32),
+            Some("treeclosed-") => *self.queue.tree_closed_mut() = None,
+            Some("pass") => self.pass(),
+            Some("fail") => self.fail(),
+            Some("expect") => {
+                let number = number(1)?;
+                let expected = args.get(2).ok_or_else(invalid)?;
+                let state = self.state_of(number);
+                if state != *expected {
+                    return Err(anyhow!(
+                        "expected #{} to be {}, but it is {}",
+                        number,
+                        expected,
+                        state
+                    ));
+                }
+            }
+            _ => return Err(invalid()),
+        }
+
+        Ok(())
+    }
+
+    /// The state of a PR, as checked by `expect` events
+    fn state_of(&self, number: u64) -> &'static str {
+        if self.landed.contains(&number) {
+            return "landed";
+        }
+
+        match self.pulls.get(&number).map(|pull| &pull.status) {
+            Some(Status::InReview) => "in-review",
+            Some(Status::Queued) => "queued",
+            Some(Status::Testing { .. }) => "testing",
+            Some(Status::Trying { .. }) => "trying",
+            Some(Status::Failed { .. }) => "failed",
+            None => "closed",
+        }
+    }
+
+    fn pull_mut(&mut self, number: u64) -> Result<&mut PullRequestState> {
+        self.pulls
+            .get_mut(&number)
+            .ok_or_else(|| anyhow!("no PR #{} in the fixture", number))
+    }
+
+    /// Abandon the head if `number` was being tested in it, returning the rest of its PRs to the
+    /// queue, just as the real queue does when a PR being tested is canceled or closed
+    fn dequeue(&mut self, number: u64) {
+        if self.queue.head().contains(&number) {
+            let batch = self.queue.take_head();
+            self.log
+                .push(format!("abandoned testing {}", pr_list(&batch)));
+            for number in batch {
+                if let Some(pull) = self.pulls.get_mut(&number) {
+                    if pull.status.is_testing() {
+                        pull.status = Status::Queued;
+                    }
+                }
+            }
+        }
+    }
+
+    fn pass(&mut self) {
+        let batch = self.queue.take_head();
+        if batch.is_empty() {
+            self.log.push("nothing is being tested".to_owned());
+            return;
+        }
+
+        self.log.push(format!("landed {}", pr_list(&batch)));
+        for number in batch {
+            self.pulls.remove(&number);
+            self.landed.insert(number);
+            mark_landed(&mut self.pulls, number);
+        }
+    }
+
+    fn fail(&mut self) {
+        let batch = self.queue.take_head();
+        match batch.len() {
+            0 => self.log.push("nothing is being tested".to_owned()),
+            1 => {
+                self.log.push(format!("#{} failed", batch[0]));
+                if let Some(pull) = self.pulls.get_mut(&batch[0]) {
+                    pull.status = Status::failed("Failed in simulation", HashMap::new());
+                }
+            }
+            _ => {
+                self.log
+                    .push(format!("batch {} failed, bisecting it", pr_list(&batch)));
+                for number in &batch {
+                    if let Some(pull) = self.pulls.get_mut(number) {
+                        pull.status = Status::Queued;
+                    }
+                }
+                self.queue.push_bisected(&batch);
+            }
+        }
+    }
+
+    /// Start testing the next batch if nothing is being tested
+    fn advance(&mut self) {
+        if !self.queue.head().is_empty() {
+            return;
+        }
+
+        let batch = loop {
+            let (candidates, bisecting) = self.queue.next_candidates(self.config, &self.pulls);
+
+            let batch = self.create_batch(candidates);
+            if !batch.is_empty() || !bisecting {
+                break batch;
+            }
+        };
+
+        if batch.is_empty() {
+            return;
+        }
+
+        for number in &batch {
+            let pull = self.pulls.get_mut(number).expect("PR should exist");
+            pull.attempts += 1;
+            pull.status = Status::testing(pull.head_ref_oid.clone(), AttemptOverrides::default());
+        }
+        self.log.push(format!("testing {}", pr_list(&batch)));
+        self.queue.restore_head(batch);
+    }
+
+    /// Build a batch from `candidates` just as `MergeQueue` does, except that PRs never conflict
+    /// with one another
+    fn create_batch(&self, candidates: Vec<u64>) -> Vec<u64> {
+        let mut batch: Vec<u64> = Vec::new();
+
+        let tree_closed = self.queue.tree_closed();
+        let candidates = batch_order(tree_closed, self.config, &self.pulls, candidates);
+        for number in candidates {
+            match admission(tree_closed, self.config, &self.pulls, &batch, None, number) {
+                Admission::Admit => batch.push(number),
+                Admission::Skip => continue,
+                Admission::Stop => break,
+            }
+        }
+
+        batch
+    }
+
+    /// Log why any PRs left in the queue aren't being tested
+    fn summarize(&mut self) {
+        if self.queue.head().is_empty() {
+            self.log.push("nothing is being tested".to_owned());
+        }
+
+        for number in queue_order(self.config, &self.pulls) {
+            let pull = &self.pulls[&number];
+            let reason = hold_reason(self.queue.tree_closed(), self.config, pull)
+                .unwrap_or_else(|| "queued".to_owned());
+            self.log.push(format!("#{} is {}", number, reason));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fixture, Simulation};
+    use crate::config::RepoConfig;
+
+    fn simulate(config: &str, fixture: &str) -> Vec<String> {
+        let config: RepoConfig = toml::from_str(config).unwrap();
+        let fixture: Fixture = toml::from_str(fixture).unwrap();
+
+        let mut simulation = Simulation::new(&config, fixture.tree_closed, fixture.pull);
+        simulation.run(&fixture.events).unwrap();
+        simulation.log
+    }
+
+    #[test]
+    fn bisects_failed_batch() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            max-batch-size = 2
+            labels = { solo = "solo" }
+            "#,
+            r#"
+            events = ["fail", "pass", "fail", "pass"]
+
+            [[pull]]
+            number = 1
+            [[pull]]
+            number = 2
+            [[pull]]
+            number = 3
+            labels = ["solo"]
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "testing #1, #2",
+                "> fail",
+                "batch #1, #2 failed, bisecting it",
+                "testing #1",
+                "> pass",
+                "landed #1",
+                "testing #2",
+                "> fail",
+                "#2 failed",
+                "testing #3",
+                "> pass",
+                "landed #3",
+                "nothing is being tested",
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_held_back_prs() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            "#,
+            r#"
+            tree-closed = 1
+
+            [[pull]]
+            number = 1
+            depends-on = [3]
+            [[pull]]
+            number = 2
+            [[pull]]
+            number = 3
+            queued = false
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "nothing is being tested",
+                "#1 is waiting on #3",
+                "#2 is held back by the closed tree",
+            ]
+        );
+    }
+
+    #[test]
+    fn holds_back_prs() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            "#,
+            r#"
+            tree-closed = 1
+            events = ["pass", "treeclosed-", "pass", "queue 4", "pass"]
+
+            [[pull]]
+            number = 1
+            priority = 1
+            [[pull]]
+            number = 2
+            depends-on = [4]
+            [[pull]]
+            number = 3
+            [[pull]]
+            number = 4
+            queued = false
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "testing #1",
+                "> pass",
+                "landed #1",
+                "> treeclosed-",
+                "testing #3",
+                "> pass",
+                "landed #3",
+                "> queue 4",
+                "testing #4",
+                "> pass",
+                "landed #4",
+                "testing #2",
+            ]
+        );
+    }
+
+    #[test]
+    fn scenario_from_open_to_land() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            "#,
+            r#"
+            events = [
+                "open 1", "expect 1 in-review",
+                "land 1", "expect 1 testing",
+                "open 2", "land 2", "expect 2 queued",
+                "fail", "expect 1 failed", "expect 2 testing",
+                "pass", "expect 2 landed",
+            ]
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "> open 1",
+                "> expect 1 in-review",
+                "> land 1",
+                "testing #1",
+                "> expect 1 testing",
+                "> open 2",
+                "> land 2",
+                "> expect 2 queued",
+                "> fail",
+                "#1 failed",
+                "testing #2",
+                "> expect 1 failed",
+                "> expect 2 testing",
+                "> pass",
+                "landed #2",
+                "> expect 2 landed",
+                "nothing is being tested",
+            ]
+        );
+    }
+
+    #[test]
+    fn unmet_expectations_stop_the_scenario() {
+        let config: RepoConfig = toml::from_str(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            "#,
+        )
+        .unwrap();
+
+        let mut simulation = Simulation::new(&config, None, Vec::new());
+        let err = simulation
+            .run(&["open 1".to_owned(), "expect 1 queued".to_owned()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected #1 to be queued, but it is in-review"
+        );
+    }
+
+    #[test]
+    fn pulls_rollup_always_prs_into_batches() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            max-batch-size = 2
+            "#,
+            r#"
+            events = ["pass", "pass", "pass"]
+
+            [[pull]]
+            number = 1
+            [[pull]]
+            number = 2
+            rollup = "never"
+            [[pull]]
+            number = 3
+            rollup = "always"
+            [[pull]]
+            number = 4
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "testing #1, #3",
+                "> pass",
+                "landed #1, #3",
+                "testing #2",
+                "> pass",
+                "landed #2",
+                "testing #4",
+                "> pass",
+                "landed #4",
+                "nothing is being tested",
+            ]
+        );
+    }
+
+    #[test]
+    fn rollup_always_prs_keep_to_their_priority() {
+        let log = simulate(
+            r#"
+            owner = "bmwill"
+            name = "bors-rs"
+            max-batch-size = 2
+            "#,
+            r#"
+            events = ["pass", "pass"]
+
+            [[pull]]
+            number = 1
+            priority = 2
+            [[pull]]
+            number = 2
+            priority = 1
+            [[pull]]
+            number = 3
+            rollup = "always"
+            "#,
+        );
+
+        assert_eq!(
+            log,
+            [
+                "testing #1, #2",
+                "> pass",
+                "landed #1, #2",
+                "testing #3",
+                "> pass",
+                "landed #3",
+                "nothing is being tested",
+            ]
+        );
+    }
+}
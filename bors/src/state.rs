@@ -1,7 +1,17 @@
-use crate::{config::RepoConfig, graphql::GithubClient, project_board::ProjectBoard, Result};
-use github::Oid;
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use crate::{
+    comment::{excerpt, CommentEvent, CommentMarker},
+    config::{MergeStrategy, RepoConfig, Rollup},
+    graphql::GithubClient,
+    project_board::ProjectBoard,
+    Result,
+};
+use github::{
+    client::{CheckRunOutput, CreateCheckRunRequest, UpdateCheckRunRequest},
+    CheckStatus, Conclusion, Oid,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct PullRequestState {
@@ -24,18 +34,138 @@ pub struct PullRequestState {
     pub is_draft: bool,
     pub approved_by: HashSet<String>,
     pub approved: bool,
+    /// The user who queued this PR for landing
+    pub approver: Option<String>,
+    /// The head the approver reviewed, if they pinned it with `land <sha>`. The PR is dropped
+    /// from the queue if its head no longer matches when it's about to be tested.
+    pub approved_oid: Option<Oid>,
+    /// The reviewer an admin approved this PR on behalf of, with `r=<user>`, who is credited in a
+    /// `Reviewed-by` trailer when it lands
+    pub reviewer: Option<String>,
+    /// A user granted the right to land this PR, via the `delegate` command, regardless of their
+    /// permissions on the repo
+    pub delegate: Option<String>,
     pub maintainer_can_modify: bool, // Use to enable 'rebase' merging and having github know a PR has been merged
     pub mergeable: bool,
     pub labels: HashSet<String>,
+    /// Priority set via the `priority` command. Higher priority PRs are tested first
+    pub priority: u32,
+    /// Set when the PR's last try build failed, allowing it to be retried
+    pub retry: Option<Retry>,
+    /// Settings overridden via the `retry` command, which only apply to the PR's next attempt and
+    /// are moved onto it once it starts
+    pub next_attempt: Option<AttemptOverrides>,
+    /// The PR isn't tested again until this point after failing to be updated in-place or landed
+    pub cooldown_until: Option<std::time::Instant>,
+    /// Merge strategy set via the `land` command, overriding the configured one
+    pub merge_strategy: Option<MergeStrategy>,
+    /// Rollup flag set via the `rollup` command, overriding any set via labels
+    pub rollup: Option<Rollup>,
+    /// PRs which must land before this one can be tested, from `Depends-on: #N` trailers in the
+    /// PR's description or the `depends` command
+    pub depends_on: BTreeSet<u64>,
+    /// Dependencies which are known to have been merged. A dependency which was closed without
+    /// being merged never lands, holding this PR back until the dependency is dropped.
+    pub landed_dependencies: BTreeSet<u64>,
+    /// The number of times this PR has started testing for landing, used to tell apart the
+    /// results of each attempt
+    pub attempts: u32,
+    /// The number of times this PR has been retried automatically after a spurious failure since
+    /// it was last queued for landing
+    pub spurious_retries: u32,
+    /// The number of a PR this one superseded via the `supersede` command, whose place in the
+    /// queue it takes
+    pub superseded: Option<u64>,
+    /// When this PR was queued for landing. Kept while it's tested and requeued, e.g. after its
+    /// batch failed, until it leaves the queue.
+    pub queued_at: Option<std::time::SystemTime>,
+    /// Indicates if maintainers have been alerted that this PR has been queued for too long
+    pub residency_alerted: bool,
+    /// Set while waiting out the quiet period after the PR was pushed to, during which it isn't
+    /// tested since bors hasn't yet reacted to the new commits
+    pub push_settling: bool,
 
     pub status: Status,
+    /// Incremented each time `status` changes
+    pub state_version: u64,
     pub project_card_id: Option<u64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TestResult {
     pub passed: bool,
     pub details_url: String,
+    /// Short, untrusted, description of the result reported by the CI provider
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Indicates the failure matched one of the configured spurious failure patterns, or a known
+    /// issue whose failures are retried
+    #[serde(default)]
+    pub spurious: bool,
+    /// Url of the known issue tracking the failure, if it matched one
+    #[serde(default)]
+    pub known_issue: Option<String>,
+}
+
+impl TestResult {
+    /// A quoted excerpt of the result's summary, suitable for appending to a comment
+    pub fn summary_note(&self) -> String {
+        match self.summary.as_deref().map(str::trim) {
+            Some(summary) if !summary.is_empty() => {
+                format!("\n\n> {}", excerpt(summary, &self.details_url))
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// A link to the known issue tracking the failure, suitable for appending to a comment
+    pub fn known_issue_note(&self) -> String {
+        match &self.known_issue {
+            Some(url) => format!("\n\n:mag: This looks like a known issue: {}", url),
+            None => String::new(),
+        }
+    }
+}
+
+/// A failed attempt, other than a failed land, which can be retried with the `retry` command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retry {
+    Try,
+}
+
+/// Repo settings overridden for a single attempt, e.g. with `retry timeout=3h checks=ci,doc`, so
+/// that special cases don't require temporarily editing the repo's config
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AttemptOverrides {
+    /// How long each check is waited on, in place of its configured timeout
+    pub timeout: Option<std::time::Duration>,
+    /// The checks required to pass, in place of the configured ones
+    pub checks: Option<Vec<String>>,
+}
+
+impl AttemptOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none() && self.checks.is_none()
+    }
+
+    /// Names of the checks, and statuses, required to pass on this attempt at landing PRs into
+    /// `base_ref`
+    pub fn checks<'a>(&'a self, config: &'a RepoConfig, base_ref: &str) -> Vec<&'a str> {
+        match &self.checks {
+            Some(checks) => checks.iter().map(String::as_str).collect(),
+            None => config.checks(base_ref).collect(),
+        }
+    }
+
+    pub fn check_timeout(
+        &self,
+        config: &RepoConfig,
+        base_ref: &str,
+        name: &str,
+    ) -> std::time::Duration {
+        self.timeout
+            .unwrap_or_else(|| config.check_timeout(base_ref, name))
+    }
 }
 
 #[derive(Debug)]
@@ -46,11 +176,21 @@ pub enum Status {
         merge_oid: Oid,
         tests_started_at: std::time::Instant,
         test_results: HashMap<String, TestResult>,
+        overrides: AttemptOverrides,
+    },
+    /// A try build of the PR is being tested. Try builds are never landed.
+    Trying {
+        merge_oid: Oid,
+        tests_started_at: std::time::Instant,
+        test_results: HashMap<String, TestResult>,
+        overrides: AttemptOverrides,
+    },
+    /// Testing the PR failed or timed-out. The PR remains failed, retaining the results of the
+    /// failed run, until it is explicitly retried or approved again.
+    Failed {
+        reason: String,
+        test_results: HashMap<String, TestResult>,
     },
-    // Failed {
-    //     merge_oid: Oid,
-    //     test_results: HashMap<String, TestResult>,
-    // },
     // Success {
     //     merge_oid: Oid,
     //     test_results: HashMap<String, TestResult>,
@@ -58,6 +198,17 @@ pub enum Status {
 }
 
 impl Status {
+    /// A short, machine readable, name for this status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::InReview => "in-review",
+            Status::Queued => "queued",
+            Status::Testing { .. } => "testing",
+            Status::Trying { .. } => "trying",
+            Status::Failed { .. } => "failed",
+        }
+    }
+
     pub fn is_queued(&self) -> bool {
         matches!(self, Status::Queued)
     }
@@ -66,11 +217,36 @@ impl Status {
         matches!(self, Status::Testing { .. })
     }
 
-    pub fn testing(merge_oid: Oid) -> Status {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Status::Failed { .. })
+    }
+
+    pub fn failed<R: Into<String>>(reason: R, test_results: HashMap<String, TestResult>) -> Status {
+        Status::Failed {
+            reason: reason.into(),
+            test_results,
+        }
+    }
+
+    pub fn is_trying(&self) -> bool {
+        matches!(self, Status::Trying { .. })
+    }
+
+    pub fn trying(merge_oid: Oid, overrides: AttemptOverrides) -> Status {
+        Status::Trying {
+            merge_oid,
+            tests_started_at: std::time::Instant::now(),
+            test_results: HashMap::new(),
+            overrides,
+        }
+    }
+
+    pub fn testing(merge_oid: Oid, overrides: AttemptOverrides) -> Status {
         Status::Testing {
             merge_oid,
             tests_started_at: std::time::Instant::now(),
             test_results: HashMap::new(),
+            overrides,
         }
     }
 }
@@ -99,10 +275,29 @@ impl PullRequestState {
             is_draft: pull.draft.unwrap_or(false),
             approved_by: HashSet::new(),
             approved: false,
+            approver: None,
             maintainer_can_modify: pull.maintainer_can_modify.unwrap_or(false),
             mergeable: pull.mergeable.unwrap_or(false),
             labels,
+            priority: 0,
+            retry: None,
+            next_attempt: None,
+            cooldown_until: None,
+            merge_strategy: None,
+            rollup: None,
+            depends_on: parse_depends_on(pull.body.as_deref().unwrap_or_default()),
+            landed_dependencies: BTreeSet::new(),
+            attempts: 0,
+            spurious_retries: 0,
+            superseded: None,
+            approved_oid: None,
+            reviewer: None,
+            delegate: None,
+            queued_at: None,
+            residency_alerted: false,
+            push_settling: false,
             status: Status::InReview,
+            state_version: 0,
             project_card_id: None,
         }
     }
@@ -116,10 +311,64 @@ impl PullRequestState {
                 .any(|s| self.title.starts_with(s))
     }
 
+    /// Construct a trailing line which cc's the author and approving reviewers of this PR,
+    /// suitable for appending to a comment
+    pub fn cc_reviewers(&self) -> String {
+        let mut users: Vec<&str> = Vec::new();
+        for user in self
+            .author
+            .iter()
+            .chain(self.approver.iter())
+            .chain(self.approved_by.iter())
+        {
+            if !users.contains(&user.as_str()) {
+                users.push(user);
+            }
+        }
+
+        if users.is_empty() {
+            return String::new();
+        }
+
+        let mentions: Vec<String> = users.iter().map(|user| format!("@{}", user)).collect();
+        format!("\n\ncc {}", mentions.join(" "))
+    }
+
+    /// Prevent this PR from being tested again until the configured cooldown has elapsed
+    pub fn start_cooldown(&mut self, config: &RepoConfig) {
+        self.cooldown_until = Some(std::time::Instant::now() + config.cooldown());
+    }
+
+    /// The time remaining before this PR can be tested again, if it is cooling down
+    pub fn cooldown_remaining(&self) -> Option<std::time::Duration> {
+        self.cooldown_until
+            .and_then(|until| until.checked_duration_since(std::time::Instant::now()))
+            .filter(|remaining| *remaining > std::time::Duration::from_secs(0))
+    }
+
+    /// The number of approving reviews this PR still needs before it can be queued
+    pub fn missing_approvals(&self, config: &RepoConfig) -> usize {
+        config
+            .required_approvals()
+            .saturating_sub(self.approved_by.len())
+    }
+
+    /// The time remaining before this PR, which was recently queued, can start being tested, if a
+    /// grace period is configured
+    pub fn grace_remaining(&self, config: &RepoConfig) -> Option<std::time::Duration> {
+        let elapsed = self.queued_at?.elapsed().unwrap_or_default();
+        config
+            .grace_period()?
+            .checked_sub(elapsed)
+            .filter(|remaining| *remaining > std::time::Duration::from_secs(0))
+    }
+
     // XXX this should probably update the status of the PR as well, like if the PR is in the queue
     // to land it should be kicked out
     pub fn update_head(&mut self, oid: Oid) {
         self.head_ref_oid = oid;
+        self.retry = None;
+        self.next_attempt = None;
     }
 
     pub async fn update_status(
@@ -130,18 +379,176 @@ impl PullRequestState {
         project_board: Option<&ProjectBoard>,
     ) -> Result<()> {
         self.status = status;
+        self.state_version += 1;
+
+        match &self.status {
+            Status::Queued | Status::Testing { .. } => {
+                if self.queued_at.is_none() {
+                    self.queued_at = Some(std::time::SystemTime::now());
+                }
+            }
+            Status::InReview | Status::Trying { .. } | Status::Failed { .. } => {
+                self.queued_at = None;
+                self.residency_alerted = false;
+            }
+        }
 
         if let Some(board) = project_board {
             match &self.status {
-                Status::InReview => board.move_to_review(github, &self).await?,
+                Status::InReview | Status::Trying { .. } => {
+                    board.move_to_review(github, &self).await?
+                }
                 Status::Queued => board.move_to_queued(github, &self).await?,
                 Status::Testing { .. } => board.move_to_testing(github, &self).await?,
+                Status::Failed { .. } => board.move_to_failed(github, &self).await?,
             }
         }
 
         Ok(())
     }
 
+    /// Post a comment to this PR, signed with a marker identifying the event being reported
+    pub async fn create_comment(
+        &self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        event: CommentEvent,
+        body: &str,
+    ) -> Result<()> {
+        let marker = CommentMarker::new(event, self.number, self.state_version);
+        github
+            .issues()
+            .create_comment(
+                config.owner(),
+                config.name(),
+                self.number,
+                &marker.sign(body),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Publish the configured check run on `oid` for an outcome of testing this PR's merge commit
+    /// which isn't otherwise reported as bors' status, e.g. the land being canceled. A `conclusion`
+    /// of `None` marks the check as being in progress. Check runs can only be published by a
+    /// Github App, so failures are only logged rather than holding up the queue.
+    pub async fn report_check_run(
+        &self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        oid: &Oid,
+        conclusion: Option<Conclusion>,
+        details_url: Option<&str>,
+    ) {
+        let name = match config.check_run() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let (status, title, summary) = match conclusion {
+            None => (
+                Some(CheckStatus::InProgress),
+                "Testing",
+                "The merge commit for this PR is being tested",
+            ),
+            Some(Conclusion::Success) => (None, "Landed", "The merge commit passed all checks"),
+            Some(Conclusion::Cancelled) => (None, "Canceled", "Landing this PR was canceled"),
+            Some(Conclusion::TimedOut) => (None, "Timed-out", "Testing the merge commit timed-out"),
+            Some(_) => (None, "Failed", "The merge commit failed to pass all checks"),
+        };
+
+        let request = CreateCheckRunRequest {
+            name,
+            head_sha: &oid.to_string(),
+            details_url,
+            external_id: None,
+            status,
+            conclusion,
+            output: Some(CheckRunOutput {
+                title,
+                summary,
+                text: None,
+            }),
+        };
+        if let Err(e) = upsert_check_run(config, github, &request).await {
+            warn!(
+                "unable to report the '{}' check run of pr #{}: {:?}",
+                name, self.number, e
+            );
+        }
+    }
+
+    /// Edit the most recent comment posted by bors on this PR reporting `event`, or post a new one
+    /// if there isn't one yet
+    pub async fn upsert_comment(
+        &self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        event: CommentEvent,
+        body: &str,
+    ) -> Result<()> {
+        let comment = match self.find_comment(config, github, event).await? {
+            Some(comment) => comment,
+            None => return self.create_comment(config, github, event, body).await,
+        };
+
+        let marker = CommentMarker::new(event, self.number, self.state_version);
+        github
+            .issues()
+            .update_comment(
+                config.owner(),
+                config.name(),
+                comment.id,
+                &marker.sign(body),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Find the most recent comment posted by bors on this PR reporting the provided event
+    pub async fn find_comment(
+        &self,
+        config: &RepoConfig,
+        github: &GithubClient,
+        event: CommentEvent,
+    ) -> Result<Option<github::Comment>> {
+        let mut comment: Option<github::Comment> = None;
+        let mut page = Some(1);
+
+        while let Some(current) = page {
+            let options = github::client::ListIssueCommentsOptions {
+                pagination_options: github::client::PaginationOptions {
+                    page: Some(current),
+                    per_page: Some(100),
+                },
+                ..Default::default()
+            };
+            let response = github
+                .issues()
+                .list_comments(config.owner(), config.name(), self.number, Some(options))
+                .await?;
+            page = response.pagination().next_page;
+
+            let latest = response
+                .into_inner()
+                .into_iter()
+                .filter(|comment| {
+                    comment
+                        .body()
+                        .and_then(CommentMarker::from_comment)
+                        .map(|marker| marker.event == event && marker.pr == self.number)
+                        .unwrap_or(false)
+                })
+                .max_by_key(|comment| comment.id);
+            comment = comment
+                .into_iter()
+                .chain(latest)
+                .max_by_key(|comment| comment.id);
+        }
+
+        Ok(comment)
+    }
+
     pub async fn add_label(
         &mut self,
         config: &RepoConfig,
@@ -161,10 +568,82 @@ impl PullRequestState {
         Ok(())
     }
 
+    /// Check if this PR's approval rights have been delegated to `user`. Github doesn't
+    /// distinguish logins by case.
+    pub fn is_delegate(&self, user: &str) -> bool {
+        self.delegate
+            .as_deref()
+            .map_or(false, |delegate| delegate.eq_ignore_ascii_case(user))
+    }
+
     pub fn has_label(&self, label: &str) -> bool {
         self.labels.contains(label)
     }
 
+    /// The strategy used to merge this PR. A strategy set with the `land` command takes precedence
+    /// over the squash label, which in turn takes precedence over the configured default.
+    pub fn merge_strategy(&self, config: &RepoConfig) -> MergeStrategy {
+        if let Some(strategy) = self.merge_strategy {
+            strategy
+        } else if self.has_label(config.labels().squash()) {
+            MergeStrategy::Squash
+        } else {
+            config.merge_strategy()
+        }
+    }
+
+    /// The dependencies of this PR which aren't known to have landed
+    pub fn unlanded_dependencies(&self) -> Vec<u64> {
+        self.depends_on
+            .difference(&self.landed_dependencies)
+            .copied()
+            .collect()
+    }
+
+    /// The number this PR is ordered by in the queue, among PRs of the same priority. A PR which
+    /// superseded another takes its place.
+    pub fn queue_number(&self) -> u64 {
+        self.superseded.unwrap_or(self.number)
+    }
+
+    /// The priority used to order this PR in the merge queue. PRs with the high-priority label have
+    /// a priority of at least 1.
+    pub fn queue_priority(&self, config: &RepoConfig) -> u32 {
+        let label_priority = if self.has_label(config.labels().high_priority()) {
+            1
+        } else {
+            0
+        };
+
+        self.priority.max(label_priority)
+    }
+
+    /// Check if this PR must always be tested on its own instead of being batched with others
+    pub fn is_solo(&self, config: &RepoConfig) -> bool {
+        self.rollup(config) == Rollup::Never
+    }
+
+    /// How willing this PR is to be batched with others. Isolated labels, and overrides for its
+    /// next attempt, always keep a PR out of batches, otherwise the `rollup` command takes
+    /// precedence over the solo and rollup labels.
+    pub fn rollup(&self, config: &RepoConfig) -> Rollup {
+        if config.isolated_labels().any(|label| self.has_label(label))
+            || self.next_attempt.is_some()
+        {
+            Rollup::Never
+        } else if let Some(rollup) = self.rollup {
+            rollup
+        } else if self.has_label(config.labels().solo())
+            || self.has_label(config.labels().rollup_never())
+        {
+            Rollup::Never
+        } else if self.has_label(config.labels().rollup_always()) {
+            Rollup::Always
+        } else {
+            Rollup::Maybe
+        }
+    }
+
     pub async fn remove_label(
         &mut self,
         config: &RepoConfig,
@@ -182,26 +661,74 @@ impl PullRequestState {
         Ok(())
     }
 
+    /// The result recorded for a check of the commit being tested, if it has reported one
+    pub fn build_result(&self, build_name: &str) -> Option<&TestResult> {
+        match &self.status {
+            Status::Testing { test_results, .. } | Status::Trying { test_results, .. } => {
+                test_results.get(build_name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Discard the result of a check which is being rerun on the commit being tested
+    pub fn remove_build_result(&mut self, build_name: &str) {
+        match self.status {
+            Status::Testing {
+                ref mut test_results,
+                ..
+            }
+            | Status::Trying {
+                ref mut test_results,
+                ..
+            } => {
+                test_results.remove(build_name);
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the result of a check on the merge commit being tested, returning how long the check
+    /// took if this is the first time it passed
     pub fn add_build_result(
         &mut self,
         build_name: &str,
         details_url: &str,
+        summary: Option<&str>,
         conclusion: github::Conclusion,
-    ) {
+        spurious: bool,
+        known_issue: Option<String>,
+    ) -> Option<std::time::Duration> {
         match self.status {
             Status::Testing {
                 ref mut test_results,
+                tests_started_at,
+                ..
+            }
+            | Status::Trying {
+                ref mut test_results,
+                tests_started_at,
                 ..
             } => {
-                test_results.insert(
+                let passed = matches!(conclusion, github::Conclusion::Success);
+                let previous = test_results.insert(
                     build_name.to_owned(),
                     TestResult {
                         details_url: details_url.to_owned(),
-                        passed: matches!(conclusion, github::Conclusion::Success),
+                        summary: summary.map(ToOwned::to_owned),
+                        passed,
+                        spurious,
+                        known_issue,
                     },
                 );
+
+                if passed && previous.map_or(true, |previous| !previous.passed) {
+                    Some(tests_started_at.elapsed())
+                } else {
+                    None
+                }
             }
-            _ => {}
+            _ => None,
         }
     }
 }
@@ -239,3 +766,107 @@ impl Repo {
         format!("git@github.com:{}/{}.git", self.owner, self.name)
     }
 }
+
+/// Record that PR `number` was merged, so that it no longer holds back the PRs depending on it
+pub fn mark_landed(pulls: &mut HashMap<u64, PullRequestState>, number: u64) {
+    for pull in pulls.values_mut() {
+        if pull.depends_on.contains(&number) {
+            pull.landed_dependencies.insert(number);
+        }
+    }
+}
+
+/// Parse the PRs listed in `Depends-on: #N` trailers in a PR's description
+pub fn parse_depends_on(body: &str) -> BTreeSet<u64> {
+    const TRAILER: &str = "depends-on:";
+
+    body.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let key = line.get(..TRAILER.len())?;
+            if key.eq_ignore_ascii_case(TRAILER) {
+                Some(&line[TRAILER.len()..])
+            } else {
+                None
+            }
+        })
+        .flat_map(|value| value.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter_map(|dependency| dependency.strip_prefix('#')?.parse().ok())
+        .collect()
+}
+
+/// Publish a check run, updating the latest run of the same name on its commit rather than adding
+/// another. A new run is only added when there's none to update or when a completed run is being
+/// started over, e.g. for another attempt.
+pub async fn upsert_check_run(
+    config: &RepoConfig,
+    github: &GithubClient,
+    request: &CreateCheckRunRequest<'_>,
+) -> Result<()> {
+    let latest = github
+        .checks()
+        .list_check_runs_named(
+            config.owner(),
+            config.name(),
+            request.head_sha,
+            request.name,
+        )
+        .await?
+        .into_inner()
+        .check_runs
+        .into_iter()
+        .max_by_key(|run| run.id)
+        .filter(|run| {
+            request.conclusion.is_some() || !matches!(run.status, CheckStatus::Completed)
+        });
+
+    match latest {
+        Some(run) => {
+            github
+                .checks()
+                .update_check_run(
+                    config.owner(),
+                    config.name(),
+                    run.id,
+                    &UpdateCheckRunRequest {
+                        details_url: request.details_url,
+                        status: request.status,
+                        conclusion: request.conclusion,
+                        output: request.output.as_ref().map(|output| CheckRunOutput {
+                            title: output.title,
+                            summary: output.summary,
+                            text: output.text,
+                        }),
+                    },
+                )
+                .await?;
+        }
+        None => {
+            github
+                .checks()
+                .create_check_run(config.owner(), config.name(), request)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_depends_on;
+
+    #[test]
+    fn depends_on_trailers() {
+        let body = "Builds on the parser refactor.\n\n\
+                    Depends-on: #12\n\
+                    depends-on: #7, #30\n\
+                    Depends-on: rust-lang/rust#1\n\
+                    Mentions #99 without depending on it";
+        let depends_on: Vec<u64> = parse_depends_on(body).into_iter().collect();
+        assert_eq!(depends_on, vec![7, 12, 30]);
+
+        assert!(parse_depends_on("").is_empty());
+        assert!(parse_depends_on("Depends-on:").is_empty());
+    }
+}
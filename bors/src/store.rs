@@ -0,0 +1,140 @@
+//! Files in which bors keeps what it has recorded about a repo, e.g. its contributors' stats or
+//! its audit log. They're kept in the state directory of the repo's clone so that they stay with
+//! the clone wherever bors is run from.
+
+use crate::Result;
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A file in a repo's state directory, holding either a single JSON document or, for logs which
+/// are only ever added to, one JSON object per line
+#[derive(Clone, Debug, Default)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// The store named `name` in `state_dir`, the repo's state directory
+    pub fn new(state_dir: &Path, name: &str) -> Self {
+        Self {
+            path: state_dir.join(name),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Read the store's document, or the default if it hasn't been written yet. A document which
+    /// can't be parsed is started over, so that a corrupt store doesn't stop the repo from being
+    /// served.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        let contents = match self.read()? {
+            Some(contents) => contents,
+            None => return Ok(T::default()),
+        };
+
+        Ok(serde_json::from_str(&contents).unwrap_or_else(|e| {
+            warn!("discarding unreadable '{}': {}", self.path.display(), e);
+            T::default()
+        }))
+    }
+
+    /// Replace the store's document
+    pub fn save<T: Serialize>(&self, value: &T) -> Result<()> {
+        write_atomic(&self.path, &serde_json::to_string_pretty(value)?)
+    }
+
+    /// Read the records appended to the store, oldest first. Lines which can't be parsed, e.g. one
+    /// cut short by a crash, are skipped.
+    pub fn records<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        let contents = match self.read()? {
+            Some(contents) => contents,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut records = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                Err(e) => warn!(
+                    "skipping unreadable line of '{}': {}",
+                    self.path.display(),
+                    e
+                ),
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Append a record to the store, on a line of its own
+    pub fn append<T: Serialize>(&self, record: &T) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Option<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Write a file by way of a temporary file so that readers never observe a partial write
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Store;
+    use std::{collections::BTreeMap, fs};
+
+    #[test]
+    fn corrupt_stores_start_over() {
+        let dir = std::env::temp_dir().join(format!("bors-store-{}", std::process::id()));
+        let document = Store::new(&dir, "document.json");
+        let log = Store::new(&dir, "log.jsonl");
+
+        let mut counts = BTreeMap::new();
+        counts.insert("alice".to_owned(), 2);
+        document.save(&counts).unwrap();
+        assert_eq!(document.load::<BTreeMap<String, u32>>().unwrap(), counts);
+
+        fs::write(dir.join("document.json"), "{\"alice\": ").unwrap();
+        assert!(document.load::<BTreeMap<String, u32>>().unwrap().is_empty());
+
+        log.append(&1).unwrap();
+        fs::write(dir.join("log.jsonl"), "1\n{\"cut short\n").unwrap();
+        log.append(&3).unwrap();
+        assert_eq!(log.records::<u32>().unwrap(), [1, 3]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+//! Live updates of a repo's merge queue, streamed to subscribers as server-sent events
+
+use crate::{config::RepoConfig, state::PullRequestState, Result};
+use futures::channel::mpsc;
+use probot::StreamEvent;
+use serde::Serialize;
+
+/// Name of the server-sent event carrying a repo's queue state
+const QUEUE_EVENT: &str = "queue";
+
+/// Number of events buffered for a subscriber before it is considered to have fallen behind
+pub const SUBSCRIBER_BUFFER: usize = 16;
+
+/// A snapshot of a repo's merge queue
+#[derive(Debug, Serialize)]
+pub struct QueueState {
+    pub paused: bool,
+    pub draining: bool,
+    pub tree_closed: Option<u32>,
+    /// All open PRs, ordered by number
+    pub pulls: Vec<PullSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PullSummary {
+    pub number: u64,
+    pub title: String,
+    pub status: &'static str,
+    /// The priority the PR is ordered by in the queue, taking its labels into account
+    pub priority: u32,
+}
+
+impl PullSummary {
+    pub fn from_pull(pull: &PullRequestState, config: &RepoConfig) -> Self {
+        Self {
+            number: pull.number,
+            title: pull.title.clone(),
+            status: pull.status.as_str(),
+            priority: pull.queue_priority(config),
+        }
+    }
+}
+
+/// Clients subscribed to a repo's queue state
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    senders: Vec<mpsc::Sender<StreamEvent>>,
+    /// The most recently published state, so that only changes are published
+    last_published: Option<String>,
+}
+
+impl Subscribers {
+    /// Add a subscriber, immediately sending it the current state
+    pub fn add(&mut self, mut sender: mpsc::Sender<StreamEvent>, state: &QueueState) -> Result<()> {
+        let data = serde_json::to_string(state)?;
+        if sender
+            .try_send(StreamEvent::new(QUEUE_EVENT, data.clone()))
+            .is_ok()
+        {
+            self.senders.push(sender);
+            self.last_published = Some(data);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// Publish `state` to all subscribers if it has changed since it was last published.
+    /// Subscribers which have gone away or aren't keeping up are dropped.
+    pub fn publish(&mut self, state: &QueueState) -> Result<()> {
+        let data = serde_json::to_string(state)?;
+        if self.last_published.as_ref() == Some(&data) {
+            return Ok(());
+        }
+
+        let event = StreamEvent::new(QUEUE_EVENT, data.clone());
+        self.senders = std::mem::take(&mut self.senders)
+            .into_iter()
+            .filter_map(|mut sender| sender.try_send(event.clone()).ok().map(|()| sender))
+            .collect();
+        self.last_published = Some(data);
+
+        Ok(())
+    }
+}
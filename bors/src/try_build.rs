@@ -0,0 +1,109 @@
+//! Try builds, which test a PR's merge commit on the try branch without ever landing it
+
+use crate::{
+    comment::{link, CommentEvent},
+    config::RepoConfig,
+    graphql::GithubClient,
+    project_board::ProjectBoard,
+    queue::{optional_failures, timed_out_check},
+    state::{PullRequestState, Retry, Status},
+    Result,
+};
+use log::info;
+use std::collections::HashMap;
+
+/// Report the outcome of any completed try builds, returning their PRs to review
+pub async fn process_try_builds(
+    config: &RepoConfig,
+    github: &GithubClient,
+    project_board: Option<&ProjectBoard>,
+    pulls: &mut HashMap<u64, PullRequestState>,
+) -> Result<()> {
+    // Only the most recent try build is still on the try branch, any others have been superseded
+    let latest = pulls
+        .values()
+        .filter_map(|pull| match &pull.status {
+            Status::Trying {
+                tests_started_at, ..
+            } => Some((*tests_started_at, pull.number)),
+            _ => None,
+        })
+        .max()
+        .map(|(_started_at, number)| number);
+
+    for pull in pulls.values_mut() {
+        let (merge_oid, tests_started_at, test_results, overrides) = match &pull.status {
+            Status::Trying {
+                merge_oid,
+                tests_started_at,
+                test_results,
+                overrides,
+            } => (
+                merge_oid.clone(),
+                *tests_started_at,
+                test_results.clone(),
+                overrides.clone(),
+            ),
+            _ => continue,
+        };
+
+        let (comment, retry) = if let Some(latest) = latest.filter(|latest| *latest != pull.number)
+        {
+            (
+                format!(":no_entry_sign: Try build superseded by #{}", latest),
+                None,
+            )
+
+        // Check if there were any test failures from configured checks
+        } else if let Some((name, result)) = overrides
+            .checks(config, &pull.base_ref_name)
+            .into_iter()
+            .filter_map(|name| test_results.get(name).map(|result| (name, result)))
+            .find(|(_name, result)| !result.passed)
+        {
+            let comment = format!(
+                ":broken_heart: Try build failed - {}{}",
+                link(name, &result.details_url),
+                result.summary_note(),
+            );
+            (comment, Some(Retry::Try))
+
+        // Check if all tests have completed and passed
+        } else if overrides
+            .checks(config, &pull.base_ref_name)
+            .into_iter()
+            .map(|name| test_results.get(name))
+            .all(|result| result.map(|r| r.passed).unwrap_or(false))
+        {
+            let mut comment = format!(":sunny: Try build successful - {}", merge_oid);
+            if let Some(failures) = optional_failures(config, &pull.base_ref_name, &test_results) {
+                comment.push_str(&format!("\n\nSome optional checks failed: {}", failures));
+            }
+            (comment, None)
+
+        // Check if any of the checks still running has timed-out
+        } else if timed_out_check(
+            config,
+            &pull.base_ref_name,
+            &overrides,
+            tests_started_at.elapsed(),
+            &test_results,
+        )
+        .is_some()
+        {
+            (":boom: Try build timed-out".to_owned(), Some(Retry::Try))
+        } else {
+            continue;
+        };
+
+        info!("try build of pr #{} completed", pull.number);
+
+        pull.update_status(Status::InReview, config, github, project_board)
+            .await?;
+        pull.retry = retry;
+        pull.create_comment(config, github, CommentEvent::TryBuild, &comment)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Syncing of landed PRs to an upstream repo, for forks which test and land PRs themselves while
+//! the canonical base branches live upstream
+
+use crate::{config::RepoConfig, git::GitRepository, graphql::GithubClient, Result};
+use github::{
+    client::{ListPullsOptions, NewPullRequest},
+    Oid,
+};
+use log::info;
+
+/// Advance the sync branch for `base_ref` to `merge_oid`, which just landed on `base_ref`, and make
+/// sure that an upstream PR is open from it. Once open the PR is kept up to date by advancing the
+/// branch as further PRs land.
+pub async fn sync_upstream(
+    config: &RepoConfig,
+    github: &GithubClient,
+    repo: &mut GitRepository,
+    base_ref: &str,
+    merge_oid: &Oid,
+) -> Result<()> {
+    let upstream = match config.upstream() {
+        Some(upstream) => upstream,
+        None => return Ok(()),
+    };
+
+    let branch = upstream.sync_branch(base_ref);
+    info!(
+        "advancing upstream sync branch '{}' to {}",
+        branch, merge_oid
+    );
+    repo.force_push(&branch, merge_oid)?;
+
+    let head = format!("{}:{}", config.owner(), branch);
+    let options = ListPullsOptions {
+        head: Some(head.clone()),
+        base: Some(base_ref.to_owned()),
+        ..Default::default()
+    };
+    let open = github
+        .pulls()
+        .list(
+            upstream.repo().owner(),
+            upstream.repo().name(),
+            Some(options),
+        )
+        .await?
+        .into_inner();
+    if !open.is_empty() {
+        return Ok(());
+    }
+
+    let pull = NewPullRequest {
+        title: format!(
+            "Sync {} from {}/{}",
+            base_ref,
+            config.owner(),
+            config.name()
+        ),
+        body: Some(format!(
+            "PRs landed on `{}` in {}/{}. This PR is updated automatically as more of them land; \
+            merge it without rewriting its commits so that {}/{} can keep building on `{}`.",
+            base_ref,
+            config.owner(),
+            config.name(),
+            config.owner(),
+            config.name(),
+            base_ref,
+        )),
+        head,
+        base: base_ref.to_owned(),
+        maintainer_can_modify: Some(false),
+        draft: None,
+    };
+    let created = github
+        .pulls()
+        .create(upstream.repo().owner(), upstream.repo().name(), pull)
+        .await?
+        .into_inner();
+    info!(
+        "opened upstream sync pr {}/{}#{}",
+        upstream.repo().owner(),
+        upstream.repo().name(),
+        created.number
+    );
+
+    Ok(())
+}
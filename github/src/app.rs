@@ -0,0 +1,40 @@
+use super::{DateTime, NodeId, User};
+use serde::Deserialize;
+
+/// An installation of a Github App on a user's or organization's account
+///
+/// GitHub API docs: https://developer.github.com/v3/apps/#get-an-installation-for-the-authenticated-app
+#[derive(Clone, Debug, Deserialize)]
+pub struct Installation {
+    pub id: u64,
+    pub account: Option<User>,
+    pub app_id: Option<u64>,
+    /// The choice of repositories the installation is on. Can be either "selected" or "all"
+    pub repository_selection: Option<String>,
+}
+
+/// A token authenticating requests as an installation of a Github App, valid for an hour
+///
+/// GitHub API docs: https://developer.github.com/v3/apps/#create-an-installation-access-token-for-an-app
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstallationToken {
+    pub token: String,
+    pub expires_at: DateTime,
+}
+
+/// A repository an installation is on, as listed by installation events
+#[derive(Clone, Debug, Deserialize)]
+pub struct InstallationRepository {
+    pub id: u64,
+    pub node_id: NodeId,
+    pub name: String,
+    pub full_name: String,
+    pub private: bool,
+}
+
+impl InstallationRepository {
+    /// The login of the repository's owner, taken from its full name
+    pub fn owner(&self) -> &str {
+        self.full_name.split('/').next().unwrap_or(&self.full_name)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    client::{Client, PaginationOptions, Response, Result},
+    Oid,
+};
+use serde::{Deserialize, Serialize};
+
+/// A single run of a GitHub Actions workflow
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub name: Option<String>,
+    pub head_sha: Oid,
+    pub head_branch: Option<String>,
+    /// One of `queued`, `in_progress` or `completed`
+    pub status: Option<String>,
+    pub conclusion: Option<String>,
+    pub html_url: String,
+}
+
+/// A single job of a GitHub Actions workflow run
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorkflowJob {
+    pub id: u64,
+    pub run_id: u64,
+    pub name: String,
+    pub head_sha: Oid,
+    /// One of `queued`, `in_progress` or `completed`
+    pub status: String,
+    pub conclusion: Option<String>,
+    pub html_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowRuns {
+    pub total_count: u64,
+    pub workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ListWorkflowRunsOptions<'a> {
+    /// Only runs associated with this branch are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<&'a str>,
+
+    /// Only runs of this commit are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_sha: Option<&'a str>,
+
+    /// Only runs with this status or conclusion are returned, e.g. `in_progress`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'a str>,
+
+    #[serde(flatten)]
+    pub pagination_options: PaginationOptions,
+}
+
+/// `ActionsClient` handles communication with the actions related methods of the GitHub API.
+///
+/// GitHub API docs: https://docs.github.com/en/rest/actions
+pub struct ActionsClient<'a> {
+    inner: &'a Client,
+}
+
+impl<'a> ActionsClient<'a> {
+    pub(super) fn new(client: &'a Client) -> Self {
+        Self { inner: client }
+    }
+
+    /// List the workflow runs of a repository.
+    ///
+    /// GitHub API docs: https://docs.github.com/en/rest/actions/workflow-runs#list-workflow-runs-for-a-repository
+    pub async fn list_workflow_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        options: &ListWorkflowRunsOptions<'_>,
+    ) -> Result<Response<WorkflowRuns>> {
+        let url = format!("repos/{}/{}/actions/runs", owner, repo);
+        let response = self.inner.get(&url).query(options).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Cancel a workflow run which is queued or in progress.
+    ///
+    /// GitHub API docs: https://docs.github.com/en/rest/actions/workflow-runs#cancel-a-workflow-run
+    pub async fn cancel_workflow_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<Response<()>> {
+        let url = format!("repos/{}/{}/actions/runs/{}/cancel", owner, repo, run_id);
+        let response = self.inner.post(&url).send().await?;
+
+        self.inner.empty(response).await
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    client::{Client, Response, Result, MEDIA_TYPE_INTEGRATION_PREVIEW},
+    App, Installation, InstallationToken,
+};
+
+/// `AppsClient` handles communication with the Github App related methods of the GitHub API.
+///
+/// Apart from creating installation access tokens, these methods need the client to be
+/// authenticated as the app itself, with a JWT signed by its private key.
+///
+/// GitHub API docs: https://developer.github.com/v3/apps/
+pub struct AppsClient<'a> {
+    inner: &'a Client,
+}
+
+impl<'a> AppsClient<'a> {
+    pub(super) fn new(client: &'a Client) -> Self {
+        Self { inner: client }
+    }
+
+    /// Get the app the client is authenticated as.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/apps/#get-the-authenticated-app
+    pub async fn get_authenticated(&self) -> Result<Response<App>> {
+        let response = self
+            .inner
+            .get("app")
+            .header(reqwest::header::ACCEPT, MEDIA_TYPE_INTEGRATION_PREVIEW)
+            .send()
+            .await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Find the installation of the app on the account owning a repository.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/apps/#get-a-repository-installation-for-the-authenticated-app
+    pub async fn get_repository_installation(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Response<Installation>> {
+        let url = format!("repos/{}/{}/installation", owner, repo);
+        let response = self
+            .inner
+            .get(&url)
+            .header(reqwest::header::ACCEPT, MEDIA_TYPE_INTEGRATION_PREVIEW)
+            .send()
+            .await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Create a token authenticating requests as an installation of the app, which expires after
+    /// an hour.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/apps/#create-an-installation-access-token-for-an-app
+    pub async fn create_installation_access_token(
+        &self,
+        installation_id: u64,
+    ) -> Result<Response<InstallationToken>> {
+        let url = format!("app/installations/{}/access_tokens", installation_id);
+        let response = self
+            .inner
+            .post(&url)
+            .header(reqwest::header::ACCEPT, MEDIA_TYPE_INTEGRATION_PREVIEW)
+            .send()
+            .await?;
+
+        self.inner.json(response).await
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    client::{Client, Response, Result},
+    CheckRun, CheckStatus, CheckSuite, Conclusion,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize)]
+pub struct CheckRunOutput<'a> {
+    pub title: &'a str,
+    pub summary: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateCheckRunRequest<'a> {
+    pub name: &'a str,
+    pub head_sha: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CheckStatus>,
+    /// Providing a conclusion automatically sets the status to `completed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<Conclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckRunOutput<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateCheckRunRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CheckStatus>,
+    /// Providing a conclusion automatically sets the status to `completed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<Conclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckRunOutput<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckRuns {
+    pub total_count: u64,
+    pub check_runs: Vec<CheckRun>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckSuites {
+    pub total_count: u64,
+    pub check_suites: Vec<CheckSuite>,
+}
+
+/// `ChecksClient` handles communication with the checks related methods of the GitHub API.
+///
+/// GitHub API docs: https://developer.github.com/v3/checks/
+pub struct ChecksClient<'a> {
+    inner: &'a Client,
+}
+
+impl<'a> ChecksClient<'a> {
+    pub(super) fn new(client: &'a Client) -> Self {
+        Self { inner: client }
+    }
+
+    /// Create a check run for the specified commit.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/runs/#create-a-check-run
+    pub async fn create_check_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        request: &CreateCheckRunRequest<'_>,
+    ) -> Result<Response<CheckRun>> {
+        let url = format!("repos/{}/{}/check-runs", owner, repo);
+        let response = self.inner.post(&url).json(request).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Update a check run, e.g. to complete it.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/runs/#update-a-check-run
+    pub async fn update_check_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        check_run_id: u64,
+        request: &UpdateCheckRunRequest<'_>,
+    ) -> Result<Response<CheckRun>> {
+        let url = format!("repos/{}/{}/check-runs/{}", owner, repo, check_run_id);
+        let response = self.inner.patch(&url).json(request).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// List the check runs called `check_name` of a commit, which can be a SHA, branch name or tag
+    /// name, most recent first.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/runs/#list-check-runs-for-a-git-reference
+    pub async fn list_check_runs_named(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+        check_name: &str,
+    ) -> Result<Response<CheckRuns>> {
+        let url = format!("repos/{}/{}/commits/{}/check-runs", owner, repo, git_ref);
+        let response = self
+            .inner
+            .get(&url)
+            .query(&[("check_name", check_name)])
+            .send()
+            .await?;
+
+        self.inner.json(response).await
+    }
+
+    /// List the check runs of a commit, which can be a SHA, branch name or tag name.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/runs/#list-check-runs-for-a-git-reference
+    pub async fn list_check_runs_for_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+    ) -> Result<Response<CheckRuns>> {
+        let url = format!("repos/{}/{}/commits/{}/check-runs", owner, repo, git_ref);
+        let response = self.inner.get(&url).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// List the check suites of a commit, which can be a SHA, branch name or tag name.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/suites/#list-check-suites-for-a-git-reference
+    pub async fn list_check_suites_for_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+    ) -> Result<Response<CheckSuites>> {
+        let url = format!("repos/{}/{}/commits/{}/check-suites", owner, repo, git_ref);
+        let response = self.inner.get(&url).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Trigger the app which owns a check suite to run it again, without pushing new code.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/checks/suites/#rerequest-a-check-suite
+    pub async fn rerequest_check_suite(
+        &self,
+        owner: &str,
+        repo: &str,
+        check_suite_id: u64,
+    ) -> Result<Response<()>> {
+        let url = format!(
+            "repos/{}/{}/check-suites/{}/rerequest",
+            owner, repo, check_suite_id
+        );
+        let response = self.inner.post(&url).send().await?;
+
+        self.inner.empty(response).await
+    }
+}
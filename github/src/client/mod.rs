@@ -2,7 +2,14 @@
 
 use log::debug;
 use reqwest::{header, Client as ReqwestClient, Method, RequestBuilder};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
 
+mod actions;
+mod apps;
+mod checks;
 mod error;
 mod git;
 #[cfg(feature = "graphql")]
@@ -16,12 +23,20 @@ mod pulls;
 mod rate_limit;
 mod reactions;
 mod repos;
-
+mod teams;
+mod users;
+
+pub use actions::{ActionsClient, ListWorkflowRunsOptions, WorkflowJob, WorkflowRun, WorkflowRuns};
+pub use apps::AppsClient;
+pub use checks::{
+    CheckRunOutput, CheckRuns, CheckSuites, ChecksClient, CreateCheckRunRequest,
+    UpdateCheckRunRequest,
+};
 pub use error::{Error, Result};
 pub use git::GitClient;
 #[cfg(feature = "graphql")]
 pub use graphql::GraphqlClient;
-pub use issues::IssuesClient;
+pub use issues::{IssuesClient, ListIssueCommentsOptions};
 pub use license::LicenseClient;
 pub use markdown::MarkdownClient;
 pub use pagination::{
@@ -32,11 +47,17 @@ pub use project::{
     ProjectClient, UpdateProjectRequest,
 };
 pub use pulls::{
-    ListPullsOptions, MergeMethod, MergePullRequest, MergePullRequestResponse, PullsClient,
+    ListPullsOptions, MergeMethod, MergePullRequest, MergePullRequestResponse, NewPullRequest,
+    PullsClient, UpdatePullRequest,
 };
 pub use rate_limit::{Rate, RateLimitClient, RateLimits};
 pub use reactions::ReactionsClient;
-pub use repos::{CombinedStatus, CreateStatusRequest, RepoStatus, RepositoryClient};
+pub use repos::{
+    CombinedStatus, CreateStatusRequest, HookDelivery, HookDeliveryDetails, HookDeliveryRequest,
+    RepoStatus, RepositoryClient, RequiredStatusChecks,
+};
+pub use teams::TeamsClient;
+pub use users::UsersClient;
 
 // Constants
 const DEFAULT_BASE_URL: &str = "https://api.github.com/";
@@ -46,12 +67,14 @@ const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VE
 const HEADER_RATE_LIMIT: &str = "X-RateLimit-Limit";
 const HEADER_RATE_REMAINING: &str = "X-RateLimit-Remaining";
 const HEADER_RATE_RESET: &str = "X-RateLimit-Reset";
+const HEADER_RATE_RESOURCE: &str = "X-RateLimit-Resource";
 const HEADER_OTP: &str = "X-GitHub-OTP";
 const HEADER_LINK: &str = "Link";
 
 const MEDIA_TYPE_V3: &str = "application/vnd.github.v3+json";
 const DEFAULT_MEDIA_TYPE: &str = "application/octet-stream";
 const MEDIA_TYPE_V3_SHA: &str = "application/vnd.github.v3.sha";
+const MEDIA_TYPE_V3_RAW: &str = "application/vnd.github.v3.raw";
 const MEDIA_TYPE_V3_DIFF: &str = "application/vnd.github.v3.diff";
 const MEDIA_TYPE_V3_PATCH: &str = "application/vnd.github.v3.patch";
 const MEDIA_TYPE_ORG_PERMISSION_REPO: &str = "application/vnd.github.v3.repository+json";
@@ -209,6 +232,7 @@ pub struct ClientBuilder {
     base_url: Option<String>,
     user_agent: Option<String>,
     github_api_token: Option<String>,
+    app_jwt: Option<String>,
 }
 
 impl ClientBuilder {
@@ -217,6 +241,7 @@ impl ClientBuilder {
             base_url: None,
             user_agent: None,
             github_api_token: None,
+            app_jwt: None,
         }
     }
 
@@ -235,29 +260,33 @@ impl ClientBuilder {
         self
     }
 
+    /// Authenticate as a Github App, rather than with an API token, using a JWT signed by the
+    /// app's private key
+    ///
+    /// GitHub API docs: https://developer.github.com/apps/building-github-apps/authenticating-with-github-apps/#authenticating-as-a-github-app
+    pub fn app_jwt<S: Into<String>>(mut self, app_jwt: S) -> Self {
+        self.app_jwt = Some(app_jwt.into());
+        self
+    }
+
     pub fn build(self) -> Result<Client> {
         let base_url = self.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_owned());
         let user_agent = self.user_agent.unwrap_or_else(|| USER_AGENT.to_owned());
 
-        let mut client_builder = ReqwestClient::builder().user_agent(&user_agent);
-
-        if let Some(token) = &self.github_api_token {
-            let mut headers = header::HeaderMap::new();
-            headers.insert(
-                header::AUTHORIZATION,
-                header::HeaderValue::from_str(&format!("token {}", token))
-                    .map_err(|e| e.to_string())?,
-            );
-            client_builder = client_builder.default_headers(headers);
-        }
+        let client = ReqwestClient::builder().user_agent(&user_agent).build()?;
 
-        let client = client_builder.build()?;
+        let authorization = match (&self.app_jwt, &self.github_api_token) {
+            (Some(jwt), _) => Some(authorization("Bearer", jwt)?),
+            (None, Some(token)) => Some(authorization("token", token)?),
+            (None, None) => None,
+        };
 
         Ok(Client {
             base_url,
             user_agent,
-            github_api_token: self.github_api_token,
+            authorization: RwLock::new(authorization),
             client,
+            rates: Mutex::new(HashMap::new()),
         })
     }
 }
@@ -268,6 +297,12 @@ impl Default for ClientBuilder {
     }
 }
 
+fn authorization(scheme: &str, credentials: &str) -> Result<header::HeaderValue> {
+    let value = header::HeaderValue::from_str(&format!("{} {}", scheme, credentials))
+        .map_err(|e| e.to_string())?;
+    Ok(value)
+}
+
 #[derive(Debug)]
 pub struct Client {
     /// Base URL to use for API requests. Defaults to the public GitHub API,
@@ -279,12 +314,15 @@ pub struct Client {
     #[allow(unused)]
     user_agent: String,
 
-    /// API token to use when issuing requests to GitHub
-    #[allow(unused)]
-    github_api_token: Option<String>,
+    /// Authorization header sent with each request to GitHub, which can be replaced when the token
+    /// it carries expires, e.g. an installation access token
+    authorization: RwLock<Option<header::HeaderValue>>,
 
     /// Client used to make http requests
     client: ReqwestClient,
+
+    /// The latest rate limit reported for each of the API's resources, e.g. "core" or "graphql"
+    rates: Mutex<HashMap<String, Rate>>,
 }
 
 impl Client {
@@ -318,7 +356,43 @@ impl Client {
 
     fn request(&self, method: Method, url: &str) -> RequestBuilder {
         let url = format!("{}{}", self.base_url, url);
-        self.client.request(method, &url)
+        let request = self.client.request(method, &url);
+        match &*self.authorization.read().unwrap() {
+            Some(authorization) => request.header(header::AUTHORIZATION, authorization.clone()),
+            None => request,
+        }
+    }
+
+    /// Replace the API token used for subsequent requests, e.g. with a fresh installation access
+    /// token once the previous one is about to expire
+    pub fn set_github_api_token(&self, github_api_token: &str) -> Result<()> {
+        let authorization = authorization("token", github_api_token)?;
+        *self.authorization.write().unwrap() = Some(authorization);
+        Ok(())
+    }
+
+    /// The latest rate limit reported for each of the API's resources used by this client, keyed
+    /// by resource, e.g. "core" or "graphql"
+    pub fn rates(&self) -> HashMap<String, Rate> {
+        self.rates.lock().unwrap().clone()
+    }
+
+    fn record_rate(&self, headers: &header::HeaderMap) -> Rate {
+        let rate = Rate::from_headers(headers);
+        debug!("RateLimit info: {:?}", rate);
+
+        if rate.limit > 0 {
+            let resource = headers
+                .get(HEADER_RATE_RESOURCE)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("core");
+            self.rates
+                .lock()
+                .unwrap()
+                .insert(resource.to_owned(), rate.clone());
+        }
+
+        rate
     }
 
     //TODO explicitly check for and construct a RateLimit error when rate limits are hit
@@ -336,9 +410,8 @@ impl Client {
         }
 
         let pagination = Pagination::from_headers(response.headers());
-        let rate = Rate::from_headers(response.headers());
+        let rate = self.record_rate(response.headers());
 
-        debug!("RateLimit info: {:?}", rate);
         Ok((response, pagination, rate))
     }
 
@@ -357,9 +430,8 @@ impl Client {
         };
 
         let pagination = Pagination::from_headers(response.headers());
-        let rate = Rate::from_headers(response.headers());
+        let rate = self.record_rate(response.headers());
 
-        debug!("RateLimit info: {:?}", rate);
         Ok(Response::new(pagination, rate, ret))
     }
 
@@ -388,17 +460,26 @@ impl Client {
         GraphqlClient::new(&self)
     }
 
-    // TODO: actions endpoint
-    // https://developer.github.com/v3/actions/
+    // actions endpoint
+    // https://docs.github.com/en/rest/actions
+    pub fn actions(&self) -> ActionsClient<'_> {
+        ActionsClient::new(&self)
+    }
 
     // TODO: activity endpoint
     // https://developer.github.com/v3/activity/
 
-    // TODO: apps endpoint
+    // apps endpoint
     // https://developer.github.com/v3/apps/
+    pub fn apps(&self) -> AppsClient<'_> {
+        AppsClient::new(&self)
+    }
 
-    // TODO checks endpoint
+    // checks endpoint
     // https://developer.github.com/v3/checks/
+    pub fn checks(&self) -> ChecksClient<'_> {
+        ChecksClient::new(&self)
+    }
 
     // TODO code of conduct endpoint
     // https://developer.github.com/v3/codes_of_conduct/
@@ -469,11 +550,17 @@ impl Client {
     // TODO search endpoint
     // https://developer.github.com/v3/search/
 
-    // TODO teams endpoint
+    // teams endpoint
     // https://developer.github.com/v3/teams/
+    pub fn teams(&self) -> TeamsClient<'_> {
+        TeamsClient::new(&self)
+    }
 
-    // TODO users endpoint
+    // users endpoint
     // https://developer.github.com/v3/users/
+    pub fn users(&self) -> UsersClient<'_> {
+        UsersClient::new(&self)
+    }
 }
 
 impl Default for Client {
@@ -5,7 +5,7 @@ use crate::{
         MEDIA_TYPE_MULTI_LINE_COMMENTS_PREVIEW, MEDIA_TYPE_REACTIONS_PREVIEW,
         MEDIA_TYPE_UPDATE_PULL_REQUEST_BRANCH_PREVIEW,
     },
-    DateTime, PullRequest, Review, ReviewComment, Team, User,
+    CommitFile, DateTime, PullRequest, RepositoryCommit, Review, ReviewComment, State, Team, User,
 };
 use serde::{Deserialize, Serialize};
 
@@ -78,6 +78,10 @@ pub struct UpdatePullRequest {
 
     /// Indicates whether the pull request is a draft
     pub draft: Option<bool>,
+
+    /// Either open or close the pull request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -357,7 +361,6 @@ impl<'a> PullsClient<'a> {
         self.inner.json(response).await
     }
 
-    //TODO add a RepositoryCommit type
     /// List commits on a pull request
     ///
     /// GitHub API docs: https://developer.github.com/v3/pulls/#list-commits-on-a-pull-request
@@ -367,16 +370,13 @@ impl<'a> PullsClient<'a> {
         repo: &str,
         pull_number: u64,
         options: Option<PaginationOptions>,
-        //) -> Result<Response<Vec<RepositoryCommit>>> {
-    ) -> Result<Response<()>> {
+    ) -> Result<Response<Vec<RepositoryCommit>>> {
         let url = format!("repos/{}/{}/pulls/{}/commits", owner, repo, pull_number);
         let response = self.inner.get(&url).query(&options).send().await?;
 
-        //self.inner.json(response).await
-        self.inner.empty(response).await
+        self.inner.json(response).await
     }
 
-    //TODO add CommitFile type
     /// List files on a pull request
     ///
     /// GitHub API docs: https://developer.github.com/v3/pulls/#list-pull-requests-files
@@ -386,13 +386,11 @@ impl<'a> PullsClient<'a> {
         repo: &str,
         pull_number: u64,
         options: Option<PaginationOptions>,
-        //) -> Result<Response<Vec<CommitFile>>> {
-    ) -> Result<Response<()>> {
+    ) -> Result<Response<Vec<CommitFile>>> {
         let url = format!("repos/{}/{}/pulls/{}/files", owner, repo, pull_number);
         let response = self.inner.get(&url).query(&options).send().await?;
 
-        //self.inner.json(response).await
-        self.inner.empty(response).await
+        self.inner.json(response).await
     }
 
     /// Check if a pull request has been merged
@@ -3,7 +3,7 @@ use crate::client::{
 };
 use serde::Deserialize;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct Rate {
     pub limit: usize,
     pub remaining: usize,
@@ -0,0 +1,33 @@
+use super::RepositoryClient;
+use crate::client::{Response, Result};
+use serde::Deserialize;
+
+/// The status checks a branch's protection rules require to pass before merging into it
+#[derive(Debug, Deserialize)]
+pub struct RequiredStatusChecks {
+    /// Indicates if branches must be up to date with the base branch before merging
+    pub strict: bool,
+    pub contexts: Vec<String>,
+}
+
+// Implementation for the branch protection endpoint
+// https://docs.github.com/en/rest/branches/branch-protection
+impl RepositoryClient<'_> {
+    /// Get the status checks required by a branch's protection rules.
+    ///
+    /// GitHub API docs: https://docs.github.com/en/rest/branches/branch-protection#get-status-checks-protection
+    pub async fn get_required_status_checks(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<Response<RequiredStatusChecks>> {
+        let url = format!(
+            "repos/{}/{}/branches/{}/protection/required_status_checks",
+            owner, repo, branch
+        );
+        let response = self.inner.get(&url).send().await?;
+
+        self.inner.json(response).await
+    }
+}
@@ -0,0 +1,36 @@
+use super::RepositoryClient;
+use crate::client::{Response, Result, MEDIA_TYPE_V3_RAW};
+use serde::Serialize;
+
+#[derive(Debug, Default, Serialize)]
+struct GetContentsOptions<'a> {
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    git_ref: Option<&'a str>,
+}
+
+// Implementation for the repository contents endpoint
+// https://developer.github.com/v3/repos/contents/
+impl RepositoryClient<'_> {
+    /// Get the raw contents of a file, as of `git_ref` or of the tip of the repository's default
+    /// branch.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/repos/contents/#get-contents
+    pub async fn get_raw_contents(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Response<String>> {
+        let url = format!("repos/{}/{}/contents/{}", owner, repo, path);
+        let response = self
+            .inner
+            .get(&url)
+            .header(reqwest::header::ACCEPT, MEDIA_TYPE_V3_RAW)
+            .query(&GetContentsOptions { git_ref })
+            .send()
+            .await?;
+
+        self.inner.text(response).await
+    }
+}
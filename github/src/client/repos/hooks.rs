@@ -0,0 +1,72 @@
+use super::RepositoryClient;
+use crate::{
+    client::{PaginationOptions, Response, Result},
+    DateTime,
+};
+use serde::Deserialize;
+
+/// A single delivery of a webhook
+#[derive(Debug, Deserialize)]
+pub struct HookDelivery {
+    pub id: u64,
+    /// The delivery's GUID, as provided in the `X-GitHub-Delivery` header of the webhook
+    pub guid: String,
+    pub delivered_at: DateTime,
+    pub redelivery: bool,
+    pub status: String,
+    pub status_code: u16,
+    pub event: String,
+    pub action: Option<String>,
+}
+
+/// A webhook delivery, including the payload which was sent
+#[derive(Debug, Deserialize)]
+pub struct HookDeliveryDetails {
+    #[serde(flatten)]
+    pub delivery: HookDelivery,
+    pub request: HookDeliveryRequest,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HookDeliveryRequest {
+    pub payload: serde_json::Value,
+}
+
+// Implementation for the webhook deliveries endpoint
+// https://docs.github.com/en/rest/webhooks/repo-deliveries
+impl RepositoryClient<'_> {
+    /// List the deliveries of a repository webhook, most recent first.
+    ///
+    /// GitHub API docs: https://docs.github.com/en/rest/webhooks/repo-deliveries#list-deliveries-for-a-repository-webhook
+    pub async fn list_hook_deliveries(
+        &self,
+        owner: &str,
+        repo: &str,
+        hook_id: u64,
+        options: PaginationOptions,
+    ) -> Result<Response<Vec<HookDelivery>>> {
+        let url = format!("repos/{}/{}/hooks/{}/deliveries", owner, repo, hook_id);
+        let response = self.inner.get(&url).query(&options).send().await?;
+
+        self.inner.json(response).await
+    }
+
+    /// Get a single delivery of a repository webhook, including its payload.
+    ///
+    /// GitHub API docs: https://docs.github.com/en/rest/webhooks/repo-deliveries#get-a-delivery-for-a-repository-webhook
+    pub async fn get_hook_delivery(
+        &self,
+        owner: &str,
+        repo: &str,
+        hook_id: u64,
+        delivery_id: u64,
+    ) -> Result<Response<HookDeliveryDetails>> {
+        let url = format!(
+            "repos/{}/{}/hooks/{}/deliveries/{}",
+            owner, repo, hook_id, delivery_id
+        );
+        let response = self.inner.get(&url).send().await?;
+
+        self.inner.json(response).await
+    }
+}
@@ -1,9 +1,14 @@
 use crate::client::Client;
 
+mod branches;
 mod collaborators;
+mod contents;
+mod hooks;
 mod status;
 
+pub use branches::RequiredStatusChecks;
 pub use collaborators::ListCollaboratorsOptions;
+pub use hooks::{HookDelivery, HookDeliveryDetails, HookDeliveryRequest};
 pub use status::{CombinedStatus, CreateStatusRequest, RepoStatus};
 
 /// `RepositoryClient` handles communication with the Repository related methods of the GitHub API.
@@ -0,0 +1,33 @@
+use crate::{
+    client::{Client, PaginationOptions, Response, Result},
+    User,
+};
+
+/// `TeamsClient` handles communication with the team related methods of the GitHub API.
+///
+/// GitHub API docs: https://developer.github.com/v3/teams/
+pub struct TeamsClient<'a> {
+    inner: &'a Client,
+}
+
+impl<'a> TeamsClient<'a> {
+    pub(super) fn new(client: &'a Client) -> Self {
+        Self { inner: client }
+    }
+
+    /// List the members of a team, including the members of its child teams. Users who have been
+    /// invited to the team but haven't yet accepted aren't listed.
+    ///
+    /// GitHub API docs: https://developer.github.com/v3/teams/members/#list-team-members
+    pub async fn list_members(
+        &self,
+        org: &str,
+        team_slug: &str,
+        options: Option<PaginationOptions>,
+    ) -> Result<Response<Vec<User>>> {
+        let url = format!("orgs/{}/teams/{}/members", org, team_slug);
+        let response = self.inner.get(&url).query(&options).send().await?;
+
+        self.inner.json(response).await
+    }
+}
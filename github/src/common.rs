@@ -10,7 +10,7 @@ impl NodeId {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Oid(String);
 
 impl Oid {
@@ -25,9 +25,39 @@ impl fmt::Display for Oid {
     }
 }
 
+/// How the author of a comment, issue or pull request is associated with a repository, ordered
+/// from least to most trusted
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuthorAssociation {
+    Mannequin,
+    None,
+    FirstTimer,
+    FirstTimeContributor,
+    Contributor,
+    Collaborator,
+    Member,
+    Owner,
+}
+
+impl std::str::FromStr for AuthorAssociation {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned()))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct DateTime(chrono::DateTime<chrono::Utc>);
 
+impl DateTime {
+    /// Seconds since the unix epoch
+    pub fn timestamp(&self) -> i64 {
+        self.0.timestamp()
+    }
+}
+
 impl Serialize for DateTime {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -1,7 +1,8 @@
 use super::{
-    CheckRun, CheckSuite, Comment, Commit, DateTime, Hook, Issue, Key, Label, Milestone, Oid,
-    Project, ProjectCard, ProjectColumn, PullRequest, Pusher, Repository, Review, ReviewComment,
-    Team, User,
+    client::{WorkflowJob, WorkflowRun},
+    CheckRun, CheckSuite, Comment, Commit, DateTime, Hook, Installation, InstallationRepository,
+    Issue, Key, Label, Milestone, Oid, Project, ProjectCard, ProjectColumn, PullRequest, Pusher,
+    Repository, Review, ReviewComment, Team, User,
 };
 use serde::{de, Deserialize, Serialize};
 use std::str::FromStr;
@@ -9,6 +10,7 @@ use thiserror::Error;
 
 #[derive(Clone, Copy, Debug)]
 pub enum EventType {
+    BranchProtectionRule,
     CheckRun,
     CheckSuite,
     CommitComment,
@@ -61,6 +63,8 @@ pub enum EventType {
     Team,
     TeamAdd,
     Watch,
+    WorkflowJob,
+    WorkflowRun,
     Wildcard,
 }
 
@@ -75,6 +79,7 @@ impl FromStr for EventType {
         use EventType::*;
 
         match s {
+            "branch_protection_rule" => Ok(BranchProtectionRule),
             "check_run" => Ok(CheckRun),
             "check_suite" => Ok(CheckSuite),
             "commit_comment" => Ok(CommitComment),
@@ -123,6 +128,8 @@ impl FromStr for EventType {
             "team" => Ok(Team),
             "team_add" => Ok(TeamAdd),
             "watch" => Ok(Watch),
+            "workflow_job" => Ok(WorkflowJob),
+            "workflow_run" => Ok(WorkflowRun),
             "*" => Ok(Wildcard),
             _ => Err(ParseEventTypeError),
         }
@@ -142,6 +149,7 @@ impl<'de> Deserialize<'de> for EventType {
 #[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum Event {
+    BranchProtectionRule(BranchProtectionRuleEvent),
     CheckRun(CheckRunEvent),
     CheckSuite(CheckSuiteEvent),
     CommitComment(CommitCommentEvent),
@@ -189,11 +197,16 @@ pub enum Event {
     Team(TeamEvent),
     TeamAdd(TeamAddEvent),
     Watch(WatchEvent),
+    WorkflowJob(WorkflowJobEvent),
+    WorkflowRun(WorkflowRunEvent),
 }
 
 impl Event {
     pub fn from_json(event_type: EventType, json: &[u8]) -> Result<Self, serde_json::Error> {
         let event = match event_type {
+            EventType::BranchProtectionRule => {
+                Event::BranchProtectionRule(serde_json::from_slice(json)?)
+            }
             EventType::CheckRun => Event::CheckRun(serde_json::from_slice(json)?),
             EventType::CheckSuite => Event::CheckSuite(serde_json::from_slice(json)?),
             EventType::CommitComment => Event::CommitComment(serde_json::from_slice(json)?),
@@ -254,6 +267,8 @@ impl Event {
             EventType::Team => Event::Team(serde_json::from_slice(json)?),
             EventType::TeamAdd => Event::TeamAdd(serde_json::from_slice(json)?),
             EventType::Watch => Event::Watch(serde_json::from_slice(json)?),
+            EventType::WorkflowJob => Event::WorkflowJob(serde_json::from_slice(json)?),
+            EventType::WorkflowRun => Event::WorkflowRun(serde_json::from_slice(json)?),
             // TODO have an error type if we try to De a wildcard event payload since they don't
             // exist
             EventType::Wildcard => unimplemented!(),
@@ -263,6 +278,7 @@ impl Event {
 
     pub fn event_type(&self) -> EventType {
         match &self {
+            Event::BranchProtectionRule(_) => EventType::BranchProtectionRule,
             Event::CheckRun(_) => EventType::CheckRun,
             Event::CheckSuite(_) => EventType::CheckSuite,
             Event::CommitComment(_) => EventType::CommitComment,
@@ -310,12 +326,15 @@ impl Event {
             Event::Team(_) => EventType::Team,
             Event::TeamAdd(_) => EventType::TeamAdd,
             Event::Watch(_) => EventType::Watch,
+            Event::WorkflowJob(_) => EventType::WorkflowJob,
+            Event::WorkflowRun(_) => EventType::WorkflowRun,
         }
     }
 
     pub fn repository(&self) -> Option<&Repository> {
         match &self {
-            Event::CheckRun(CheckRunEvent { repository, .. })
+            Event::BranchProtectionRule(BranchProtectionRuleEvent { repository, .. })
+            | Event::CheckRun(CheckRunEvent { repository, .. })
             | Event::CheckSuite(CheckSuiteEvent { repository, .. })
             | Event::CommitComment(CommitCommentEvent { repository, .. })
             | Event::ContentReference(ContentReferenceEvent { repository, .. })
@@ -357,7 +376,9 @@ impl Event {
             | Event::Star(StarEvent { repository, .. })
             | Event::Status(StatusEvent { repository, .. })
             | Event::TeamAdd(TeamAddEvent { repository, .. })
-            | Event::Watch(WatchEvent { repository, .. }) => Some(&repository),
+            | Event::Watch(WatchEvent { repository, .. })
+            | Event::WorkflowJob(WorkflowJobEvent { repository, .. })
+            | Event::WorkflowRun(WorkflowRunEvent { repository, .. }) => Some(&repository),
 
             Event::Installation(_)
             | Event::InstallationRepositories(_)
@@ -372,6 +393,38 @@ impl Event {
     }
 }
 
+/// The Action performed by a `BranchProtectionRuleEvent`
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchProtectionRuleEventAction {
+    Created,
+    Edited,
+    Deleted,
+}
+
+/// A branch protection rule, applying to the branches matching its name pattern
+#[derive(Clone, Debug, Deserialize)]
+pub struct BranchProtectionRule {
+    pub id: u64,
+    /// The branch name pattern the rule applies to
+    pub name: String,
+    /// The status check contexts required to pass before merging
+    pub required_status_checks: Vec<String>,
+}
+
+/// Triggered when a branch protection rule is created, edited or deleted
+///
+/// GitHub API docs: https://docs.github.com/en/developers/webhooks-and-events/webhooks/webhook-events-and-payloads#branch_protection_rule
+#[derive(Clone, Debug, Deserialize)]
+pub struct BranchProtectionRuleEvent {
+    pub action: BranchProtectionRuleEventAction,
+    pub rule: BranchProtectionRule,
+
+    // Populated by Webhook events
+    pub repository: Repository,
+    pub sender: User,
+}
+
 /// The Action performed by a `CheckRunEvent`
 #[derive(Clone, Copy, Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -558,7 +611,9 @@ pub struct GollumEvent {
 pub struct InstallationEvent {
     /// The action performed. Possible values are: "created", "deleted", "new_permissions_accepted"
     pub action: String,
-    //pub installation: Installation, //TODO add type
+    pub installation: Installation,
+    /// The repositories the installation is on, when it's created or deleted
+    pub repositories: Option<Vec<InstallationRepository>>,
     pub sender: User,
 }
 
@@ -567,11 +622,11 @@ pub struct InstallationEvent {
 pub struct InstallationRepositoriesEvent {
     /// The action performed. Possible values are: "added", "removed"
     pub action: String,
-    //pub installation: Installation, //TODO add type
+    pub installation: Installation,
     /// The choice of repositories the installation is on. Can be either "selected" or "all"
     pub repository_selection: String,
-    pub repositories_added: Vec<Repository>,
-    pub repositories_removed: Vec<Repository>,
+    pub repositories_added: Vec<InstallationRepository>,
+    pub repositories_removed: Vec<InstallationRepository>,
 
     pub sender: User,
 }
@@ -1249,13 +1304,77 @@ pub struct WatchEvent {
     //pub installation: Installation, //TODO add type
 }
 
+/// The Action performed by a `WorkflowJobEvent`
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowJobEventAction {
+    Queued,
+    InProgress,
+    Completed,
+    Waiting,
+}
+
+/// Triggered when a job of a GitHub Actions workflow run is queued, started or completed
+///
+/// GitHub API docs: https://docs.github.com/en/developers/webhooks-and-events/webhooks/webhook-events-and-payloads#workflow_job
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorkflowJobEvent {
+    pub action: WorkflowJobEventAction,
+    pub workflow_job: WorkflowJob,
+
+    // Populated by Webhook events
+    pub repository: Repository,
+    pub sender: User,
+    //pub organization: Organization, //TODO add type
+    //pub installation: Installation, //TODO add type
+}
+
+/// The Action performed by a `WorkflowRunEvent`
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunEventAction {
+    Requested,
+    InProgress,
+    Completed,
+}
+
+/// Triggered when a GitHub Actions workflow run is requested, started or completed
+///
+/// GitHub API docs: https://docs.github.com/en/developers/webhooks-and-events/webhooks/webhook-events-and-payloads#workflow_run
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorkflowRunEvent {
+    pub action: WorkflowRunEventAction,
+    pub workflow_run: WorkflowRun,
+
+    // Populated by Webhook events
+    pub repository: Repository,
+    pub sender: User,
+    //pub organization: Organization, //TODO add type
+    //pub installation: Installation, //TODO add type
+}
+
 #[cfg(test)]
 mod test {
     use super::{
-        CheckRunEvent, CheckSuiteEvent, IssueCommentEvent, IssueEvent,
-        PullRequestReviewCommentEvent, PullRequestReviewEvent, PushEvent, StatusEvent,
+        BranchProtectionRuleEvent, CheckRunEvent, CheckSuiteEvent, InstallationRepositoriesEvent,
+        IssueCommentEvent, IssueEvent, PullRequestReviewCommentEvent, PullRequestReviewEvent,
+        PushEvent, StatusEvent, WorkflowJobEvent, WorkflowRunEvent,
     };
 
+    #[test]
+    fn branch_protection_rule_event() {
+        const JSON: &str = include_str!("../test-input/branch-protection-rule-event.json");
+        let _: BranchProtectionRuleEvent = serde_json::from_str(JSON).unwrap();
+    }
+
+    #[test]
+    fn installation_repositories_event() {
+        const JSON: &str = include_str!("../test-input/installation-repositories-event.json");
+        let event: InstallationRepositoriesEvent = serde_json::from_str(JSON).unwrap();
+        assert_eq!(event.installation.id, 2);
+        assert_eq!(event.repositories_added[0].owner(), "octocat");
+    }
+
     #[test]
     fn push_event() {
         const PUSH_JSON: &str = include_str!("../test-input/push-event.json");
@@ -1303,4 +1422,16 @@ mod test {
         const JSON: &str = include_str!("../test-input/pull-request-review-comment-event.json");
         let _: PullRequestReviewCommentEvent = serde_json::from_str(JSON).unwrap();
     }
+
+    #[test]
+    fn workflow_run_event() {
+        const JSON: &str = include_str!("../test-input/workflow-run-event.json");
+        let _: WorkflowRunEvent = serde_json::from_str(JSON).unwrap();
+    }
+
+    #[test]
+    fn workflow_job_event() {
+        const JSON: &str = include_str!("../test-input/workflow-job-event.json");
+        let _: WorkflowJobEvent = serde_json::from_str(JSON).unwrap();
+    }
 }
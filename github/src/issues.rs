@@ -81,6 +81,7 @@ pub struct Comment {
     pub body: Option<String>,
     pub reactions: Option<ReactionSummary>,
     pub user: User,
+    pub author_association: Option<String>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -2,6 +2,7 @@
 //! https://developer.github.com/webhooks/
 //! https://developer.github.com/v3/
 
+mod app;
 mod check;
 pub mod client; //TODO Maybe hide with a feature?
 mod common;
@@ -16,6 +17,7 @@ mod repo;
 mod user;
 mod webhook;
 
+pub use app::*;
 pub use check::*;
 pub use client::Client;
 pub use common::*;
@@ -137,6 +137,59 @@ impl ReviewComment {
     }
 }
 
+/// A file changed by a pull request
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommitFile {
+    pub sha: Option<String>,
+    pub filename: String,
+    pub status: String,
+    pub additions: u64,
+    pub deletions: u64,
+    pub changes: u64,
+    pub blob_url: Option<String>,
+    pub raw_url: Option<String>,
+    pub contents_url: Option<String>,
+    pub patch: Option<String>,
+    pub previous_filename: Option<String>,
+}
+
+/// A commit of a pull request
+#[derive(Clone, Debug, Deserialize)]
+pub struct RepositoryCommit {
+    pub sha: Oid,
+    pub node_id: NodeId,
+    pub url: String,
+    pub html_url: String,
+    pub commit: GitCommit,
+    /// The Github user the commit's author is linked to, if any
+    pub author: Option<User>,
+    /// The Github user the commit's committer is linked to, if any
+    pub committer: Option<User>,
+    pub parents: Vec<CommitParent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitCommit {
+    pub url: String,
+    pub author: GitSignature,
+    pub committer: GitSignature,
+    pub message: String,
+    pub comment_count: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitSignature {
+    pub name: String,
+    pub email: String,
+    pub date: DateTime,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommitParent {
+    pub sha: Oid,
+    pub url: String,
+}
+
 #[cfg(test)]
 mod test {
     use super::PullRequest;
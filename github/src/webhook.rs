@@ -1,5 +1,7 @@
 use super::{Event, EventType};
-use log::{debug, warn};
+use hmac::{Hmac, Mac, NewMac};
+use log::warn;
+use sha2::Sha256;
 
 /// The GitHub header key used to pass the event type
 ///
@@ -11,10 +13,10 @@ pub const EVENT_TYPE_HEADER: &str = "X-Github-Event";
 /// Github API docs: https://developer.github.com/webhooks/#delivery-headers
 pub const DELIVERY_ID_HEADER: &str = "X-Github-Delivery";
 
-/// The GitHub header key used to pass the HMAC hexdigest
+/// The GitHub header key used to pass the HMAC-SHA256 hexdigest of the payload
 ///
 /// Github API docs: https://developer.github.com/webhooks/#delivery-headers
-pub const SIGNATURE_HEADER: &str = "X-Hub-Signature";
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
 
 #[derive(Clone, Debug)]
 pub struct Webhook {
@@ -25,19 +27,29 @@ pub struct Webhook {
 }
 
 impl Webhook {
+    /// Check the payload's HMAC-SHA256 signature against the webhook secret `key`
+    ///
+    /// Github API docs: https://developer.github.com/webhooks/securing/
     pub fn check_signature(&self, key: Option<&[u8]>) -> bool {
         match (key, &self.signature) {
-            (Some(key), Some(signature)) if signature.starts_with("sha1=") => {
-                let hash = hex::encode(hmacsha1::hmac_sha1(key, &self.body));
-                let signature = &signature["sha1=".len()..];
+            (Some(key), Some(signature)) => {
+                let digest = match signature
+                    .strip_prefix("sha256=")
+                    .and_then(|digest| hex::decode(digest).ok())
+                {
+                    Some(digest) => digest,
+                    None => return false,
+                };
 
-                debug!("hash: {}", hash);
-                debug!("sig:  {}", signature);
-                hash == signature
+                let mut mac =
+                    Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any length");
+                mac.update(&self.body);
+                // Compare in constant time so that how long the comparison takes doesn't reveal how
+                // much of a forged signature was correct
+                mac.verify(&digest).is_ok()
             }
-            // We are expecting a signature and we either recieved it in a different format than
-            // expected or no signature was sent.
-            (Some(_), _) => false,
+            // We are expecting a signature and none was sent
+            (Some(_), None) => false,
             // No key or signature to check
             (None, _) => {
                 warn!("No secret specified; signature ignored");
@@ -50,3 +62,37 @@ impl Webhook {
         Event::from_json(self.event_type, &self.body)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Webhook;
+    use crate::EventType;
+
+    // The example from Github's docs on validating webhook deliveries
+    #[test]
+    fn signature() {
+        let webhook = |signature: Option<&str>| Webhook {
+            event_type: EventType::Ping,
+            delivery_id: String::new(),
+            signature: signature.map(ToOwned::to_owned),
+            body: b"Hello, World!".to_vec(),
+        };
+        let key = Some(&b"It's a Secret to Everybody"[..]);
+
+        let signed = webhook(Some(
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17",
+        ));
+        assert!(signed.check_signature(key));
+        assert!(!signed.check_signature(Some(b"the wrong secret")));
+        assert!(signed.check_signature(None));
+
+        let forged = webhook(Some(
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e18",
+        ));
+        assert!(!forged.check_signature(key));
+        assert!(
+            !webhook(Some("sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59")).check_signature(key)
+        );
+        assert!(!webhook(None).check_signature(key));
+    }
+}
@@ -0,0 +1,51 @@
+//! Streams of server-sent events which services publish to HTTP clients
+
+use futures::stream::BoxStream;
+
+/// A stream of events published by a service
+pub type EventStream = BoxStream<'static, StreamEvent>;
+
+/// A single server-sent event
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamEvent {
+    event: String,
+    data: String,
+}
+
+impl StreamEvent {
+    pub fn new<E: Into<String>, D: Into<String>>(event: E, data: D) -> Self {
+        Self {
+            event: event.into(),
+            data: data.into(),
+        }
+    }
+
+    /// Encode this event in the `text/event-stream` format
+    pub fn encode(&self) -> String {
+        let mut encoded = format!("event: {}\n", self.event);
+        for line in self.data.lines() {
+            encoded.push_str("data: ");
+            encoded.push_str(line);
+            encoded.push('\n');
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamEvent;
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            StreamEvent::new("queue", r#"{"paused":false}"#).encode(),
+            "event: queue\ndata: {\"paused\":false}\n\n"
+        );
+        assert_eq!(
+            StreamEvent::new("log", "one\ntwo").encode(),
+            "event: log\ndata: one\ndata: two\n\n"
+        );
+    }
+}
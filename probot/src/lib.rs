@@ -1,12 +1,16 @@
 mod error;
+mod event_stream;
 mod installation;
+mod registry;
 mod server;
 mod service;
 mod smee_client;
 
 pub use self::{
     error::{Error, Result},
+    event_stream::{EventStream, StreamEvent},
     installation::Installation,
+    registry::Registry,
     server::{Server, ServerBuilder},
     service::Service,
 };
@@ -0,0 +1,109 @@
+use crate::{installation::Installation, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// The installations served by a `Server`, keyed by the repository they're for, so that webhooks
+/// are routed to the services of the repository they were delivered for. Handles to a registry
+/// are cheap to clone and share the same installations, which can be registered while the server
+/// is running.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    installations: Arc<RwLock<HashMap<String, Arc<Installation>>>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an installation, failing if one is already registered for its repository
+    pub fn register(&self, installation: Installation) -> Result<()> {
+        let key = key(installation.owner(), installation.name());
+        let mut installations = self.installations.write().unwrap();
+        if installations.contains_key(&key) {
+            return Err(format!(
+                "{}/{} is already registered",
+                installation.owner(),
+                installation.name()
+            )
+            .into());
+        }
+
+        installations.insert(key, Arc::new(installation));
+        Ok(())
+    }
+
+    /// The installation for the `owner/name` repository. Github doesn't distinguish owners or
+    /// repositories by case, so neither does the registry.
+    pub fn get(&self, owner: &str, name: &str) -> Option<Arc<Installation>> {
+        self.installations
+            .read()
+            .unwrap()
+            .get(&key(owner, name))
+            .cloned()
+    }
+
+    /// Remove the installation for the `owner/name` repository, returning it if it was registered
+    pub fn unregister(&self, owner: &str, name: &str) -> Option<Arc<Installation>> {
+        self.installations
+            .write()
+            .unwrap()
+            .remove(&key(owner, name))
+    }
+
+    pub fn contains(&self, owner: &str, name: &str) -> bool {
+        self.installations
+            .read()
+            .unwrap()
+            .contains_key(&key(owner, name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.installations.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn key(owner: &str, name: &str) -> String {
+    format!("{}/{}", owner, name).to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Registry;
+    use crate::installation::Installation;
+
+    #[test]
+    fn register() {
+        let registry = Registry::new();
+        registry
+            .register(Installation::new("bmwill", "bors-rs"))
+            .unwrap();
+        registry
+            .register(Installation::new("bmwill", "other"))
+            .unwrap();
+
+        let handle = registry.clone();
+        assert!(handle
+            .register(Installation::new("BMWill", "Bors-RS"))
+            .is_err());
+        assert_eq!(registry.len(), 2);
+
+        let installation = registry.get("BMWILL", "bors-rs").unwrap();
+        assert_eq!(installation.name(), "bors-rs");
+        assert!(registry.contains("bmwill", "other"));
+        assert!(registry.get("other", "bors-rs").is_none());
+
+        assert!(registry.unregister("BMWill", "other").is_some());
+        assert!(registry.unregister("bmwill", "other").is_none());
+        assert!(!registry.contains("bmwill", "other"));
+        registry
+            .register(Installation::new("bmwill", "other"))
+            .unwrap();
+    }
+}
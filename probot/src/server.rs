@@ -1,18 +1,24 @@
-use crate::{installation::Installation, smee_client::SmeeClient, Error, Result};
+use crate::{
+    installation::Installation, registry::Registry, service::Service, smee_client::SmeeClient,
+    Error, Result,
+};
 use futures::{
     future::{self, FutureExt, TryFutureExt},
+    stream::StreamExt,
     try_join,
 };
 use github::{EventType, Webhook, DELIVERY_ID_HEADER, EVENT_TYPE_HEADER, SIGNATURE_HEADER};
 use hyper::{
     body,
-    header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    header::{HeaderValue, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE},
     server::conn::AddrStream,
     service::{make_service_fn, service_fn},
     Body, Method, Request, Response, Server as HyperServer, StatusCode,
 };
 use log::{error, info, warn};
+use serde::Deserialize;
 use std::{
+    convert::Infallible,
     net::SocketAddr,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -26,7 +32,17 @@ pub struct ServerBuilder {
     smee: bool,
     /// smee.io URL
     smee_url: Option<String>,
-    installations: Vec<Installation>,
+    registry: Registry,
+    app: AppServices,
+    admin_token: Option<String>,
+}
+
+/// Services handling deliveries which aren't for a registered repository, e.g. the installation
+/// events of a Github App, along with the app's webhook secret
+#[derive(Default, Debug)]
+struct AppServices {
+    secret: Option<String>,
+    services: Vec<Box<dyn Service>>,
 }
 
 impl ServerBuilder {
@@ -36,14 +52,41 @@ impl ServerBuilder {
         self
     }
 
-    pub fn add_installation(&mut self, installation: Installation) -> &mut Self {
-        self.installations.push(installation);
+    /// Register an installation, failing if one is already registered for its repository
+    pub fn add_installation(&mut self, installation: Installation) -> Result<&mut Self> {
+        self.registry.register(installation)?;
+        Ok(self)
+    }
+
+    /// Set the webhook secret of the Github App whose deliveries are handled by the services added
+    /// with `add_app_service`
+    pub fn app_secret<T: Into<String>>(&mut self, secret: T) -> &mut Self {
+        self.app.secret = Some(secret.into());
+        self
+    }
+
+    /// Add a service handling deliveries which aren't for a registered repository, e.g. to
+    /// register the repositories a Github App is installed on as the app is installed on them
+    pub fn add_app_service(&mut self, service: Box<dyn Service>) -> &mut Self {
+        self.app.services.push(service);
+        self
+    }
+
+    /// Enable the admin API, whose requests must carry `token` as a bearer token
+    pub fn admin_token<T: Into<String>>(&mut self, token: T) -> &mut Self {
+        self.admin_token = Some(token.into());
         self
     }
 
+    /// A handle to the registry of the server's installations, through which installations can
+    /// be added once the server is running
+    pub fn registry(&self) -> Registry {
+        self.registry.clone()
+    }
+
     pub async fn serve(self, addr: SocketAddr) -> Result<()> {
         // Construct the server
-        let server = Server::new(self.installations);
+        let server = Server::new(self.registry, self.app, self.admin_token);
 
         // The closure inside `make_service_fn` is run for each connection,
         // creating a 'service' to handle requests for that specific connection.
@@ -87,8 +130,11 @@ impl ServerBuilder {
 #[derive(Clone, Debug)]
 pub struct Server {
     counter: Arc<AtomicUsize>,
-    /// Installations which contain various services
-    installations: Arc<Vec<Installation>>,
+    /// Installations which contain various services, keyed by repository
+    registry: Registry,
+    app: Arc<AppServices>,
+    /// Bearer token authenticating requests to the admin API, which is disabled without one
+    admin_token: Option<Arc<str>>,
 }
 
 impl Server {
@@ -96,10 +142,12 @@ impl Server {
         ServerBuilder::default()
     }
 
-    fn new(installations: Vec<Installation>) -> Self {
+    fn new(registry: Registry, app: AppServices, admin_token: Option<String>) -> Self {
         Self {
             counter: Arc::new(AtomicUsize::new(0)),
-            installations: Arc::new(installations),
+            registry,
+            app: Arc::new(app),
+            admin_token: admin_token.map(Into::into),
         }
     }
 
@@ -119,6 +167,10 @@ impl Server {
                 .status(StatusCode::METHOD_NOT_ALLOWED)
                 .body(Body::empty())?),
             (&Method::POST, "/github") => self.route_github(request).await,
+            (&Method::GET, path) if path.starts_with("/events/") => {
+                self.route_events(request).await
+            }
+            (&Method::POST, path) if path.starts_with("/admin/") => self.route_admin(request).await,
             _ => Ok(Response::builder()
                 .status(StatusCode::NOT_FOUND)
                 .body(Body::empty())?),
@@ -140,7 +192,11 @@ impl Server {
         };
 
         info!("{:#?}", webhook.event_type);
-        self.handle_webhook(webhook).await?;
+        if !self.handle_webhook(webhook).await? {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(Body::empty())?);
+        }
 
         Ok(Response::builder()
             .status(StatusCode::OK)
@@ -149,11 +205,161 @@ impl Server {
             .body(Body::from("OK"))?)
     }
 
+    /// Stream the events published by the services of the installation identified by a path of
+    /// the form `/events/<owner>/<name>`, once the request's bearer token has been checked
+    async fn route_events(&mut self, request: Request<Body>) -> Result<Response<Body>> {
+        if let Some(rejection) = self.reject_unauthorized(&request)? {
+            return Ok(rejection);
+        }
+
+        let path = request.uri().path();
+        let mut components = path["/events/".len()..].splitn(2, '/');
+        let installation = match (components.next(), components.next()) {
+            (Some(owner), Some(name)) => self.registry.get(owner, name),
+            _ => None,
+        };
+
+        for service in installation.iter().flat_map(|i| i.services()) {
+            if let Some(stream) = service.subscribe().await {
+                info!("Streaming events from {} for {}", service.name(), path);
+
+                let body =
+                    Body::wrap_stream(stream.map(|event| Ok::<_, Infallible>(event.encode())));
+                return Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .header(CACHE_CONTROL, "no-cache")
+                    .body(body)?);
+            }
+        }
+
+        Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())?)
+    }
+
+    /// Hand a request to the admin API, at a path of the form `/admin/<owner>/<name>`, to the
+    /// services of the installation it's for, once its bearer token has been checked
+    async fn route_admin(&mut self, request: Request<Body>) -> Result<Response<Body>> {
+        if let Some(rejection) = self.reject_unauthorized(&request)? {
+            return Ok(rejection);
+        }
+
+        let path = request.uri().path().to_owned();
+        let mut components = path["/admin/".len()..].splitn(2, '/');
+        let installation = match (components.next(), components.next()) {
+            (Some(owner), Some(name)) => self.registry.get(owner, name),
+            _ => None,
+        };
+        let body = body::to_bytes(request.into_body()).await?;
+
+        for service in installation.iter().flat_map(|i| i.services()) {
+            let (status, response) = match service.admin(&body).await {
+                Some(Ok(response)) => (StatusCode::OK, response),
+                Some(Err(error)) => (
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({ "error": error }),
+                ),
+                None => continue,
+            };
+
+            info!("{} handled admin request for {}", service.name(), path);
+            return Ok(Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(&response)?))?);
+        }
+
+        Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())?)
+    }
+
+    /// Check that a request to the admin API or an event stream carries the admin token as a
+    /// bearer token, returning the response to reject it with if it doesn't. Both are disabled
+    /// without an admin token.
+    fn reject_unauthorized(&self, request: &Request<Body>) -> Result<Option<Response<Body>>> {
+        let token = match &self.admin_token {
+            Some(token) => token,
+            None => {
+                return Ok(Some(
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())?,
+                ))
+            }
+        };
+
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|h| HeaderValue::to_str(h).ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map_or(false, |bearer| tokens_match(bearer, token));
+        if authorized {
+            return Ok(None);
+        }
+
+        warn!(
+            "Rejecting request for {} without a valid token",
+            request.uri().path()
+        );
+        Ok(Some(
+            Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(Body::empty())?,
+        ))
+    }
+
+    /// The installation for the repository a webhook was delivered for, found without
+    /// deserializing the whole payload so that nothing more is done with a payload before its
+    /// signature is checked
+    fn installation_for(&self, webhook: &Webhook) -> Option<Arc<Installation>> {
+        #[derive(Deserialize)]
+        struct Payload {
+            repository: Option<Repository>,
+        }
+
+        #[derive(Deserialize)]
+        struct Repository {
+            name: String,
+            owner: Owner,
+        }
+
+        #[derive(Deserialize)]
+        struct Owner {
+            login: String,
+        }
+
+        let repository = serde_json::from_slice::<Payload>(&webhook.body)
+            .ok()?
+            .repository?;
+        self.registry.get(&repository.owner.login, &repository.name)
+    }
+
     //TODO maybe insert into database here
-    pub(super) async fn handle_webhook(&mut self, webhook: Webhook) -> Result<()> {
+    /// Handle a webhook, returning whether its signature was accepted
+    pub(super) async fn handle_webhook(&mut self, webhook: Webhook) -> Result<bool> {
         info!("Handling Webhook: {}", webhook.delivery_id);
 
-        // Convert the webhook to an event so that we can get out the installation information
+        // Deliveries for repositories which aren't registered, or which aren't for a repository at
+        // all, are left to the app's services
+        let installation = self.installation_for(&webhook);
+        let (secret, services) = match &installation {
+            Some(installation) => (installation.secret(), installation.services()),
+            None => (self.app.secret.as_deref(), self.app.services.as_slice()),
+        };
+        if webhook.check_signature(secret.map(str::as_bytes)) {
+            info!("Signature check PASSED!");
+        } else {
+            warn!("Signature check FAILED! Rejecting Event.");
+            return Ok(false);
+        }
+
+        if services.is_empty() {
+            return Ok(true);
+        }
+
         let event = match webhook.to_event() {
             Ok(webhook) => webhook,
             Err(_err) => {
@@ -172,34 +378,33 @@ impl Server {
                 let error_path = format!("{}.err", webhook.delivery_id);
                 std::fs::write(json_path, pretty_json.as_bytes()).unwrap();
                 std::fs::write(error_path, error.as_bytes()).unwrap();
-                return Ok(());
+                return Ok(true);
             }
         };
 
-        // XXX Right now we only handle Webhook installations for Repositories
-        if let Some(installation) = event.repository().and_then(|repository| {
-            self.installations
-                .iter()
-                .find(|i| i.owner() == repository.owner.login && i.name() == repository.name)
-        }) {
-            if webhook.check_signature(installation.secret().map(str::as_bytes)) {
-                info!("Signature check PASSED!");
-            } else {
-                warn!("Signature check FAILED! Skipping Event.");
-                return Ok(());
-            }
-
-            for service in installation.services() {
-                if service.route(webhook.event_type) {
-                    service.handle(&event, &webhook.delivery_id).await;
-                }
+        for service in services {
+            if service.route(webhook.event_type) {
+                service
+                    .handle(&event, &webhook.body, &webhook.delivery_id)
+                    .await;
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 }
 
+/// Compare a bearer token in constant time, so that how long the comparison takes doesn't reveal
+/// how much of a guessed token was correct
+fn tokens_match(bearer: &str, token: &str) -> bool {
+    bearer.len() == token.len()
+        && bearer
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
 async fn webhook_from_request(request: Request<Body>) -> Result<Webhook> {
     // Webhooks from github should only contain json payloads
     match request.headers().get(CONTENT_TYPE).map(HeaderValue::to_str) {
@@ -248,6 +453,7 @@ async fn webhook_from_request(request: Request<Body>) -> Result<Webhook> {
 #[cfg(test)]
 mod test {
     use super::Server;
+    use crate::{installation::Installation, registry::Registry};
     use hyper::{Body, Method, Request, StatusCode, Uri, Version};
 
     #[tokio::test]
@@ -255,13 +461,94 @@ mod test {
         static PAYLOAD: &str = include_str!("../test-input/pull-request-event-payload");
         let request = request_from_raw_http(PAYLOAD);
 
-        let mut service = Server::new(vec![]);
+        let mut service = Server::new(Registry::new(), Default::default(), None);
 
         let resp = service.route_github(request).await.unwrap();
         assert_eq!(resp.status(), StatusCode::OK);
         println!("{:?}", resp);
     }
 
+    #[tokio::test]
+    async fn unsigned_event_is_rejected() {
+        static PAYLOAD: &str = include_str!("../test-input/pull-request-event-payload");
+
+        let mut installation = Installation::new("Codertocat", "Hello-World");
+        installation.with_secret("secret");
+        let registry = Registry::new();
+        registry.register(installation).unwrap();
+        let mut service = Server::new(registry, Default::default(), None);
+
+        let resp = service
+            .route_github(request_from_raw_http(PAYLOAD))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn admin_requests_need_the_token() {
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method(Method::POST)
+                .uri("/admin/bmwill/bors-rs");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::from("{}")).unwrap()
+        };
+
+        let registry = Registry::new();
+        registry
+            .register(Installation::new("bmwill", "bors-rs"))
+            .unwrap();
+
+        let mut disabled = Server::new(registry.clone(), Default::default(), None);
+        let resp = disabled.route_admin(request(Some("token"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let mut service = Server::new(registry, Default::default(), Some("token".to_owned()));
+        let resp = service.route_admin(request(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = service.route_admin(request(Some("tokem"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // Installations without an admin API aren't found
+        let resp = service.route_admin(request(Some("token"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn event_streams_need_the_token() {
+        let request = |token: Option<&str>| {
+            let mut builder = Request::builder()
+                .method(Method::GET)
+                .uri("/events/bmwill/bors-rs");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let registry = Registry::new();
+        registry
+            .register(Installation::new("bmwill", "bors-rs"))
+            .unwrap();
+
+        let mut disabled = Server::new(registry.clone(), Default::default(), None);
+        let resp = disabled.route_events(request(Some("token"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let mut service = Server::new(registry, Default::default(), Some("token".to_owned()));
+        let resp = service.route_events(request(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = service.route_events(request(Some("tokem"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        // Installations without an event stream aren't found
+        let resp = service.route_events(request(Some("token"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     // Super quick and dirty parsing of raw http into a `Request<Body>` type.
     // This assumes that the content is JSON
     fn request_from_raw_http(raw: &'static str) -> Request<Body> {
@@ -1,3 +1,4 @@
+use crate::event_stream::EventStream;
 use github::{Event, EventType};
 use std::fmt::Debug;
 
@@ -8,7 +9,21 @@ pub trait Service: Send + Sync + Debug {
 
     /// Event Handling
     fn route(&self, event_type: EventType) -> bool;
-    async fn handle(&self, event: &Event, delivery_id: &str);
+    /// Handle an event, along with the raw JSON `payload` of the webhook it was parsed from
+    async fn handle(&self, event: &Event, payload: &[u8], delivery_id: &str);
+
+    /// Subscribe to the events published by this service, which are streamed to HTTP clients at
+    /// `/events/<owner>/<name>`. Returns `None` if the service doesn't publish any events.
+    async fn subscribe(&self) -> Option<EventStream> {
+        None
+    }
+
+    /// Handle a request made through the admin API with `POST /admin/<owner>/<name>`, given the
+    /// request's JSON `body`, returning the JSON response or why the request failed. Returns `None`
+    /// if the service doesn't have an admin API.
+    async fn admin(&self, _body: &[u8]) -> Option<Result<serde_json::Value, String>> {
+        None
+    }
 
     // TODO: HTTP routing
 }
@@ -52,7 +52,8 @@ impl SmeeClient {
                 SmeeEvent::Ping => debug!("ping!"),
                 SmeeEvent::Message(webhook) => {
                     debug!("message!");
-                    // Have the server process the webhook
+                    // Have the server process the webhook, which is skipped if its signature is
+                    // rejected
                     self.server.handle_webhook(webhook).await?;
                 }
             }
@@ -77,7 +78,7 @@ struct SmeeMessage<'a> {
     event: &'a RawValue,
     #[serde(rename = "x-github-delivery")]
     delivery_id: String,
-    #[serde(rename = "x-hub-signature")]
+    #[serde(rename = "x-hub-signature-256")]
     signature: Option<String>,
 }
 